@@ -1,26 +1,121 @@
-use crate::models::{ChatRequest, Message};
+use crate::models::{ChatRequest, GenerationParams, Message};
+use crate::provider_config::{ProviderConfig, ProviderKind};
+use crate::stream_decoder::{decode, JsonArrayDecoder, SseDecoder, SseEvent};
 use serde_json::Value;
 
 /// 执行非流式 AI 请求的通用方法
 pub async fn call_ai_backend(
     client: &reqwest::Client,
-    api_key: &str,
-    base_url: &str,
+    provider: &ProviderConfig,
     payload: &ChatRequest,
 ) -> Result<String, String> {
-    let base = base_url.trim_end_matches('/');
-    let url = if base.ends_with("/chat/completions") {
-        base.to_string()
-    } else if base.ends_with("/v1") {
-        format!("{}/chat/completions", base)
-    } else {
-        format!("{}/v1/chat/completions", base)
+    if provider.kind == ProviderKind::Ollama {
+        return crate::local_llm::ollama_chat(
+            client,
+            provider,
+            &payload.model,
+            &payload.messages,
+            &payload.params,
+        )
+        .await;
+    }
+
+    post_chat_completion(client, provider, payload).await
+}
+
+/// 非流式对话补全：Gemini 使用原生接口，其余走 call_ai_backend
+pub async fn complete_chat(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    model: &str,
+    messages: Vec<Message>,
+    params: GenerationParams,
+) -> Result<String, String> {
+    if provider.is_gemini() {
+        return call_gemini_backend(client, provider, model, messages, &params).await;
+    }
+
+    let payload = ChatRequest {
+        model: model.to_string(),
+        messages,
+        stream: false,
+        params,
     };
+    call_ai_backend(client, provider, &payload).await
+}
+
+/// 结构化 JSON 输出的请求方式；服务端不支持时 (返回 400 / 422) 由调用方逐级降级
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsonMode {
+    /// response_format: json_schema (严格按 schema 输出)
+    Schema,
+    /// response_format: json_object (只保证是 JSON)
+    Object,
+    /// 仅依靠提示词约束
+    Prompt,
+}
+
+impl JsonMode {
+    pub fn downgrade(self) -> Option<Self> {
+        match self {
+            JsonMode::Schema => Some(JsonMode::Object),
+            JsonMode::Object => Some(JsonMode::Prompt),
+            JsonMode::Prompt => None,
+        }
+    }
+}
+
+/// 非流式请求 JSON 输出
+/// OpenAI 兼容接口按 mode 附加 response_format；Gemini 与 Ollama 原生接口仅依靠提示词约束
+pub async fn call_json_backend(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    payload: &ChatRequest,
+    schema_name: &str,
+    schema: &Value,
+    mode: JsonMode,
+) -> Result<String, String> {
+    if provider.is_gemini() {
+        return call_gemini_backend(
+            client,
+            provider,
+            &payload.model,
+            payload.messages.clone(),
+            &payload.params,
+        )
+        .await;
+    }
+    if provider.kind == ProviderKind::Ollama {
+        return call_ai_backend(client, provider, payload).await;
+    }
+
+    let mut body = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+    match mode {
+        JsonMode::Schema => {
+            body["response_format"] = serde_json::json!({
+                "type": "json_schema",
+                "json_schema": { "name": schema_name, "schema": schema, "strict": true }
+            })
+        }
+        JsonMode::Object => body["response_format"] = serde_json::json!({ "type": "json_object" }),
+        JsonMode::Prompt => {}
+    }
+    post_chat_completion(client, provider, &body).await
+}
 
-    let response = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(payload)
+/// 服务端拒绝请求参数 (多为不支持 response_format)
+pub fn is_unsupported_request(error: &str) -> bool {
+    error.starts_with("API Error (400") || error.starts_with("API Error (422")
+}
+
+async fn post_chat_completion<T: serde::Serialize + ?Sized>(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    body: &T,
+) -> Result<String, String> {
+    let response = provider
+        .authorize(client.post(provider.chat_completions_url()))
+        .json(body)
         .send()
         .await
         .map_err(|e| e.to_string())?;
@@ -39,16 +134,82 @@ pub async fn call_ai_backend(
     Ok(content.to_string())
 }
 
+/// 流式调用任意提供商，逐段回调 (正文, 思考过程)；回调返回 false 时停止读取
+/// Gemini 原生接口不支持提前停止，会读完整个响应
+pub async fn stream_chat<F>(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    model: &str,
+    messages: Vec<Message>,
+    params: &GenerationParams,
+    mut on_delta: F,
+) -> Result<(), String>
+where
+    F: FnMut(&str, &str) -> bool,
+{
+    if provider.is_gemini() {
+        return call_gemini_streaming(client, provider, model, messages, params, |text| {
+            on_delta(&text, "");
+        })
+        .await;
+    }
+    if provider.kind == ProviderKind::Ollama {
+        return crate::local_llm::ollama_chat_streaming(
+            client,
+            provider,
+            model,
+            &messages,
+            params,
+            |d| on_delta(&d.content, &d.thinking),
+        )
+        .await;
+    }
+
+    let payload = ChatRequest {
+        model: model.to_string(),
+        messages,
+        stream: true,
+        params: params.clone(),
+    };
+    let response = provider
+        .authorize(client.post(provider.chat_completions_url()))
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let err_body = response.text().await.unwrap_or_default();
+        return Err(format!("API Error ({}): {}", status, err_body));
+    }
+
+    use futures_util::StreamExt;
+    let mut events = std::pin::pin!(decode(response.bytes_stream(), SseDecoder::default()));
+    while let Some(event) = events.next().await {
+        let delta = parse_openai_event(&event?)?;
+        if delta.done {
+            break;
+        }
+        let content = delta.content.unwrap_or_default();
+        let reasoning = delta.reasoning.unwrap_or_default();
+        if (!content.is_empty() || !reasoning.is_empty()) && !on_delta(&content, &reasoning) {
+            break;
+        }
+    }
+    Ok(())
+}
+
 /// 发送原生 Gemini 请求的通用方法 (非流式)
 pub async fn call_gemini_backend(
     client: &reqwest::Client,
-    api_key: &str,
-    base_url: &str,
+    provider: &ProviderConfig,
     model: &str,
     messages: Vec<Message>,
+    params: &GenerationParams,
 ) -> Result<String, String> {
     let mut full_content = String::new();
-    call_gemini_streaming(client, api_key, base_url, model, messages, |chunk| {
+    call_gemini_streaming(client, provider, model, messages, params, |chunk| {
         full_content.push_str(&chunk);
     })
     .await?;
@@ -58,10 +219,10 @@ pub async fn call_gemini_backend(
 /// 发送原生 Gemini 请求的通用方法 (流式)
 pub async fn call_gemini_streaming<F>(
     client: &reqwest::Client,
-    api_key: &str,
-    base_url: &str,
+    provider: &ProviderConfig,
     model: &str,
     messages: Vec<Message>,
+    params: &GenerationParams,
     mut on_chunk: F,
 ) -> Result<(), String>
 where
@@ -83,6 +244,9 @@ where
         #[serde(skip_serializing_if = "Option::is_none")]
         #[serde(rename = "systemInstruction")]
         system_instruction: Option<GeminiContent>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "generationConfig")]
+        generation_config: Option<Value>,
     }
 
     let mut system_instruction = None;
@@ -106,17 +270,12 @@ where
     let payload = GeminiRequest {
         contents,
         system_instruction,
+        generation_config: crate::generation_config::gemini_generation_config(params),
     };
 
-    let url = format!(
-        "{}/v1beta/models/{}:streamGenerateContent?key={}",
-        base_url.trim_end_matches('/'),
-        model,
-        api_key
-    );
-
-    let response = client
-        .post(&url)
+    let url = provider.gemini_url(model, "streamGenerateContent");
+    let response = provider
+        .authorize(client.post(&url))
         .header("Content-Type", "application/json")
         .json(&payload)
         .send()
@@ -130,54 +289,128 @@ where
     }
 
     use futures_util::StreamExt;
-    let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
-
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| e.to_string())?;
-        buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-        while let Some(start_idx) = buffer.find('{') {
-            let mut depth = 0;
-            let mut end_idx = None;
-            let bytes = buffer.as_bytes();
-
-            for i in start_idx..bytes.len() {
-                if bytes[i] == b'{' {
-                    depth += 1;
-                } else if bytes[i] == b'}' {
-                    if depth > 0 {
-                        depth -= 1;
-                        if depth == 0 {
-                            end_idx = Some(i);
-                            break;
-                        }
-                    }
-                }
-            }
+    let mut values = std::pin::pin!(decode(response.bytes_stream(), JsonArrayDecoder::default()));
+    while let Some(json) = values.next().await {
+        for text in gemini_chunk_text(&json?)? {
+            on_chunk(text);
+        }
+    }
 
-            if let Some(end) = end_idx {
-                let json_str = &buffer[start_idx..=end];
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(json_str) {
-                    if let Some(candidates) = json["candidates"].as_array() {
-                        if let Some(candidate) = candidates.get(0) {
-                            if let Some(parts) = candidate["content"]["parts"].as_array() {
-                                for part in parts {
-                                    if let Some(text) = part["text"].as_str() {
-                                        on_chunk(text.to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                // 🛠️ 完善：确保 drain 掉 end 之前的所有内容 (包括可能存在的 [ 或 , 等 JSON 数组分隔符)
-                buffer.drain(..=end);
-            } else {
-                break;
+    Ok(())
+}
+
+/// 取出 Gemini 流式响应块中的文本片段 (candidates[0].content.parts[*].text)
+pub fn gemini_chunk_text(json: &Value) -> Result<Vec<String>, String> {
+    if !json["error"].is_null() {
+        return Err(format!("Gemini Stream Error: {}", json["error"]));
+    }
+    Ok(json["candidates"][0]["content"]["parts"]
+        .as_array()
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| part["text"].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// OpenAI 兼容流式响应中的一个增量
+#[derive(Debug, Default, PartialEq)]
+pub struct OpenAiDelta {
+    pub content: Option<String>,
+    pub reasoning: Option<String>,
+    pub done: bool,
+}
+
+/// 解析一个 SSE 事件；无法解析的 data 视为空增量
+pub fn parse_openai_event(event: &SseEvent) -> Result<OpenAiDelta, String> {
+    let data = event.data.trim();
+    if data == "[DONE]" {
+        return Ok(OpenAiDelta {
+            done: true,
+            ..Default::default()
+        });
+    }
+    if event.event == "error" {
+        return Err(format!("Stream Error: {}", data));
+    }
+
+    // 个别兼容服务把多个 JSON 放进同一事件的多行 data 中，整体解析失败时逐行解析
+    let values: Vec<Value> = match serde_json::from_str(data) {
+        Ok(json) => vec![json],
+        Err(_) => data
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+    };
+
+    let mut delta = OpenAiDelta::default();
+    for json in values {
+        let Some(choices) = json["choices"].as_array() else {
+            if let Some(err) = json["error"].as_object() {
+                return Err(format!("Stream Error: {:?}", err));
             }
+            continue;
+        };
+        let Some(choice) = choices.first() else {
+            continue;
+        };
+        let d = &choice["delta"];
+        if let Some(content) = d["content"].as_str() {
+            delta
+                .content
+                .get_or_insert_with(String::new)
+                .push_str(content);
+        }
+        if let Some(reasoning) = d["reasoning_content"]
+            .as_str()
+            .or_else(|| d["reasoning"].as_str())
+            .or_else(|| d["thought"].as_str())
+        {
+            delta
+                .reasoning
+                .get_or_insert_with(String::new)
+                .push_str(reasoning);
         }
     }
+    Ok(delta)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(data: &str) -> SseEvent {
+        SseEvent {
+            event: "message".into(),
+            data: data.into(),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_openai_event() {
+        let delta = parse_openai_event(&event(
+            r#"{"choices":[{"delta":{"content":"你","reasoning_content":"嗯"}}]}"#,
+        ))
+        .unwrap();
+        assert_eq!(delta.content.as_deref(), Some("你"));
+        assert_eq!(delta.reasoning.as_deref(), Some("嗯"));
+
+        let joined = parse_openai_event(&event(concat!(
+            r#"{"choices":[{"delta":{"content":"a"}}]}"#,
+            "\n",
+            r#"{"choices":[{"delta":{"content":"b"}}]}"#
+        )))
+        .unwrap();
+        assert_eq!(joined.content.as_deref(), Some("ab"));
+
+        assert!(parse_openai_event(&event("[DONE]")).unwrap().done);
+        assert!(parse_openai_event(&event(r#"{"error":{"message":"quota"}}"#)).is_err());
+        assert_eq!(
+            parse_openai_event(&event(r#"{"choices":[]}"#)).unwrap(),
+            OpenAiDelta::default()
+        );
+    }
 }
@@ -0,0 +1,748 @@
+use crate::commands::ai::{run_chat, ChatInvocation};
+use crate::models::Message;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, Limited, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::{Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+
+// ==================================================================================
+// 本地 HTTP 接口 (OpenAI 兼容)：编辑器插件、脚本可以把 Goge Chat 当作网关使用，
+// 走与界面对话相同的流程 (记忆注入、[SEARCH] 联网搜索、会话保存)
+// ==================================================================================
+
+const DEFAULT_PORT: u16 = 11435;
+/// 请求体上限，防止误传超大文件
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+/// settings.json 中的 apiServer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiServerSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// 访问令牌 (Authorization: Bearer <token>)，为空时拒绝启动
+    #[serde(default)]
+    pub token: String,
+}
+
+impl Default for ApiServerSettings {
+    fn default() -> Self {
+        ApiServerSettings {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ApiServerStatus {
+    pub running: bool,
+    /// 例如 http://127.0.0.1:11435/v1
+    #[serde(rename = "baseUrl")]
+    pub base_url: Option<String>,
+    pub error: Option<String>,
+}
+
+// ==================================================================================
+// OpenAI 请求 / 响应格式
+// ==================================================================================
+
+#[derive(Deserialize, Debug)]
+pub struct ApiChatRequest {
+    /// "提供商ID/模型ID"，或只写模型 ID (使用默认提供商)，为空或 "default" 时使用默认模型
+    #[serde(default)]
+    pub model: String,
+    pub messages: Vec<ApiMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub max_completion_tokens: Option<u32>,
+    /// 扩展字段：提供时把本轮问答保存到该会话
+    pub session_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiMessage {
+    pub role: String,
+    /// 字符串，或 [{ "type": "text", "text": ... }] 形式的分段内容 (非文本分段被忽略)
+    #[serde(default)]
+    pub content: Value,
+}
+
+impl ApiChatRequest {
+    /// 拆分 model 字段为 (提供商, 模型)
+    pub fn target(&self, providers: &Value) -> (Option<String>, Option<String>) {
+        crate::generation_config::split_model_ref(&self.model, providers)
+    }
+
+    pub fn to_messages(&self) -> Vec<Message> {
+        self.messages
+            .iter()
+            .map(|m| Message {
+                id: None,
+                model: None,
+                role: m.role.clone(),
+                content: content_text(&m.content),
+                reasoning_content: None,
+                file_metadata: None,
+                search_metadata: None,
+                provider: None,
+                mode: None,
+                role_id: None,
+                parent_id: None,
+            })
+            .collect()
+    }
+}
+
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|p| p["type"] == "text")
+            .filter_map(|p| p["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// 后端推送给 HTTP 层的输出
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiDelta {
+    Content(String),
+    Reasoning(String),
+}
+
+/// 对话后端：HTTP 层只负责协议转换，测试时可以换成替身
+pub trait ChatBackend: Send + Sync + 'static {
+    fn models(&self) -> BoxFuture<'_, Result<Vec<String>, String>>;
+
+    /// 运行一次对话，输出逐段写入 sink；sink 发送失败表示客户端已断开，应置位 stop 尽快结束
+    fn chat(
+        &self,
+        request: ApiChatRequest,
+        stop: Arc<AtomicBool>,
+        sink: mpsc::UnboundedSender<ApiDelta>,
+    ) -> BoxFuture<'_, Result<(), String>>;
+}
+
+// ==================================================================================
+// HTTP 层
+// ==================================================================================
+
+type Body = BoxBody<Bytes, Infallible>;
+
+fn full(bytes: impl Into<Bytes>) -> Body {
+    Full::new(bytes.into()).boxed()
+}
+
+fn json_response(status: StatusCode, value: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(full(value.to_string()))
+        .unwrap()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    let kind = match status {
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::NOT_FOUND => "not_found_error",
+        s if s.is_server_error() => "api_error",
+        _ => "invalid_request_error",
+    };
+    json_response(
+        status,
+        json!({ "error": { "message": message, "type": kind } }),
+    )
+}
+
+/// 逐字节比较，耗时与首个不同字符的位置无关
+fn token_matches(header: Option<&str>, token: &str) -> bool {
+    let Some(given) = header.and_then(|h| h.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn chunk_json(id: &str, created: i64, model: &str, delta: Value, finish: Option<&str>) -> String {
+    json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": created,
+        "model": model,
+        "choices": [{ "index": 0, "delta": delta, "finish_reason": finish }],
+    })
+    .to_string()
+}
+
+async fn handle(
+    req: Request<Incoming>,
+    token: Arc<str>,
+    backend: Arc<dyn ChatBackend>,
+) -> Result<Response<Body>, Infallible> {
+    let auth = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    if !token_matches(auth, &token) {
+        return Ok(error_response(StatusCode::UNAUTHORIZED, "访问令牌无效"));
+    }
+
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/v1/models") => match backend.models().await {
+            Ok(models) => {
+                let data: Vec<Value> = models
+                    .iter()
+                    .map(|id| json!({ "id": id, "object": "model", "owned_by": "goge-chat" }))
+                    .collect();
+                json_response(StatusCode::OK, json!({ "object": "list", "data": data }))
+            }
+            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        },
+        (&Method::POST, "/v1/chat/completions") => {
+            let body = match Limited::new(req.into_body(), MAX_BODY_BYTES)
+                .collect()
+                .await
+            {
+                Ok(collected) => collected.to_bytes(),
+                Err(e) => {
+                    return Ok(error_response(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        &format!("请求体读取失败: {}", e),
+                    ))
+                }
+            };
+            match serde_json::from_slice::<ApiChatRequest>(&body) {
+                Ok(request) if request.messages.is_empty() => {
+                    error_response(StatusCode::BAD_REQUEST, "messages 不能为空")
+                }
+                Ok(request) => chat_completions(request, backend).await,
+                Err(e) => error_response(StatusCode::BAD_REQUEST, &format!("请求格式错误: {}", e)),
+            }
+        }
+        _ => error_response(StatusCode::NOT_FOUND, "接口不存在"),
+    };
+    Ok(response)
+}
+
+async fn chat_completions(
+    request: ApiChatRequest,
+    backend: Arc<dyn ChatBackend>,
+) -> Response<Body> {
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4().simple());
+    let created = chrono::Utc::now().timestamp();
+    let model = if request.model.is_empty() {
+        "default".to_string()
+    } else {
+        request.model.clone()
+    };
+    let stream = request.stream;
+    let stop = Arc::new(AtomicBool::new(false));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    if !stream {
+        let result = backend.chat(request, stop, tx).await;
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        while let Ok(delta) = rx.try_recv() {
+            match delta {
+                ApiDelta::Content(text) => content.push_str(&text),
+                ApiDelta::Reasoning(text) => reasoning.push_str(&text),
+            }
+        }
+        if let Err(e) = result {
+            return error_response(StatusCode::BAD_GATEWAY, &e);
+        }
+        let mut message = json!({ "role": "assistant", "content": content });
+        if !reasoning.is_empty() {
+            message["reasoning_content"] = json!(reasoning);
+        }
+        return json_response(
+            StatusCode::OK,
+            json!({
+                "id": id,
+                "object": "chat.completion",
+                "created": created,
+                "model": model,
+                "choices": [{ "index": 0, "message": message, "finish_reason": "stop" }],
+            }),
+        );
+    }
+
+    // 流式：后端在独立任务中运行；客户端断开后 body 被丢弃，sink 发送失败即可得知
+    let (done_tx, done_rx) = oneshot::channel::<Result<(), String>>();
+    tokio::spawn(async move {
+        let result = backend.chat(request, stop, tx).await;
+        let _ = done_tx.send(result);
+    });
+
+    let head = chunk_json(&id, created, &model, json!({ "role": "assistant" }), None);
+    let deltas = futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx)).map({
+        let (id, model) = (id.clone(), model.clone());
+        move |delta| {
+            let delta = match delta {
+                ApiDelta::Content(text) => json!({ "content": text }),
+                ApiDelta::Reasoning(text) => json!({ "reasoning_content": text }),
+            };
+            chunk_json(&id, created, &model, delta, None)
+        }
+    });
+    let tail = futures_util::stream::once(async move {
+        match done_rx.await {
+            Ok(Err(e)) => json!({ "error": { "message": e, "type": "api_error" } }).to_string(),
+            _ => chunk_json(&id, created, &model, json!({}), Some("stop")),
+        }
+    });
+    let frames = futures_util::stream::once(async move { head })
+        .chain(deltas)
+        .chain(tail)
+        .map(|data| format!("data: {}\n\n", data))
+        .chain(futures_util::stream::once(async {
+            "data: [DONE]\n\n".to_string()
+        }))
+        .map(|text| Ok::<_, Infallible>(Frame::data(Bytes::from(text))));
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(BodyExt::boxed(StreamBody::new(frames)))
+        .unwrap()
+}
+
+/// 在已绑定的端口上提供服务，直到 shutdown 触发
+pub async fn serve(
+    listener: TcpListener,
+    token: String,
+    backend: Arc<dyn ChatBackend>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let token: Arc<str> = token.into();
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    println!("⚠️ [API] 接受连接失败: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let (token, backend) = (token.clone(), backend.clone());
+        tokio::spawn(async move {
+            let service =
+                hyper::service::service_fn(move |req| handle(req, token.clone(), backend.clone()));
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                .await
+            {
+                println!("⚠️ [API] 连接异常结束: {}", e);
+            }
+        });
+    }
+}
+
+// ==================================================================================
+// 应用后端：复用界面对话的完整流程
+// ==================================================================================
+
+struct AppBackend {
+    app: AppHandle,
+}
+
+impl ChatBackend for AppBackend {
+    fn models(&self) -> BoxFuture<'_, Result<Vec<String>, String>> {
+        Box::pin(async move {
+            let config = crate::commands::config_cmd::load_config(self.app.clone()).await?;
+            Ok(crate::provider_config::parse_providers(&config.providers)
+                .iter()
+                .filter(|p| p.enabled)
+                .flat_map(|p| p.models.iter().map(move |m| format!("{}/{}", p.id, m.id)))
+                .collect())
+        })
+    }
+
+    fn chat(
+        &self,
+        request: ApiChatRequest,
+        stop: Arc<AtomicBool>,
+        sink: mpsc::UnboundedSender<ApiDelta>,
+    ) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            let config = crate::commands::config_cmd::load_config(self.app.clone()).await?;
+            let (provider_id, model_id) = request.target(&config.providers);
+            let messages = request.to_messages();
+            let session = request
+                .session_id
+                .as_deref()
+                .map(|id| {
+                    id.parse::<i64>()
+                        .map_err(|_| format!("无效的会话 ID: {}", id))
+                })
+                .transpose()?;
+
+            // 会话保存：先保存本轮用户消息
+            if let (Some(session_id), Some(user_msg)) =
+                (session, messages.iter().rev().find(|m| m.role == "user"))
+            {
+                let db = self.app.state::<crate::db::DbState>();
+                let conn = db.0.lock().map_err(|e| e.to_string())?;
+                crate::db::save_message(
+                    &conn,
+                    session_id,
+                    None,
+                    None,
+                    "user",
+                    &user_msg.content,
+                    None,
+                    None,
+                    None,
+                )
+                .map_err(|e| e.to_string())?;
+            }
+
+            // 界面对话通过 Channel 下发 c: / r: 前缀文本，这里在 Rust 侧接收并转给 HTTP 层
+            let collected = Arc::new(Mutex::new((String::new(), String::new())));
+            let on_event = {
+                let (collected, stop) = (collected.clone(), stop.clone());
+                tauri::ipc::Channel::<String>::new(move |body| {
+                    let tauri::ipc::InvokeResponseBody::Json(json) = body else {
+                        return Ok(());
+                    };
+                    let Ok(text) = serde_json::from_str::<String>(&json) else {
+                        return Ok(());
+                    };
+                    let delta = if let Some(c) = text.strip_prefix("c:") {
+                        collected.lock().unwrap().0.push_str(c);
+                        ApiDelta::Content(c.to_string())
+                    } else if let Some(r) = text.strip_prefix("r:") {
+                        collected.lock().unwrap().1.push_str(r);
+                        ApiDelta::Reasoning(r.to_string())
+                    } else {
+                        return Ok(());
+                    };
+                    if sink.send(delta).is_err() {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    Ok(())
+                })
+            };
+
+            let invocation = ChatInvocation {
+                msg: messages,
+                temperature: request.temperature,
+                max_tokens: request.max_tokens.or(request.max_completion_tokens),
+                stream: Some(true),
+                explicit_provider_id: provider_id.clone(),
+                explicit_model_id: model_id.clone(),
+                session_id: request.session_id.clone(),
+            };
+            run_chat(&self.app, invocation, &stop, on_event).await?;
+
+            if let Some(session_id) = session {
+                let (content, reasoning) = collected.lock().unwrap().clone();
+                if !content.is_empty() {
+                    let db = self.app.state::<crate::db::DbState>();
+                    let conn = db.0.lock().map_err(|e| e.to_string())?;
+                    crate::db::save_message(
+                        &conn,
+                        session_id,
+                        model_id.as_deref(),
+                        provider_id.as_deref(),
+                        "assistant",
+                        &content,
+                        Some(reasoning.as_str()).filter(|r| !r.is_empty()),
+                        None,
+                        None,
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+// ==================================================================================
+// 生命周期：配置变更时按需重启
+// ==================================================================================
+
+#[derive(Default)]
+pub struct ApiServerState {
+    running: Mutex<Option<(ApiServerSettings, oneshot::Sender<()>)>>,
+    status: Arc<Mutex<ApiServerStatus>>,
+}
+
+/// 按配置启动 / 停止 / 重启服务；配置未变化时不做任何事
+pub fn apply(app: &AppHandle, settings: &ApiServerSettings) {
+    let state = app.state::<ApiServerState>();
+    let mut running = state.running.lock().unwrap();
+    if running.as_ref().map(|(s, _)| s) == Some(settings) {
+        return;
+    }
+    if let Some((_, shutdown)) = running.take() {
+        let _ = shutdown.send(());
+        println!("🛑 [API] 本地接口已停止");
+    }
+
+    let status = state.status.clone();
+    *status.lock().unwrap() = ApiServerStatus::default();
+    if !settings.enabled {
+        return;
+    }
+    if settings.token.trim().is_empty() {
+        println!("⚠️ [API] 未设置访问令牌，拒绝启动本地接口");
+        status.lock().unwrap().error = Some("未设置访问令牌".into());
+        return;
+    }
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    *running = Some((settings.clone(), shutdown_tx));
+    let (port, token) = (settings.port, settings.token.clone());
+    let backend: Arc<dyn ChatBackend> = Arc::new(AppBackend { app: app.clone() });
+    tauri::async_runtime::spawn(async move {
+        // 重启时旧监听可能尚未释放端口，稍作重试
+        let mut listener = TcpListener::bind(("127.0.0.1", port)).await;
+        for _ in 0..5 {
+            if listener.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            listener = TcpListener::bind(("127.0.0.1", port)).await;
+        }
+        let listener = match listener {
+            Ok(l) => l,
+            Err(e) => {
+                println!("❌ [API] 无法监听端口 {}: {}", port, e);
+                status.lock().unwrap().error = Some(format!("无法监听端口 {}: {}", port, e));
+                return;
+            }
+        };
+        let base_url = format!("http://127.0.0.1:{}/v1", port);
+        println!("🌐 [API] 本地接口已启动: {}", base_url);
+        *status.lock().unwrap() = ApiServerStatus {
+            running: true,
+            base_url: Some(base_url),
+            error: None,
+        };
+        serve(listener, token, backend, shutdown_rx).await;
+        status.lock().unwrap().running = false;
+    });
+}
+
+#[tauri::command]
+pub fn get_api_server_status(state: tauri::State<'_, ApiServerState>) -> ApiServerStatus {
+    state.status.lock().unwrap().clone()
+}
+
+/// 生成随机访问令牌 (由前端写入 settings.apiServer.token)
+#[tauri::command]
+pub fn generate_api_token() -> String {
+    format!(
+        "goge-{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_decoder::{decode, SseDecoder};
+
+    const TOKEN: &str = "test-token";
+
+    /// 替身后端：回显最后一条用户消息，并附带一段思考过程
+    struct EchoBackend;
+
+    impl ChatBackend for EchoBackend {
+        fn models(&self) -> BoxFuture<'_, Result<Vec<String>, String>> {
+            Box::pin(async { Ok(vec!["deepseek/deepseek-chat".to_string()]) })
+        }
+
+        fn chat(
+            &self,
+            request: ApiChatRequest,
+            _stop: Arc<AtomicBool>,
+            sink: mpsc::UnboundedSender<ApiDelta>,
+        ) -> BoxFuture<'_, Result<(), String>> {
+            Box::pin(async move {
+                let last = request.to_messages().pop().unwrap().content;
+                if last == "fail" {
+                    return Err("上游错误".into());
+                }
+                let _ = sink.send(ApiDelta::Reasoning("想".into()));
+                for ch in last.chars() {
+                    let _ = sink.send(ApiDelta::Content(ch.to_string()));
+                }
+                Ok(())
+            })
+        }
+    }
+
+    async fn start() -> (String, oneshot::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(serve(listener, TOKEN.into(), Arc::new(EchoBackend), rx));
+        (base, tx)
+    }
+
+    #[test]
+    fn test_request_parsing() {
+        let request: ApiChatRequest = serde_json::from_value(json!({
+            "model": "gemini/gemini-2.0-flash",
+            "messages": [
+                { "role": "user", "content": [
+                    { "type": "text", "text": "看图" },
+                    { "type": "image_url", "image_url": { "url": "data:..." } },
+                    { "type": "text", "text": "[SEARCH] 今天" }
+                ]}
+            ]
+        }))
+        .unwrap();
+        let providers = json!([{ "id": "gemini" }, { "id": "siliconflow" }]);
+        assert_eq!(
+            request.target(&providers),
+            (Some("gemini".into()), Some("gemini-2.0-flash".into()))
+        );
+        // 前缀不是提供商时整串作为模型 ID (OpenRouter / SiliconFlow 的模型 ID 含 "/")
+        for model in ["deepseek-ai/DeepSeek-V3.2", "anthropic/claude-3.5-sonnet"] {
+            let bare: ApiChatRequest =
+                serde_json::from_value(json!({ "model": model, "messages": [] })).unwrap();
+            assert_eq!(bare.target(&providers), (None, Some(model.into())));
+        }
+        let prefixed: ApiChatRequest = serde_json::from_value(
+            json!({ "model": "siliconflow/deepseek-ai/DeepSeek-V3.2", "messages": [] }),
+        )
+        .unwrap();
+        assert_eq!(
+            prefixed.target(&providers),
+            (
+                Some("siliconflow".into()),
+                Some("deepseek-ai/DeepSeek-V3.2".into())
+            )
+        );
+        assert_eq!(request.to_messages()[0].content, "看图\n[SEARCH] 今天");
+        assert!(token_matches(Some("Bearer test-token"), TOKEN));
+        assert!(!token_matches(Some("Bearer test-tokem"), TOKEN));
+        assert!(!token_matches(Some("test-token"), TOKEN));
+    }
+
+    #[tokio::test]
+    async fn test_server_auth_and_completions() {
+        let (base, _shutdown) = start().await;
+        let client = reqwest::Client::new();
+
+        let unauthorized = client
+            .get(format!("{}/v1/models", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status(), 401);
+
+        let models: Value = client
+            .get(format!("{}/v1/models", base))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(models["data"][0]["id"], "deepseek/deepseek-chat");
+
+        let completion: Value = client
+            .post(format!("{}/v1/chat/completions", base))
+            .bearer_auth(TOKEN)
+            .json(&json!({ "messages": [{ "role": "user", "content": "你好" }] }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(completion["choices"][0]["message"]["content"], "你好");
+        assert_eq!(
+            completion["choices"][0]["message"]["reasoning_content"],
+            "想"
+        );
+
+        let failed = client
+            .post(format!("{}/v1/chat/completions", base))
+            .bearer_auth(TOKEN)
+            .json(&json!({ "messages": [{ "role": "user", "content": "fail" }] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(failed.status(), 502);
+    }
+
+    #[tokio::test]
+    async fn test_server_streams_sse() {
+        let (base, _shutdown) = start().await;
+        let response = reqwest::Client::new()
+            .post(format!("{}/v1/chat/completions", base))
+            .bearer_auth(TOKEN)
+            .json(&json!({
+                "model": "deepseek/deepseek-chat",
+                "stream": true,
+                "messages": [{ "role": "user", "content": "你好" }]
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[CONTENT_TYPE.as_str()],
+            "text/event-stream"
+        );
+
+        let mut events = std::pin::pin!(decode(response.bytes_stream(), SseDecoder::default()));
+        let mut content = String::new();
+        let mut data = Vec::new();
+        while let Some(event) = events.next().await {
+            let event = event.unwrap();
+            if event.data == "[DONE]" {
+                data.push(event.data);
+                break;
+            }
+            let json: Value = serde_json::from_str(&event.data).unwrap();
+            assert_eq!(json["model"], "deepseek/deepseek-chat");
+            if let Some(text) = json["choices"][0]["delta"]["content"].as_str() {
+                content.push_str(text);
+            }
+            data.push(json["choices"][0]["finish_reason"].to_string());
+        }
+        assert_eq!(content, "你好");
+        assert_eq!(data[data.len() - 2], "\"stop\"");
+        assert_eq!(data.last().unwrap(), "[DONE]");
+    }
+}
@@ -1,5 +1,9 @@
 use crate::immersive_settings::{BehaviorAction, ImmersiveSettings};
-use rand::Rng;
+use crate::routine::{ActivityKind, ActivityState};
+use crate::segmenter::{self, StreamSegmenter};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
 
 /// 会话上下文信息
 #[derive(Clone, Debug)]
@@ -12,16 +16,52 @@ pub struct SessionContext {
     pub busy_level: Option<f32>,
     /// 对话兴趣度 [0.0-1.0]
     pub interest_level: Option<f32>,
+    /// 按作息表推算的当前活动 (联系人没有作息表时为 None)
+    pub activity: Option<ActivityState>,
+}
+
+/// 流水线回复的开场决策
+pub enum StreamOpening {
+    /// 不立即回复 (已读不回或延迟决策)，完整回复生成后交给 `ignored_chain`
+    Ignore(BehaviorAction),
+    /// 边生成边发送；`opening` 是拿到任何文本前就执行的行为 (已读回执)
+    Reply {
+        stream: Box<ReplyStream>,
+        opening: Vec<BehaviorAction>,
+    },
+}
+
+/// 流水线回复的状态
+pub struct ReplyStream {
+    segmenter: StreamSegmenter,
+    context: SessionContext,
+    /// 首段的回复延迟，后续段间延迟以此为基准
+    delay: Option<u32>,
+    /// 首段是否模拟手滑
+    typo: bool,
 }
 
 /// 行为决策引擎
+/// 所有随机决策共用同一个 RNG；用固定种子创建时决策序列可复现 (见 behavior_sim)
 pub struct BehaviorEngine {
     settings: ImmersiveSettings,
+    rng: RefCell<StdRng>,
 }
 
 impl BehaviorEngine {
     pub fn new(settings: ImmersiveSettings) -> Self {
-        Self { settings }
+        Self {
+            settings,
+            rng: RefCell::new(StdRng::from_entropy()),
+        }
+    }
+
+    /// 使用固定种子创建，相同种子与输入得到相同的行为链
+    pub fn with_seed(settings: ImmersiveSettings, seed: u64) -> Self {
+        Self {
+            settings,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+        }
     }
 
     /// 主决策方法:根据消息内容和上下文生成行为链
@@ -41,12 +81,125 @@ impl BehaviorEngine {
 
         // 1. 检查是否忽略 (已读不回或延迟决策)
         if let Some(action) = self.should_ignore(message, context) {
-            return vec![action];
+            return self.ignored_chain(action, message);
+        }
+
+        self.with_read_receipt(self.decide_delivery(message, context))
+    }
+
+    /// 已读不回或延迟决策的行为链 (流水线模式下拿到完整回复后调用)
+    pub fn ignored_chain(&self, action: BehaviorAction, message: &str) -> Vec<BehaviorAction> {
+        match action {
+            // 延迟决策时消息还没被看到，已读回执留到重新决策时再发
+            BehaviorAction::DelayedDecision(ms, _) => {
+                vec![BehaviorAction::DelayedDecision(ms, message.to_string())]
+            }
+            action => self.with_read_receipt(vec![action]),
+        }
+    }
+
+    /// 流水线模式：回复开始生成前做出决策
+    pub fn open_stream(&self, context: &SessionContext) -> StreamOpening {
+        if let Some(action) = self.should_ignore("", context) {
+            return StreamOpening::Ignore(action);
+        }
+
+        let typo = self.should_trigger_typo();
+        let behaviors = &self.settings.behaviors;
+        let segmenter = StreamSegmenter::new(
+            behaviors.segment_delimiter.as_deref(),
+            behaviors.multi_segment.unwrap_or(1) as usize,
+            behaviors.segmentation_threshold_range.unwrap_or((40, 100)),
+            &mut *self.rng.borrow_mut(),
+        );
+        StreamOpening::Reply {
+            stream: Box::new(ReplyStream {
+                segmenter,
+                context: context.clone(),
+                delay: None,
+                typo,
+            }),
+            opening: self.with_read_receipt(Vec::new()),
+        }
+    }
+
+    /// 追加生成的文本，返回新近写完的各段对应的行为 (每段一批)
+    /// `elapsed_ms` 是开始生成以来的耗时，会从首段的回复延迟中扣除
+    pub fn stream_chunk(
+        &self,
+        stream: &mut ReplyStream,
+        chunk: &str,
+        elapsed_ms: u32,
+    ) -> Vec<Vec<BehaviorAction>> {
+        let segments = stream.segmenter.push(chunk, &mut *self.rng.borrow_mut());
+        segments
+            .into_iter()
+            .map(|segment| self.segment_actions(stream, segment, elapsed_ms))
+            .collect()
+    }
+
+    /// 回复生成完毕，返回剩余各段的行为
+    pub fn finish_stream(
+        &self,
+        mut stream: ReplyStream,
+        elapsed_ms: u32,
+    ) -> Vec<Vec<BehaviorAction>> {
+        let segments = stream.segmenter.finish(&mut *self.rng.borrow_mut());
+        segments
+            .into_iter()
+            .filter(|segment| !segment.trim().is_empty())
+            .map(|segment| self.segment_actions(&mut stream, segment, elapsed_ms))
+            .collect()
+    }
+
+    /// 流水线中一段的行为：首段等待按回复延迟扣除生成耗时，之后按段间延迟
+    fn segment_actions(
+        &self,
+        stream: &mut ReplyStream,
+        segment: String,
+        elapsed_ms: u32,
+    ) -> Vec<BehaviorAction> {
+        if let Some(delay) = stream.delay {
+            let (min_f, max_f) = self.settings.behaviors.segment_delay_factor;
+            let factor = self.rng.borrow_mut().gen_range(min_f..max_f);
+            return vec![
+                BehaviorAction::Wait((delay as f32 * factor) as u32),
+                BehaviorAction::Speak(segment),
+            ];
+        }
+
+        let delay = self.calculate_delay(&segment, &stream.context);
+        stream.delay = Some(delay);
+        let mut actions = vec![BehaviorAction::Wait(delay.saturating_sub(elapsed_ms))];
+        if stream.typo {
+            if let Some(fix) = self.typo_fix_actions(segment.clone()) {
+                actions.extend(fix);
+                return actions;
+            }
+        }
+        actions.push(BehaviorAction::Speak(segment));
+        actions
+    }
+
+    /// 启用已读回执时，在行为链最前面标记用户消息已读
+    fn with_read_receipt(&self, mut chain: Vec<BehaviorAction>) -> Vec<BehaviorAction> {
+        if self.settings.behaviors.expressions.read_receipts {
+            chain.insert(0, BehaviorAction::MarkRead);
+        }
+        chain
+    }
+
+    /// 必须送达的消息 (如用户要求的提醒)：跳过已读不回判定，其余与 decide 相同
+    pub fn decide_delivery(&self, message: &str, context: &SessionContext) -> Vec<BehaviorAction> {
+        if !self.settings.enabled {
+            return vec![BehaviorAction::Speak(message.to_string())];
         }
 
         // 2. 检查是否触发撤回修正
         if self.should_trigger_typo() {
-            return self.build_typo_correction_chain(message);
+            if let Some(chain) = self.build_typo_correction_chain(message) {
+                return chain;
+            }
         }
 
         // 3. 计算延迟 (考虑角色状态)
@@ -98,8 +251,17 @@ impl BehaviorEngine {
             println!("🤔 延迟决策: 忙碌度调整回复率 {:.2}", adjusted_rate);
         }
 
-        let mut rng = rand::thread_rng();
-        if rng.gen::<f32>() < adjusted_rate {
+        // 按作息调整：睡觉时几乎不会回复
+        if let Some(ref activity) = context.activity {
+            adjusted_rate *= activity.availability;
+            println!(
+                "🤔 延迟决策: 作息调整回复率 {:.2} (正在{})",
+                adjusted_rate, activity.label
+            );
+        }
+
+        let roll = self.rng.borrow_mut().gen::<f32>();
+        let chain = if roll < adjusted_rate {
             println!("✅ 延迟后决定回复");
             // 决定回复,生成正常行为链
             let delay = self.calculate_delay(message, context);
@@ -109,7 +271,9 @@ impl BehaviorEngine {
             println!("❌ 延迟后决定不回复");
             // 决定不回复
             vec![BehaviorAction::Idle]
-        }
+        };
+        // 延迟结束时才看到消息
+        self.with_read_receipt(chain)
     }
 
     /// 判断是否应该忽略此消息 (已读不回)
@@ -141,11 +305,21 @@ impl BehaviorEngine {
             );
         }
 
+        // 根据当前活动增加忽略率
+        if let Some(ref activity) = context.activity {
+            // availability: 1.0 -> +0%, 0.0 -> +60% (睡觉时大概率看不到消息)
+            ignore_rate = (ignore_rate + (1.0 - activity.availability) * 0.6).min(1.0);
+            println!(
+                "📊 作息调整忽略率: {:.2} (正在{}, availability: {:.2})",
+                ignore_rate, activity.label, activity.availability
+            );
+        }
+
         if ignore_rate <= 0.0 {
             return None;
         }
 
-        let mut rng = rand::thread_rng();
+        let mut rng = self.rng.borrow_mut();
         if rng.gen::<f32>() < ignore_rate {
             // 检查是否配置了延迟决策
             if let Some(ref idle_config) = self.settings.behaviors.idle_delay_config {
@@ -163,8 +337,7 @@ impl BehaviorEngine {
     /// 判断是否触发撤回修正行为
     fn should_trigger_typo(&self) -> bool {
         if let Some(ref typo_config) = self.settings.behaviors.typo_correction {
-            let mut rng = rand::thread_rng();
-            return rng.gen::<f32>() < typo_config.trigger_rate;
+            return self.rng.borrow_mut().gen::<f32>() < typo_config.trigger_rate;
         }
         false
     }
@@ -205,81 +378,36 @@ impl BehaviorEngine {
         }
 
         // 5. 增加随机抖动 (Jitter) ±20%
-        let mut rng = rand::thread_rng();
-        let jitter = rng.gen_range(0.8..1.2);
+        let jitter = self.rng.borrow_mut().gen_range(0.8..1.2);
         base = (base as f32 * jitter) as u32;
 
         // 6. 限制在配置范围内
-        base.clamp(min, max)
-    }
+        base = base.clamp(min, max);
 
-    /// 智能拆分消息
-    /// 按自然断点(句号、换行符等)拆分, 增加随机性以模拟人类习惯
-    fn segment_message(&self, message: &str) -> Vec<String> {
-        let max_segments = self.settings.behaviors.multi_segment.unwrap_or(1) as usize;
-
-        if max_segments <= 1 {
-            return vec![message.to_string()];
-        }
-
-        let mut rng = rand::thread_rng();
-        // 1. 随机化本次回复的最大段数 (1 ~ max_segments)
-        let actual_max = rng.gen_range(1..=max_segments);
-
-        if actual_max <= 1 {
-            return vec![message.to_string()];
-        }
-
-        let mut segments = Vec::new();
-        let mut current = String::new();
-
-        for ch in message.chars() {
-            current.push(ch);
-
-            // 定义拆分符号
-            let is_delimiter = ch == '\n'
-                || ch == '。'
-                || ch == '.'
-                || ch == '!'
-                || ch == '?'
-                || ch == '！'
-                || ch == '？';
-
-            if is_delimiter && segments.len() < actual_max - 1 {
-                // 2. 优化：不再是逢标点就拆，而是看长度
-                // 只有当前累积的片段足够长时，才考虑拆分
-                let current_len = current.chars().count();
-
-                // 动态生成拆分阈值 (默认 40 ~ 100)，增加不可预测性
-                let (min_t, max_t) = self
-                    .settings
-                    .behaviors
-                    .segmentation_threshold_range
-                    .unwrap_or((40, 100));
-                let split_threshold = rng.gen_range(min_t..=max_t) as usize;
-
-                if current_len >= split_threshold {
-                    // 3. 概率降低到 30%，即使长句子也可能不拆
-                    if rng.gen::<f32>() < 0.3 {
-                        if !current.trim().is_empty() {
-                            segments.push(current.trim().to_string());
-                            current.clear();
-                        }
-                    }
-                }
-            }
+        // 7. 根据当前活动放大延迟 (在限制之后，睡觉或上班时可以超出配置范围)
+        if let Some(ref activity) = context.activity {
+            let factor = activity.kind.delay_factor();
+            base = (base as f32 * factor) as u32;
+            println!(
+                "🕰️ 作息调整延迟: 正在{}, factor={:.1}x",
+                activity.label, factor
+            );
         }
 
-        // 添加剩余部分
-        if !current.trim().is_empty() {
-            segments.push(current.trim().to_string());
-        }
+        base
+    }
 
-        if segments.is_empty() {
-            vec![message.to_string()]
-        } else {
-            segments
-        }
+    /// 智能拆分消息
+    /// 优先按模型输出的分段标记拆分，否则在句子边界处随机拆分 (代码块、列表等保持完整)
+    fn segment_message(&self, message: &str) -> Vec<String> {
+        let behaviors = &self.settings.behaviors;
+        segmenter::segment_reply(
+            message,
+            behaviors.segment_delimiter.as_deref(),
+            behaviors.multi_segment.unwrap_or(1) as usize,
+            behaviors.segmentation_threshold_range.unwrap_or((40, 100)),
+            &mut *self.rng.borrow_mut(),
+        )
     }
 
     /// 构建普通行为链 (延迟 + 分段发送)
@@ -293,7 +421,7 @@ impl BehaviorEngine {
         let (min_f, max_f) = self.settings.behaviors.segment_delay_factor;
 
         // 发送每个分段
-        let mut rng = rand::thread_rng();
+        let mut rng = self.rng.borrow_mut();
         for (i, segment) in segments.iter().enumerate() {
             chain.push(BehaviorAction::Speak(segment.clone()));
 
@@ -311,53 +439,76 @@ impl BehaviorEngine {
 
     /// 构建撤回修正行为链
     /// 流程: 发送错误版本 -> 等待 -> 撤回 -> 等待 -> 发送修正版本
-    fn build_typo_correction_chain(&self, message: &str) -> Vec<BehaviorAction> {
-        let typo_version = self.introduce_typo(message);
-        let fix_delay = self
-            .settings
-            .behaviors
-            .typo_correction
-            .as_ref()
-            .map(|c| c.fix_delay_ms)
-            .unwrap_or(1500);
+    /// (启用 useEdit 时: 发送错误版本 -> 等待 -> 编辑为修正版本)
+    /// 消息里没有可以安全改错的文字时返回 None
+    fn build_typo_correction_chain(&self, message: &str) -> Option<Vec<BehaviorAction>> {
+        let message = segmenter::join_marked(
+            message,
+            self.settings.behaviors.segment_delimiter.as_deref(),
+        );
+        let mut chain = vec![BehaviorAction::Wait(800)]; // 初始延迟
+        chain.extend(self.typo_fix_actions(message)?);
+        Some(chain)
+    }
 
-        vec![
-            BehaviorAction::Wait(800), // 初始延迟
+    /// 发送带错字的版本并修正 (不含开头的延迟)
+    fn typo_fix_actions(&self, message: String) -> Option<Vec<BehaviorAction>> {
+        let typo_version = self.introduce_typo(&message);
+        if typo_version == message {
+            return None;
+        }
+        let typo_config = self.settings.behaviors.typo_correction.as_ref();
+        let fix_delay = typo_config.map(|c| c.fix_delay_ms).unwrap_or(1500);
+
+        if typo_config.is_some_and(|c| c.use_edit) {
+            return Some(vec![
+                BehaviorAction::Speak(typo_version),
+                BehaviorAction::Wait(fix_delay),
+                BehaviorAction::Edit(0, message),
+            ]);
+        }
+
+        Some(vec![
             BehaviorAction::Speak(typo_version),
             BehaviorAction::Wait(fix_delay), // 等待后发现"错误"
             BehaviorAction::Retract(0),      // 撤回最后一条消息 (0 表示最后一条)
             BehaviorAction::Wait(500),       // 短暂延迟
-            BehaviorAction::Speak(message.to_string()), // 发送修正版本
-        ]
+            BehaviorAction::Speak(message),  // 发送修正版本
+        ])
     }
 
     /// 引入"错别字"或小错误
-    /// 简单实现:随机替换一个字符或添加重复字符
+    /// 只改普通文字 (不碰代码、链接、数字等)：重复一个字，或中文换成常见别字、英文对调相邻字母
     fn introduce_typo(&self, message: &str) -> String {
-        if message.len() < 3 {
+        let candidates = segmenter::typo_candidates(message);
+        if candidates.is_empty() {
             return message.to_string();
         }
 
-        let mut rng = rand::thread_rng();
-        let chars: Vec<char> = message.chars().collect();
-        let mut typo_chars = chars.clone();
+        let mut rng = self.rng.borrow_mut();
+        let mut chars: Vec<char> = message.chars().collect();
+        let pos = candidates[rng.gen_range(0..candidates.len())];
+        let ch = chars[pos];
 
-        // 随机选择一个位置
-        let pos = rng.gen_range(1..chars.len() - 1);
-
-        // 50% 概率重复字符, 50% 概率替换为相似字符
+        // 50% 概率重复字符, 50% 概率替换/对调
         if rng.gen_bool(0.5) {
-            // 重复字符
-            typo_chars.insert(pos, chars[pos]);
+            chars.insert(pos, ch);
+        } else if ch.is_ascii_alphabetic() {
+            match chars.get(pos + 1) {
+                Some(&next) if next.is_ascii_alphabetic() && next != ch => chars.swap(pos, pos + 1),
+                _ => chars.insert(pos, ch),
+            }
         } else {
-            // 简单替换 (这里可以扩展为更智能的相似字符映射)
             let similar_chars = ['的', '地', '得', '在', '再', '做', '作'];
-            if let Some(&similar) = similar_chars.get(rng.gen_range(0..similar_chars.len())) {
-                typo_chars[pos] = similar;
+            let similar = similar_chars[rng.gen_range(0..similar_chars.len())];
+            if similar == ch {
+                chars.insert(pos, ch);
+            } else {
+                chars[pos] = similar;
             }
         }
 
-        typo_chars.into_iter().collect()
+        chars.into_iter().collect()
     }
 
     /// 获取动态主动发言参数
@@ -429,8 +580,20 @@ impl BehaviorEngine {
             }
         }
 
-        // 4. 在最终范围内随机选择
-        let mut rng = rand::thread_rng();
+        // 4. 作息影响 (睡觉时不主动发言，其余按可用度降低成功率)
+        if let Some(ref activity) = context.activity {
+            if activity.kind == ActivityKind::Sleeping {
+                success_rate = 0.0;
+            } else {
+                success_rate *= activity.availability;
+                let factor = 1.0 + (1.0 - activity.availability) * 0.5;
+                t_min *= factor;
+                t_max *= factor;
+            }
+        }
+
+        // 5. 在最终范围内随机选择
+        let mut rng = self.rng.borrow_mut();
         // 确保 min <= max
         if t_min > t_max {
             t_max = t_min;
@@ -469,12 +632,50 @@ mod tests {
             mood: None,
             busy_level: None,
             interest_level: None,
+            activity: None,
         };
 
         let chain = engine.decide("你好", &context);
         assert!(!chain.is_empty());
     }
 
+    #[test]
+    fn test_read_receipts_and_edit_typos() {
+        let mut settings = ImmersiveSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        let typo = settings.behaviors.typo_correction.as_mut().unwrap();
+        typo.trigger_rate = 1.0;
+        typo.use_edit = true;
+        let context = SessionContext {
+            session_id: 1,
+            contact_id: 1,
+            mood: None,
+            busy_level: None,
+            interest_level: None,
+            activity: None,
+        };
+
+        let chain = BehaviorEngine::with_seed(settings.clone(), 3).decide("今天天气不错", &context);
+        assert_eq!(chain.first(), Some(&BehaviorAction::MarkRead));
+        assert_eq!(
+            chain.last(),
+            Some(&BehaviorAction::Edit(0, "今天天气不错".into()))
+        );
+        assert!(!chain
+            .iter()
+            .any(|a| matches!(a, BehaviorAction::Retract(_))));
+
+        // 延迟决策时先不发已读，重新决策时再发
+        settings.behaviors.ignore_rate = 1.0;
+        let engine = BehaviorEngine::with_seed(settings, 3);
+        let chain = engine.decide("在吗", &context);
+        assert!(matches!(chain[..], [BehaviorAction::DelayedDecision(..)]));
+        let after = engine.decide_after_delay("在吗", &context);
+        assert_eq!(after.first(), Some(&BehaviorAction::MarkRead));
+    }
+
     #[test]
     fn test_message_segmentation() {
         let mut settings = ImmersiveSettings::default();
@@ -484,4 +685,140 @@ mod tests {
         let segments = engine.segment_message("第一句。第二句。第三句。");
         assert!(segments.len() <= 3);
     }
+
+    #[test]
+    fn test_streamed_reply() {
+        let mut settings = ImmersiveSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        settings.behaviors.typo_correction = None;
+        settings.behaviors.reply_delay = Some((1000, 1000));
+        settings.behaviors.segment_delimiter = Some("||".into());
+        let context = SessionContext {
+            session_id: 1,
+            contact_id: 1,
+            mood: None,
+            busy_level: None,
+            interest_level: None,
+            activity: None,
+        };
+
+        let engine = BehaviorEngine::with_seed(settings.clone(), 7);
+        let StreamOpening::Reply {
+            mut stream,
+            opening,
+        } = engine.open_stream(&context)
+        else {
+            panic!("不应忽略消息");
+        };
+        assert_eq!(opening, vec![BehaviorAction::MarkRead]);
+        assert!(engine.stream_chunk(&mut stream, "第一段", 100).is_empty());
+        // 首段写完时已经生成了 400ms，从回复延迟中扣除
+        assert_eq!(
+            engine.stream_chunk(&mut stream, "||第二", 400),
+            vec![vec![
+                BehaviorAction::Wait(600),
+                BehaviorAction::Speak("第一段".into()),
+            ]]
+        );
+        let rest = engine.finish_stream(*stream, 900);
+        assert!(matches!(
+            &rest[..],
+            [batch] if matches!(
+                &batch[..],
+                [BehaviorAction::Wait(200..=500), BehaviorAction::Speak(s)] if s == "第二"
+            )
+        ));
+
+        // 已读不回：完整回复生成后再生成延迟决策
+        settings.behaviors.ignore_rate = 1.0;
+        let engine = BehaviorEngine::with_seed(settings, 7);
+        let StreamOpening::Ignore(action) = engine.open_stream(&context) else {
+            panic!("应当忽略消息");
+        };
+        assert!(matches!(
+            &engine.ignored_chain(action, "完整回复")[..],
+            [BehaviorAction::DelayedDecision(_, m)] if m == "完整回复"
+        ));
+    }
+
+    #[test]
+    fn test_typos_leave_links_and_numbers_alone() {
+        let message = "链接是 https://example.com/a?b=1 ，价格 199.5 元，代码 `x_y`";
+        for seed in 0..40 {
+            let engine = BehaviorEngine::with_seed(ImmersiveSettings::default(), seed);
+            let typo = engine.introduce_typo(message);
+            assert_ne!(typo, message);
+            for token in ["https://example.com/a?b=1", "199.5", "`x_y`"] {
+                assert!(typo.contains(token), "{}", typo);
+            }
+        }
+
+        // 没有可改的文字时不走撤回修正
+        let mut settings = ImmersiveSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        settings
+            .behaviors
+            .typo_correction
+            .as_mut()
+            .unwrap()
+            .trigger_rate = 1.0;
+        let engine = BehaviorEngine::with_seed(settings, 1);
+        let context = SessionContext {
+            session_id: 1,
+            contact_id: 1,
+            mood: None,
+            busy_level: None,
+            interest_level: None,
+            activity: None,
+        };
+        let chain = engine.decide_delivery("https://example.com", &context);
+        assert!(!chain
+            .iter()
+            .any(|a| matches!(a, BehaviorAction::Retract(_) | BehaviorAction::Edit(..))));
+    }
+
+    #[test]
+    fn test_activity_modulation() {
+        let mut settings = ImmersiveSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        settings.behaviors.reply_delay = Some((500, 2000));
+        let free = SessionContext {
+            session_id: 1,
+            contact_id: 1,
+            mood: None,
+            busy_level: None,
+            interest_level: None,
+            activity: None,
+        };
+        let sleeping = SessionContext {
+            activity: Some(ActivityState {
+                kind: ActivityKind::Sleeping,
+                label: "睡觉".into(),
+                availability: 0.05,
+                local_time: "星期三 03:12".into(),
+                until: Some("07:30".into()),
+            }),
+            ..free.clone()
+        };
+
+        // 相同种子下只有作息不同：睡觉时延迟按系数放大并可超出配置上限
+        let message = "好的".repeat(100);
+        let awake = BehaviorEngine::with_seed(settings.clone(), 9).calculate_delay(&message, &free);
+        let asleep =
+            BehaviorEngine::with_seed(settings.clone(), 9).calculate_delay(&message, &sleeping);
+        assert_eq!(awake, 2000);
+        assert_eq!(asleep, 6000);
+
+        let engine = BehaviorEngine::with_seed(settings, 9);
+        let (_, rate, _) = engine.get_proactive_parameters(&free);
+        assert!(rate > 0.0);
+        let (_, rate, _) = engine.get_proactive_parameters(&sleeping);
+        assert_eq!(rate, 0.0);
+    }
 }
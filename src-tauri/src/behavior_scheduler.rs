@@ -1,38 +1,228 @@
-use crate::immersive_settings::BehaviorAction;
+use crate::behavior_engine::{BehaviorEngine, SessionContext};
+use crate::expression::{Attachment, Reaction, Sticker};
+use crate::immersive_settings::{BehaviorAction, ImmersiveSettings, OverduePolicy, ResumeConfig};
+use crate::proactive_trigger::{ProactiveTrigger, TriggerKind};
+use crate::routine::ActivityKind;
 use crate::social_db::SocialDbState;
+use futures_util::future::BoxFuture;
 use rand::Rng;
 
 use rusqlite;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, Manager};
-use tokio::sync::RwLock;
-use tokio::time::{sleep, Duration, Instant};
+use tokio::sync::{mpsc, Notify, RwLock};
+use tokio::time::Duration;
 use tokio_util::sync::CancellationToken;
 
-/// 会话活动追踪信息
+// ==================================================================================
+// 运行环境：调度器只通过下面的接口计时、发事件、读写数据库和生成主动消息，
+// 界面程序使用 AppHandle 的实现；测试可替换为内存实现并配合 tokio 暂停时间快进
+// ==================================================================================
+
+/// 时钟：行为链中的等待与空闲计时都经过它
+pub trait Clock: Send + Sync + 'static {
+    /// 当前时间 (Unix 毫秒)，会持久化到数据库，重启后继续计时
+    fn now_ms(&self) -> i64;
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// 系统时钟：墙上时间 + tokio 定时器
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// 前端事件输出
+pub trait EventSink: Send + Sync + 'static {
+    fn emit(&self, event: &str, payload: serde_json::Value);
+}
+
+/// 调度器发出的一条角色消息
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutgoingMessage {
+    pub contact_id: i64,
+    pub session_id: i64,
+    pub content: String,
+    /// 引用回复的消息 ID
+    pub quote_id: Option<i64>,
+    /// 图片或表情包
+    pub attachment: Option<Attachment>,
+}
+
+/// 调度器用到的持久化操作
+pub trait SchedulerStore: Send + Sync + 'static {
+    /// 保存一条角色消息，返回消息 ID
+    fn save_message(&self, message: &OutgoingMessage) -> Result<i64, String>;
+    fn delete_message(&self, message_id: i64) -> Result<(), String>;
+    /// 修改已发送消息的内容并记录编辑时间
+    fn edit_message(&self, message_id: i64, content: &str, at_ms: i64) -> Result<(), String>;
+    /// 给消息追加一个表情回应；消息不存在时返回 false
+    fn add_reaction(&self, message_id: i64, reaction: &Reaction) -> Result<bool, String>;
+    /// 把会话中未读的用户消息标记为已读，返回被标记的消息 ID
+    fn mark_read(&self, session_id: i64, at_ms: i64) -> Result<Vec<i64>, String>;
+    /// 会话中最近一条用户消息
+    fn latest_user_message(&self, session_id: i64) -> Result<Option<i64>, String>;
+    /// 按名称查找联系人素材库中的图片或表情包
+    fn find_sticker(&self, contact_id: i64, name: &str) -> Result<Option<Sticker>, String>;
+    /// 会话所属联系人、缓存的角色状态及 now_ms 时刻的作息活动；会话不存在时返回 None
+    fn session_context(&self, session_id: i64, now_ms: i64) -> Option<SessionContext>;
+
+    fn save_activity(&self, session_id: i64, activity: &SessionActivity) -> Result<(), String>;
+    fn load_activities(&self) -> Result<Vec<(i64, SessionActivity)>, String>;
+    /// 保存未完成的行为链 (每个会话中的每个联系人最多一条，覆盖旧记录)
+    fn save_pending(&self, pending: &PendingChain) -> Result<(), String>;
+    fn clear_pending(&self, session_id: i64, contact_id: i64) -> Result<(), String>;
+    fn load_pending(&self) -> Result<Vec<PendingChain>, String>;
+    /// 会话已删除：清除它的活动记录与未完成行为链
+    fn forget_session(&self, session_id: i64) -> Result<(), String>;
+
+    /// 到期的事件触发器及要发往的会话
+    fn due_triggers(&self, now_ms: i64) -> Result<Vec<(ProactiveTrigger, i64)>, String>;
+    /// 触发器已处理 (每年重复的推到下一次，其余删除)
+    fn complete_trigger(&self, trigger: &ProactiveTrigger, now_ms: i64) -> Result<(), String>;
+}
+
+/// 主动发言的原因，决定使用哪个提示词模板
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProactiveReason {
+    /// 会话闲置超过阈值
+    Idle { minutes: u64 },
+    /// 纪念日、跟进或提醒到期
+    Trigger(ProactiveTrigger),
+}
+
+/// 主动发言所需的设置与消息生成
+pub trait ProactiveSource: Send + Sync + 'static {
+    /// 当前沉浸式设置；读取失败返回 None (本轮跳过)
+    fn settings(&self) -> BoxFuture<'_, Option<ImmersiveSettings>>;
+    /// 生成主动消息；返回空字符串表示本次不发言
+    fn compose(
+        &self,
+        context: SessionContext,
+        reason: ProactiveReason,
+    ) -> BoxFuture<'_, Result<String, String>>;
+}
+
+/// 调度器运行环境
 #[derive(Clone)]
-struct SessionActivity {
-    last_interaction: Instant,
-    last_proactive: Option<Instant>,
+pub struct SchedulerEnv {
+    pub clock: Arc<dyn Clock>,
+    pub events: Arc<dyn EventSink>,
+    pub store: Arc<dyn SchedulerStore>,
+    pub proactive: Arc<dyn ProactiveSource>,
+    /// 启动后台任务 (界面程序使用 tauri 运行时，测试使用当前 tokio 运行时)
+    pub spawn: fn(BoxFuture<'static, ()>),
 }
 
-/// 消息调度器 - 管理异步行为链的执行
-pub struct MessageScheduler {
-    /// 活跃任务的取消令牌 (session_id -> CancellationToken)
-    active_tasks: Arc<RwLock<HashMap<i64, CancellationToken>>>,
+impl SchedulerEnv {
+    pub fn for_app(app: AppHandle) -> Self {
+        let host = Arc::new(AppHost { app });
+        Self {
+            clock: Arc::new(SystemClock),
+            events: host.clone(),
+            store: host.clone(),
+            proactive: host,
+            spawn: |task| {
+                tauri::async_runtime::spawn(task);
+            },
+        }
+    }
+
+    fn typing(&self, contact_id: i64, is_typing: bool) {
+        self.events.emit(
+            "typing-status",
+            serde_json::json!({
+                "contactId": contact_id,
+                "isTyping": is_typing
+            }),
+        );
+    }
+}
+
+/// 会话活动追踪信息 (Unix 毫秒)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionActivity {
+    pub last_interaction: i64,
+    pub last_proactive: Option<i64>,
+}
+
+/// 持久化的未完成行为链，用于重启后恢复
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingChain {
+    pub session_id: i64,
+    pub contact_id: i64,
+    /// 剩余行为，第一个是正在执行 (或即将执行) 的行为
+    pub actions: Vec<BehaviorAction>,
+    /// 第一个剩余行为开始执行的时间 (Unix 毫秒)
+    pub started_at: i64,
+    /// 撤回 (Retract(0)) 的目标
+    pub last_message_id: Option<i64>,
+}
+
+impl PendingChain {
+    /// 计算重启后要执行的行为：未到期的等待扣除已过去的时间；
+    /// 已过期的行为按策略立即执行或丢弃 (返回 None)
+    pub fn resume_plan(&self, now_ms: i64, config: &ResumeConfig) -> Option<Vec<BehaviorAction>> {
+        let mut actions = self.actions.clone();
+        let elapsed = (now_ms - self.started_at).max(0) as u64;
+        let overdue = match actions.first_mut()? {
+            BehaviorAction::Wait(ms) | BehaviorAction::DelayedDecision(ms, _) => {
+                if elapsed < *ms as u64 {
+                    *ms -= elapsed as u32;
+                    return Some(actions);
+                }
+                let overdue = elapsed - *ms as u64;
+                *ms = 0;
+                overdue
+            }
+            _ => elapsed,
+        };
+        let within_limit = overdue <= config.max_overdue_min as u64 * 60_000;
+        (config.overdue_policy == OverduePolicy::Execute && within_limit).then_some(actions)
+    }
+}
+
+/// 调度状态 (可克隆，供后台任务使用)
+#[derive(Clone)]
+struct SchedulerCore {
+    /// 活跃任务的取消令牌 ((session_id, contact_id) -> CancellationToken)
+    /// 群聊中每个参与者的行为链各自独立，互不打断
+    active_tasks: Arc<RwLock<HashMap<(i64, i64), CancellationToken>>>,
+    /// 群聊轮流发言的取消令牌 (session_id -> CancellationToken)
+    group_turns: Arc<RwLock<HashMap<i64, CancellationToken>>>,
     /// 会话活动追踪 (session_id -> SessionActivity)
     session_activities: Arc<RwLock<HashMap<i64, SessionActivity>>>,
+    env: SchedulerEnv,
+}
+
+/// 消息调度器 - 管理异步行为链的执行
+pub struct MessageScheduler {
+    core: SchedulerCore,
     /// IdleMonitor 取消令牌
     idle_monitor_token: CancellationToken,
+    /// 配置变化通知 (打断当前等待，立即按新设置重新计算检查间隔)
+    config_changed: Arc<Notify>,
 }
 
 impl MessageScheduler {
-    pub fn new() -> Self {
+    pub fn new(env: SchedulerEnv) -> Self {
         Self {
-            active_tasks: Arc::new(RwLock::new(HashMap::new())),
-            session_activities: Arc::new(RwLock::new(HashMap::new())),
+            core: SchedulerCore {
+                active_tasks: Arc::new(RwLock::new(HashMap::new())),
+                group_turns: Arc::new(RwLock::new(HashMap::new())),
+                session_activities: Arc::new(RwLock::new(HashMap::new())),
+                env,
+            },
             idle_monitor_token: CancellationToken::new(),
+            config_changed: Arc::new(Notify::new()),
         }
     }
 
@@ -42,100 +232,384 @@ impl MessageScheduler {
         session_id: i64,
         contact_id: i64,
         chain: Vec<BehaviorAction>,
-        context: crate::behavior_engine::SessionContext,
-        settings: crate::immersive_settings::ImmersiveSettings,
-        app: AppHandle,
+        context: SessionContext,
+        settings: ImmersiveSettings,
+    ) -> Result<(), String> {
+        self.core
+            .execute_behavior_chain(session_id, contact_id, chain, context, settings)
+            .await
+    }
+
+    /// 取消指定会话的所有行为
+    pub async fn cancel_session_behaviors(&self, session_id: i64) {
+        self.core.cancel_session_behaviors(session_id).await;
+    }
+
+    /// 取消所有活跃的行为
+    pub async fn cancel_all_behaviors(&self) {
+        for (_, token) in self.core.group_turns.write().await.drain() {
+            token.cancel();
+        }
+        let mut tasks = self.core.active_tasks.write().await;
+        for ((session_id, contact_id), token) in tasks.drain() {
+            token.cancel();
+            self.core.clear_pending(session_id, contact_id);
+            println!("[Scheduler] [STOP] Cancelled session {}", session_id);
+        }
+    }
+
+    // ========== 群聊轮流发言 ==========
+
+    /// 开始新一轮群聊发言：取消该会话上一轮尚未完成的发言，返回本轮的取消令牌
+    pub async fn begin_group_turns(&self, session_id: i64) -> CancellationToken {
+        self.core.cancel_session_behaviors(session_id).await;
+        let token = CancellationToken::new();
+        self.core
+            .group_turns
+            .write()
+            .await
+            .insert(session_id, token.clone());
+        token
+    }
+
+    /// 本轮发言结束 (已被新一轮取代时不做任何事)
+    pub async fn end_group_turns(&self, session_id: i64, token: &CancellationToken) {
+        let mut turns = self.core.group_turns.write().await;
+        if !token.is_cancelled() {
+            turns.remove(&session_id);
+        }
+    }
+
+    /// 执行某个参与者的行为链并等待其完成 (后面的发言者需要看到前面的回复)
+    ///
+    /// 行为链的令牌是本轮发言令牌的子令牌，新一轮开始时一并取消
+    pub async fn run_participant_chain(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        chain: Vec<BehaviorAction>,
+        context: SessionContext,
+        settings: ImmersiveSettings,
+        turns: &CancellationToken,
+    ) {
+        if turns.is_cancelled() {
+            return;
+        }
+        // 群聊不登记到空闲监控：空闲主动消息按单人会话的提示词与历史生成
+        let token = self
+            .core
+            .register_task(session_id, contact_id, turns.child_token())
+            .await;
+        let chain = self.core.pending_chain(session_id, contact_id, chain, None);
+        self.core.run_chain(chain, context, settings, token).await;
+    }
+
+    /// 流水线执行：回复还在生成时就登记行为链，之后按段送入行为
+    ///
+    /// 返回的令牌被取消 (用户发了新消息或手动取消) 时，调用方应中止上游请求；
+    /// 发送端全部释放后行为链在执行完已送入的行为后结束
+    pub async fn begin_streamed_chain(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        context: SessionContext,
+        settings: ImmersiveSettings,
+    ) -> (
+        CancellationToken,
+        mpsc::UnboundedSender<Vec<BehaviorAction>>,
+    ) {
+        self.core.touch_session(session_id).await;
+        let token = self
+            .core
+            .register_task(session_id, contact_id, CancellationToken::new())
+            .await;
+
+        let (sender, batches) = mpsc::unbounded_channel();
+        let core = self.core.clone();
+        let task_token = token.clone();
+        (self.core.env.spawn)(Box::pin(async move {
+            core.run_streamed_chain(
+                session_id, contact_id, batches, context, settings, task_token,
+            )
+            .await;
+        }));
+        (token, sender)
+    }
+
+    /// 启动时恢复持久化的会话活动与未完成的行为链 (后台执行)
+    pub fn restore(&self) {
+        let core = self.core.clone();
+        (self.core.env.spawn)(Box::pin(async move { core.restore().await }));
+    }
+
+    // ========== IdleMonitor 功能 ==========
+
+    /// 更新会话的最后交互时间
+    pub async fn touch_session(&self, session_id: i64) {
+        self.core.touch_session(session_id).await;
+    }
+
+    /// 启动 IdleMonitor 后台任务
+    pub fn start_idle_monitor(&self) {
+        let core = self.core.clone();
+        let token = self.idle_monitor_token.clone();
+        let config_changed = self.config_changed.clone();
+
+        (self.core.env.spawn)(Box::pin(async move {
+            loop {
+                // 获取配置的检查间隔范围
+                let (min_interval, max_interval) = core
+                    .env
+                    .proactive
+                    .settings()
+                    .await
+                    .and_then(|s| s.behaviors.proactive_initiation)
+                    .and_then(|c| c.idle_check_interval_range)
+                    .unwrap_or((30, 90));
+
+                let interval = {
+                    let mut rng = rand::thread_rng();
+                    rng.gen_range(min_interval..=max_interval)
+                };
+
+                tokio::select! {
+                    _ = core.env.clock.sleep(Duration::from_secs(interval)) => {
+                        // 执行空闲检查
+                        core.check_idle_sessions().await;
+                    }
+                    _ = config_changed.notified() => {
+                        println!("[闲置] 沉浸式设置已更新，重新计算检查间隔");
+                    }
+                    _ = token.cancelled() => {
+                        println!("[闲置] 监控已停止");
+                        break;
+                    }
+                }
+            }
+        }));
+
+        println!("[闲置] 监控已启动");
+    }
+
+    /// 沉浸式设置变化时调用，IdleMonitor 会立即按新配置继续
+    pub fn notify_config_changed(&self) {
+        self.config_changed.notify_one();
+    }
+
+    /// 停止 IdleMonitor
+    pub fn stop_idle_monitor(&self) {
+        self.idle_monitor_token.cancel();
+    }
+}
+
+impl SchedulerCore {
+    async fn execute_behavior_chain(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        chain: Vec<BehaviorAction>,
+        context: SessionContext,
+        settings: ImmersiveSettings,
     ) -> Result<(), String> {
         // 1. 更新会话活动时间
         self.touch_session(session_id).await;
 
-        // 2. 取消该会话的现有任务
-        self.cancel_session_behaviors(session_id).await;
+        self.start_chain(session_id, contact_id, chain, context, settings, None)
+            .await;
+        Ok(())
+    }
 
-        // 3. 创建新的取消令牌
-        let token = CancellationToken::new();
+    /// 取消会话现有任务并在后台执行新的行为链
+    async fn start_chain(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        chain: Vec<BehaviorAction>,
+        context: SessionContext,
+        settings: ImmersiveSettings,
+        last_message_id: Option<i64>,
+    ) {
+        // 2-3. 取消该联系人在会话中的现有任务并创建新的取消令牌
+        let token = self
+            .register_task(session_id, contact_id, CancellationToken::new())
+            .await;
+
+        // 4. 异步执行行为链
+        let chain = self.pending_chain(session_id, contact_id, chain, last_message_id);
+        let core = self.clone();
+        (self.env.spawn)(Box::pin(async move {
+            core.run_chain(chain, context, settings, token).await;
+        }));
+    }
+
+    /// 取消 (会话, 联系人) 的现有任务并登记新的取消令牌
+    async fn register_task(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        token: CancellationToken,
+    ) -> CancellationToken {
+        let mut tasks = self.active_tasks.write().await;
+        if let Some(old) = tasks.insert((session_id, contact_id), token.clone()) {
+            old.cancel();
+            println!(
+                "[Scheduler] [STOP] Replaced chain of contact {} in session {}",
+                contact_id, session_id
+            );
+        }
+        token
+    }
+
+    fn pending_chain(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        actions: Vec<BehaviorAction>,
+        last_message_id: Option<i64>,
+    ) -> PendingChain {
+        PendingChain {
+            session_id,
+            contact_id,
+            actions,
+            started_at: self.env.clock.now_ms(),
+            last_message_id,
+        }
+    }
+
+    async fn run_chain(
+        &self,
+        chain: PendingChain,
+        context: SessionContext,
+        settings: ImmersiveSettings,
+        token: CancellationToken,
+    ) {
+        let (session_id, contact_id) = (chain.session_id, chain.contact_id);
+        let result =
+            Self::execute_chain_internal(&self.env, chain, context, settings, token.clone()).await;
+        self.finish_task(session_id, contact_id, &token, result)
+            .await;
+    }
+
+    /// 执行完成后清理 (已被取消时令牌与持久化记录可能已属于新的行为链)
+    async fn finish_task(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        token: &CancellationToken,
+        result: Result<(), String>,
+    ) {
         {
             let mut tasks = self.active_tasks.write().await;
-            tasks.insert(session_id, token.clone());
+            if !token.is_cancelled() {
+                tasks.remove(&(session_id, contact_id));
+                self.clear_pending(session_id, contact_id);
+            }
         }
 
-        // 4. 异步执行行为链
-        let active_tasks = self.active_tasks.clone();
-        tauri::async_runtime::spawn(async move {
-            let result = Box::pin(Self::execute_chain_internal(
-                session_id,
-                contact_id,
+        if let Err(e) = result {
+            eprintln!("❌ 行为链执行失败: {}", e);
+        }
+    }
+
+    /// 流水线执行：行为按批从通道送入，依次执行直到通道关闭
+    /// 等待下一批时显示"正在输入" (回复还在生成)
+    async fn run_streamed_chain(
+        &self,
+        session_id: i64,
+        contact_id: i64,
+        mut batches: mpsc::UnboundedReceiver<Vec<BehaviorAction>>,
+        context: SessionContext,
+        settings: ImmersiveSettings,
+        token: CancellationToken,
+    ) {
+        let mut result = Ok(());
+        loop {
+            let batch = match batches.try_recv() {
+                Ok(batch) => Some(batch),
+                Err(mpsc::error::TryRecvError::Disconnected) => None,
+                Err(mpsc::error::TryRecvError::Empty) => {
+                    self.env.typing(contact_id, true);
+                    tokio::select! {
+                        batch = batches.recv() => batch,
+                        _ = token.cancelled() => None,
+                    }
+                }
+            };
+            let Some(actions) = batch else {
+                break;
+            };
+
+            let chain = self.pending_chain(session_id, contact_id, actions, None);
+            result = Self::execute_chain_internal(
+                &self.env,
                 chain,
-                context,
-                settings,
+                context.clone(),
+                settings.clone(),
                 token.clone(),
-                app,
-            ))
+            )
             .await;
-
-            // 5. 执行完成后清理
-            {
-                let mut tasks = active_tasks.write().await;
-                tasks.remove(&session_id);
-            }
-
-            if let Err(e) = result {
-                eprintln!("❌ 行为链执行失败: {}", e);
+            if result.is_err() || token.is_cancelled() {
+                break;
             }
-        });
+            // 这一批已执行完，避免重启后重复发送
+            self.clear_pending(session_id, contact_id);
+        }
 
-        Ok(())
+        self.env.typing(contact_id, false);
+        self.finish_task(session_id, contact_id, &token, result)
+            .await;
     }
 
     /// 内部执行逻辑 (可被取消)
     async fn execute_chain_internal(
-        session_id: i64,
-        contact_id: i64,
-        chain: Vec<BehaviorAction>,
-        context: crate::behavior_engine::SessionContext,
-        settings: crate::immersive_settings::ImmersiveSettings,
+        env: &SchedulerEnv,
+        chain: PendingChain,
+        context: SessionContext,
+        settings: ImmersiveSettings,
         token: CancellationToken,
-        app: AppHandle,
     ) -> Result<(), String> {
-        let mut last_message_id: Option<i64> = None;
-
-        for action in chain {
+        let PendingChain {
+            session_id,
+            contact_id,
+            actions,
+            mut last_message_id,
+            ..
+        } = chain;
+        let mut queue = VecDeque::from(actions);
+
+        while let Some(action) = queue.pop_front() {
             // 检查是否被取消
             if token.is_cancelled() {
                 println!("[调度器] [停止] 已取消 (SID: {})", session_id);
                 // 清除打字状态
-                let _ = app.emit(
-                    "typing-status",
-                    serde_json::json!({
-                        "contactId": contact_id,
-                        "isTyping": false
-                    }),
-                );
+                env.typing(contact_id, false);
                 return Ok(());
             }
 
+            // 记录剩余行为，应用中途退出后可以恢复
+            let pending = PendingChain {
+                session_id,
+                contact_id,
+                actions: std::iter::once(action.clone())
+                    .chain(queue.iter().cloned())
+                    .collect(),
+                started_at: env.clock.now_ms(),
+                last_message_id,
+            };
+            if let Err(e) = env.store.save_pending(&pending) {
+                println!("⚠️ [调度器] 保存未完成行为失败: {}", e);
+            }
+
             match action {
                 BehaviorAction::Wait(ms) => {
                     // 显示"正在输入"
-                    let _ = app.emit(
-                        "typing-status",
-                        serde_json::json!({
-                            "contactId": contact_id,
-                            "isTyping": true
-                        }),
-                    );
+                    env.typing(contact_id, true);
 
                     // 可中断的等待
                     tokio::select! {
-                        _ = sleep(Duration::from_millis(ms as u64)) => {},
+                        _ = env.clock.sleep(Duration::from_millis(ms as u64)) => {},
                         _ = token.cancelled() => {
-                            let _ = app.emit(
-                                "typing-status",
-                                serde_json::json!({
-                                    "contactId": contact_id,
-                                    "isTyping": false
-                                }),
-                            );
+                            env.typing(contact_id, false);
                             return Ok(());
                         }
                     }
@@ -143,47 +617,133 @@ impl MessageScheduler {
 
                 BehaviorAction::Speak(content) => {
                     // 清除打字状态
-                    let _ = app.emit(
-                        "typing-status",
+                    env.typing(contact_id, false);
+
+                    // 保存消息到数据库并发送消息事件到前端
+                    let message_id = Self::send_message(
+                        env,
+                        OutgoingMessage {
+                            contact_id,
+                            session_id,
+                            content,
+                            quote_id: None,
+                            attachment: None,
+                        },
+                    )?;
+                    last_message_id = Some(message_id);
+                }
+
+                BehaviorAction::Quote(quoted_id, content) => {
+                    env.typing(contact_id, false);
+
+                    // 0 表示引用最近一条用户消息
+                    let quote_id = if quoted_id == 0 {
+                        env.store.latest_user_message(session_id)?
+                    } else {
+                        Some(quoted_id)
+                    };
+                    let message_id = Self::send_message(
+                        env,
+                        OutgoingMessage {
+                            contact_id,
+                            session_id,
+                            content,
+                            quote_id,
+                            attachment: None,
+                        },
+                    )?;
+                    last_message_id = Some(message_id);
+                }
+
+                BehaviorAction::Sticker(name) => {
+                    // 素材已被删除时跳过
+                    let Some(sticker) = env.store.find_sticker(contact_id, &name)? else {
+                        println!("⚠️ [调度器] 素材不存在: {}", name);
+                        continue;
+                    };
+                    env.typing(contact_id, false);
+                    let message_id = Self::send_message(
+                        env,
+                        OutgoingMessage {
+                            contact_id,
+                            session_id,
+                            content: format!("[{}]", sticker.name),
+                            quote_id: None,
+                            attachment: Some(Attachment::from(&sticker)),
+                        },
+                    )?;
+                    last_message_id = Some(message_id);
+                }
+
+                BehaviorAction::React(msg_id, emoji) => {
+                    // 0 表示最近一条用户消息
+                    let target_id = if msg_id == 0 {
+                        env.store.latest_user_message(session_id)?
+                    } else {
+                        Some(msg_id)
+                    };
+                    let Some(target_id) = target_id else {
+                        continue;
+                    };
+                    let reaction = Reaction {
+                        contact_id,
+                        emoji,
+                        reacted_at: env.clock.now_ms(),
+                    };
+                    // 目标消息已被删除时跳过
+                    if !env.store.add_reaction(target_id, &reaction)? {
+                        println!("⚠️ [调度器] 回应的消息不存在 (ID: {})", target_id);
+                        continue;
+                    }
+                    env.events.emit(
+                        "message-reaction",
                         serde_json::json!({
+                            "messageId": target_id,
                             "contactId": contact_id,
-                            "isTyping": false
+                            "sessionId": session_id,
+                            "emoji": reaction.emoji,
+                            "reactedAt": reaction.reacted_at,
                         }),
                     );
+                    println!("[调度器] 表情回应 {} (ID: {})", reaction.emoji, target_id);
+                }
 
-                    // 保存消息到数据库
-                    let db_state = app.state::<SocialDbState>();
-                    let conn = db_state
-                        .0
-                        .lock()
-                        .map_err(|e: std::sync::PoisonError<_>| e.to_string())?;
-
-                    let message_id = conn
-                        .query_row(
-                            "INSERT INTO social_messages (contact_id, session_id, role, content, created_at)
-                             VALUES (?1, ?2, 'assistant', ?3, datetime('now'))
-                             RETURNING id",
-                            rusqlite::params![contact_id, session_id, content],
-                            |row| row.get::<_, i64>(0),
-                        )
-                        .map_err(|e| e.to_string())?;
-
-                    last_message_id = Some(message_id);
+                BehaviorAction::MarkRead => {
+                    let read_at = env.clock.now_ms();
+                    let message_ids = env.store.mark_read(session_id, read_at)?;
+                    if !message_ids.is_empty() {
+                        env.events.emit(
+                            "messages-read",
+                            serde_json::json!({
+                                "messageIds": message_ids,
+                                "contactId": contact_id,
+                                "sessionId": session_id,
+                                "readAt": read_at,
+                            }),
+                        );
+                    }
+                }
 
-                    // 发送消息事件到前端
-                    let _ = app.emit(
-                        "new-social-message",
+                BehaviorAction::Edit(msg_id, content) => {
+                    // 0 表示编辑最后一条消息
+                    let target_id = if msg_id == 0 {
+                        last_message_id.ok_or("没有可编辑的消息")?
+                    } else {
+                        msg_id
+                    };
+                    let edited_at = env.clock.now_ms();
+                    env.store.edit_message(target_id, &content, edited_at)?;
+                    env.events.emit(
+                        "message-edited",
                         serde_json::json!({
-                            "messageId": message_id,
+                            "messageId": target_id,
                             "contactId": contact_id,
                             "sessionId": session_id,
-                            "role": "assistant",
                             "content": content,
-                            "createdAt": chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+                            "editedAt": edited_at,
                         }),
                     );
-
-                    println!("[调度器] 说话: {} (ID: {})", content, message_id);
+                    println!("[调度器] 编辑 (ID: {})", target_id);
                 }
 
                 BehaviorAction::Retract(msg_id) => {
@@ -195,20 +755,10 @@ impl MessageScheduler {
                     };
 
                     // 从数据库删除消息
-                    let db_state = app.state::<SocialDbState>();
-                    let conn = db_state
-                        .0
-                        .lock()
-                        .map_err(|e: std::sync::PoisonError<_>| e.to_string())?;
-
-                    conn.execute(
-                        "DELETE FROM social_messages WHERE id = ?1",
-                        rusqlite::params![target_id],
-                    )
-                    .map_err(|e| e.to_string())?;
+                    env.store.delete_message(target_id)?;
 
                     // 发送撤回事件到前端
-                    let _ = app.emit(
+                    env.events.emit(
                         "message-retracted",
                         serde_json::json!({
                             "messageId": target_id,
@@ -238,23 +788,17 @@ impl MessageScheduler {
 
                     // 可中断的等待
                     tokio::select! {
-                        _ = sleep(Duration::from_millis(delay_ms as u64)) => {
+                        _ = env.clock.sleep(Duration::from_millis(delay_ms as u64)) => {
                             println!("[调度器] 延迟结束, 重新决策 (SID: {})", session_id);
 
                             // 重新获取角色状态并在延迟后重新决策
-                            let engine = crate::behavior_engine::BehaviorEngine::new(settings.clone());
+                            let engine = BehaviorEngine::new(settings.clone());
                             let sub_chain = engine.decide_after_delay(&original_message, &context);
 
-                            // 递归执行新的行为链 (注意: 这里依然使用当前的 token)
-                            Box::pin(Self::execute_chain_internal(
-                                session_id,
-                                contact_id,
-                                sub_chain,
-                                context.clone(),
-                                settings.clone(),
-                                token.clone(),
-                                app.clone(),
-                            )).await?;
+                            // 新的行为链插到剩余行为之前 (依然使用当前的 token)
+                            for sub_action in sub_chain.into_iter().rev() {
+                                queue.push_front(sub_action);
+                            }
                         }
                         _ = token.cancelled() => {
                             println!("[调度器] [停止] 延迟已取消 (SID: {})", session_id);
@@ -266,205 +810,208 @@ impl MessageScheduler {
         }
 
         // 确保最后清除打字状态
-        let _ = app.emit(
-            "typing-status",
+        env.typing(contact_id, false);
+
+        Ok(())
+    }
+
+    /// 保存角色消息并通知前端，返回消息 ID
+    fn send_message(env: &SchedulerEnv, message: OutgoingMessage) -> Result<i64, String> {
+        let message_id = env.store.save_message(&message)?;
+        env.events.emit(
+            "new-social-message",
             serde_json::json!({
-                "contactId": contact_id,
-                "isTyping": false
+                "messageId": message_id,
+                "contactId": message.contact_id,
+                "sessionId": message.session_id,
+                "role": "assistant",
+                "content": message.content,
+                "quoteId": message.quote_id,
+                "attachment": message.attachment,
+                "createdAt": chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
             }),
         );
-
-        Ok(())
+        println!("[调度器] 说话: {} (ID: {})", message.content, message_id);
+        Ok(message_id)
     }
 
-    /// 取消指定会话的所有行为
-    pub async fn cancel_session_behaviors(&self, session_id: i64) {
-        let mut tasks = self.active_tasks.write().await;
-        if let Some(token) = tasks.remove(&session_id) {
+    async fn cancel_session_behaviors(&self, session_id: i64) {
+        if let Some(token) = self.group_turns.write().await.remove(&session_id) {
             token.cancel();
-            println!("[Scheduler] [STOP] Cancelled session {}", session_id);
         }
-    }
-
-    /// 取消所有活跃的行为
-    pub async fn cancel_all_behaviors(&self) {
         let mut tasks = self.active_tasks.write().await;
-        for (session_id, token) in tasks.drain() {
-            token.cancel();
-            println!("[Scheduler] [STOP] Cancelled session {}", session_id);
+        let keys: Vec<(i64, i64)> = tasks
+            .keys()
+            .filter(|(sid, _)| *sid == session_id)
+            .copied()
+            .collect();
+        for key in keys {
+            if let Some(token) = tasks.remove(&key) {
+                token.cancel();
+                self.clear_pending(key.0, key.1);
+                println!(
+                    "[Scheduler] [STOP] Cancelled session {} (contact {})",
+                    key.0, key.1
+                );
+            }
         }
     }
 
-    // ========== IdleMonitor 功能 ==========
-
-    /// 更新会话的最后交互时间
-    pub async fn touch_session(&self, session_id: i64) {
-        let mut activities = self.session_activities.write().await;
-
-        // 先读取旧的 last_proactive 值
-        let last_proactive = activities.get(&session_id).and_then(|a| a.last_proactive);
-
-        // 然后插入新的活动记录
-        activities.insert(
-            session_id,
-            SessionActivity {
-                last_interaction: Instant::now(),
-                last_proactive,
-            },
-        );
+    fn clear_pending(&self, session_id: i64, contact_id: i64) {
+        if let Err(e) = self.env.store.clear_pending(session_id, contact_id) {
+            println!("⚠️ [调度器] 清除未完成行为失败: {}", e);
+        }
     }
 
-    /// 启动 IdleMonitor 后台任务
-    pub fn start_idle_monitor(&self, app: AppHandle) {
-        let activities = self.session_activities.clone();
-        let token = self.idle_monitor_token.clone();
+    fn save_activity(&self, session_id: i64, activity: &SessionActivity) {
+        if let Err(e) = self.env.store.save_activity(session_id, activity) {
+            println!("⚠️ [调度器] 保存会话活动失败: {}", e);
+        }
+    }
 
-        tauri::async_runtime::spawn(async move {
-            loop {
-                // 获取配置的检查间隔范围
-                let (min_interval, max_interval) = {
-                    match crate::commands::config_cmd::load_config(app.clone()).await {
-                        Ok(config) => config
-                            .immersive_mode
-                            .behaviors
-                            .proactive_initiation
-                            .as_ref()
-                            .and_then(|c| c.idle_check_interval_range)
-                            .unwrap_or((30, 90)),
-                        Err(_) => (30, 90),
+    async fn restore(&self) {
+        let now = self.env.clock.now_ms();
+
+        // 1. 会话活动：重启后空闲监控继续追踪这些会话
+        match self.env.store.load_activities() {
+            Ok(records) => {
+                let mut activities = self.session_activities.write().await;
+                let mut restored = 0;
+                for (session_id, activity) in records {
+                    if self.env.store.session_context(session_id, now).is_none() {
+                        let _ = self.env.store.forget_session(session_id);
+                        continue;
                     }
-                };
-
-                let interval = {
-                    let mut rng = rand::thread_rng();
-                    rng.gen_range(min_interval..=max_interval)
-                };
+                    activities.entry(session_id).or_insert(activity);
+                    restored += 1;
+                }
+                println!("[调度器] 已恢复 {} 个会话的活动记录", restored);
+            }
+            Err(e) => println!("⚠️ [调度器] 读取会话活动失败: {}", e),
+        }
 
-                tokio::select! {
-                    _ = sleep(Duration::from_secs(interval as u64)) => {
-                        // 执行空闲检查
-                        Self::check_idle_sessions(app.clone(), activities.clone()).await;
-                    }
-                    _ = token.cancelled() => {
-                        println!("[闲置] 监控已停止");
-                        break;
-                    }
+        // 2. 未完成的行为链
+        let pending = match self.env.store.load_pending() {
+            Ok(p) => p,
+            Err(e) => {
+                println!("⚠️ [调度器] 读取未完成行为失败: {}", e);
+                return;
+            }
+        };
+        if pending.is_empty() {
+            return;
+        }
+        let settings = self.env.proactive.settings().await.unwrap_or_default();
+        for chain in pending {
+            let session_id = chain.session_id;
+            let context = match self.env.store.session_context(session_id, now) {
+                Some(ctx) => ctx,
+                None => {
+                    let _ = self.env.store.forget_session(session_id);
+                    continue;
+                }
+            };
+            match chain.resume_plan(now, &settings.behaviors.resume_config) {
+                Some(actions) => {
+                    println!(
+                        "[调度器] 恢复会话 {} 的 {} 个未完成行为",
+                        session_id,
+                        actions.len()
+                    );
+                    self.start_chain(
+                        session_id,
+                        chain.contact_id,
+                        actions,
+                        context,
+                        settings.clone(),
+                        chain.last_message_id,
+                    )
+                    .await;
+                }
+                None => {
+                    println!("[调度器] 会话 {} 的未完成行为已过期，丢弃", session_id);
+                    self.clear_pending(session_id, chain.contact_id);
                 }
             }
-        });
+        }
+    }
 
-        println!("[闲置] 监控已启动");
+    async fn touch_session(&self, session_id: i64) {
+        let mut activities = self.session_activities.write().await;
+
+        // 先读取旧的 last_proactive 值
+        let last_proactive = activities.get(&session_id).and_then(|a| a.last_proactive);
+
+        // 然后插入新的活动记录
+        let activity = SessionActivity {
+            last_interaction: self.env.clock.now_ms(),
+            last_proactive,
+        };
+        self.save_activity(session_id, &activity);
+        activities.insert(session_id, activity);
     }
 
-    /// 检查空闲会话并触发主动消息
-    async fn check_idle_sessions(
-        app: AppHandle,
-        activities: Arc<RwLock<HashMap<i64, SessionActivity>>>,
-    ) {
+    /// 检查到期的事件触发器与空闲会话，触发主动消息
+    async fn check_idle_sessions(&self) {
         // 1. 加载全局配置
-        let config = match crate::commands::config_cmd::load_config(app.clone()).await {
-            Ok(c) => c,
-            Err(_) => return,
+        let settings = match self.env.proactive.settings().await {
+            Some(s) => s,
+            None => return,
         };
-        let settings = config.immersive_mode;
 
-        // 如果沉浸式模式或主动发言未启用,直接返回
-        if !settings.enabled || settings.behaviors.proactive_initiation.is_none() {
+        // 如果沉浸式模式未启用,直接返回
+        if !settings.enabled {
+            return;
+        }
+
+        let now = self.env.clock.now_ms();
+        // 事件触发优先，本轮已因事件发言的会话不再参与空闲判定
+        let triggered = self.check_triggers(&settings, now).await;
+
+        if settings.behaviors.proactive_initiation.is_none() {
             return;
         }
 
-        let now = Instant::now();
+        let seconds_since = |then: i64| ((now - then).max(0) / 1000) as u64;
         let mut sessions_to_trigger = Vec::new();
 
         {
-            let activities_guard = activities.read().await;
-            let db_state = app.state::<SocialDbState>();
+            let activities_guard = self.session_activities.read().await;
 
             println!("[Idle] Checking {} sessions", activities_guard.len());
 
             for (session_id, activity) in activities_guard.iter() {
                 let session_id = *session_id;
-                let idle_duration = now.duration_since(activity.last_interaction);
-
-                // 获取联系人 ID (从活动记录中通过 session_id 无法直接获取, 除非我们也存储它)
-                // 这里我们假设 session_id 可以用来查询数据库获取上下文
-
-                // 2. 获取该会话的角色状态
-                let context = {
-                    // 查询数据库获取 contact_id
-                    let conn = match db_state.0.lock() {
-                        Ok(c) => c,
-                        Err(_) => continue,
-                    };
-
-                    let contact_id = conn
-                        .query_row(
-                            "SELECT contact_id FROM social_sessions WHERE id = ?1",
-                            rusqlite::params![session_id],
-                            |row| row.get::<_, i64>(0),
-                        )
-                        .ok();
-
-                    if let Some(contact_id) = contact_id {
-                        // 加载状态
-                        drop(conn); // 释放锁
-                        let state_val = crate::social_db::get_character_state(
-                            db_state.clone(),
-                            contact_id,
-                            session_id,
-                        )
-                        .ok()
-                        .flatten();
-
-                        let mut ctx = crate::behavior_engine::SessionContext {
-                            session_id,
-                            contact_id,
-                            mood: None,
-                            busy_level: None,
-                            interest_level: None,
-                        };
-
-                        if let Some(s) = state_val {
-                            ctx.mood = s.get("mood").and_then(|v| v.as_str()).map(String::from);
-                            ctx.busy_level = s
-                                .get("busyLevel")
-                                .and_then(|v| v.as_f64())
-                                .map(|f| f as f32);
-                            ctx.interest_level = s
-                                .get("interestLevel")
-                                .and_then(|v| v.as_f64())
-                                .map(|f| f as f32);
-                        }
-                        Some(ctx)
-                    } else {
-                        None
-                    }
-                };
+                if triggered.contains(&session_id) {
+                    continue;
+                }
+                let idle_secs = seconds_since(activity.last_interaction);
 
-                let context = match context {
+                // 2. 获取该会话的联系人与角色状态
+                let context = match self.env.store.session_context(session_id, now) {
                     Some(ctx) => ctx,
                     None => continue,
                 };
 
                 // 3. 计算动态参数
-                let engine = crate::behavior_engine::BehaviorEngine::new(settings.clone());
+                let engine = BehaviorEngine::new(settings.clone());
                 let (idle_threshold, success_rate, cooldown) =
                     engine.get_proactive_parameters(&context);
 
                 println!(
                     "[闲置] 会话 {} 统计: 闲置={}s (阈值={}s), 成功率={:.1}%, 冷却={}s",
                     session_id,
-                    idle_duration.as_secs(),
+                    idle_secs,
                     idle_threshold,
                     success_rate * 100.0,
                     cooldown
                 );
 
                 // 检查是否超过空闲阈值 (都是秒)
-                if idle_duration.as_secs() >= (idle_threshold as u64) {
+                if idle_secs >= (idle_threshold as u64) {
                     // 检查冷却时间
                     let is_cooled_down = if let Some(last_proactive) = activity.last_proactive {
-                        now.duration_since(last_proactive).as_secs() >= (cooldown as u64)
+                        seconds_since(last_proactive) >= (cooldown as u64)
                     } else {
                         true
                     };
@@ -482,7 +1029,7 @@ impl MessageScheduler {
                             roll < success_rate
                         );
                         if roll < success_rate {
-                            sessions_to_trigger.push((session_id, context.contact_id, context));
+                            sessions_to_trigger.push((session_id, context));
                         }
                     } else {
                         println!("[闲置] 会话 {} 冷却中", session_id);
@@ -494,161 +1041,122 @@ impl MessageScheduler {
         }
 
         // 5. 触发主动消息
-        for (session_id, _contact_id, context) in sessions_to_trigger {
-            let mut activities_guard = activities.write().await;
+        for (session_id, context) in sessions_to_trigger {
+            let mut activities_guard = self.session_activities.write().await;
             if let Some(activity) = activities_guard.get_mut(&session_id) {
                 println!("[闲置] 会话 {} 触发主动消息", session_id);
+                let minutes = seconds_since(activity.last_interaction) / 60;
                 activity.last_proactive = Some(now);
+                self.save_activity(session_id, activity);
+                self.spawn_proactive(context, ProactiveReason::Idle { minutes }, &settings);
+            }
+        }
+    }
 
-                // 获取 AI 响应并执行行为链
-                let app_clone = app.clone();
-                let settings_clone = settings.clone();
-                let context_clone = context.clone();
-
-                tauri::async_runtime::spawn(async move {
-                    // A. 获取对话历史 (最后 5 条)
-                    let history = {
-                        let db_state = app_clone.state::<SocialDbState>();
-                        let conn = match db_state.0.lock() {
-                            Ok(c) => c,
-                            Err(_) => return,
-                        };
-                        let mut stmt = match conn.prepare(
-                            "SELECT role, content FROM social_messages 
-                             WHERE session_id = ?1 
-                             ORDER BY id DESC LIMIT 5",
-                        ) {
-                            Ok(s) => s,
-                            Err(_) => return,
-                        };
-
-                        let history: Vec<crate::models::Message> = stmt
-                            .query_map(rusqlite::params![session_id], |row| {
-                                Ok(crate::models::Message {
-                                    id: None,
-                                    model: None,
-                                    role: row.get::<_, String>(0)?,
-                                    content: row.get::<_, String>(1)?,
-                                    reasoning_content: None,
-                                    file_metadata: None,
-                                    search_metadata: None,
-                                    provider: None,
-                                    mode: None,
-                                    role_id: None,
-                                })
-                            })
-                            .and_then(|iter| iter.collect())
-                            .unwrap_or_default();
-
-                        let mut history = history;
-                        history.reverse();
-                        history
-                    };
+    /// 处理到期的事件触发器，返回本轮已发言的会话
+    async fn check_triggers(&self, settings: &ImmersiveSettings, now: i64) -> Vec<i64> {
+        let config = &settings.behaviors.event_triggers;
+        let mut triggered = Vec::new();
+        if !config.enabled {
+            return triggered;
+        }
 
-                    // B. 加载提示词模板
-                    let prompt_template = match crate::character_state::StateAnalyzer::load_prompt_template("proactive_message.txt") {
-                        Ok(p) => p,
-                        Err(_) => "你正处于单人沉浸式聊天模式。由于对方长时间没说话，请根据当前气氛主动开启一个小话题或关怀。".to_string(),
-                    };
+        let due = match self.env.store.due_triggers(now) {
+            Ok(due) => due,
+            Err(e) => {
+                println!("⚠️ [触发器] 读取失败: {}", e);
+                return triggered;
+            }
+        };
 
-                    // C. 构建系统提示词
-                    let mood_str = context_clone.mood.as_deref().unwrap_or("neutral");
-                    let busy_str = context_clone
-                        .busy_level
-                        .map(|f| format!("{:.1}", f))
-                        .unwrap_or("0.5".to_string());
-                    let interest_str = context_clone
-                        .interest_level
-                        .map(|f| format!("{:.1}", f))
-                        .unwrap_or("0.5".to_string());
-
-                    let system_content = format!(
-                        "{}\n\n当前角色状态:\n- 心情: {}\n- 忙碌度: {}\n- 兴趣度: {}\n\n请直接输出你想说的内容，保持角色人设，语气自然。",
-                        prompt_template, mood_str, busy_str, interest_str
-                    );
+        for (trigger, session_id) in due {
+            // 每个会话每轮最多一条，其余留到下一轮
+            if triggered.contains(&session_id) {
+                continue;
+            }
+            let complete = |trigger: &ProactiveTrigger| {
+                if let Err(e) = self.env.store.complete_trigger(trigger, now) {
+                    println!("⚠️ [触发器] 更新 #{} 失败: {}", trigger.id, e);
+                }
+            };
 
-                    let mut full_messages = vec![crate::models::Message {
-                        id: None,
-                        model: None,
-                        role: "system".to_string(),
-                        content: system_content,
-                        reasoning_content: None,
-                        file_metadata: None,
-                        search_metadata: None,
-                        provider: None,
-                        mode: None,
-                        role_id: None,
-                    }];
-                    full_messages.extend(history);
-
-                    // D. 调用 AI
-                    let client = app_clone.state::<reqwest::Client>();
-
-                    // 加载 AI 配置
-                    let config =
-                        match crate::commands::config_cmd::load_config(app_clone.clone()).await {
-                            Ok(c) => c,
-                            Err(_) => return,
-                        };
-
-                    let provider_id = config.default_provider_id;
-                    let model = config.selected_model_id;
-
-                    let providers = config.providers.as_array().unwrap();
-                    let provider_config = providers
-                        .iter()
-                        .find(|p| p["id"].as_str() == Some(&provider_id))
-                        .unwrap();
-                    let api_key = provider_config["apiKey"].as_str().unwrap_or_default();
-                    let base_url = provider_config["baseUrl"].as_str().unwrap_or_default();
-
-                    let ai_response = if provider_id == "gemini" {
-                        crate::ai_utils::call_gemini_backend(
-                            &client,
-                            api_key,
-                            base_url,
-                            &model,
-                            full_messages,
-                        )
-                        .await
-                    } else {
-                        let payload = crate::models::ChatRequest {
-                            model: model.clone(),
-                            messages: full_messages,
-                            stream: false,
-                            temperature: Some(0.8),
-                            max_tokens: Some(512),
-                        };
-                        crate::ai_utils::call_ai_backend(&client, api_key, base_url, &payload).await
-                    };
+            // 错过太久 (例如应用没有运行) 的不再补发
+            if now - trigger.fire_at > config.max_late_hours as i64 * 3600 * 1000 {
+                println!("[触发器] #{} {} 已过期，跳过", trigger.id, trigger.title);
+                complete(&trigger);
+                continue;
+            }
 
-                    if let Ok(content) = ai_response {
-                        // E. 生成行为链并执行
-                        let engine =
-                            crate::behavior_engine::BehaviorEngine::new(settings_clone.clone());
-                        let chain = engine.decide(&content, &context_clone);
-
-                        // 获取主窗口执行
-                        let scheduler = app_clone.state::<Arc<MessageScheduler>>();
-                        let _ = scheduler
-                            .execute_behavior_chain(
-                                session_id,
-                                context_clone.contact_id,
-                                chain,
-                                context_clone,
-                                settings_clone,
-                                app_clone.clone(),
-                            )
-                            .await;
-                    }
-                });
+            let context = match self.env.store.session_context(session_id, now) {
+                Some(ctx) => ctx,
+                None => continue,
+            };
+            // 提醒按时发出；纪念日与跟进等角色醒来再说
+            let asleep = context
+                .activity
+                .as_ref()
+                .is_some_and(|a| a.kind == ActivityKind::Sleeping);
+            if asleep && trigger.kind != TriggerKind::Reminder {
+                println!("[触发器] #{} 角色正在睡觉，稍后再发", trigger.id);
+                continue;
             }
+
+            println!(
+                "[触发器] 会话 {} 触发 #{} {:?}: {}",
+                session_id, trigger.id, trigger.kind, trigger.title
+            );
+            complete(&trigger);
+            if let Some(activity) = self.session_activities.write().await.get_mut(&session_id) {
+                activity.last_proactive = Some(now);
+                self.save_activity(session_id, activity);
+            }
+            triggered.push(session_id);
+            self.spawn_proactive(context, ProactiveReason::Trigger(trigger), settings);
         }
+        triggered
     }
 
-    /// 停止 IdleMonitor
-    pub fn stop_idle_monitor(&self) {
-        self.idle_monitor_token.cancel();
+    /// 后台生成主动消息并按行为链发出
+    fn spawn_proactive(
+        &self,
+        context: SessionContext,
+        reason: ProactiveReason,
+        settings: &ImmersiveSettings,
+    ) {
+        let core = self.clone();
+        let settings = settings.clone();
+        (self.env.spawn)(Box::pin(async move {
+            let session_id = context.session_id;
+            let must_deliver = matches!(
+                reason,
+                ProactiveReason::Trigger(ref t) if t.kind == TriggerKind::Reminder
+            );
+            let content = match core.env.proactive.compose(context.clone(), reason).await {
+                Ok(c) => c,
+                Err(e) => {
+                    println!("⚠️ [闲置] 会话 {} 无法主动发言: {}", session_id, e);
+                    return;
+                }
+            };
+
+            // 模板约定不发言时输出空字符串
+            let trimmed = content.trim();
+            if trimmed.is_empty() || trimmed == "\"\"" {
+                println!("[闲置] 会话 {} 决定暂不主动发言", session_id);
+                return;
+            }
+
+            // 生成行为链并执行
+            let engine = BehaviorEngine::new(settings.clone());
+            let chain = if must_deliver {
+                engine.decide_delivery(&content, &context)
+            } else {
+                engine.decide(&content, &context)
+            };
+            let _ = core
+                .execute_behavior_chain(session_id, context.contact_id, chain, context, settings)
+                .await;
+        }));
     }
 }
 
@@ -660,33 +1168,1263 @@ impl Drop for MessageScheduler {
     }
 }
 
-impl Default for MessageScheduler {
-    fn default() -> Self {
-        Self::new()
+// ==================================================================================
+// 界面程序的运行环境实现
+// ==================================================================================
+
+impl SchedulerStore for SocialDbState {
+    fn save_message(&self, message: &OutgoingMessage) -> Result<i64, String> {
+        let attachment = message
+            .attachment
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "INSERT INTO social_messages
+                 (contact_id, session_id, role, content, quote_id, attachment, created_at)
+             VALUES (?1, ?2, 'assistant', ?3, ?4, ?5, datetime('now'))
+             RETURNING id",
+            rusqlite::params![
+                message.contact_id,
+                message.session_id,
+                message.content,
+                message.quote_id,
+                attachment
+            ],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    fn edit_message(&self, message_id: i64, content: &str, at_ms: i64) -> Result<(), String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE social_messages SET content = ?1, edited_at = ?2 WHERE id = ?3",
+            rusqlite::params![content, at_ms, message_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn add_reaction(&self, message_id: i64, reaction: &Reaction) -> Result<bool, String> {
+        use rusqlite::OptionalExtension;
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        let Some(existing) = conn
+            .query_row(
+                "SELECT reactions FROM social_messages WHERE id = ?1",
+                rusqlite::params![message_id],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(false);
+        };
+        let mut reactions: Vec<Reaction> = existing
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        // 同一联系人只保留最新的回应
+        reactions.retain(|r| r.contact_id != reaction.contact_id);
+        reactions.push(reaction.clone());
+        conn.execute(
+            "UPDATE social_messages SET reactions = ?1 WHERE id = ?2",
+            rusqlite::params![
+                serde_json::to_string(&reactions).map_err(|e| e.to_string())?,
+                message_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    fn mark_read(&self, session_id: i64, at_ms: i64) -> Result<Vec<i64>, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "UPDATE social_messages SET read_at = ?1
+                 WHERE session_id = ?2 AND role = 'user' AND read_at IS NULL
+                 RETURNING id",
+            )
+            .map_err(|e| e.to_string())?;
+        let mut ids = stmt
+            .query_map(rusqlite::params![at_ms, session_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<i64>, _>>()
+            .map_err(|e| e.to_string())?;
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn latest_user_message(&self, session_id: i64) -> Result<Option<i64>, String> {
+        use rusqlite::OptionalExtension;
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT id FROM social_messages
+             WHERE session_id = ?1 AND role = 'user'
+             ORDER BY id DESC LIMIT 1",
+            rusqlite::params![session_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    fn find_sticker(&self, contact_id: i64, name: &str) -> Result<Option<Sticker>, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        crate::expression::find_sticker(&conn, contact_id, name)
+    }
+
+    fn delete_message(&self, message_id: i64) -> Result<(), String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM social_messages WHERE id = ?1",
+            rusqlite::params![message_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn session_context(&self, session_id: i64, now_ms: i64) -> Option<SessionContext> {
+        let conn = self.0.lock().ok()?;
+        let contact_id = conn
+            .query_row(
+                "SELECT contact_id FROM social_sessions WHERE id = ?1",
+                rusqlite::params![session_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .ok()?;
+        let state = crate::character_state::CharacterState::load(&conn, contact_id, session_id)
+            .ok()
+            .flatten();
+
+        Some(SessionContext {
+            session_id,
+            contact_id,
+            mood: state.as_ref().map(|s| s.mood.clone()),
+            busy_level: state.as_ref().map(|s| s.busy_level),
+            interest_level: state.as_ref().map(|s| s.interest_level),
+            activity: chrono::DateTime::from_timestamp_millis(now_ms)
+                .and_then(|now| crate::routine::contact_activity(&conn, contact_id, now)),
+        })
+    }
+
+    fn save_activity(&self, session_id: i64, activity: &SessionActivity) -> Result<(), String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO scheduler_activity (session_id, last_interaction, last_proactive)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(session_id) DO UPDATE SET
+                 last_interaction = excluded.last_interaction,
+                 last_proactive = excluded.last_proactive",
+            rusqlite::params![
+                session_id,
+                activity.last_interaction,
+                activity.last_proactive
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn load_activities(&self) -> Result<Vec<(i64, SessionActivity)>, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT session_id, last_interaction, last_proactive FROM scheduler_activity
+                 WHERE session_id NOT IN (SELECT id FROM social_sessions WHERE is_group = 1)",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    SessionActivity {
+                        last_interaction: row.get(1)?,
+                        last_proactive: row.get(2)?,
+                    },
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    fn save_pending(&self, pending: &PendingChain) -> Result<(), String> {
+        let actions = serde_json::to_string(&pending.actions).map_err(|e| e.to_string())?;
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO scheduler_pending
+                 (session_id, contact_id, actions, started_at, last_message_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                pending.session_id,
+                pending.contact_id,
+                actions,
+                pending.started_at,
+                pending.last_message_id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn clear_pending(&self, session_id: i64, contact_id: i64) -> Result<(), String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM scheduler_pending WHERE session_id = ?1 AND contact_id = ?2",
+            rusqlite::params![session_id, contact_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn load_pending(&self) -> Result<Vec<PendingChain>, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT session_id, contact_id, actions, started_at, last_message_id
+                 FROM scheduler_pending",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let mut pending = Vec::new();
+        for (session_id, contact_id, actions, started_at, last_message_id) in rows {
+            // 无法解析的记录 (如旧版本写入) 直接跳过
+            match serde_json::from_str(&actions) {
+                Ok(actions) => pending.push(PendingChain {
+                    session_id,
+                    contact_id,
+                    actions,
+                    started_at,
+                    last_message_id,
+                }),
+                Err(e) => println!(
+                    "⚠️ [调度器] 会话 {} 的未完成行为无法解析: {}",
+                    session_id, e
+                ),
+            }
+        }
+        Ok(pending)
+    }
+
+    fn forget_session(&self, session_id: i64) -> Result<(), String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM scheduler_activity WHERE session_id = ?1",
+            rusqlite::params![session_id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM scheduler_pending WHERE session_id = ?1",
+            rusqlite::params![session_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn due_triggers(&self, now_ms: i64) -> Result<Vec<(ProactiveTrigger, i64)>, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        crate::proactive_trigger::due_triggers(&conn, now_ms)
+    }
+
+    fn complete_trigger(&self, trigger: &ProactiveTrigger, now_ms: i64) -> Result<(), String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        crate::proactive_trigger::complete_trigger(&conn, trigger, now_ms)
+    }
+}
+
+struct AppHost {
+    app: AppHandle,
+}
+
+impl EventSink for AppHost {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        let _ = self.app.emit(event, payload);
+    }
+}
+
+impl SchedulerStore for AppHost {
+    fn save_message(&self, message: &OutgoingMessage) -> Result<i64, String> {
+        self.app.state::<SocialDbState>().save_message(message)
+    }
+
+    fn delete_message(&self, message_id: i64) -> Result<(), String> {
+        self.app.state::<SocialDbState>().delete_message(message_id)
+    }
+
+    fn edit_message(&self, message_id: i64, content: &str, at_ms: i64) -> Result<(), String> {
+        self.app
+            .state::<SocialDbState>()
+            .edit_message(message_id, content, at_ms)
+    }
+
+    fn add_reaction(&self, message_id: i64, reaction: &Reaction) -> Result<bool, String> {
+        self.app
+            .state::<SocialDbState>()
+            .add_reaction(message_id, reaction)
+    }
+
+    fn mark_read(&self, session_id: i64, at_ms: i64) -> Result<Vec<i64>, String> {
+        self.app
+            .state::<SocialDbState>()
+            .mark_read(session_id, at_ms)
+    }
+
+    fn latest_user_message(&self, session_id: i64) -> Result<Option<i64>, String> {
+        self.app
+            .state::<SocialDbState>()
+            .latest_user_message(session_id)
+    }
+
+    fn find_sticker(&self, contact_id: i64, name: &str) -> Result<Option<Sticker>, String> {
+        self.app
+            .state::<SocialDbState>()
+            .find_sticker(contact_id, name)
+    }
+
+    fn session_context(&self, session_id: i64, now_ms: i64) -> Option<SessionContext> {
+        self.app
+            .state::<SocialDbState>()
+            .session_context(session_id, now_ms)
+    }
+
+    fn save_activity(&self, session_id: i64, activity: &SessionActivity) -> Result<(), String> {
+        self.app
+            .state::<SocialDbState>()
+            .save_activity(session_id, activity)
+    }
+
+    fn load_activities(&self) -> Result<Vec<(i64, SessionActivity)>, String> {
+        self.app.state::<SocialDbState>().load_activities()
+    }
+
+    fn save_pending(&self, pending: &PendingChain) -> Result<(), String> {
+        self.app.state::<SocialDbState>().save_pending(pending)
+    }
+
+    fn clear_pending(&self, session_id: i64, contact_id: i64) -> Result<(), String> {
+        self.app
+            .state::<SocialDbState>()
+            .clear_pending(session_id, contact_id)
+    }
+
+    fn load_pending(&self) -> Result<Vec<PendingChain>, String> {
+        self.app.state::<SocialDbState>().load_pending()
+    }
+
+    fn forget_session(&self, session_id: i64) -> Result<(), String> {
+        self.app.state::<SocialDbState>().forget_session(session_id)
+    }
+
+    fn due_triggers(&self, now_ms: i64) -> Result<Vec<(ProactiveTrigger, i64)>, String> {
+        self.app.state::<SocialDbState>().due_triggers(now_ms)
+    }
+
+    fn complete_trigger(&self, trigger: &ProactiveTrigger, now_ms: i64) -> Result<(), String> {
+        self.app
+            .state::<SocialDbState>()
+            .complete_trigger(trigger, now_ms)
+    }
+}
+
+impl ProactiveSource for AppHost {
+    fn settings(&self) -> BoxFuture<'_, Option<ImmersiveSettings>> {
+        Box::pin(async move {
+            crate::commands::config_cmd::load_config(self.app.clone())
+                .await
+                .ok()
+                .map(|c| c.immersive_mode)
+        })
+    }
+
+    fn compose(
+        &self,
+        context: SessionContext,
+        reason: ProactiveReason,
+    ) -> BoxFuture<'_, Result<String, String>> {
+        Box::pin(async move {
+            let session_id = context.session_id;
+
+            // A. 获取对话历史 (最后 5 条) 与模板变量
+            let (history, mut template_ctx) = {
+                let db_state = self.app.state::<SocialDbState>();
+                let conn = db_state.0.lock().map_err(|e| e.to_string())?;
+                let mut stmt = conn
+                    .prepare(
+                        "SELECT role, content FROM social_messages
+                         WHERE session_id = ?1
+                         ORDER BY id DESC LIMIT 5",
+                    )
+                    .map_err(|e| e.to_string())?;
+
+                let history: Vec<crate::models::Message> = stmt
+                    .query_map(rusqlite::params![session_id], |row| {
+                        Ok(crate::models::Message {
+                            id: None,
+                            model: None,
+                            role: row.get::<_, String>(0)?,
+                            content: row.get::<_, String>(1)?,
+                            reasoning_content: None,
+                            file_metadata: None,
+                            search_metadata: None,
+                            provider: None,
+                            mode: None,
+                            role_id: None,
+                            parent_id: None,
+                        })
+                    })
+                    .and_then(|iter| iter.collect())
+                    .unwrap_or_default();
+
+                let mut history = history;
+                history.reverse();
+
+                let mut template_ctx = crate::prompt_template::TemplateContext::new();
+                crate::prompt_template::load_user(&mut template_ctx, &conn);
+                let _ = crate::prompt_template::load_social(
+                    &mut template_ctx,
+                    &conn,
+                    context.contact_id,
+                    Some(session_id),
+                );
+                (history, template_ctx)
+            };
+
+            // 加载 AI 配置
+            let config = crate::commands::config_cmd::load_config(self.app.clone()).await?;
+
+            // B. 渲染提示词模板
+            let (template_name, fallback) = match reason {
+                ProactiveReason::Idle { .. } => (
+                    "proactive_message.txt",
+                    "你正处于单人沉浸式聊天模式。由于对方长时间没说话，请根据当前气氛主动开启一个小话题或关怀。",
+                ),
+                ProactiveReason::Trigger(_) => (
+                    "proactive_trigger.txt",
+                    "你正处于单人沉浸式聊天模式。请根据下面的原因主动给对方发一条自然的消息：\n{{trigger.reason}}",
+                ),
+            };
+            let prompt_template =
+                crate::character_state::StateAnalyzer::load_prompt_template(template_name)
+                    .unwrap_or_else(|_| fallback.to_string());
+
+            let recent_messages: Vec<String> = history
+                .iter()
+                .map(|m| format!("{}: {}", m.role, m.content))
+                .collect();
+            template_ctx
+                .with_state(
+                    context.mood.as_deref(),
+                    context.busy_level,
+                    context.interest_level,
+                )
+                .with_activity(context.activity.as_ref())
+                .set(
+                    "recent_messages",
+                    serde_json::json!(recent_messages.join("\n")),
+                );
+            match reason {
+                ProactiveReason::Idle { minutes } => {
+                    template_ctx.set("idle_minutes", serde_json::json!(minutes));
+                }
+                ProactiveReason::Trigger(ref trigger) => {
+                    template_ctx.set(
+                        "trigger",
+                        serde_json::json!({
+                            "kind": trigger.kind,
+                            "title": trigger.title,
+                            "note": trigger.note.clone().unwrap_or_default(),
+                            "reason": trigger.reason(),
+                        }),
+                    );
+                }
+            }
+            let resolver = crate::prompt_template::library_resolver(&config.prompt_library);
+            let rendered =
+                crate::prompt_template::render_or_raw(&prompt_template, &template_ctx, &resolver);
+            let mut system_content = rendered.text;
+            // 模板没有引用作息变量时，把当前活动追加到提示词末尾
+            if let Some(ref activity) = context.activity {
+                if !rendered.used.contains("activity") {
+                    system_content = format!("{}\n\n{}", system_content, activity.prompt_line());
+                }
+            }
+
+            // C. 构建消息
+            let mut full_messages = vec![crate::models::Message {
+                id: None,
+                model: None,
+                role: "system".to_string(),
+                content: system_content,
+                reasoning_content: None,
+                file_metadata: None,
+                search_metadata: None,
+                provider: None,
+                mode: None,
+                role_id: None,
+                parent_id: None,
+            }];
+            full_messages.extend(history);
+
+            // D. 调用 AI
+            let resolved = crate::generation_config::resolve_generation(
+                &config,
+                Some(&crate::generation_config::SessionOverrides {
+                    preset_id: Some(config.global_preset_id.clone()),
+                    ..Default::default()
+                }),
+                None,
+                None,
+                crate::models::GenerationParams::default(),
+                crate::models::GenerationParams {
+                    temperature: Some(0.8),
+                    max_tokens: Some(512),
+                    ..Default::default()
+                },
+            );
+            let model = resolved.model;
+
+            let provider =
+                crate::provider_config::resolve_provider(&config, &resolved.provider_id)?;
+            let client = self
+                .app
+                .state::<crate::net::HttpClients>()
+                .for_provider(&provider)?;
+
+            crate::ai_utils::complete_chat(
+                &client,
+                &provider,
+                &model,
+                full_messages,
+                resolved.params,
+            )
+            .await
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordedEvents(Mutex<Vec<(String, serde_json::Value)>>);
+
+    impl EventSink for RecordedEvents {
+        fn emit(&self, event: &str, payload: serde_json::Value) {
+            self.0.lock().unwrap().push((event.to_string(), payload));
+        }
+    }
+
+    impl RecordedEvents {
+        fn names(&self) -> Vec<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(e, _)| e.clone())
+                .collect()
+        }
+    }
+
+    struct FixedProactive {
+        settings: ImmersiveSettings,
+        composed: AtomicUsize,
+        reasons: Mutex<Vec<ProactiveReason>>,
+    }
+
+    impl ProactiveSource for FixedProactive {
+        fn settings(&self) -> BoxFuture<'_, Option<ImmersiveSettings>> {
+            Box::pin(async move { Some(self.settings.clone()) })
+        }
+
+        fn compose(
+            &self,
+            _context: SessionContext,
+            reason: ProactiveReason,
+        ) -> BoxFuture<'_, Result<String, String>> {
+            self.composed.fetch_add(1, Ordering::SeqCst);
+            let content = match reason {
+                ProactiveReason::Idle { .. } => "在忙吗？".to_string(),
+                ProactiveReason::Trigger(ref t) => format!("别忘了{}", t.title),
+            };
+            self.reasons.lock().unwrap().push(reason);
+            Box::pin(async move { Ok(content) })
+        }
+    }
+
+    /// 由 tokio 时间推算的墙上时间 (暂停时间后随 sleep 快进)
+    struct VirtualClock {
+        origin: tokio::time::Instant,
+    }
+
+    impl Clock for VirtualClock {
+        fn now_ms(&self) -> i64 {
+            1_700_000_000_000 + self.origin.elapsed().as_millis() as i64
+        }
+
+        fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            Box::pin(tokio::time::sleep(duration))
+        }
+    }
+
+    struct Harness {
+        scheduler: MessageScheduler,
+        events: Arc<RecordedEvents>,
+        db: Arc<SocialDbState>,
+        proactive: Arc<FixedProactive>,
+    }
+
+    impl Harness {
+        fn new(settings: ImmersiveSettings) -> Self {
+            let conn = rusqlite::Connection::open_in_memory().unwrap();
+            crate::social_db::init_social_db(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO contacts (id, name) VALUES (1, 'Alice');
+                 INSERT INTO social_sessions (id, contact_id) VALUES (1, 1);",
+            )
+            .unwrap();
+            let events = Arc::new(RecordedEvents::default());
+            let db = Arc::new(SocialDbState(Mutex::new(conn)));
+            let proactive = Arc::new(FixedProactive {
+                settings,
+                composed: AtomicUsize::new(0),
+                reasons: Mutex::new(Vec::new()),
+            });
+            let scheduler = MessageScheduler::new(SchedulerEnv {
+                clock: Arc::new(VirtualClock {
+                    origin: tokio::time::Instant::now(),
+                }),
+                events: events.clone(),
+                store: db.clone(),
+                proactive: proactive.clone(),
+                spawn: |task| {
+                    tokio::spawn(task);
+                },
+            });
+            Self {
+                scheduler,
+                events,
+                db,
+                proactive,
+            }
+        }
+
+        fn messages(&self) -> Vec<String> {
+            let conn = self.db.0.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT content FROM social_messages ORDER BY id")
+                .unwrap();
+            let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+            rows.collect::<Result<_, _>>().unwrap()
+        }
+
+        fn now_ms(&self) -> i64 {
+            self.scheduler.core.env.clock.now_ms()
+        }
+
+        async fn run(&self, chain: Vec<BehaviorAction>) {
+            self.scheduler
+                .execute_behavior_chain(1, 1, chain, context(), self.proactive.settings.clone())
+                .await
+                .unwrap();
+        }
+    }
+
+    fn context() -> SessionContext {
+        SessionContext {
+            session_id: 1,
+            contact_id: 1,
+            mood: None,
+            busy_level: None,
+            interest_level: None,
+            activity: None,
+        }
+    }
+
+    /// 主动发言必定触发、回复不拆分不撤回的设置
+    fn proactive_settings() -> ImmersiveSettings {
+        let mut settings = ImmersiveSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        let behaviors = &mut settings.behaviors;
+        behaviors.multi_segment = Some(1);
+        behaviors.typo_correction = None;
+        let proactive = behaviors.proactive_initiation.as_mut().unwrap();
+        proactive.idle_threshold_range = (60, 60);
+        proactive.success_rate = 1.0;
+        proactive.cooldown_range = (600, 600);
+        settings
+    }
+
+    async fn advance(ms: u64) {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_chain_runs_in_virtual_time() {
+        let harness = Harness::new(ImmersiveSettings::default());
+        harness
+            .run(vec![
+                BehaviorAction::Wait(1000),
+                BehaviorAction::Speak("在吗".into()),
+                BehaviorAction::Wait(500),
+                BehaviorAction::Speak("在干嘛".into()),
+                BehaviorAction::Retract(0),
+            ])
+            .await;
+
+        advance(999).await;
+        assert!(harness.messages().is_empty());
+        advance(2).await;
+        assert_eq!(harness.messages(), vec!["在吗"]);
+        advance(500).await;
+        assert_eq!(harness.messages(), vec!["在吗"]);
+        assert_eq!(
+            harness.events.names(),
+            vec![
+                "typing-status",
+                "typing-status",
+                "new-social-message",
+                "typing-status",
+                "typing-status",
+                "new-social-message",
+                "message-retracted",
+                "typing-status",
+            ]
+        );
+        assert!(harness.scheduler.core.active_tasks.read().await.is_empty());
+        assert!(harness.db.load_pending().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resume_plan() {
+        let pending = PendingChain {
+            session_id: 1,
+            contact_id: 1,
+            actions: vec![
+                BehaviorAction::Wait(60_000),
+                BehaviorAction::Speak("嗯".into()),
+            ],
+            started_at: 0,
+            last_message_id: None,
+        };
+        let config = ResumeConfig::default();
+        // 未到期：扣除已等待的时间
+        assert_eq!(
+            pending.resume_plan(20_000, &config).unwrap()[0],
+            BehaviorAction::Wait(40_000)
+        );
+        // 过期但在限度内：跳过等待立即执行
+        assert_eq!(
+            pending.resume_plan(120_000, &config).unwrap(),
+            vec![BehaviorAction::Wait(0), BehaviorAction::Speak("嗯".into())]
+        );
+        // 过期太久或策略为丢弃
+        assert!(pending.resume_plan(60_000 + 31 * 60_000, &config).is_none());
+        let discard = ResumeConfig {
+            overdue_policy: OverduePolicy::Discard,
+            ..Default::default()
+        };
+        assert!(pending.resume_plan(120_000, &discard).is_none());
+        assert!(pending.resume_plan(20_000, &discard).is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pending_chain_is_persisted() {
+        let harness = Harness::new(ImmersiveSettings::default());
+        let started = harness.now_ms();
+        harness
+            .run(vec![
+                BehaviorAction::Wait(60_000),
+                BehaviorAction::Speak("稍等".into()),
+            ])
+            .await;
+
+        advance(10_000).await;
+        let pending = harness.db.load_pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].started_at, started);
+        assert_eq!(pending[0].actions.len(), 2);
+        let activities = harness.db.load_activities().unwrap();
+        assert_eq!(activities[0].1.last_interaction, started);
+
+        // 用户取消时不再保留
+        harness.scheduler.cancel_session_behaviors(1).await;
+        assert!(harness.db.load_pending().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restore_after_restart() {
+        let harness = Harness::new(proactive_settings());
+        let now = harness.now_ms();
+        // 模拟上次运行留下的记录：等待已过去 50 秒，会话已闲置 2 分钟
+        harness
+            .db
+            .save_pending(&PendingChain {
+                session_id: 1,
+                contact_id: 1,
+                actions: vec![
+                    BehaviorAction::Wait(60_000),
+                    BehaviorAction::Speak("重启前的回复".into()),
+                ],
+                started_at: now - 50_000,
+                last_message_id: None,
+            })
+            .unwrap();
+        harness
+            .db
+            .save_activity(
+                1,
+                &SessionActivity {
+                    last_interaction: now - 120_000,
+                    last_proactive: None,
+                },
+            )
+            .unwrap();
+
+        harness.scheduler.core.restore().await;
+        advance(9_000).await;
+        assert!(harness.messages().is_empty());
+        advance(2_000).await;
+        assert_eq!(harness.messages(), vec!["重启前的回复"]);
+        assert!(harness.db.load_pending().unwrap().is_empty());
+
+        // 恢复的会话继续参与空闲检查
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(5_000).await;
+        assert_eq!(harness.proactive.composed.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restore_discards_stale_chain() {
+        let harness = Harness::new(proactive_settings());
+        let now = harness.now_ms();
+        harness
+            .db
+            .save_pending(&PendingChain {
+                session_id: 1,
+                contact_id: 1,
+                actions: vec![BehaviorAction::Speak("两小时前的回复".into())],
+                started_at: now - 2 * 3600 * 1000,
+                last_message_id: None,
+            })
+            .unwrap();
+
+        harness.scheduler.core.restore().await;
+        advance(5_000).await;
+        assert!(harness.messages().is_empty());
+        assert!(harness.db.load_pending().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_stops_pending_chain() {
+        let harness = Harness::new(ImmersiveSettings::default());
+        harness
+            .run(vec![
+                BehaviorAction::Wait(60_000),
+                BehaviorAction::Speak("太晚了".into()),
+            ])
+            .await;
+
+        advance(1000).await;
+        harness.scheduler.cancel_session_behaviors(1).await;
+        advance(120_000).await;
+        assert!(harness.messages().is_empty());
+        let events = harness.events.0.lock().unwrap();
+        assert_eq!(events.last().unwrap().1["isTyping"], false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delayed_decision_replies_after_delay() {
+        let mut settings = proactive_settings();
+        settings
+            .behaviors
+            .idle_delay_config
+            .as_mut()
+            .unwrap()
+            .reply_after_delay_rate = 1.0;
+        let harness = Harness::new(settings);
+        harness
+            .run(vec![BehaviorAction::DelayedDecision(
+                300_000,
+                "刚看到消息".into(),
+            )])
+            .await;
+
+        advance(299_000).await;
+        assert!(harness.messages().is_empty());
+        // 延迟结束后按正常行为链回复 (回复延迟最长 3 秒)
+        advance(5_000).await;
+        assert_eq!(harness.messages(), vec!["刚看到消息"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_check_triggers_proactive_message() {
+        let harness = Harness::new(proactive_settings());
+        harness.scheduler.touch_session(1).await;
+
+        advance(30_000).await;
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(10).await;
+        assert_eq!(harness.proactive.composed.load(Ordering::SeqCst), 0);
+
+        advance(31_000).await;
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(5_000).await;
+        assert_eq!(harness.proactive.composed.load(Ordering::SeqCst), 1);
+        assert_eq!(harness.messages(), vec!["在忙吗？"]);
+
+        // 主动发言后进入冷却，即使再次闲置也不会重复触发
+        advance(120_000).await;
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(5_000).await;
+        assert_eq!(harness.proactive.composed.load(Ordering::SeqCst), 1);
+    }
 
     #[tokio::test]
     async fn test_scheduler_creation() {
-        let scheduler = MessageScheduler::new();
-        assert_eq!(scheduler.active_tasks.read().await.len(), 0);
+        let harness = Harness::new(ImmersiveSettings::default());
+        assert_eq!(harness.scheduler.core.active_tasks.read().await.len(), 0);
     }
 
     #[tokio::test]
     async fn test_cancellation() {
-        let scheduler = MessageScheduler::new();
+        let harness = Harness::new(ImmersiveSettings::default());
+        let scheduler = &harness.scheduler;
         let token = CancellationToken::new();
 
         {
-            let mut tasks = scheduler.active_tasks.write().await;
-            tasks.insert(1, token.clone());
+            let mut tasks = scheduler.core.active_tasks.write().await;
+            tasks.insert((1, 1), token.clone());
         }
 
         scheduler.cancel_session_behaviors(1).await;
         assert!(token.is_cancelled());
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rich_actions_are_persisted() {
+        let harness = Harness::new(ImmersiveSettings::default());
+        harness
+            .db
+            .0
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO social_messages (id, contact_id, session_id, role, content)
+                     VALUES (1, 1, 1, 'user', '周末去爬山吗'), (2, 1, 1, 'user', '去吧去吧');
+                 INSERT INTO contact_stickers (contact_id, name, path)
+                     VALUES (1, '猫猫点头', '/stickers/nod.png');",
+            )
+            .unwrap();
+
+        harness
+            .run(vec![
+                BehaviorAction::MarkRead,
+                BehaviorAction::React(0, "👍".into()),
+                BehaviorAction::React(99, "❓".into()),
+                BehaviorAction::Quote(1, "好啊".into()),
+                BehaviorAction::Speak("几点出发？".into()),
+                BehaviorAction::Edit(0, "几点出发呀？".into()),
+                BehaviorAction::Sticker("猫猫点头".into()),
+                BehaviorAction::Sticker("已删除的表情".into()),
+            ])
+            .await;
+        advance(10).await;
+
+        let conn = harness.db.0.lock().unwrap();
+        let read: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM social_messages WHERE role = 'user' AND read_at IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(read, 2);
+        let reactions: String = conn
+            .query_row(
+                "SELECT reactions FROM social_messages WHERE id = 2",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let reactions: Vec<Reaction> = serde_json::from_str(&reactions).unwrap();
+        assert_eq!(reactions[0].emoji, "👍");
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT content, quote_id, attachment, edited_at IS NOT NULL FROM social_messages
+                 WHERE role = 'assistant' ORDER BY id",
+            )
+            .unwrap();
+        let rows: Vec<(String, Option<i64>, Option<String>, bool)> = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], ("好啊".to_string(), Some(1), None, false));
+        assert_eq!(rows[1], ("几点出发呀？".to_string(), None, None, true));
+        let attachment: Attachment = serde_json::from_str(rows[2].2.as_deref().unwrap()).unwrap();
+        assert_eq!(attachment.path, "/stickers/nod.png");
+
+        let names = harness.events.names();
+        for event in ["messages-read", "message-reaction", "message-edited"] {
+            assert_eq!(names.iter().filter(|n| *n == event).count(), 1);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_group_participants_do_not_cancel_each_other() {
+        let harness = Harness::new(ImmersiveSettings::default());
+        harness
+            .db
+            .0
+            .lock()
+            .unwrap()
+            .execute("INSERT INTO contacts (id, name) VALUES (2, 'Bob')", [])
+            .unwrap();
+        let settings = ImmersiveSettings::default();
+        let bob = SessionContext {
+            contact_id: 2,
+            ..context()
+        };
+
+        harness
+            .run(vec![
+                BehaviorAction::Wait(2000),
+                BehaviorAction::Speak("我周末去爬山".into()),
+            ])
+            .await;
+        harness
+            .scheduler
+            .execute_behavior_chain(
+                1,
+                2,
+                vec![
+                    BehaviorAction::Wait(1000),
+                    BehaviorAction::Speak("我去看展".into()),
+                ],
+                bob.clone(),
+                settings.clone(),
+            )
+            .await
+            .unwrap();
+        advance(10).await;
+        assert_eq!(harness.db.load_pending().unwrap().len(), 2);
+        advance(2500).await;
+        assert_eq!(harness.messages(), vec!["我去看展", "我周末去爬山"]);
+
+        // 成员行为链执行完才返回，后面的发言者能看到前面的回复
+        let turns = harness.scheduler.begin_group_turns(1).await;
+        harness
+            .scheduler
+            .run_participant_chain(
+                1,
+                2,
+                vec![
+                    BehaviorAction::Wait(1000),
+                    BehaviorAction::Speak("一起吗".into()),
+                ],
+                bob,
+                settings.clone(),
+                &turns,
+            )
+            .await;
+        assert_eq!(harness.messages().len(), 3);
+
+        // 新一轮开始后，旧一轮剩下的发言不再执行
+        let next = harness.scheduler.begin_group_turns(1).await;
+        assert!(turns.is_cancelled());
+        harness
+            .scheduler
+            .run_participant_chain(
+                1,
+                1,
+                vec![BehaviorAction::Speak("过时的回复".into())],
+                context(),
+                settings,
+                &turns,
+            )
+            .await;
+        assert_eq!(harness.messages().len(), 3);
+        harness.scheduler.end_group_turns(1, &next).await;
+        assert!(harness.scheduler.core.group_turns.read().await.is_empty());
+        assert!(harness.scheduler.core.active_tasks.read().await.is_empty());
+    }
+
+    fn add_trigger(harness: &Harness, kind: TriggerKind, title: &str, fire_at: i64) {
+        let conn = harness.db.0.lock().unwrap();
+        crate::proactive_trigger::insert_trigger(
+            &conn,
+            &crate::proactive_trigger::TriggerInput {
+                contact_id: 1,
+                session_id: None,
+                kind,
+                title: title.into(),
+                note: None,
+                fire_at,
+                yearly: false,
+            },
+            "user",
+        )
+        .unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_streamed_chain_runs_batches_as_they_arrive() {
+        let harness = Harness::new(ImmersiveSettings::default());
+        let settings = ImmersiveSettings::default();
+        let (token, batches) = harness
+            .scheduler
+            .begin_streamed_chain(1, 1, context(), settings.clone())
+            .await;
+        batches
+            .send(vec![
+                BehaviorAction::Wait(500),
+                BehaviorAction::Speak("第一段".into()),
+            ])
+            .unwrap();
+        advance(600).await;
+        // 第一段已发出，后面的内容还在生成时显示"正在输入"
+        assert_eq!(harness.messages(), vec!["第一段"]);
+        assert!(harness.db.load_pending().unwrap().is_empty());
+        assert_eq!(
+            harness.events.0.lock().unwrap().last().unwrap().1["isTyping"],
+            true
+        );
+
+        batches
+            .send(vec![
+                BehaviorAction::Wait(300),
+                BehaviorAction::Speak("第二段".into()),
+            ])
+            .unwrap();
+        drop(batches);
+        advance(400).await;
+        assert_eq!(harness.messages(), vec!["第一段", "第二段"]);
+        assert!(!token.is_cancelled());
+        assert!(harness.scheduler.core.active_tasks.read().await.is_empty());
+        assert_eq!(
+            harness.events.0.lock().unwrap().last().unwrap().1["isTyping"],
+            false
+        );
+
+        // 用户发来新消息时令牌被取消 (调用方据此中止上游请求)，已送入的行为不再执行
+        let (token, batches) = harness
+            .scheduler
+            .begin_streamed_chain(1, 1, context(), settings)
+            .await;
+        batches
+            .send(vec![
+                BehaviorAction::Wait(1000),
+                BehaviorAction::Speak("过时的回复".into()),
+            ])
+            .unwrap();
+        advance(10).await;
+        harness
+            .run(vec![BehaviorAction::Speak("新的回复".into())])
+            .await;
+        assert!(token.is_cancelled());
+        advance(2000).await;
+        assert_eq!(harness.messages(), vec!["第一段", "第二段", "新的回复"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_due_trigger_fires_before_idle_threshold() {
+        let mut settings = proactive_settings();
+        settings.behaviors.proactive_initiation = None;
+        let harness = Harness::new(settings);
+        harness.scheduler.touch_session(1).await;
+        let now = harness.now_ms();
+        add_trigger(&harness, TriggerKind::Reminder, "交房租", now + 10_000);
+
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(5_000).await;
+        assert_eq!(harness.proactive.composed.load(Ordering::SeqCst), 0);
+
+        // 到期后立即发出，不需要等空闲阈值，也不受主动发言开关影响
+        advance(6_000).await;
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(5_000).await;
+        assert_eq!(harness.messages(), vec!["别忘了交房租"]);
+        match &harness.proactive.reasons.lock().unwrap()[0] {
+            ProactiveReason::Trigger(t) => assert_eq!(t.kind, TriggerKind::Reminder),
+            other => panic!("unexpected reason: {:?}", other),
+        }
+
+        // 一次性触发器用完即删
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(5_000).await;
+        assert_eq!(harness.proactive.composed.load(Ordering::SeqCst), 1);
+        assert!(harness.db.due_triggers(i64::MAX).unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stale_trigger_is_skipped() {
+        let harness = Harness::new(proactive_settings());
+        let now = harness.now_ms();
+        add_trigger(
+            &harness,
+            TriggerKind::FollowUp,
+            "面试",
+            now - 25 * 3600 * 1000,
+        );
+
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(5_000).await;
+        assert!(harness.messages().is_empty());
+        assert!(harness.db.due_triggers(i64::MAX).unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_trigger_waits_while_character_sleeps() {
+        let harness = Harness::new(proactive_settings());
+        let now = harness.now_ms();
+        {
+            let conn = harness.db.0.lock().unwrap();
+            let routine = crate::routine::Routine {
+                timezone: Some("UTC".into()),
+                blocks: vec![crate::routine::RoutineBlock {
+                    days: vec![],
+                    start: "00:00".into(),
+                    end: "00:00".into(),
+                    activity: ActivityKind::Sleeping,
+                    label: None,
+                    availability: None,
+                }],
+            };
+            crate::routine::save_routine(&conn, 1, Some(&routine)).unwrap();
+        }
+        add_trigger(&harness, TriggerKind::Date, "纪念日", now);
+        add_trigger(&harness, TriggerKind::Reminder, "吃药", now);
+
+        // 整天都在睡觉：提醒照常发出，纪念日留到醒来
+        harness.scheduler.core.check_idle_sessions().await;
+        advance(30_000).await;
+        assert_eq!(harness.messages(), vec!["别忘了吃药"]);
+        let remaining = harness.db.due_triggers(i64::MAX).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].0.title, "纪念日");
+    }
 }
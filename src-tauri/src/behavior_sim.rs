@@ -0,0 +1,316 @@
+use crate::behavior_engine::{BehaviorEngine, SessionContext};
+use crate::immersive_settings::{BehaviorAction, ImmersiveSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// ==================================================================================
+// 行为模拟：在虚拟时间下把脚本化对话交给 BehaviorEngine 决策，输出行为时间线与统计，
+// 用于根据数据调整 BehaviorToggles。相同种子、设置与脚本得到完全相同的结果 (可回放)
+// ==================================================================================
+
+/// 脚本中的一轮对话
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptTurn {
+    /// 距上一轮行为链结束的空闲时长 (毫秒)，期间评估主动发言
+    #[serde(default)]
+    pub gap_ms: u64,
+    /// 模型生成的回复文本 (交给 decide 处理)
+    pub reply: String,
+    /// 从本轮开始覆盖角色状态 (模拟状态分析结果变化)
+    #[serde(default)]
+    pub mood: Option<String>,
+    #[serde(default)]
+    pub busy_level: Option<f32>,
+    #[serde(default)]
+    pub interest_level: Option<f32>,
+}
+
+/// 时间线上的一个行为 (at_ms 为行为开始时的虚拟时间)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineEntry {
+    pub at_ms: u64,
+    pub turn: usize,
+    pub action: BehaviorAction,
+}
+
+/// 单轮的决策结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnOutcome {
+    /// 从本轮开始到第一条消息发出的延迟；None 表示最终没有回复
+    pub first_reply_ms: Option<u64>,
+    /// 发出的消息条数 (不含撤回前的错误版本)
+    pub segments: usize,
+    pub delayed_decision: bool,
+    pub typo_correction: bool,
+}
+
+/// 一次空闲期内的主动发言评估
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProactiveSample {
+    pub at_ms: u64,
+    pub idle_ms: u64,
+    pub threshold_s: u32,
+    pub success_rate: f32,
+    pub cooldown_s: u32,
+    /// 空闲时长达到阈值 (调度器会按 success_rate 决定是否主动发言)
+    pub eligible: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationStats {
+    pub turns: usize,
+    pub replied: usize,
+    pub ignore_rate: f32,
+    pub delayed_decisions: usize,
+    pub typo_corrections: usize,
+    /// 回复轮次中从开始到第一条消息的平均延迟
+    pub average_delay_ms: f64,
+    /// 每轮分段数 -> 轮次数 (仅统计有回复的轮次)
+    pub segment_counts: BTreeMap<usize, usize>,
+    pub average_segments: f64,
+    pub proactive_checks: usize,
+    pub proactive_eligible: usize,
+    /// 期望的主动发言次数 (达到阈值的空闲期成功率之和)
+    pub expected_proactive: f64,
+}
+
+impl SimulationStats {
+    pub fn from_outcomes(outcomes: &[TurnOutcome], proactive: &[ProactiveSample]) -> Self {
+        let replies: Vec<&TurnOutcome> = outcomes
+            .iter()
+            .filter(|o| o.first_reply_ms.is_some())
+            .collect();
+        let mut segment_counts = BTreeMap::new();
+        for outcome in &replies {
+            *segment_counts.entry(outcome.segments).or_insert(0) += 1;
+        }
+        let average = |total: f64, count: usize| {
+            if count == 0 {
+                0.0
+            } else {
+                total / count as f64
+            }
+        };
+        let eligible: Vec<&ProactiveSample> = proactive.iter().filter(|p| p.eligible).collect();
+
+        Self {
+            turns: outcomes.len(),
+            replied: replies.len(),
+            ignore_rate: if outcomes.is_empty() {
+                0.0
+            } else {
+                (outcomes.len() - replies.len()) as f32 / outcomes.len() as f32
+            },
+            delayed_decisions: outcomes.iter().filter(|o| o.delayed_decision).count(),
+            typo_corrections: outcomes.iter().filter(|o| o.typo_correction).count(),
+            average_delay_ms: average(
+                replies
+                    .iter()
+                    .filter_map(|o| o.first_reply_ms)
+                    .map(|ms| ms as f64)
+                    .sum(),
+                replies.len(),
+            ),
+            average_segments: average(
+                replies.iter().map(|o| o.segments as f64).sum(),
+                replies.len(),
+            ),
+            segment_counts,
+            proactive_checks: proactive.len(),
+            proactive_eligible: eligible.len(),
+            expected_proactive: eligible.iter().map(|p| p.success_rate as f64).sum(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationReport {
+    pub seed: u64,
+    pub timeline: Vec<TimelineEntry>,
+    pub outcomes: Vec<TurnOutcome>,
+    pub proactive: Vec<ProactiveSample>,
+    pub stats: SimulationStats,
+}
+
+/// 以虚拟时间运行一段脚本化对话
+pub fn simulate(settings: ImmersiveSettings, seed: u64, script: &[ScriptTurn]) -> SimulationReport {
+    let engine = BehaviorEngine::with_seed(settings, seed);
+    let mut context = SessionContext {
+        session_id: 0,
+        contact_id: 0,
+        mood: None,
+        busy_level: None,
+        interest_level: None,
+        activity: None,
+    };
+    let mut clock = 0u64;
+    let mut timeline = Vec::new();
+    let mut outcomes = Vec::new();
+    let mut proactive = Vec::new();
+
+    for (turn, step) in script.iter().enumerate() {
+        if step.mood.is_some() {
+            context.mood = step.mood.clone();
+        }
+        if step.busy_level.is_some() {
+            context.busy_level = step.busy_level;
+        }
+        if step.interest_level.is_some() {
+            context.interest_level = step.interest_level;
+        }
+
+        // 1. 上一轮结束后的空闲期：评估主动发言
+        if turn > 0 && step.gap_ms > 0 {
+            let (threshold_s, success_rate, cooldown_s) = engine.get_proactive_parameters(&context);
+            proactive.push(ProactiveSample {
+                at_ms: clock,
+                idle_ms: step.gap_ms,
+                threshold_s,
+                success_rate,
+                cooldown_s,
+                eligible: step.gap_ms >= threshold_s as u64 * 1000,
+            });
+        }
+        clock += step.gap_ms;
+
+        // 2. 决策并按虚拟时间展开行为链
+        let started = clock;
+        let mut outcome = TurnOutcome {
+            first_reply_ms: None,
+            segments: 0,
+            delayed_decision: false,
+            typo_correction: false,
+        };
+        let mut pending = engine.decide(&step.reply, &context);
+        while !pending.is_empty() {
+            let mut next = Vec::new();
+            for action in pending {
+                timeline.push(TimelineEntry {
+                    at_ms: clock,
+                    turn,
+                    action: action.clone(),
+                });
+                match action {
+                    BehaviorAction::Wait(ms) => clock += ms as u64,
+                    BehaviorAction::Speak(_) | BehaviorAction::Quote(..) => {
+                        outcome.first_reply_ms.get_or_insert(clock - started);
+                        outcome.segments += 1;
+                    }
+                    BehaviorAction::Retract(_) => {
+                        outcome.typo_correction = true;
+                        outcome.segments = outcome.segments.saturating_sub(1);
+                    }
+                    BehaviorAction::Edit(..) => outcome.typo_correction = true,
+                    BehaviorAction::Idle
+                    | BehaviorAction::MarkRead
+                    | BehaviorAction::React(..)
+                    | BehaviorAction::Sticker(_) => {}
+                    BehaviorAction::DelayedDecision(ms, message) => {
+                        outcome.delayed_decision = true;
+                        clock += ms as u64;
+                        next = engine.decide_after_delay(&message, &context);
+                    }
+                }
+            }
+            pending = next;
+        }
+        outcomes.push(outcome);
+    }
+
+    let stats = SimulationStats::from_outcomes(&outcomes, &proactive);
+    SimulationReport {
+        seed,
+        timeline,
+        outcomes,
+        proactive,
+        stats,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script() -> Vec<ScriptTurn> {
+        (0..20)
+            .map(|i| ScriptTurn {
+                gap_ms: if i % 5 == 4 { 900_000 } else { 20_000 },
+                reply: "今天去了公园。天气很好，人也不多！晚上准备做饭？\n你呢".repeat(3),
+                interest_level: (i == 10).then_some(0.9),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn settings() -> ImmersiveSettings {
+        let mut settings = ImmersiveSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        settings.behaviors.ignore_rate = 0.3;
+        settings
+            .behaviors
+            .typo_correction
+            .as_mut()
+            .unwrap()
+            .trigger_rate = 0.2;
+        settings
+    }
+
+    #[test]
+    fn test_same_seed_replays() {
+        let first = simulate(settings(), 42, &script());
+        let second = simulate(settings(), 42, &script());
+        assert_eq!(first.timeline, second.timeline);
+        assert_eq!(first.stats, second.stats);
+
+        let other = simulate(settings(), 43, &script());
+        assert_ne!(first.timeline, other.timeline);
+    }
+
+    #[test]
+    fn test_stats_match_timeline() {
+        let report = simulate(settings(), 7, &script());
+        let stats = &report.stats;
+        assert_eq!(stats.turns, 20);
+        assert_eq!(stats.segment_counts.values().sum::<usize>(), stats.replied);
+        assert_eq!(
+            stats.ignore_rate,
+            (stats.turns - stats.replied) as f32 / stats.turns as f32
+        );
+        assert_eq!(stats.proactive_checks, 19);
+        // 900 秒的空闲期总能超过默认阈值上限
+        assert!(stats.proactive_eligible >= 3);
+
+        // 时间线按虚拟时间单调递增
+        assert!(report.timeline.windows(2).all(|w| w[0].at_ms <= w[1].at_ms));
+        let speaks = report
+            .timeline
+            .iter()
+            .filter(|e| matches!(e.action, BehaviorAction::Speak(_)))
+            .count();
+        let retracts = report
+            .timeline
+            .iter()
+            .filter(|e| matches!(e.action, BehaviorAction::Retract(_)))
+            .count();
+        assert_eq!(
+            speaks - retracts,
+            report.outcomes.iter().map(|o| o.segments).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_disabled_speaks_immediately() {
+        let report = simulate(ImmersiveSettings::default(), 1, &script()[..3]);
+        assert_eq!(report.stats.replied, 3);
+        assert_eq!(report.stats.average_delay_ms, 0.0);
+        assert_eq!(report.stats.segment_counts.get(&1), Some(&3));
+    }
+}
@@ -0,0 +1,4 @@
+// 命令行工具：与界面程序共用数据目录，用法见 `goge-cli --help`
+fn main() {
+    std::process::exit(goge_chat_lib::run_cli())
+}
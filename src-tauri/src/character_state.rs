@@ -32,8 +32,47 @@ impl Default for CharacterState {
     }
 }
 
+impl CharacterState {
+    /// 读取缓存的角色状态 (分析失败或未到分析时机时使用)
+    pub fn load(
+        conn: &rusqlite::Connection,
+        contact_id: i64,
+        session_id: i64,
+    ) -> Result<Option<Self>, String> {
+        conn.query_row(
+            "SELECT mood, busy_level, interest_level, message_count, last_analyzed
+             FROM character_states
+             WHERE contact_id = ?1 AND session_id = ?2",
+            rusqlite::params![contact_id, session_id],
+            |row| {
+                Ok(Self {
+                    contact_id,
+                    session_id,
+                    mood: row.get(0)?,
+                    busy_level: row.get(1)?,
+                    interest_level: row.get(2)?,
+                    message_count: row.get(3)?,
+                    last_analyzed: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// 允许的心情取值 (与 state_analysis.txt 及行为引擎的取值一致)
+pub const MOODS: [&str; 5] = ["happy", "neutral", "busy", "tired", "annoyed"];
+
+/// 单次分析最多请求次数 (输出格式错误时带着错误信息重试)
+const MAX_ATTEMPTS: usize = 3;
+/// 对话历史中单条消息的最大字符数
+const MAX_MESSAGE_CHARS: usize = 500;
+
+const DEFAULT_ANALYSIS_PROMPT: &str = "你是心理状态分析师。根据下面的对话历史分析AI角色当前的心情 (mood: happy/neutral/busy/tired/annoyed)、忙碌程度 (busy_level: 0.0-1.0) 和对话兴趣度 (interest_level: 0.0-1.0)，只输出 JSON：{\"mood\": ..., \"busy_level\": ..., \"interest_level\": ..., \"reasoning\": \"不超过50字\"}\n\n## 对话历史\n{{conversation_history}}";
+
 /// LLM分析返回的状态结构
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct StateAnalysisResult {
     pub mood: String,
     pub busy_level: f32,
@@ -42,20 +81,77 @@ pub struct StateAnalysisResult {
     pub reasoning: Option<String>,
 }
 
-/// 状态分析器
+impl StateAnalysisResult {
+    /// 请求结构化输出时使用的 JSON Schema
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "mood": { "type": "string", "enum": MOODS },
+                "busy_level": { "type": "number", "description": "0.0-1.0" },
+                "interest_level": { "type": "number", "description": "0.0-1.0" },
+                "reasoning": { "type": "string", "description": "不超过50字" }
+            },
+            "required": ["mood", "busy_level", "interest_level", "reasoning"],
+            "additionalProperties": false
+        })
+    }
+
+    /// 从模型输出中解析并校验：心情必须是已知取值，数值截断到 [0, 1]
+    /// 兼容 ```json 代码块及前后的多余文字
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let start = text.find('{').ok_or("输出中没有 JSON 对象")?;
+        let end = text
+            .rfind('}')
+            .filter(|&end| end > start)
+            .ok_or("JSON 对象不完整")?;
+        let mut result: Self = serde_json::from_str(&text[start..=end])
+            .map_err(|e| format!("JSON 格式不符合要求: {}", e))?;
+
+        result.mood = result.mood.trim().to_lowercase();
+        if !MOODS.contains(&result.mood.as_str()) {
+            return Err(format!(
+                "mood 取值 \"{}\" 无效，应为 {}",
+                result.mood,
+                MOODS.join(" / ")
+            ));
+        }
+        for (name, value) in [
+            ("busy_level", &mut result.busy_level),
+            ("interest_level", &mut result.interest_level),
+        ] {
+            if !value.is_finite() {
+                return Err(format!("{} 不是有效数值", name));
+            }
+            *value = value.clamp(0.0, 1.0);
+        }
+        Ok(result)
+    }
+}
+
+/// 状态分析器：使用联系人配置的提供商/模型分析最近的对话
 pub struct StateAnalyzer {
-    // 将来会添加LLM客户端引用
+    client: reqwest::Client,
+    provider: crate::provider_config::ProviderConfig,
+    model: String,
 }
 
 impl StateAnalyzer {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(
+        client: reqwest::Client,
+        provider: crate::provider_config::ProviderConfig,
+        model: String,
+    ) -> Self {
+        Self {
+            client,
+            provider,
+            model,
+        }
     }
 
     /// 检查是否应该触发状态分析
     /// 返回 true 表示应该分析，false 表示跳过
     pub async fn should_analyze(
-        &self,
         contact_id: i64,
         session_id: i64,
         config: &crate::immersive_settings::CharacterStateConfig,
@@ -116,62 +212,127 @@ impl StateAnalyzer {
     }
 
     /// 分析角色状态
-    /// TODO: 实现真正的LLM调用逻辑
+    ///
+    /// recent_messages 按时间正序 (role, content)。请求按 JSON Schema 输出，服务端不支持时
+    /// 依次降级为 json_object 与纯提示词；输出无法解析时带着错误信息重试，全部失败返回 Err，
+    /// 由调用方沿用缓存状态
     pub async fn analyze_state(
         &self,
         contact_id: i64,
         session_id: i64,
         recent_messages: Vec<(String, String)>, // (role, content)
     ) -> Result<StateAnalysisResult, String> {
-        // 1. 加载 state_analysis.txt 提示词模板
-        let _prompt = match Self::load_prompt_template("state_analysis.txt") {
-            Ok(p) => p,
-            Err(e) => {
-                println!("⚠️ 无法加载状态分析模板: {}, 使用默认逻辑", e);
-                "".to_string()
-            }
-        };
-
-        // TODO: 2. 构建对话历史字符串
-        // TODO: 3. 调用 LLM API
-        // TODO: 4. 解析 JSON 响应
+        use crate::ai_utils::{call_json_backend, is_unsupported_request, JsonMode};
+        use crate::models::{ChatRequest, GenerationParams, Message};
 
-        // 占位实现: 基于消息数量和内容做简单推断
-        let total_messages = recent_messages.len();
+        // 1. 加载 state_analysis.txt 提示词模板并填入对话历史
+        let template = Self::load_prompt_template("state_analysis.txt").unwrap_or_else(|e| {
+            println!("⚠️ 无法加载状态分析模板: {}, 使用内置模板", e);
+            DEFAULT_ANALYSIS_PROMPT.to_string()
+        });
+        let mut ctx = crate::prompt_template::TemplateContext::new();
+        ctx.set(
+            "conversation_history",
+            serde_json::json!(Self::format_history(&recent_messages)),
+        );
+        let prompt = crate::prompt_template::render_or_raw(&template, &ctx, &|_| None).text;
 
-        let mood = if total_messages > 10 {
-            "busy".to_string()
-        } else if total_messages > 5 {
-            "neutral".to_string()
-        } else {
-            "happy".to_string()
+        let message = |role: &str, content: String| Message {
+            id: None,
+            model: None,
+            role: role.to_string(),
+            content,
+            reasoning_content: None,
+            file_metadata: None,
+            search_metadata: None,
+            provider: None,
+            mode: None,
+            role_id: None,
+            parent_id: None,
         };
+        let mut messages = vec![
+            message("system", prompt),
+            message("user", "请根据以上对话历史输出分析结果 JSON。".to_string()),
+        ];
 
-        let busy_level = (total_messages as f32 / 20.0).min(1.0);
-        let interest_level = 0.5; // 默认中等兴趣
-
-        // 使用 CharacterState 结构体记录 (消除 never constructed 警告)
-        let _state = CharacterState {
-            contact_id,
-            session_id,
-            mood: mood.clone(),
-            busy_level,
-            interest_level,
-            message_count: total_messages as u32,
-            last_analyzed: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-        };
+        // 2. 调用 LLM，格式错误时重试
+        let schema = StateAnalysisResult::json_schema();
+        let mut mode = JsonMode::Schema;
+        let mut last_error = String::new();
+        for attempt in 1..=MAX_ATTEMPTS {
+            let payload = ChatRequest {
+                model: self.model.clone(),
+                messages: messages.clone(),
+                stream: false,
+                params: GenerationParams {
+                    temperature: Some(0.3),
+                    max_tokens: Some(512),
+                    ..Default::default()
+                },
+            };
+            let output = match call_json_backend(
+                &self.client,
+                &self.provider,
+                &payload,
+                "character_state",
+                &schema,
+                mode,
+            )
+            .await
+            {
+                Ok(output) => output,
+                Err(e) => {
+                    // 服务端不支持当前的结构化输出方式时降级后重试
+                    if let Some(next) = mode.downgrade().filter(|_| is_unsupported_request(&e)) {
+                        println!("⚠️ [状态] {:?} 模式不受支持，降级为 {:?}", mode, next);
+                        mode = next;
+                    }
+                    println!("⚠️ [状态] 第 {} 次分析请求失败: {}", attempt, e);
+                    last_error = e;
+                    continue;
+                }
+            };
 
-        println!(
-            "🧠 [Contact {}] 状态分析完成: mood={}, busy={:.2}, interest={:.2}",
-            contact_id, mood, busy_level, interest_level
-        );
+            // 3. 解析并校验 JSON 响应
+            match StateAnalysisResult::parse(&output) {
+                Ok(result) => {
+                    println!(
+                        "🧠 [Contact {} / Session {}] 状态分析完成: mood={}, busy={:.2}, interest={:.2}",
+                        contact_id, session_id, result.mood, result.busy_level, result.interest_level
+                    );
+                    return Ok(result);
+                }
+                Err(e) => {
+                    println!("⚠️ [状态] 第 {} 次分析输出无效: {}", attempt, e);
+                    messages.push(message("assistant", output));
+                    messages.push(message(
+                        "user",
+                        format!(
+                            "上面的输出无法解析 ({})，请只输出符合格式要求的 JSON 对象。",
+                            e
+                        ),
+                    ));
+                    last_error = e;
+                }
+            }
+        }
+        Err(format!(
+            "状态分析失败 (已尝试 {} 次): {}",
+            MAX_ATTEMPTS, last_error
+        ))
+    }
 
-        Ok(StateAnalysisResult {
-            mood,
-            busy_level,
-            interest_level,
-            reasoning: Some(format!("基于{}条消息的简单推断", total_messages)),
-        })
+    /// 对话历史转为文本，过长的单条消息截断
+    fn format_history(messages: &[(String, String)]) -> String {
+        messages
+            .iter()
+            .map(|(role, content)| {
+                let speaker = if role == "user" { "用户" } else { "角色" };
+                let content: String = content.trim().chars().take(MAX_MESSAGE_CHARS).collect();
+                format!("{}: {}", speaker, content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
     /// 加载提示词模板
@@ -195,8 +356,160 @@ impl StateAnalyzer {
     }
 }
 
-impl Default for StateAnalyzer {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 本地替身服务器：按顺序为每个连接返回一条 (状态行, 响应体)，并回传收到的请求体
+    async fn stand_in_server(
+        responses: Vec<(&'static str, String)>,
+    ) -> (String, tokio::task::JoinHandle<Vec<serde_json::Value>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for (status, body) in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let head_end = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(head_end) = text.find("\r\n\r\n") {
+                        let len = text[..head_end]
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= head_end + 4 + len || n == 0 {
+                            break head_end;
+                        }
+                    }
+                };
+                bodies.push(serde_json::from_slice(&request[head_end + 4..]).unwrap());
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            bodies
+        });
+        (format!("http://{}", addr), handle)
+    }
+
+    fn completion(content: &str) -> String {
+        serde_json::json!({ "choices": [{ "message": { "role": "assistant", "content": content } }] })
+            .to_string()
+    }
+
+    #[test]
+    fn test_parse_validates_and_clamps() {
+        let result = StateAnalysisResult::parse(
+            "```json\n{\"mood\": \"Happy\", \"busy_level\": 1.4, \"interest_level\": -0.2, \"reasoning\": \"聊得开心\"}\n```",
+        )
+        .unwrap();
+        assert_eq!(result.mood, "happy");
+        assert_eq!(result.busy_level, 1.0);
+        assert_eq!(result.interest_level, 0.0);
+
+        assert!(StateAnalysisResult::parse(
+            "{\"mood\": \"excited\", \"busy_level\": 0.5, \"interest_level\": 0.5}"
+        )
+        .is_err());
+        assert!(StateAnalysisResult::parse("{\"mood\": \"happy\"}").is_err());
+        assert!(StateAnalysisResult::parse("我现在心情不错").is_err());
+    }
+
+    #[test]
+    fn test_format_history_truncates() {
+        let long = "啊".repeat(MAX_MESSAGE_CHARS + 10);
+        let history = StateAnalyzer::format_history(&[
+            ("user".to_string(), " 在吗 ".to_string()),
+            ("assistant".to_string(), long),
+        ]);
+        let lines: Vec<_> = history.lines().collect();
+        assert_eq!(lines[0], "用户: 在吗");
+        assert_eq!(
+            lines[1].chars().count(),
+            "角色: ".chars().count() + MAX_MESSAGE_CHARS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_analyze_downgrades_and_retries() {
+        let (base, server) = stand_in_server(vec![
+            (
+                "400 Bad Request",
+                "{\"error\":\"response_format json_schema is not supported\"}".to_string(),
+            ),
+            ("200 OK", completion("好的，我来分析一下")),
+            (
+                "200 OK",
+                completion("{\"mood\": \"tired\", \"busy_level\": 0.7, \"interest_level\": 0.3, \"reasoning\": \"回复变短\"}"),
+            ),
+        ])
+        .await;
+        let provider = crate::provider_config::ProviderConfig::from_value(&serde_json::json!({
+            "id": "stand-in",
+            "baseUrl": format!("{}/v1", base),
+            "apiKey": "k"
+        }))
+        .unwrap();
+        let analyzer = StateAnalyzer::new(reqwest::Client::new(), provider, "m".to_string());
+
+        let result = analyzer
+            .analyze_state(
+                1,
+                2,
+                vec![
+                    ("user".to_string(), "今天好累".to_string()),
+                    ("assistant".to_string(), "嗯".to_string()),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.mood, "tired");
+        assert_eq!(result.busy_level, 0.7);
+
+        let bodies = server.await.unwrap();
+        assert_eq!(bodies[0]["response_format"]["type"], "json_schema");
+        assert_eq!(bodies[1]["response_format"]["type"], "json_object");
+        assert_eq!(bodies[2]["response_format"]["type"], "json_object");
+        // 重试时带上无效输出与纠正提示
+        let messages = bodies[2]["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[2]["content"], "好的，我来分析一下");
+        assert!(messages[0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("用户: 今天好累"));
+    }
+
+    #[tokio::test]
+    async fn test_analyze_fails_after_max_attempts() {
+        let (base, server) = stand_in_server(
+            (0..MAX_ATTEMPTS)
+                .map(|_| ("200 OK", completion("不是 JSON")))
+                .collect(),
+        )
+        .await;
+        let provider = crate::provider_config::ProviderConfig::from_value(&serde_json::json!({
+            "id": "stand-in",
+            "baseUrl": format!("{}/v1", base),
+            "apiKey": "k"
+        }))
+        .unwrap();
+        let analyzer = StateAnalyzer::new(reqwest::Client::new(), provider, "m".to_string());
+        let err = analyzer.analyze_state(1, 2, Vec::new()).await.unwrap_err();
+        assert!(err.contains("已尝试 3 次"));
+        assert_eq!(server.await.unwrap().len(), MAX_ATTEMPTS);
     }
 }
@@ -0,0 +1,884 @@
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 导出文件格式版本 (用于导入时识别与兼容)
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+const EXPORT_APP_TAG: &str = "goge-chat";
+
+// ==================================================================================
+// 导出数据模型 (JSON 格式即为该结构的无损序列化)
+// ==================================================================================
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportBundle {
+    #[serde(rename = "app")]
+    pub app: String,
+    #[serde(rename = "formatVersion")]
+    pub format_version: u32,
+    #[serde(rename = "exportedAt")]
+    pub exported_at: String,
+    #[serde(default)]
+    pub folders: Vec<ExportFolder>,
+    #[serde(default)]
+    pub sessions: Vec<ExportSession>,
+    #[serde(default, rename = "socialSessions")]
+    pub social_sessions: Vec<ExportSocialSession>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportFolder {
+    /// 导出时的原始 ID，仅用于在导入时重建会话归属
+    pub id: i64,
+    pub name: String,
+    #[serde(default, rename = "sortOrder")]
+    pub sort_order: i32,
+    #[serde(default, rename = "isCollapsed")]
+    pub is_collapsed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportSession {
+    pub id: i64,
+    pub title: String,
+    #[serde(default, rename = "folderId")]
+    pub folder_id: Option<i64>,
+    #[serde(default, rename = "sortOrder")]
+    pub sort_order: i32,
+    #[serde(default, rename = "updatedAt")]
+    pub updated_at: Option<String>,
+    #[serde(default, rename = "presetId")]
+    pub preset_id: Option<String>,
+    #[serde(default, rename = "modelId")]
+    pub model_id: Option<String>,
+    #[serde(default, rename = "systemPrompt")]
+    pub system_prompt: Option<String>,
+    #[serde(default, rename = "generationParams")]
+    pub generation_params: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<ExportMessage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportMessage {
+    /// 导出时的消息 ID，仅用于导入时还原对比模式的 parentId
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    /// 对比模式的兄弟回答指向其用户消息
+    #[serde(default, rename = "parentId", skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<i64>,
+    pub role: String,
+    pub content: String,
+    #[serde(default, rename = "reasoningContent", alias = "reasoning_content")]
+    pub reasoning_content: Option<String>,
+    #[serde(default, rename = "fileMetadata", alias = "file_metadata")]
+    pub file_metadata: Option<String>,
+    #[serde(default, rename = "searchMetadata", alias = "search_metadata")]
+    pub search_metadata: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default, rename = "createdAt")]
+    pub created_at: Option<String>,
+    /// 附件内容 (仅在导出时选择包含附件时填充)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<ExportAttachment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportAttachment {
+    pub name: String,
+    /// 导出时的原始路径
+    pub path: String,
+    #[serde(default)]
+    pub mime: String,
+    /// Base64 编码的文件内容 (文件丢失时为空)
+    #[serde(default, rename = "dataBase64")]
+    pub data_base64: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportContact {
+    pub name: String,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub remark: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportSocialSession {
+    pub id: i64,
+    pub title: String,
+    pub contact: ExportContact,
+    #[serde(default, rename = "createdAt")]
+    pub created_at: Option<String>,
+    #[serde(default, rename = "updatedAt")]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<ExportMessage>,
+}
+
+impl ExportBundle {
+    pub fn new() -> Self {
+        Self {
+            app: EXPORT_APP_TAG.to_string(),
+            format_version: EXPORT_FORMAT_VERSION,
+            exported_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            folders: Vec::new(),
+            sessions: Vec::new(),
+            social_sessions: Vec::new(),
+        }
+    }
+
+    pub fn message_count(&self) -> usize {
+        self.sessions
+            .iter()
+            .map(|s| s.messages.len())
+            .sum::<usize>()
+            + self
+                .social_sessions
+                .iter()
+                .map(|s| s.messages.len())
+                .sum::<usize>()
+    }
+}
+
+impl Default for ExportBundle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            "html" | "htm" => Ok(Self::Html),
+            other => Err(format!("不支持的导出格式: {}", other)),
+        }
+    }
+}
+
+// ==================================================================================
+// 附件辅助
+// ==================================================================================
+
+/// `file_metadata` 中的单个文件条目 (前端 ChatInput 写入的 {name, path, icon})
+#[derive(Deserialize, Debug, Clone)]
+pub struct FileMetadataEntry {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub path: String,
+}
+
+pub fn parse_file_metadata(raw: Option<&str>) -> Vec<FileMetadataEntry> {
+    raw.and_then(|s| serde_json::from_str::<Vec<FileMetadataEntry>>(s).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|f| !f.path.is_empty())
+        .collect()
+}
+
+pub fn guess_mime(name: &str) -> &'static str {
+    let ext = name
+        .rsplit('.')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        "md" | "txt" | "js" | "ts" | "py" | "rs" | "cpp" | "h" | "css" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// 读取附件内容 (文件不存在时保留元数据，内容为空)
+pub fn load_attachments(file_metadata: Option<&str>) -> Vec<ExportAttachment> {
+    parse_file_metadata(file_metadata)
+        .into_iter()
+        .map(|f| {
+            let name = if f.name.is_empty() {
+                f.path
+                    .rsplit(['/', '\\'])
+                    .next()
+                    .unwrap_or_default()
+                    .to_string()
+            } else {
+                f.name
+            };
+            let data_base64 = std::fs::read(&f.path)
+                .ok()
+                .map(|bytes| BASE64_STANDARD.encode(bytes));
+            ExportAttachment {
+                mime: guess_mime(&name).to_string(),
+                name,
+                path: f.path,
+                data_base64,
+            }
+        })
+        .collect()
+}
+
+// ==================================================================================
+// 渲染：Markdown / JSON / HTML
+// ==================================================================================
+
+fn role_heading(role: &str) -> &'static str {
+    match role {
+        "user" => "🧑 用户",
+        "assistant" => "🤖 助手",
+        "system" => "⚙️ 系统",
+        _ => "💬 消息",
+    }
+}
+
+/// Markdown 中附件的相对链接目录 (由调用方负责把文件复制过去)
+pub fn markdown_attachment_link(dir_name: &str, index: usize, name: &str) -> String {
+    format!("{}/{}_{}", dir_name, index, safe_attachment_name(name))
+}
+
+/// 附件名来自导入文件或消息元数据，不可信：只保留最后一段文件名
+/// 两种路径分隔符都会被剥离，`..` 或空名称改用生成的名称
+pub fn safe_attachment_name(name: &str) -> String {
+    let base = Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let base: String = base
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| *c != ':' && !c.is_control())
+        .collect();
+    let base = base.trim();
+    if base.is_empty() || base.chars().all(|c| c == '.') {
+        "attachment".to_string()
+    } else {
+        base.to_string()
+    }
+}
+
+/// 渲染为 Markdown
+/// `attachment_dir`: 若提供，则附件链接指向该相对目录 (由 [`markdown_attachment_link`] 生成)
+pub fn render_markdown(bundle: &ExportBundle, attachment_dir: Option<&str>) -> String {
+    let mut out = String::new();
+    let mut attachment_index = 0usize;
+
+    let folder_names: HashMap<i64, &str> = bundle
+        .folders
+        .iter()
+        .map(|f| (f.id, f.name.as_str()))
+        .collect();
+
+    let mut write_messages = |out: &mut String, messages: &[ExportMessage]| {
+        for m in messages {
+            let mut heading = role_heading(&m.role).to_string();
+            if m.role == "assistant" {
+                if let Some(model) = m.model.as_deref().filter(|s| !s.is_empty()) {
+                    heading.push_str(&format!(" · `{}`", model));
+                }
+            }
+            out.push_str(&format!("### {}\n\n", heading));
+
+            if let Some(reasoning) = m
+                .reasoning_content
+                .as_deref()
+                .filter(|s| !s.trim().is_empty())
+            {
+                out.push_str("<details>\n<summary>💭 思考过程</summary>\n\n");
+                out.push_str(reasoning.trim());
+                out.push_str("\n\n</details>\n\n");
+            }
+
+            out.push_str(m.content.trim());
+            out.push_str("\n\n");
+
+            let files = if m.attachments.is_empty() {
+                parse_file_metadata(m.file_metadata.as_deref())
+                    .into_iter()
+                    .map(|f| (f.name, f.path))
+                    .collect::<Vec<_>>()
+            } else {
+                m.attachments
+                    .iter()
+                    .map(|a| (a.name.clone(), a.path.clone()))
+                    .collect()
+            };
+            if !files.is_empty() {
+                out.push_str("**附件**\n\n");
+                for (name, path) in files {
+                    let link = match attachment_dir {
+                        Some(dir) => {
+                            attachment_index += 1;
+                            markdown_attachment_link(dir, attachment_index, &name)
+                        }
+                        None => path,
+                    };
+                    out.push_str(&format!("- [{}](<{}>)\n", name, link));
+                }
+                out.push('\n');
+            }
+        }
+    };
+
+    for session in &bundle.sessions {
+        out.push_str(&format!("# {}\n\n", session.title));
+        let mut meta = Vec::new();
+        if let Some(folder) = session.folder_id.and_then(|id| folder_names.get(&id)) {
+            meta.push(format!("文件夹: {}", folder));
+        }
+        if let Some(model) = session.model_id.as_deref().filter(|s| !s.is_empty()) {
+            meta.push(format!("模型: {}", model));
+        }
+        if let Some(updated) = session.updated_at.as_deref() {
+            meta.push(format!("更新于: {}", updated));
+        }
+        if !meta.is_empty() {
+            out.push_str(&format!("> {}\n\n", meta.join(" · ")));
+        }
+        if let Some(prompt) = session
+            .system_prompt
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+        {
+            out.push_str("<details>\n<summary>⚙️ 系统提示词</summary>\n\n");
+            out.push_str(prompt.trim());
+            out.push_str("\n\n</details>\n\n");
+        }
+        write_messages(&mut out, &session.messages);
+        out.push_str("---\n\n");
+    }
+
+    for session in &bundle.social_sessions {
+        out.push_str(&format!(
+            "# {} · {}\n\n",
+            session.contact.name, session.title
+        ));
+        if let Some(updated) = session.updated_at.as_deref() {
+            out.push_str(&format!("> 更新于: {}\n\n", updated));
+        }
+        write_messages(&mut out, &session.messages);
+        out.push_str("---\n\n");
+    }
+
+    out
+}
+
+/// 渲染为 JSON (无损)
+pub fn render_json(bundle: &ExportBundle) -> Result<String, String> {
+    serde_json::to_string_pretty(bundle).map_err(|e| e.to_string())
+}
+
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn render_html_attachment(a: &ExportAttachment) -> String {
+    let name = html_escape(&a.name);
+    match a.data_base64.as_deref() {
+        Some(data) if a.mime.starts_with("image/") => format!(
+            "<figure class=\"att\"><img alt=\"{}\" src=\"data:{};base64,{}\"><figcaption>{}</figcaption></figure>",
+            name, a.mime, data, name
+        ),
+        Some(data) if a.mime.starts_with("text/") || a.mime == "application/json" => {
+            let text = BASE64_STANDARD
+                .decode(data)
+                .ok()
+                .and_then(|b| String::from_utf8(b).ok())
+                .unwrap_or_default();
+            format!(
+                "<details class=\"att\"><summary>📎 {}</summary><pre>{}</pre></details>",
+                name,
+                html_escape(&text)
+            )
+        }
+        Some(data) => format!(
+            "<a class=\"att\" download=\"{}\" href=\"data:{};base64,{}\">📎 {}</a>",
+            name, a.mime, data, name
+        ),
+        None => format!("<span class=\"att missing\">📎 {} (文件缺失)</span>", name),
+    }
+}
+
+fn render_html_messages(out: &mut String, messages: &[ExportMessage]) {
+    for m in messages {
+        let role_class = match m.role.as_str() {
+            "user" => "user",
+            "assistant" => "assistant",
+            _ => "system",
+        };
+        out.push_str(&format!("<section class=\"msg {}\">", role_class));
+        out.push_str(&format!("<h3>{}", html_escape(role_heading(&m.role))));
+        if let Some(model) = m.model.as_deref().filter(|s| !s.is_empty()) {
+            out.push_str(&format!(" <code>{}</code>", html_escape(model)));
+        }
+        if let Some(time) = m.created_at.as_deref() {
+            out.push_str(&format!(" <time>{}</time>", html_escape(time)));
+        }
+        out.push_str("</h3>");
+
+        if let Some(reasoning) = m
+            .reasoning_content
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+        {
+            out.push_str(&format!(
+                "<details class=\"reasoning\"><summary>💭 思考过程</summary><div class=\"body\">{}</div></details>",
+                html_escape(reasoning.trim())
+            ));
+        }
+        out.push_str(&format!(
+            "<div class=\"body\">{}</div>",
+            html_escape(m.content.trim())
+        ));
+
+        if m.attachments.is_empty() {
+            let files = parse_file_metadata(m.file_metadata.as_deref());
+            for f in files {
+                out.push_str(&format!(
+                    "<span class=\"att missing\">📎 {}</span>",
+                    html_escape(&f.name)
+                ));
+            }
+        } else {
+            for a in &m.attachments {
+                out.push_str(&render_html_attachment(a));
+            }
+        }
+        out.push_str("</section>\n");
+    }
+}
+
+/// 渲染为自包含 HTML (内联样式，附件以 data URI 内嵌)
+pub fn render_html(bundle: &ExportBundle) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n<title>Goge Chat 导出</title>\n<style>\n\
+body{font-family:-apple-system,'Segoe UI','PingFang SC','Microsoft YaHei',sans-serif;max-width:860px;margin:0 auto;padding:24px;background:#f6f7f9;color:#202124}\n\
+article{background:#fff;border-radius:12px;padding:20px 24px;margin-bottom:24px;box-shadow:0 1px 3px rgba(0,0,0,.08)}\n\
+h1{font-size:22px;margin:0 0 4px}.meta{color:#70757a;font-size:13px;margin-bottom:16px}\n\
+.msg{border-top:1px solid #eee;padding:12px 0}.msg h3{font-size:14px;margin:0 0 8px;color:#5f6368}\n\
+.msg h3 time{font-weight:normal;font-size:12px;color:#9aa0a6;margin-left:8px}\n\
+.msg.user .body{background:#eef3fe;border-radius:8px;padding:8px 12px}\n\
+.body{white-space:pre-wrap;word-break:break-word;line-height:1.7}\n\
+details.reasoning{background:#fafafa;border-left:3px solid #c4c7c5;padding:6px 12px;margin-bottom:8px;color:#5f6368}\n\
+.att{display:block;margin-top:8px;font-size:13px}.att img{max-width:100%;border-radius:6px}.att pre{background:#f1f3f4;padding:8px;overflow:auto}\n\
+.missing{color:#b3261e}\n\
+</style>\n</head>\n<body>\n",
+    );
+    out.push_str(&format!(
+        "<p class=\"meta\">导出时间: {}</p>\n",
+        html_escape(&bundle.exported_at)
+    ));
+
+    for session in &bundle.sessions {
+        out.push_str("<article>");
+        out.push_str(&format!("<h1>{}</h1>", html_escape(&session.title)));
+        let mut meta = Vec::new();
+        if let Some(model) = session.model_id.as_deref().filter(|s| !s.is_empty()) {
+            meta.push(format!("模型: {}", model));
+        }
+        if let Some(updated) = session.updated_at.as_deref() {
+            meta.push(format!("更新于: {}", updated));
+        }
+        out.push_str(&format!(
+            "<div class=\"meta\">{}</div>",
+            html_escape(&meta.join(" · "))
+        ));
+        render_html_messages(&mut out, &session.messages);
+        out.push_str("</article>\n");
+    }
+
+    for session in &bundle.social_sessions {
+        out.push_str("<article>");
+        out.push_str(&format!(
+            "<h1>{} · {}</h1>",
+            html_escape(&session.contact.name),
+            html_escape(&session.title)
+        ));
+        render_html_messages(&mut out, &session.messages);
+        out.push_str("</article>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// ==================================================================================
+// 导入：本应用 JSON 与 ChatGPT conversations.json
+// ==================================================================================
+
+/// 解析导入文件，自动识别格式
+pub fn parse_import(raw: &str) -> Result<ExportBundle, String> {
+    let value: Value = serde_json::from_str(raw).map_err(|e| format!("JSON 解析失败: {}", e))?;
+
+    if value.get("app").and_then(|v| v.as_str()) == Some(EXPORT_APP_TAG) {
+        let version = value
+            .get("formatVersion")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as u32;
+        if version > EXPORT_FORMAT_VERSION {
+            return Err(format!(
+                "导出文件版本 {} 高于当前支持的版本 {}，请升级应用",
+                version, EXPORT_FORMAT_VERSION
+            ));
+        }
+        return serde_json::from_value(value).map_err(|e| format!("导出文件结构错误: {}", e));
+    }
+
+    if let Some(conversations) = value.as_array() {
+        if conversations.iter().all(|c| c.get("mapping").is_some()) {
+            return parse_chatgpt_export(conversations);
+        }
+    }
+
+    Err("无法识别的导入格式 (支持 Goge Chat JSON 与 ChatGPT conversations.json)".to_string())
+}
+
+fn epoch_to_string(v: &Value) -> Option<String> {
+    let secs = v.as_f64()?;
+    chrono::DateTime::from_timestamp(secs.trunc() as i64, 0).map(|dt| {
+        dt.with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    })
+}
+
+/// 提取 ChatGPT 消息正文 (仅文本部分)
+fn chatgpt_message_text(content: &Value) -> String {
+    if let Some(parts) = content["parts"].as_array() {
+        return parts
+            .iter()
+            .filter_map(|p| p.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    content["text"].as_str().unwrap_or_default().to_string()
+}
+
+/// 解析 ChatGPT 官方导出 (conversations.json)
+/// 每个会话是一棵消息树，从 current_node 沿 parent 回溯得到最终展示的分支
+fn parse_chatgpt_export(conversations: &[Value]) -> Result<ExportBundle, String> {
+    let mut bundle = ExportBundle::new();
+
+    // 所有导入会话放进一个文件夹，便于整理
+    let folder_id = 1;
+    bundle.folders.push(ExportFolder {
+        id: folder_id,
+        name: "ChatGPT 导入".to_string(),
+        sort_order: 0,
+        is_collapsed: false,
+    });
+
+    // 最新的会话排在最前
+    let mut ordered: Vec<&Value> = conversations.iter().collect();
+    ordered.sort_by(|a, b| {
+        let ta = a["update_time"].as_f64().unwrap_or(0.0);
+        let tb = b["update_time"].as_f64().unwrap_or(0.0);
+        tb.partial_cmp(&ta).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    for (index, conv) in ordered.into_iter().enumerate() {
+        let mapping = match conv["mapping"].as_object() {
+            Some(m) => m,
+            None => continue,
+        };
+
+        // 找到回溯起点：优先 current_node，否则取任意叶子节点
+        let mut node_id = conv["current_node"].as_str().map(|s| s.to_string());
+        if node_id.is_none() {
+            node_id = mapping
+                .iter()
+                .find(|(_, n)| n["children"].as_array().is_none_or(|c| c.is_empty()))
+                .map(|(id, _)| id.clone());
+        }
+
+        let mut chain = Vec::new();
+        let mut guard = 0;
+        while let Some(id) = node_id {
+            guard += 1;
+            if guard > mapping.len() + 1 {
+                return Err("ChatGPT 导出数据中存在循环引用".to_string());
+            }
+            let node = match mapping.get(&id) {
+                Some(n) => n,
+                None => break,
+            };
+            chain.push(node);
+            node_id = node["parent"].as_str().map(|s| s.to_string());
+        }
+        chain.reverse();
+
+        let mut messages: Vec<ExportMessage> = Vec::new();
+        let mut pending_reasoning: Option<String> = None;
+
+        for node in chain {
+            let msg = &node["message"];
+            if msg.is_null() {
+                continue;
+            }
+            let role = msg["author"]["role"].as_str().unwrap_or_default();
+            if msg["metadata"]["is_visually_hidden_from_conversation"]
+                .as_bool()
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let content = &msg["content"];
+            let content_type = content["content_type"].as_str().unwrap_or("text");
+
+            // o 系列模型的思考过程单独存放，挂到下一条助手回复上
+            if content_type == "thoughts" {
+                let thoughts = content["thoughts"]
+                    .as_array()
+                    .map(|arr| {
+                        arr.iter()
+                            .filter_map(|t| t["content"].as_str())
+                            .collect::<Vec<_>>()
+                            .join("\n\n")
+                    })
+                    .unwrap_or_default();
+                if !thoughts.is_empty() {
+                    pending_reasoning = Some(thoughts);
+                }
+                continue;
+            }
+
+            if !matches!(role, "user" | "assistant" | "system") {
+                continue;
+            }
+            if !matches!(content_type, "text" | "multimodal_text" | "code") {
+                continue;
+            }
+
+            let text = chatgpt_message_text(content);
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            let reasoning = if role == "assistant" {
+                pending_reasoning.take()
+            } else {
+                None
+            };
+
+            messages.push(ExportMessage {
+                id: None,
+                parent_id: None,
+                role: role.to_string(),
+                content: text,
+                reasoning_content: reasoning,
+                file_metadata: None,
+                search_metadata: None,
+                model: msg["metadata"]["model_slug"]
+                    .as_str()
+                    .map(|s| s.to_string()),
+                provider: if role == "assistant" {
+                    Some("openai".to_string())
+                } else {
+                    None
+                },
+                created_at: epoch_to_string(&msg["create_time"]),
+                attachments: Vec::new(),
+            });
+        }
+
+        if messages.is_empty() {
+            continue;
+        }
+
+        let model_id = messages.iter().rev().find_map(|m| m.model.clone());
+
+        bundle.sessions.push(ExportSession {
+            id: index as i64 + 1,
+            title: conv["title"]
+                .as_str()
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or("ChatGPT 对话")
+                .to_string(),
+            folder_id: Some(folder_id),
+            sort_order: index as i32,
+            updated_at: epoch_to_string(&conv["update_time"])
+                .or_else(|| epoch_to_string(&conv["create_time"])),
+            preset_id: None,
+            model_id,
+            system_prompt: None,
+            generation_params: None,
+            messages,
+        });
+    }
+
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_bundle() -> ExportBundle {
+        let mut bundle = ExportBundle::new();
+        bundle.folders.push(ExportFolder {
+            id: 7,
+            name: "工作".into(),
+            sort_order: 1,
+            is_collapsed: false,
+        });
+        bundle.sessions.push(ExportSession {
+            id: 3,
+            title: "Rust <生命周期>".into(),
+            folder_id: Some(7),
+            sort_order: 2,
+            updated_at: Some("2024-05-01 10:00:00".into()),
+            preset_id: None,
+            model_id: Some("deepseek-chat".into()),
+            system_prompt: None,
+            generation_params: None,
+            messages: vec![
+                ExportMessage {
+                    id: Some(11),
+                    parent_id: None,
+                    role: "user".into(),
+                    content: "什么是 'a？".into(),
+                    reasoning_content: None,
+                    file_metadata: Some(r#"[{"name":"a.rs","path":"/tmp/a.rs","icon":""}]"#.into()),
+                    search_metadata: None,
+                    model: None,
+                    provider: None,
+                    created_at: None,
+                    attachments: Vec::new(),
+                },
+                ExportMessage {
+                    id: Some(12),
+                    parent_id: Some(11),
+                    role: "assistant".into(),
+                    content: "生命周期标注。".into(),
+                    reasoning_content: Some("先解释概念".into()),
+                    file_metadata: None,
+                    search_metadata: Some("[]".into()),
+                    model: Some("deepseek-reasoner".into()),
+                    provider: Some("deepseek".into()),
+                    created_at: None,
+                    attachments: Vec::new(),
+                },
+            ],
+        });
+        bundle
+    }
+
+    #[test]
+    fn test_markdown_has_headings_and_collapsible_reasoning() {
+        let md = render_markdown(&sample_bundle(), None);
+        assert!(md.contains("# Rust <生命周期>"));
+        assert!(md.contains("### 🧑 用户"));
+        assert!(md.contains("### 🤖 助手 · `deepseek-reasoner`"));
+        assert!(md.contains("<details>\n<summary>💭 思考过程</summary>"));
+        assert!(md.contains("- [a.rs](</tmp/a.rs>)"));
+        assert!(md.contains("文件夹: 工作"));
+    }
+
+    #[test]
+    fn test_html_escapes_content() {
+        let html = render_html(&sample_bundle());
+        assert!(html.contains("Rust &lt;生命周期&gt;"));
+        assert!(!html.contains("<生命周期>"));
+    }
+
+    #[test]
+    fn test_json_roundtrip_is_lossless() {
+        let bundle = sample_bundle();
+        let json = render_json(&bundle).unwrap();
+        let parsed = parse_import(&json).unwrap();
+        assert_eq!(parsed.sessions.len(), 1);
+        let m = &parsed.sessions[0].messages[1];
+        assert_eq!(m.reasoning_content.as_deref(), Some("先解释概念"));
+        assert_eq!(m.search_metadata.as_deref(), Some("[]"));
+        assert_eq!(m.provider.as_deref(), Some("deepseek"));
+        assert_eq!(m.parent_id, Some(11));
+        assert_eq!(parsed.sessions[0].folder_id, Some(7));
+    }
+
+    #[test]
+    fn test_parse_chatgpt_follows_current_branch() {
+        let raw = r#"[{
+            "title": "测试对话",
+            "create_time": 1700000000.5,
+            "update_time": 1700000100.0,
+            "current_node": "c",
+            "mapping": {
+                "root": {"id": "root", "message": null, "parent": null, "children": ["a"]},
+                "a": {"id": "a", "parent": "root", "children": ["b", "x"],
+                      "message": {"author": {"role": "user"}, "create_time": 1700000001.0,
+                                  "content": {"content_type": "text", "parts": ["你好"]}, "metadata": {}}},
+                "x": {"id": "x", "parent": "a", "children": [],
+                      "message": {"author": {"role": "assistant"},
+                                  "content": {"content_type": "text", "parts": ["被放弃的分支"]}, "metadata": {}}},
+                "t": {"id": "t", "parent": "a", "children": ["b"],
+                      "message": {"author": {"role": "assistant"},
+                                  "content": {"content_type": "thoughts", "thoughts": [{"content": "想一想"}]}, "metadata": {}}},
+                "b": {"id": "b", "parent": "t", "children": ["c"],
+                      "message": {"author": {"role": "assistant"},
+                                  "content": {"content_type": "text", "parts": ["你好！"]},
+                                  "metadata": {"model_slug": "gpt-4o"}}},
+                "c": {"id": "c", "parent": "b", "children": [],
+                      "message": {"author": {"role": "tool"},
+                                  "content": {"content_type": "text", "parts": ["ignored"]}, "metadata": {}}}
+            }
+        }]"#;
+
+        let bundle = parse_import(raw).unwrap();
+        assert_eq!(bundle.folders.len(), 1);
+        assert_eq!(bundle.sessions.len(), 1);
+        let s = &bundle.sessions[0];
+        assert_eq!(s.title, "测试对话");
+        assert_eq!(s.model_id.as_deref(), Some("gpt-4o"));
+        assert_eq!(s.messages.len(), 2);
+        assert_eq!(s.messages[0].content, "你好");
+        assert_eq!(s.messages[1].content, "你好！");
+        assert_eq!(s.messages[1].reasoning_content.as_deref(), Some("想一想"));
+    }
+
+    #[test]
+    fn test_rejects_unknown_format() {
+        assert!(parse_import(r#"{"foo": 1}"#).is_err());
+        assert!(ExportFormat::parse("docx").is_err());
+        assert_eq!(ExportFormat::parse("MD").unwrap(), ExportFormat::Markdown);
+    }
+}
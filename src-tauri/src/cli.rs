@@ -0,0 +1,744 @@
+use crate::behavior_sim::{self, ScriptTurn, SimulationStats};
+use crate::commands::ai::{inject_memory, render_system_prompt_with, strip_control_tags};
+use crate::commands::config_cmd::{load_config_from_dir, AppConfig, ConfigLoadReport};
+use crate::commands::export_cmd::{self, ExportScope};
+use crate::db;
+use crate::generation_config::{resolve_generation, split_model_ref, SessionOverrides};
+use crate::memory::processor::{get_relevant_context, upsert_fact, MemoryState};
+use crate::models::{GenerationParams, Message};
+use crate::net::HttpClients;
+use crate::provider_config::{parse_providers, resolve_provider};
+use crate::{chat_export, model_catalog, paths, secret_store, social_db};
+use clap::{Args, Parser, Subcommand};
+use rusqlite::Connection;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// ==================================================================================
+// 命令行工具 (goge-cli)：无界面环境下脚本化对话、会话导出、记忆管理与配置校验
+// 与界面程序共用数据目录 (见 crate::paths)，不依赖 AppHandle
+// ==================================================================================
+
+#[derive(Parser, Debug)]
+#[command(name = "goge-cli", version, about = "Goge Chat 命令行工具")]
+struct Cli {
+    /// 数据目录 (默认为可执行文件同级的 data，也可用 GOGE_DATA_DIR 指定)
+    #[arg(long, global = true, value_name = "DIR")]
+    data_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 发送一条消息并把回复流式输出到 stdout
+    Chat(ChatArgs),
+    /// 会话管理
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+    /// 长期记忆管理
+    #[command(subcommand)]
+    Memory(MemoryCommand),
+    /// 配置管理
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// 用沉浸式行为引擎模拟一段脚本化对话，输出行为统计
+    Simulate(SimulateArgs),
+}
+
+#[derive(Args, Debug)]
+struct ChatArgs {
+    /// 消息内容；省略或为 "-" 时从 stdin 读取
+    message: Vec<String>,
+    /// "提供商ID/模型ID" 或模型 ID，默认使用设置中的模型
+    #[arg(short, long)]
+    model: Option<String>,
+    /// 续写并保存到该会话 (会话 ID)
+    #[arg(short, long)]
+    session: Option<i64>,
+    /// 系统提示词 (覆盖预设)
+    #[arg(long)]
+    system: Option<String>,
+    #[arg(long)]
+    temperature: Option<f32>,
+    #[arg(long)]
+    max_tokens: Option<u32>,
+    /// 不检索长期记忆
+    #[arg(long)]
+    no_memory: bool,
+    /// 把思考过程输出到 stderr
+    #[arg(long)]
+    reasoning: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum SessionsCommand {
+    /// 列出所有会话
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+    /// 导出会话 (默认导出全部)
+    Export(ExportArgs),
+}
+
+#[derive(Args, Debug)]
+struct ExportArgs {
+    /// 输出文件路径
+    #[arg(short, long)]
+    output: PathBuf,
+    /// md / json / html，默认按输出文件扩展名判断
+    #[arg(short, long)]
+    format: Option<String>,
+    #[arg(long, conflicts_with = "folder")]
+    session: Option<i64>,
+    #[arg(long)]
+    folder: Option<i64>,
+    /// 同时导出附件
+    #[arg(long)]
+    attachments: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum MemoryCommand {
+    /// 语义检索记忆
+    Search {
+        query: String,
+        #[arg(long, default_value = "Standard")]
+        mode: String,
+        #[arg(long, default_value = "global")]
+        role_id: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        #[arg(long)]
+        json: bool,
+    },
+    /// 写入一条记忆 (相似的旧记忆会被替换)
+    Insert {
+        content: String,
+        #[arg(long, default_value = "Standard")]
+        mode: String,
+        #[arg(long, default_value = "global")]
+        role_id: String,
+        /// 标记为指令类记忆 (所有角色可见)
+        #[arg(long)]
+        instruction: bool,
+    },
+    /// 以 JSON 导出全部记忆
+    Export {
+        /// 输出文件路径，省略时写到 stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct SimulateArgs {
+    /// 脚本文件：ScriptTurn 的 JSON 数组 ({ "gapMs", "reply", "mood", "busyLevel", "interestLevel" })
+    script: PathBuf,
+    /// 行为设置 (ImmersiveSettings JSON)，默认使用 settings.json 中的 immersiveMode
+    #[arg(long)]
+    settings: Option<PathBuf>,
+    /// 随机种子；多次运行时依次使用 seed, seed+1, ...
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[arg(long, default_value_t = 1)]
+    runs: u64,
+    /// 输出完整报告 (含时间线) 的 JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// 校验配置文件 (只读，不执行迁移写入)；有错误时退出码为 1
+    Validate,
+}
+
+/// 命令行入口，返回进程退出码
+pub fn run() -> i32 {
+    let cli = Cli::parse();
+    if let Some(dir) = &cli.data_dir {
+        std::env::set_var(paths::DATA_DIR_ENV, dir);
+    }
+    let mut out = take_stdout();
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("❌ 无法创建异步运行时: {}", e);
+            return 1;
+        }
+    };
+    match runtime.block_on(execute(cli.command, &mut out)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            1
+        }
+    }
+}
+
+/// 库代码的日志都通过 println! 写到 stdout；为了让结果可以直接被脚本解析，
+/// 把进程 stdout 重定向到 stderr，命令结果写入原 stdout 的副本
+#[cfg(unix)]
+fn take_stdout() -> Box<dyn Write> {
+    use std::os::fd::FromRawFd;
+    // SAFETY: dup 返回的新描述符只交给这一个 File 持有
+    unsafe {
+        let fd = libc::dup(libc::STDOUT_FILENO);
+        if fd >= 0 {
+            if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) >= 0 {
+                return Box::new(std::fs::File::from_raw_fd(fd));
+            }
+            libc::close(fd);
+        }
+    }
+    Box::new(std::io::stdout())
+}
+
+#[cfg(not(unix))]
+fn take_stdout() -> Box<dyn Write> {
+    Box::new(std::io::stdout())
+}
+
+async fn execute(command: Command, out: &mut dyn Write) -> Result<i32, String> {
+    match command {
+        Command::Chat(args) => chat(args, out).await.map(|_| 0),
+        Command::Sessions(SessionsCommand::List { json }) => {
+            let conn = open_chat_db()?;
+            let sessions = db::get_sessions(&conn).map_err(|e| e.to_string())?;
+            if json {
+                let text = serde_json::to_string_pretty(&sessions).map_err(|e| e.to_string())?;
+                writeln!(out, "{}", text).map_err(|e| e.to_string())?;
+            } else {
+                for s in sessions {
+                    writeln!(out, "{}\t{}\t{}", s.id, s.updated_at, s.title)
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            Ok(0)
+        }
+        Command::Sessions(SessionsCommand::Export(args)) => export(args, out).map(|_| 0),
+        Command::Memory(cmd) => memory(cmd, out).await.map(|_| 0),
+        Command::Config(ConfigCommand::Validate) => validate_config(out),
+        Command::Simulate(args) => simulate(args, out).map(|_| 0),
+    }
+}
+
+fn open_chat_db() -> Result<Connection, String> {
+    let conn = Connection::open(paths::chat_db_path()).map_err(|e| e.to_string())?;
+    db::init_db(&conn).map_err(|e| format!("数据库初始化或升级失败: {}", e))?;
+    Ok(conn)
+}
+
+fn open_social_db() -> Result<Connection, String> {
+    let conn = Connection::open(paths::social_db_path()).map_err(|e| e.to_string())?;
+    social_db::init_social_db(&conn).map_err(|e| format!("社交数据库初始化失败: {}", e))?;
+    Ok(conn)
+}
+
+fn load_config(persist_migration: bool) -> (AppConfig, ConfigLoadReport) {
+    let config_dir = paths::config_dir();
+    let mut store = secret_store::SecretStore::open(&config_dir);
+    load_config_from_dir(&config_dir, &mut store, persist_migration)
+}
+
+fn open_memory() -> Result<Arc<RwLock<MemoryState>>, String> {
+    let state = MemoryState::open(&paths::memory_dir(), paths::embedding_model_dir())?;
+    Ok(Arc::new(RwLock::new(state)))
+}
+
+fn text_message(role: &str, content: String) -> Message {
+    Message {
+        id: None,
+        model: None,
+        role: role.to_string(),
+        content,
+        reasoning_content: None,
+        file_metadata: None,
+        search_metadata: None,
+        provider: None,
+        mode: None,
+        role_id: None,
+        parent_id: None,
+    }
+}
+
+/// 命令行参数拼成消息；为空或 "-" 时读取 stdin
+fn read_message(parts: &[String]) -> Result<String, String> {
+    let joined = parts.join(" ");
+    if !joined.trim().is_empty() && joined != "-" {
+        return Ok(joined);
+    }
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| format!("读取 stdin 失败: {}", e))?;
+    if input.trim().is_empty() {
+        return Err("消息内容为空".into());
+    }
+    Ok(input)
+}
+
+async fn chat(args: ChatArgs, out: &mut dyn Write) -> Result<(), String> {
+    let content = read_message(&args.message)?;
+    let (config, _) = load_config(true);
+    let conn = open_chat_db()?;
+
+    let session = match args.session {
+        Some(id) => Some(
+            db::get_session(&conn, id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("会话不存在: {}", id))?,
+        ),
+        None => None,
+    };
+    let mut messages: Vec<Message> = match &session {
+        Some(s) => db::get_messages(&conn, s.id)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|m| m.parent_id.is_none())
+            .map(|m| text_message(&m.role, m.content))
+            .collect(),
+        None => Vec::new(),
+    };
+    if let Some(system) = args.system {
+        messages.retain(|m| m.role != "system");
+        messages.insert(0, text_message("system", system));
+    }
+    messages.push(text_message("user", content.clone()));
+
+    let (provider_id, model_id) = args
+        .model
+        .as_deref()
+        .map(|m| split_model_ref(m, &config.providers))
+        .unwrap_or((None, None));
+    let resolved = resolve_generation(
+        &config,
+        session
+            .as_ref()
+            .map(SessionOverrides::from_session)
+            .as_ref(),
+        provider_id,
+        model_id,
+        GenerationParams {
+            temperature: args.temperature,
+            max_tokens: args.max_tokens,
+            ..Default::default()
+        },
+        GenerationParams::default(),
+    );
+    let provider = resolve_provider(&config, &resolved.provider_id)?;
+    let client = HttpClients::new(config.network.clone()).for_provider(&provider)?;
+    let model = resolved.model.clone();
+    resolved.apply_system_prompt(&mut messages);
+
+    // 长期记忆检索失败不影响对话
+    let memory_context = if config.enable_rag && !args.no_memory {
+        match open_memory() {
+            Ok(state) => get_relevant_context(state, &content, "Standard", "global")
+                .await
+                .unwrap_or_else(|e| {
+                    eprintln!("⚠️ 记忆检索失败: {}", e);
+                    String::new()
+                }),
+            Err(e) => {
+                eprintln!("⚠️ 无法打开记忆库: {}", e);
+                String::new()
+            }
+        }
+    } else {
+        String::new()
+    };
+    let memory_context = Some(memory_context).filter(|c| !c.is_empty());
+    let social_conn = open_social_db().ok();
+    let memory_in_template = render_system_prompt_with(
+        social_conn.as_ref(),
+        &config,
+        session.as_ref(),
+        &mut messages,
+        memory_context.as_deref(),
+    );
+    if let Some(context) = memory_context.filter(|_| !memory_in_template) {
+        inject_memory(&mut messages, context);
+    }
+    strip_control_tags(&mut messages);
+
+    let catalog_entry = model_catalog::cached_model(&conn, &provider.id, &model)
+        .ok()
+        .flatten();
+    model_catalog::check_features(
+        &model,
+        catalog_entry.as_ref(),
+        provider.model(&model),
+        &messages,
+        &resolved.params,
+    )?;
+
+    let mut reply = String::new();
+    let mut reasoning = String::new();
+    let mut write_error = None;
+    crate::ai_utils::stream_chat(
+        &client,
+        &provider,
+        &model,
+        messages,
+        &resolved.params,
+        |text, thought| {
+            if !thought.is_empty() {
+                reasoning.push_str(thought);
+                if args.reasoning {
+                    eprint!("{}", thought);
+                }
+            }
+            if !text.is_empty() {
+                reply.push_str(text);
+                if let Err(e) = out.write_all(text.as_bytes()).and_then(|_| out.flush()) {
+                    // 下游管道关闭 (例如 | head) 时停止生成
+                    write_error = Some(e.to_string());
+                    return false;
+                }
+            }
+            true
+        },
+    )
+    .await?;
+    if let Some(e) = write_error {
+        return Err(format!("写入输出失败: {}", e));
+    }
+    if !reply.ends_with('\n') {
+        let _ = writeln!(out);
+    }
+
+    if let Some(s) = session {
+        db::save_message(&conn, s.id, None, None, "user", &content, None, None, None)
+            .map_err(|e| e.to_string())?;
+        db::save_message(
+            &conn,
+            s.id,
+            Some(&model),
+            Some(&provider.id),
+            "assistant",
+            &reply,
+            Some(reasoning.as_str()).filter(|r| !r.is_empty()),
+            None,
+            None,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn export(args: ExportArgs, out: &mut dyn Write) -> Result<(), String> {
+    let format = match &args.format {
+        Some(f) => chat_export::ExportFormat::parse(f)?,
+        None => chat_export::ExportFormat::parse(
+            &args
+                .output
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        )
+        .map_err(|_| "无法从文件扩展名判断导出格式，请使用 --format 指定".to_string())?,
+    };
+    let scope = match (args.session, args.folder) {
+        (Some(id), _) => ExportScope::Session { id: id.to_string() },
+        (None, Some(id)) => ExportScope::Folder { id: id.to_string() },
+        (None, None) => ExportScope::All,
+    };
+    // Markdown 的附件以文件形式复制，不需要把内容读进内存
+    let embed_attachments = args.attachments && format != chat_export::ExportFormat::Markdown;
+
+    let mut bundle = chat_export::ExportBundle::new();
+    export_cmd::collect_chat_sessions(&open_chat_db()?, &scope, embed_attachments, &mut bundle)?;
+    export_cmd::collect_social_sessions(
+        &open_social_db()?,
+        &scope,
+        embed_attachments,
+        &mut bundle,
+    )?;
+    let summary = export_cmd::write_export(&bundle, format, &args.output, args.attachments)?;
+    writeln!(
+        out,
+        "{} 个会话 / {} 条消息 / {} 个附件 -> {}",
+        summary.sessions, summary.messages, summary.attachments, summary.path
+    )
+    .map_err(|e| e.to_string())
+}
+
+async fn memory(command: MemoryCommand, out: &mut dyn Write) -> Result<(), String> {
+    let state = open_memory()?;
+    match command {
+        MemoryCommand::Search {
+            query,
+            mode,
+            role_id,
+            limit,
+            json,
+        } => {
+            let state_read = state.read().await;
+            let engine = state_read.get_engine().await?;
+            let vector = engine.get_vector(&query)?;
+            let filter = format!("mode = '{}' AND role_id = '{}'", mode, role_id);
+            let results = state_read
+                .db
+                .search_similar_facts(vector, limit, Some(filter))
+                .await?;
+            if json {
+                let items: Vec<serde_json::Value> = results
+                    .iter()
+                    .map(|(fact, distance)| {
+                        serde_json::json!({ "distance": distance, "fact": fact })
+                    })
+                    .collect();
+                let text = serde_json::to_string_pretty(&items).map_err(|e| e.to_string())?;
+                writeln!(out, "{}", text).map_err(|e| e.to_string())?;
+            } else {
+                for (fact, distance) in results {
+                    writeln!(out, "{:.3}\t{}\t{}", distance, fact.id, fact.content)
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        MemoryCommand::Insert {
+            content,
+            mode,
+            role_id,
+            instruction,
+        } => {
+            state.read().await.db.ensure_table(512).await?;
+            upsert_fact(state, &content, &role_id, &mode, instruction).await?;
+            writeln!(out, "已写入").map_err(|e| e.to_string())?;
+        }
+        MemoryCommand::Export { output } => {
+            let all = state.read().await.db.get_all_memories().await?;
+            let text = serde_json::to_string_pretty(&all).map_err(|e| e.to_string())?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, text).map_err(|e| format!("写入失败: {}", e))?;
+                    writeln!(out, "{} 条记忆 -> {}", all.len(), path.display())
+                        .map_err(|e| e.to_string())?;
+                }
+                None => writeln!(out, "{}", text).map_err(|e| e.to_string())?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn simulate(args: SimulateArgs, out: &mut dyn Write) -> Result<(), String> {
+    let read_json = |path: &PathBuf| -> Result<serde_json::Value, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("{} 不是有效的 JSON: {}", path.display(), e))
+    };
+    let script: Vec<ScriptTurn> = serde_json::from_value(read_json(&args.script)?)
+        .map_err(|e| format!("脚本格式错误: {}", e))?;
+    let mut settings = match &args.settings {
+        Some(path) => serde_json::from_value(read_json(path)?)
+            .map_err(|e| format!("行为设置格式错误: {}", e))?,
+        None => load_config(false).0.immersive_mode,
+    };
+    // 模拟的就是沉浸式行为，忽略设置中的总开关
+    settings.enabled = true;
+
+    let reports: Vec<_> = (0..args.runs.max(1))
+        .map(|i| behavior_sim::simulate(settings.clone(), args.seed.wrapping_add(i), &script))
+        .collect();
+    if args.json {
+        let text = serde_json::to_string_pretty(&reports).map_err(|e| e.to_string())?;
+        return writeln!(out, "{}", text).map_err(|e| e.to_string());
+    }
+
+    let outcomes: Vec<_> = reports.iter().flat_map(|r| r.outcomes.clone()).collect();
+    let proactive: Vec<_> = reports.iter().flat_map(|r| r.proactive.clone()).collect();
+    let stats = SimulationStats::from_outcomes(&outcomes, &proactive);
+    let segments = stats
+        .segment_counts
+        .iter()
+        .map(|(n, count)| format!("{}段×{}", n, count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let lines = [
+        format!("轮次: {} (运行 {} 次)", stats.turns, reports.len()),
+        format!(
+            "回复: {}，已读不回率: {:.1}%",
+            stats.replied,
+            stats.ignore_rate * 100.0
+        ),
+        format!(
+            "延迟决策: {}，撤回修正: {}",
+            stats.delayed_decisions, stats.typo_corrections
+        ),
+        format!("平均首条回复延迟: {:.0} ms", stats.average_delay_ms),
+        format!("平均分段数: {:.2} ({})", stats.average_segments, segments),
+        format!(
+            "主动发言: 评估 {} 次，达到阈值 {} 次，期望触发 {:.1} 次",
+            stats.proactive_checks, stats.proactive_eligible, stats.expected_proactive
+        ),
+    ];
+    for line in lines {
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn validate_config(out: &mut dyn Write) -> Result<i32, String> {
+    let (config, report) = load_config(false);
+    let mut errors: Vec<String> = report.issues.iter().map(|e| e.to_string()).collect();
+    if let Err(e) = config.network.validate() {
+        errors.push(format!("settings.json (network): {}", e));
+    }
+    if config.api_server.enabled && config.api_server.token.trim().is_empty() {
+        errors.push("settings.json (apiServer.token): 已启用本地接口但未设置访问令牌".into());
+    }
+    // 缺少 API Key 不算配置文件错误 (密钥可能在钥匙串中且当前环境无法访问)
+    let mut warnings: Vec<String> = report.warnings.iter().map(|w| w.to_string()).collect();
+    match parse_providers(&config.providers)
+        .into_iter()
+        .find(|p| p.id == config.default_provider_id)
+    {
+        Some(provider) => {
+            if let Err(e) = provider.require_credentials() {
+                warnings.push(format!("settings.json (defaultProviderId): {}", e));
+            }
+        }
+        None => errors.push(format!(
+            "settings.json (defaultProviderId): 找不到提供商配置: {}",
+            config.default_provider_id
+        )),
+    }
+
+    let write = |out: &mut dyn Write, line: String| writeln!(out, "{}", line);
+    for e in &errors {
+        write(out, format!("❌ {}", e)).map_err(|e| e.to_string())?;
+    }
+    for w in &warnings {
+        write(out, format!("⚠️ {}", w)).map_err(|e| e.to_string())?;
+    }
+    if errors.is_empty() {
+        write(out, "✅ 配置有效".into()).map_err(|e| e.to_string())?;
+        Ok(0)
+    } else {
+        Ok(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let cli = Cli::try_parse_from([
+            "goge-cli",
+            "chat",
+            "-m",
+            "deepseek/deepseek-chat",
+            "--session",
+            "3",
+            "你好",
+            "世界",
+        ])
+        .unwrap();
+        let Command::Chat(args) = cli.command else {
+            panic!("应解析为 chat");
+        };
+        assert_eq!(args.message, vec!["你好", "世界"]);
+        assert_eq!(args.session, Some(3));
+        assert_eq!(
+            split_model_ref(
+                args.model.as_deref().unwrap(),
+                &serde_json::json!([{ "id": "deepseek" }])
+            ),
+            (Some("deepseek".into()), Some("deepseek-chat".into()))
+        );
+
+        // 模型 ID 自带 "/" 时不能把前半段当成提供商
+        let cli =
+            Cli::try_parse_from(["goge-cli", "chat", "-m", "deepseek-ai/DeepSeek-V3.2", "hi"])
+                .unwrap();
+        let Command::Chat(args) = cli.command else {
+            panic!("应解析为 chat");
+        };
+        let providers = AppConfig::default().providers;
+        assert_eq!(
+            split_model_ref(args.model.as_deref().unwrap(), &providers),
+            (None, Some("deepseek-ai/DeepSeek-V3.2".into()))
+        );
+        assert_eq!(
+            split_model_ref("siliconflow/deepseek-ai/DeepSeek-V3.2", &providers),
+            (
+                Some("siliconflow".into()),
+                Some("deepseek-ai/DeepSeek-V3.2".into())
+            )
+        );
+
+        let cli = Cli::try_parse_from([
+            "goge-cli",
+            "--data-dir",
+            "/tmp/goge",
+            "sessions",
+            "export",
+            "-o",
+            "out.md",
+            "--folder",
+            "2",
+        ])
+        .unwrap();
+        assert_eq!(cli.data_dir, Some(PathBuf::from("/tmp/goge")));
+        assert!(matches!(
+            cli.command,
+            Command::Sessions(SessionsCommand::Export(ExportArgs {
+                folder: Some(2),
+                session: None,
+                ..
+            }))
+        ));
+
+        let cli = Cli::try_parse_from([
+            "goge-cli",
+            "simulate",
+            "script.json",
+            "--seed",
+            "7",
+            "--runs",
+            "50",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Simulate(SimulateArgs {
+                seed: 7,
+                runs: 50,
+                json: false,
+                ..
+            })
+        ));
+
+        // 会话与文件夹范围互斥
+        assert!(Cli::try_parse_from([
+            "goge-cli",
+            "sessions",
+            "export",
+            "-o",
+            "a.json",
+            "--session",
+            "1",
+            "--folder",
+            "2"
+        ])
+        .is_err());
+    }
+}
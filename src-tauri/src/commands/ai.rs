@@ -1,19 +1,26 @@
 use crate::commands::config_cmd;
+use crate::context_window;
+use crate::generation_config::{resolve_generation, SessionOverrides};
+use crate::local_llm::PullProgress;
 use crate::memory::processor::{get_relevant_context, MemoryState};
-use crate::models::{ChatRequest, Message};
+use crate::model_catalog::{self, ModelCatalog};
+use crate::models::{ChatRequest, GenerationParams, Message};
+use crate::net::{HttpClients, NetworkSettings};
+use crate::prompt_template::{self, TemplateContext};
+use crate::provider_config::{resolve_provider, ProviderConfig, ProviderKind};
+use crate::stream_decoder::{decode, JsonArrayDecoder, SseDecoder};
 use futures_util::StreamExt;
 use serde::Serialize;
 use serde_json::{json, Value};
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tauri::{ipc::Channel, AppHandle, Emitter, State};
+use tauri::{ipc::Channel, AppHandle, Emitter, Manager, State};
 use tokio::sync::RwLock;
 
 #[tauri::command]
 pub async fn ask_ai(
     app: AppHandle,
     state: State<'_, crate::GoleState>,
-    memory_state: State<'_, Arc<RwLock<MemoryState>>>,
     msg: Vec<Message>,
     on_event: Channel<String>,
     temperature: Option<f32>,
@@ -22,41 +29,88 @@ pub async fn ask_ai(
     // 🟢 新增：允许前端显式传入当前绘画的 provider 和 model
     explicit_provider_id: Option<String>,
     explicit_model_id: Option<String>,
-    client: State<'_, reqwest::Client>,
+    // 会话 ID：用于上下文超限时缓存滚动摘要 (可选)
+    session_id: Option<String>,
 ) -> Result<(), String> {
+    let request = ChatInvocation {
+        msg,
+        temperature,
+        max_tokens,
+        stream,
+        explicit_provider_id,
+        explicit_model_id,
+        session_id,
+    };
+    run_chat(&app, request, &state.stop_flag, on_event).await
+}
+
+/// 一次对话请求的参数 (Tauri 指令与本地 HTTP 接口共用)
+pub(crate) struct ChatInvocation {
+    pub msg: Vec<Message>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub stream: Option<bool>,
+    pub explicit_provider_id: Option<String>,
+    pub explicit_model_id: Option<String>,
+    pub session_id: Option<String>,
+}
+
+/// 完整对话流程：配置解析、搜索与记忆注入、上下文裁剪，再按提供商流式请求
+/// 输出通过 on_event 以 c: (正文) / r: (思考过程) 前缀下发；stop_flag 置位时尽快停止
+pub(crate) async fn run_chat(
+    app: &AppHandle,
+    request: ChatInvocation,
+    stop_flag: &AtomicBool,
+    on_event: Channel<String>,
+) -> Result<(), String> {
+    let ChatInvocation {
+        msg,
+        temperature,
+        max_tokens,
+        stream,
+        explicit_provider_id,
+        explicit_model_id,
+        session_id,
+    } = request;
+    let memory_state = app.state::<Arc<RwLock<MemoryState>>>();
+    let clients = app.state::<HttpClients>();
+
     // --- 🚀 核心优化：并行执行预处理任务 ---
     let start_total = std::time::Instant::now(); // ⏱️ 开始计时
     let config = config_cmd::load_config(app.clone()).await?;
 
-    // 2. 确定当前使用的模型和提供商
-    // 优先使用显式传入的参数，如果没有（旧版前端），则回退到全局配置
-    let selected_model = explicit_model_id.unwrap_or(config.selected_model_id.clone());
-    let selected_provider_id = explicit_provider_id.unwrap_or(config.default_provider_id.clone());
-
-    // 从 providers 数组中找到当前选中的提供商配置
-    let providers = config
-        .providers
-        .as_array()
-        .ok_or("配置错误：无法读取提供商列表")?;
-    let provider_config = providers
-        .iter()
-        .find(|p| p["id"].as_str() == Some(&selected_provider_id))
-        .ok_or(format!("找不到提供商配置: {}", selected_provider_id))?;
-
-    let api_key = provider_config["apiKey"].as_str().unwrap_or("").to_string();
-    let base_url = provider_config["baseUrl"]
-        .as_str()
-        .unwrap_or("https://api.deepseek.com")
-        .to_string();
+    // 2. 解析生效配置：显式参数 > 会话覆盖 > 预设 > 提供商默认值
+    let numeric_session_id = session_id.as_deref().and_then(|id| id.parse::<i64>().ok());
+    let session = match numeric_session_id {
+        Some(id) => {
+            let db = app.state::<crate::db::DbState>();
+            let conn = db.0.lock().unwrap();
+            crate::db::get_session(&conn, id).map_err(|e| e.to_string())?
+        }
+        None => None,
+    };
+    let session_overrides = session.as_ref().map(SessionOverrides::from_session);
+    let resolved = resolve_generation(
+        &config,
+        session_overrides.as_ref(),
+        explicit_provider_id,
+        explicit_model_id,
+        GenerationParams {
+            temperature,
+            max_tokens,
+            ..Default::default()
+        },
+        GenerationParams::default(),
+    );
+    let selected_model = resolved.model.clone();
+    let selected_provider_id = resolved.provider_id.clone();
 
-    if api_key.trim().is_empty() {
-        return Err(format!(
-            "{} 的 API Key 未配置，请前往设置页面填写",
-            provider_config["name"].as_str().unwrap_or("该提供商")
-        ));
-    }
+    let provider = resolve_provider(&config, &selected_provider_id)?;
+    let client = clients.for_provider(&provider)?;
 
-    let messages = msg;
+    // 对比模式的兄弟回答 (带 parentId) 不进入上下文，只保留主线回答
+    let mut messages: Vec<Message> = msg.into_iter().filter(|m| m.parent_id.is_none()).collect();
+    resolved.apply_system_prompt(&mut messages);
 
     // 检查是否需要强制使用推理 (如果用户手动输入了 [REASON] 标记)
     let has_reason_tag = messages
@@ -74,6 +128,22 @@ pub async fn ask_ai(
         selected_model
     };
 
+    // 发送前按模型目录校验能力 (例如给纯文本模型发图片)
+    {
+        let db = app.state::<crate::db::DbState>();
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let catalog_entry = model_catalog::cached_model(&conn, &provider.id, &model)
+            .ok()
+            .flatten();
+        model_catalog::check_features(
+            &model,
+            catalog_entry.as_ref(),
+            provider.model(&model),
+            &messages,
+            &resolved.params,
+        )?;
+    }
+
     // --- 🚀 核心优化：并行执行[搜索]和[记忆]任务 ---
     let messages_for_search = messages.clone();
     let search_instance_url = config.search_instance_url.clone();
@@ -119,67 +189,47 @@ pub async fn ask_ai(
     // 处理搜索结果
     let mut clean_msgs = search_res?;
 
+    let memory_context = memory_res.ok().flatten();
+
+    // 渲染系统提示词中的模板变量；模板自行引用了 {{memory}} 时不再额外前置记忆
+    let memory_in_template = render_system_prompt(
+        app,
+        &config,
+        session.as_ref(),
+        &mut clean_msgs,
+        memory_context.as_deref(),
+    );
+
     // 处理记忆结果并注入
-    if let Ok(Some(context)) = memory_res {
-        if let Some(sys_msg) = clean_msgs.iter_mut().find(|m| m.role == "system") {
-            sys_msg.content = format!("{}\n\n{}", context, sys_msg.content);
-        } else {
-            clean_msgs.insert(
-                0,
-                Message {
-                    id: None,
-                    model: None,
-                    role: "system".to_string(),
-                    content: context,
-                    reasoning_content: None,
-                    file_metadata: None,
-                    search_metadata: None,
-                    provider: None,
-                    mode: None,
-                    role_id: None,
-                },
-            );
-        }
+    if let Some(context) = memory_context.filter(|_| !memory_in_template) {
+        inject_memory(&mut clean_msgs, context);
     }
 
-    let temperature =
-        temperature.or_else(|| provider_config["temperature"].as_f64().map(|f| f as f32));
-    let max_tokens = max_tokens.or_else(|| provider_config["maxTokens"].as_u64().map(|u| u as u32));
+    let params = resolved.params;
 
     // --- 🧹 极致优化：在发送给 AI 之前抹除所有逻辑标记 ---
-    for m in clean_msgs.iter_mut() {
-        if m.role == "user" {
-            // 剔除 [REASON]
-            m.content = m.content.replace("[REASON]", "");
-            // 剔除 [SEARCH] (支持带参数的格式 [SEARCH:provider])
-            if m.content.contains("[SEARCH") {
-                // 使用简单的正则或字符串处理移除 [SEARCH...]
-                let mut start = 0;
-                while let Some(s_idx) = m.content[start..].find("[SEARCH") {
-                    let absolute_start = start + s_idx;
-                    if let Some(e_idx) = m.content[absolute_start..].find(']') {
-                        m.content
-                            .replace_range(absolute_start..=absolute_start + e_idx, "");
-                        // 替换后字符串变短，从当前位置继续找
-                        start = absolute_start;
-                    } else {
-                        break;
-                    }
-                }
-            }
-            // 最终修剪一下首尾空白
-            m.content = m.content.trim().to_string();
-        }
-    }
+    strip_control_tags(&mut clean_msgs);
+
+    // --- ✂️ 上下文窗口管理：超出模型上限时压缩早期对话 ---
+    let context_limit = context_window::context_limit_for(&provider, &model);
+    let session_key = numeric_session_id.map(|id| (context_window::SessionKind::Chat, id));
+    let clean_msgs = context_window::fit_to_context(
+        app,
+        session_key,
+        clean_msgs,
+        context_limit,
+        params.max_tokens,
+    )
+    .await;
 
     // --- ⬇️ Google Gemini Native 支持 ⬇️ ---
-    if selected_provider_id == "gemini" {
+    if provider.is_gemini() {
         return handle_gemini_native(
-            api_key,
-            base_url,
+            &provider,
             model,
             clean_msgs,
-            state,
+            &params,
+            stop_flag,
             on_event,
             stream.unwrap_or(true),
             &client,
@@ -190,38 +240,33 @@ pub async fn ask_ai(
     }
     // --- ⬆️ Google Gemini Native 支持 ⬆️ ---
 
+    // --- 🦙 Ollama 原生 /api/chat (本地模型，无需 API Key) ---
+    if provider.kind == ProviderKind::Ollama {
+        return handle_ollama_native(
+            &provider,
+            &model,
+            &clean_msgs,
+            &params,
+            stop_flag,
+            on_event,
+            &client,
+        )
+        .await;
+    }
+
     let payload = ChatRequest {
         model: model.to_string(),
         messages: clean_msgs,
         stream: stream.unwrap_or(true),
-        temperature,
-        max_tokens,
+        params,
     };
 
-    let disable_url_suffix = provider_config["disableUrlSuffix"]
-        .as_bool()
-        .unwrap_or(false);
-
-    let url = if disable_url_suffix {
-        base_url.clone()
-    } else {
-        let base = base_url.trim_end_matches('/');
-        if base.ends_with("/chat/completions") {
-            base.to_string()
-        } else if base.ends_with("/v1") {
-            format!("{}/chat/completions", base)
-        } else {
-            // 🛡️ 修复：如果不包含 v1，自动补全 /v1/chat/completions，与前端测试保持一致
-            // 这解决了类似 https://api.ohmygpt.com 这种 BaseURL 导致的测试通过但对话失败的问题
-            format!("{}/v1/chat/completions", base)
-        }
-    };
+    let url = provider.chat_completions_url();
 
     // println!("🔗 最终对话请求地址: {}", url);
 
-    let response = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", api_key))
+    let response = provider
+        .authorize(client.post(&url))
         .json(&payload)
         .send()
         .await
@@ -234,8 +279,7 @@ pub async fn ask_ai(
 
     if !stream.unwrap_or(true) {
         // --- 🛑 非流式响应处理 ---
-        let content =
-            crate::ai_utils::call_ai_backend(&client, &api_key, &base_url, &payload).await?;
+        let content = crate::ai_utils::call_ai_backend(&client, &provider, &payload).await?;
 
         on_event
             .send(format!("c:{}", content))
@@ -245,8 +289,7 @@ pub async fn ask_ai(
     }
 
     // --- 🌊 流式响应处理 (⚡️ 极致优化：20ms 微合批减少 IPC 频率) ---
-    let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
+    let mut events = std::pin::pin!(decode(response.bytes_stream(), SseDecoder::default()));
     let mut ttft_logged = false;
 
     let mut pending_content = String::new();
@@ -254,86 +297,183 @@ pub async fn ask_ai(
     let mut last_emit = std::time::Instant::now();
     let mut emit_count = 0; // 🚀 前几个字不合批，立即发送以获得最快体感速度
 
-    while let Some(chunk) = stream.next().await {
-        if state.stop_flag.load(Ordering::Relaxed) {
+    while let Some(event) = events.next().await {
+        if stop_flag.load(Ordering::Relaxed) {
             break;
         }
 
-        let chunk = chunk.map_err(|e| e.to_string())?;
-        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        let delta = crate::ai_utils::parse_openai_event(&event?)?;
+        if delta.done {
+            break;
+        }
 
-        while let Some(newline_idx) = buffer.find('\n') {
-            let line = buffer.drain(..=newline_idx).collect::<String>();
-            let line = line.trim();
+        if let Some(content) = delta.content {
+            if !ttft_logged {
+                let network_ttft = start_total.elapsed().as_millis() as i64
+                    - pre_processing_time.as_millis() as i64;
+                println!(
+                    "⏱️ [性能] 首字总响应: {}ms | 网络等待: {}ms",
+                    start_total.elapsed().as_millis(),
+                    network_ttft
+                );
+                ttft_logged = true;
+            }
+            pending_content.push_str(&content);
+        }
+        if let Some(reasoning) = delta.reasoning {
+            pending_reasoning.push_str(&reasoning);
+        }
 
-            if line.is_empty() || line == "data: [DONE]" {
-                if line == "data: [DONE]" {
-                    // 彻底结束前清空缓存
-                    if !pending_content.is_empty() {
-                        let _ = on_event.send(format!("c:{}", pending_content));
-                    }
-                    if !pending_reasoning.is_empty() {
-                        let _ = on_event.send(format!("r:{}", pending_reasoning));
-                    }
-                    return Ok(());
-                }
-                continue;
+        // ⏱️ 判定：前 5 次下发立即执行 (保证极速 TTFT)，后续切换到 20ms 周期或 100 字符缓冲区
+        if emit_count < 5 || last_emit.elapsed().as_millis() >= 20 || pending_content.len() > 100 {
+            if !pending_content.is_empty() {
+                let _ = on_event.send(format!("c:{}", pending_content));
+                pending_content.clear();
+                emit_count += 1;
+            }
+            if !pending_reasoning.is_empty() {
+                let _ = on_event.send(format!("r:{}", pending_reasoning));
+                pending_reasoning.clear();
             }
+            last_emit = std::time::Instant::now();
+        }
+    }
 
-            if let Some(data) = line.strip_prefix("data: ") {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                    if let Some(choices) = json["choices"].as_array() {
-                        if choices.is_empty() {
-                            continue;
-                        }
-                        let choice = &choices[0];
-                        let delta = &choice["delta"];
-
-                        if let Some(content) = delta["content"].as_str() {
-                            if !ttft_logged {
-                                let network_ttft = start_total.elapsed().as_millis() as i64
-                                    - pre_processing_time.as_millis() as i64;
-                                println!(
-                                    "⏱️ [性能] 首字总响应: {}ms | 网络等待: {}ms",
-                                    start_total.elapsed().as_millis(),
-                                    network_ttft
-                                );
-                                ttft_logged = true;
-                            }
-                            pending_content.push_str(content);
-                        }
-
-                        if let Some(reasoning) = delta["reasoning_content"]
-                            .as_str()
-                            .or_else(|| delta["reasoning"].as_str())
-                            .or_else(|| delta["thought"].as_str())
-                        {
-                            pending_reasoning.push_str(reasoning);
-                        }
-
-                        // ⏱️ 判定：前 5 次下发立即执行 (保证极速 TTFT)，后续切换到 20ms 周期或 100 字符缓冲区
-                        if emit_count < 5
-                            || last_emit.elapsed().as_millis() >= 20
-                            || pending_content.len() > 100
-                        {
-                            if !pending_content.is_empty() {
-                                let _ = on_event.send(format!("c:{}", pending_content));
-                                pending_content.clear();
-                                emit_count += 1;
-                            }
-                            if !pending_reasoning.is_empty() {
-                                let _ = on_event.send(format!("r:{}", pending_reasoning));
-                                pending_reasoning.clear();
-                            }
-                            last_emit = std::time::Instant::now();
-                        }
-                    } else if let Some(err) = json["error"].as_object() {
-                        return Err(format!("Stream Error: {:?}", err));
+    // 扫尾
+    if !pending_content.is_empty() {
+        let _ = on_event.send(format!("c:{}", pending_content));
+    }
+    if !pending_reasoning.is_empty() {
+        let _ = on_event.send(format!("r:{}", pending_reasoning));
+    }
+
+    // println!("✅ AI 生成任务已彻底释放");
+    Ok(())
+}
+
+/// 渲染系统提示词中的模板变量，返回模板是否自行引用了 {{memory}}
+pub(crate) fn render_system_prompt(
+    app: &AppHandle,
+    config: &config_cmd::AppConfig,
+    session: Option<&crate::db::ChatSession>,
+    messages: &mut [Message],
+    memory: Option<&str>,
+) -> bool {
+    let social = app.state::<crate::social_db::SocialDbState>();
+    let conn = social.0.lock().ok();
+    render_system_prompt_with(conn.as_deref(), config, session, messages, memory)
+}
+
+/// 同 render_system_prompt，用户资料直接从社交库连接读取 (命令行工具使用)
+pub(crate) fn render_system_prompt_with(
+    social_conn: Option<&rusqlite::Connection>,
+    config: &config_cmd::AppConfig,
+    session: Option<&crate::db::ChatSession>,
+    messages: &mut [Message],
+    memory: Option<&str>,
+) -> bool {
+    let Some(sys_msg) = messages.iter_mut().find(|m| m.role == "system") else {
+        return false;
+    };
+    let mut template_ctx = TemplateContext::new();
+    if let Some(conn) = social_conn {
+        prompt_template::load_user(&mut template_ctx, conn);
+    }
+    if let Some(s) = session {
+        template_ctx.with_session(s.id, &s.title);
+    }
+    template_ctx.set("memory", json!(memory.unwrap_or_default()));
+    let resolver = prompt_template::library_resolver(&config.prompt_library);
+    let rendered = prompt_template::render_or_raw(&sys_msg.content, &template_ctx, &resolver);
+    sys_msg.content = rendered.text;
+    rendered.used.contains("memory")
+}
+
+/// 把检索到的记忆前置到系统提示词 (没有系统消息时新建一条)
+pub(crate) fn inject_memory(messages: &mut Vec<Message>, context: String) {
+    if let Some(sys_msg) = messages.iter_mut().find(|m| m.role == "system") {
+        sys_msg.content = format!("{}\n\n{}", context, sys_msg.content);
+    } else {
+        messages.insert(
+            0,
+            Message {
+                id: None,
+                model: None,
+                role: "system".to_string(),
+                content: context,
+                reasoning_content: None,
+                file_metadata: None,
+                search_metadata: None,
+                provider: None,
+                mode: None,
+                role_id: None,
+                parent_id: None,
+            },
+        );
+    }
+}
+
+/// 抹除用户消息中的 [REASON] / [SEARCH...] 等逻辑标记
+pub(crate) fn strip_control_tags(messages: &mut [Message]) {
+    for m in messages.iter_mut() {
+        if m.role == "user" {
+            // 剔除 [REASON]
+            m.content = m.content.replace("[REASON]", "");
+            // 剔除 [SEARCH] (支持带参数的格式 [SEARCH:provider])
+            if m.content.contains("[SEARCH") {
+                // 使用简单的正则或字符串处理移除 [SEARCH...]
+                let mut start = 0;
+                while let Some(s_idx) = m.content[start..].find("[SEARCH") {
+                    let absolute_start = start + s_idx;
+                    if let Some(e_idx) = m.content[absolute_start..].find(']') {
+                        m.content
+                            .replace_range(absolute_start..=absolute_start + e_idx, "");
+                        // 替换后字符串变短，从当前位置继续找
+                        start = absolute_start;
+                    } else {
+                        break;
                     }
                 }
             }
+            // 最终修剪一下首尾空白
+            m.content = m.content.trim().to_string();
         }
     }
+}
+
+/// Ollama 流式对话：沿用 20ms 合批策略，思考过程以 r: 前缀下发
+async fn handle_ollama_native(
+    provider: &ProviderConfig,
+    model: &str,
+    messages: &[Message],
+    params: &GenerationParams,
+    stop_flag: &AtomicBool,
+    on_event: Channel<String>,
+    client: &reqwest::Client,
+) -> Result<(), String> {
+    let mut pending_content = String::new();
+    let mut pending_reasoning = String::new();
+    let mut last_emit = std::time::Instant::now();
+    let mut emit_count = 0;
+
+    crate::local_llm::ollama_chat_streaming(client, provider, model, messages, params, |delta| {
+        pending_content.push_str(&delta.content);
+        pending_reasoning.push_str(&delta.thinking);
+        if emit_count < 5 || last_emit.elapsed().as_millis() >= 20 || pending_content.len() > 100 {
+            if !pending_content.is_empty() {
+                let _ = on_event.send(format!("c:{}", pending_content));
+                pending_content.clear();
+                emit_count += 1;
+            }
+            if !pending_reasoning.is_empty() {
+                let _ = on_event.send(format!("r:{}", pending_reasoning));
+                pending_reasoning.clear();
+            }
+            last_emit = std::time::Instant::now();
+        }
+        !stop_flag.load(Ordering::Relaxed)
+    })
+    .await?;
 
     // 扫尾
     if !pending_content.is_empty() {
@@ -342,8 +482,6 @@ pub async fn ask_ai(
     if !pending_reasoning.is_empty() {
         let _ = on_event.send(format!("r:{}", pending_reasoning));
     }
-
-    // println!("✅ AI 生成任务已彻底释放");
     Ok(())
 }
 
@@ -367,14 +505,17 @@ struct GeminiRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "systemInstruction")]
     system_instruction: Option<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "generationConfig")]
+    generation_config: Option<Value>,
 }
 
 async fn handle_gemini_native(
-    api_key: String,
-    base_url: String,
+    provider: &ProviderConfig,
     model: String,
     messages: Vec<Message>,
-    state: State<'_, crate::GoleState>,
+    params: &GenerationParams,
+    stop_flag: &AtomicBool,
     on_event: Channel<String>,
     stream: bool,
     client: &reqwest::Client,
@@ -385,10 +526,10 @@ async fn handle_gemini_native(
         // --- 🛑 非流式处理 ---
         let content = crate::ai_utils::call_gemini_backend(
             client,
-            &api_key,
-            &base_url,
+            provider,
             &model,
             messages.clone(),
+            params,
         )
         .await?;
 
@@ -426,26 +567,14 @@ async fn handle_gemini_native(
     let payload = GeminiRequest {
         contents,
         system_instruction,
+        generation_config: crate::generation_config::gemini_generation_config(params),
     };
 
     // 2. 构造 URL (更加鲁棒的判断)
-    let mode = "streamGenerateContent";
-    let base = base_url.trim_end_matches('/');
-
-    let url = if base.contains("/models/") {
-        // 如果用户提供了完整路径，只补全 key
-        format!("{}?key={}", base, api_key)
-    } else {
-        // 智能补全版本和路径
-        let version = if base.contains("/v1") { "" } else { "/v1beta" };
-        format!(
-            "{}{}/models/{}:{}?key={}",
-            base, version, model, mode, api_key
-        )
-    };
+    let url = provider.gemini_url(&model, "streamGenerateContent");
 
-    let response = client
-        .post(&url)
+    let response = provider
+        .authorize(client.post(&url))
         .header("Content-Type", "application/json")
         .json(&payload)
         .send()
@@ -459,102 +588,113 @@ async fn handle_gemini_native(
     }
 
     // --- 🌊 流式处理 ---
-    let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
+    // Gemini 的 stream 数据是一个包含多个 JSON 对象的数组，格式大致为 [ {...}, {...} ]
+    let mut values = std::pin::pin!(decode(response.bytes_stream(), JsonArrayDecoder::default()));
     let mut ttft_logged = false;
     let mut pending_content = String::new();
     let mut last_emit = std::time::Instant::now();
     let mut emit_count = 0;
 
-    while let Some(chunk) = stream.next().await {
-        if state.stop_flag.load(Ordering::Relaxed) {
+    while let Some(json) = values.next().await {
+        if stop_flag.load(Ordering::Relaxed) {
             break;
         }
 
-        let chunk = chunk.map_err(|e| e.to_string())?;
-        buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-        // Gemini 的 stream 数据是一个包含多个 JSON 对象的数组，格式大致为 [ {...}, {...} ]
-        // 这里尝试解析完整的 JSON 对象块
-        while let Some(start_idx) = buffer.find('{') {
-            if state.stop_flag.load(Ordering::Relaxed) {
-                break;
-            }
-            let mut depth = 0;
-            let mut end_idx = None;
-            let bytes = buffer.as_bytes();
-
-            for i in start_idx..bytes.len() {
-                if bytes[i] == b'{' {
-                    depth += 1;
-                } else if bytes[i] == b'}' {
-                    if depth > 0 {
-                        depth -= 1;
-                        if depth == 0 {
-                            end_idx = Some(i);
-                            break;
-                        }
-                    }
-                }
+        for text in crate::ai_utils::gemini_chunk_text(&json?)? {
+            if !ttft_logged {
+                // 🟢 监测：从用户输入到流式输出首字的性能耗时 (Gemini)
+                let network_ttft = start_total.elapsed().as_millis() as i64
+                    - pre_processing_time.as_millis() as i64;
+                println!(
+                    "⏱️ [性能] 首字总响应 (Gemini): {}ms | 网络等待: {}ms",
+                    start_total.elapsed().as_millis(),
+                    network_ttft
+                );
+                ttft_logged = true;
             }
+            pending_content.push_str(&text);
+        }
 
-            if let Some(end) = end_idx {
-                let json_str = &buffer[start_idx..=end];
-                if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                    // 解析 candidates[0].content.parts[0].text
-                    if let Some(parts) = json["candidates"][0]["content"]["parts"].as_array() {
-                        for part in parts {
-                            if let Some(text) = part["text"].as_str() {
-                                if !ttft_logged {
-                                    // 🟢 监测：从用户输入到流式输出首字的性能耗时 (Gemini)
-                                    let network_ttft = start_total.elapsed().as_millis() as i64
-                                        - pre_processing_time.as_millis() as i64;
-                                    println!(
-                                        "⏱️ [性能] 首字总响应 (Gemini): {}ms | 网络等待: {}ms",
-                                        start_total.elapsed().as_millis(),
-                                        network_ttft
-                                    );
-                                    ttft_logged = true;
-                                }
-                                pending_content.push_str(text);
-
-                                // ⏱️ Gemini 同样采用：前 5 次极速响应，后续合批策略
-                                if emit_count < 5
-                                    || last_emit.elapsed().as_millis() >= 20
-                                    || pending_content.len() > 100
-                                {
-                                    if !pending_content.is_empty() {
-                                        on_event
-                                            .send(format!("c:{}", pending_content))
-                                            .map_err(|e| e.to_string())?;
-                                        pending_content.clear();
-                                        emit_count += 1;
-                                    }
-                                    last_emit = std::time::Instant::now();
-                                }
-                            }
-                        }
-                    }
-                }
-                buffer.drain(..=end);
-            } else {
-                break; // 等待更多数据
+        // ⏱️ Gemini 同样采用：前 5 次极速响应，后续合批策略
+        if emit_count < 5 || last_emit.elapsed().as_millis() >= 20 || pending_content.len() > 100 {
+            if !pending_content.is_empty() {
+                on_event
+                    .send(format!("c:{}", pending_content))
+                    .map_err(|e| e.to_string())?;
+                pending_content.clear();
+                emit_count += 1;
             }
+            last_emit = std::time::Instant::now();
         }
     }
 
+    // 扫尾：合批窗口内尚未下发的内容
+    if !pending_content.is_empty() {
+        let _ = on_event.send(format!("c:{}", pending_content));
+    }
+
     Ok(())
 }
 
-#[tauri::command]
-pub async fn discover_models_raw(
-    url: String,
+/// 前端只持有 API Key 占位符，转发请求前把 url、密钥和请求头中的占位符换成真实密钥
+/// 只有发往该提供商已保存 Base URL 同源地址的请求才会填入密钥，防止被转发到任意主机
+async fn resolve_redacted(
+    app: &AppHandle,
+    provider_id: Option<&str>,
+    url: &mut String,
+    api_key: &mut Option<String>,
+    headers_map: &mut Option<std::collections::HashMap<String, String>>,
+) -> Result<(), String> {
+    let placeholder = crate::secret_store::REDACTED_KEY;
+    let redacted = url.contains(placeholder)
+        || api_key.as_deref().is_some_and(|k| k.contains(placeholder))
+        || headers_map
+            .as_ref()
+            .is_some_and(|h| h.values().any(|v| v.contains(placeholder)));
+    if !redacted {
+        return Ok(());
+    }
+    let provider_id = provider_id.ok_or("缺少提供商 ID，无法使用已保存的 API Key")?;
+    let config = config_cmd::load_config(app.clone()).await?;
+    let raw = config
+        .providers
+        .as_array()
+        .and_then(|list| list.iter().find(|p| p["id"].as_str() == Some(provider_id)))
+        .ok_or(format!("找不到提供商配置: {}", provider_id))?;
+    let provider = ProviderConfig::from_value(raw)
+        .map_err(|e| format!("提供商 {} 配置错误: {}", provider_id, e))?;
+    if !same_origin(url, &provider.base_url) {
+        return Err(format!(
+            "请求地址与 {} 已保存的 API 地址不一致，请先保存配置后再试",
+            provider.name
+        ));
+    }
+
+    let real = provider.api_key;
+    *url = url.replace(placeholder, &real);
+    if let Some(key) = api_key.as_mut() {
+        *key = key.replace(placeholder, &real);
+    }
+    if let Some(headers) = headers_map.as_mut() {
+        for value in headers.values_mut() {
+            *value = value.replace(placeholder, &real);
+        }
+    }
+    Ok(())
+}
+
+fn same_origin(url: &str, base_url: &str) -> bool {
+    match (reqwest::Url::parse(url), reqwest::Url::parse(base_url)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+fn with_headers(
+    mut request: reqwest::RequestBuilder,
     api_key: Option<String>,
     headers_map: Option<std::collections::HashMap<String, String>>,
-    client: State<'_, reqwest::Client>,
-) -> Result<Value, String> {
-    let mut request = client.get(&url);
-
+) -> reqwest::RequestBuilder {
     if let Some(key) = api_key {
         if !key.is_empty() {
             request = request.header("Authorization", format!("Bearer {}", key));
@@ -566,7 +706,10 @@ pub async fn discover_models_raw(
             request = request.header(k, v);
         }
     }
+    request
+}
 
+async fn read_json(request: reqwest::RequestBuilder) -> Result<Value, String> {
     let response = request
         .send()
         .await
@@ -585,6 +728,146 @@ pub async fn discover_models_raw(
     Ok(data)
 }
 
+#[tauri::command]
+pub async fn discover_models_raw(
+    app: AppHandle,
+    mut url: String,
+    mut api_key: Option<String>,
+    mut headers_map: Option<std::collections::HashMap<String, String>>,
+    provider_id: Option<String>,
+    clients: State<'_, HttpClients>,
+) -> Result<Value, String> {
+    resolve_redacted(
+        &app,
+        provider_id.as_deref(),
+        &mut url,
+        &mut api_key,
+        &mut headers_map,
+    )
+    .await?;
+    let request = with_headers(clients.global()?.get(&url), api_key, headers_map);
+    read_json(request).await
+}
+
+/// 设置页的连通性测试：以 POST 发送最小请求，由后端代为填入真实密钥
+#[tauri::command]
+pub async fn probe_provider_raw(
+    app: AppHandle,
+    mut url: String,
+    mut headers_map: Option<std::collections::HashMap<String, String>>,
+    body: Value,
+    provider_id: Option<String>,
+    clients: State<'_, HttpClients>,
+) -> Result<Value, String> {
+    let mut api_key = None;
+    resolve_redacted(
+        &app,
+        provider_id.as_deref(),
+        &mut url,
+        &mut api_key,
+        &mut headers_map,
+    )
+    .await?;
+    let request = with_headers(clients.global()?.post(&url).json(&body), None, headers_map);
+    read_json(request).await
+}
+
+/// 从本地 Ollama 拉取模型，进度通过 on_progress 逐条下发
+#[tauri::command]
+pub async fn pull_local_model(
+    app: AppHandle,
+    provider_id: String,
+    model: String,
+    on_progress: Channel<PullProgress>,
+    clients: State<'_, HttpClients>,
+) -> Result<(), String> {
+    let config = config_cmd::load_config(app.clone()).await?;
+    let provider = resolve_provider(&config, &provider_id)?;
+    if provider.kind != ProviderKind::Ollama {
+        return Err(format!("{} 不支持拉取模型 (仅 Ollama 可用)", provider.name));
+    }
+
+    println!("⬇️ [本地模型] 开始拉取 {} ({})", model, provider_id);
+    // 模型文件可能有数 GB，未显式配置超时时不限制
+    let client = clients.for_provider_with_defaults(
+        &provider,
+        &NetworkSettings {
+            timeout_secs: Some(0),
+            ..Default::default()
+        },
+    )?;
+    crate::local_llm::pull_model(&client, &provider, &model, |progress| {
+        let _ = on_progress.send(progress);
+    })
+    .await?;
+    println!("✅ [本地模型] {} 拉取完成", model);
+
+    let db = app.state::<crate::db::DbState>();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    model_catalog::clear_cached(&conn, &provider_id).map_err(|e| e.to_string())
+}
+
+/// 获取规范化后的模型目录 (带能力信息)，缓存未过期时不发起网络请求
+/// 刷新失败时若有旧缓存则返回旧缓存并标记 stale
+#[tauri::command]
+pub async fn get_model_catalog(
+    app: AppHandle,
+    provider_id: String,
+    force_refresh: Option<bool>,
+    clients: State<'_, HttpClients>,
+) -> Result<ModelCatalog, String> {
+    let config = config_cmd::load_config(app.clone()).await?;
+    let provider = resolve_provider(&config, &provider_id)?;
+    let now = chrono::Utc::now().timestamp();
+
+    let cached = {
+        let db = app.state::<crate::db::DbState>();
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        model_catalog::load_cached(&conn, &provider_id).map_err(|e| e.to_string())?
+    };
+    if let Some((models, fetched_at)) = &cached {
+        if !force_refresh.unwrap_or(false) && model_catalog::is_fresh(*fetched_at, now) {
+            return Ok(ModelCatalog {
+                provider_id,
+                models: models.clone(),
+                fetched_at: *fetched_at,
+                stale: false,
+                error: None,
+            });
+        }
+    }
+
+    match model_catalog::fetch_catalog(&clients.for_provider(&provider)?, &provider).await {
+        Ok(models) => {
+            let db = app.state::<crate::db::DbState>();
+            let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+            model_catalog::save_cached(&mut conn, &provider_id, &models, now)
+                .map_err(|e| e.to_string())?;
+            println!("📚 [模型目录] {} 共 {} 个模型", provider_id, models.len());
+            Ok(ModelCatalog {
+                provider_id,
+                models,
+                fetched_at: now,
+                stale: false,
+                error: None,
+            })
+        }
+        Err(e) => match cached {
+            Some((models, fetched_at)) => {
+                println!("⚠️ [模型目录] {} 刷新失败，使用缓存: {}", provider_id, e);
+                Ok(ModelCatalog {
+                    provider_id,
+                    models,
+                    fetched_at,
+                    stale: true,
+                    error: Some(e),
+                })
+            }
+            None => Err(e),
+        },
+    }
+}
+
 // --- 🚀 助手函数：并行处理搜索逻辑 ---
 async fn handle_search_parallel(
     app: AppHandle,
@@ -613,6 +896,7 @@ async fn handle_search_parallel(
             );
 
             match crate::commands::search::perform_search(
+                &app.state::<HttpClients>(),
                 &search_instance_url,
                 &original_query,
                 &provider,
@@ -697,7 +981,7 @@ async fn get_relevant_context_parallel(
 #[tauri::command]
 pub async fn prewarm_connection(
     base_url: String,
-    client: State<'_, reqwest::Client>,
+    clients: State<'_, HttpClients>,
 ) -> Result<(), String> {
     let start = std::time::Instant::now();
 
@@ -716,7 +1000,8 @@ pub async fn prewarm_connection(
     };
 
     // 发起预热请求（不关心结果，只为建立连接）
-    let _ = client
+    let _ = clients
+        .global()?
         .get(&url)
         .timeout(std::time::Duration::from_secs(5))
         .send()
@@ -730,3 +1015,18 @@ pub async fn prewarm_connection(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_origin() {
+        let base = "https://api.openai.com/v1";
+        assert!(same_origin("https://api.openai.com/v1/models", base));
+        assert!(!same_origin("https://evil.example/v1/models", base));
+        assert!(!same_origin("http://api.openai.com/v1/models", base));
+        assert!(!same_origin("https://api.openai.com:8443/v1/models", base));
+        assert!(!same_origin("not a url", base));
+    }
+}
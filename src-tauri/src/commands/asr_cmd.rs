@@ -1,3 +1,4 @@
+use crate::net::{HttpClients, NetworkSettings};
 use futures_util::StreamExt;
 use once_cell::sync::OnceCell;
 // use sherpa_rs::sense_voice::{SenseVoiceConfig, SenseVoiceRecognizer};
@@ -167,7 +168,13 @@ pub async fn download_asr_model(app_handle: AppHandle) -> Result<String, String>
         ("model.int8.onnx", "https://hf-mirror.com/csukuangfj/sherpa-onnx-paraformer-zh-2023-09-14/resolve/main/model.int8.onnx", 100_000_000),
     ];
 
-    let client = reqwest::Client::new();
+    // 模型文件较大，未显式配置超时时不限制下载时长
+    let client = app_handle
+        .state::<HttpClients>()
+        .global_with_defaults(&NetworkSettings {
+            timeout_secs: Some(0),
+            ..Default::default()
+        })?;
 
     for (filename, url, min_size) in files {
         let file_path = target_dir.join(filename);
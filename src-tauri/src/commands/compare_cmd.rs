@@ -0,0 +1,526 @@
+use crate::commands::ai::{render_system_prompt, strip_control_tags};
+use crate::commands::config_cmd::{self, AppConfig};
+use crate::db::{ChatSession, DbState};
+use crate::generation_config::{resolve_generation, SessionOverrides};
+use crate::models::{GenerationParams, Message};
+use crate::net::HttpClients;
+use crate::provider_config::resolve_provider;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use tauri::{ipc::Channel, AppHandle, Manager, State};
+
+/// 同时对比的模型数量上限
+const MAX_TARGETS: usize = 8;
+
+/// 参与对比的一个模型
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareTarget {
+    pub provider_id: String,
+    pub model_id: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JudgeMode {
+    /// 给每个回答打分并选出最佳
+    #[default]
+    Score,
+    /// 综合各回答写出一份合并答案 (保存为新的兄弟回答)
+    Merge,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareJudge {
+    pub provider_id: String,
+    pub model_id: String,
+    #[serde(default)]
+    pub mode: JudgeMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JudgeScore {
+    pub target: usize,
+    pub score: f32,
+    #[serde(default)]
+    pub comment: String,
+}
+
+/// 对比请求；提供 session_id 与 user_message_id 时，每个回答保存为挂在该用户消息下的兄弟回答
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareRequest {
+    pub targets: Vec<CompareTarget>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub user_message_id: Option<i64>,
+    #[serde(default)]
+    pub judge: Option<CompareJudge>,
+}
+
+/// 对比模式下发的事件，target 为请求中 targets 的下标
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CompareEvent {
+    Content {
+        target: usize,
+        text: String,
+    },
+    Reasoning {
+        target: usize,
+        text: String,
+    },
+    Done {
+        target: usize,
+        /// 已保存的兄弟回答 ID (未提供会话或用户消息时为空)
+        #[serde(rename = "messageId")]
+        message_id: Option<i64>,
+        #[serde(rename = "firstTokenMs")]
+        first_token_ms: Option<u64>,
+        #[serde(rename = "elapsedMs")]
+        elapsed_ms: u64,
+    },
+    Error {
+        target: usize,
+        message: String,
+    },
+    JudgeContent {
+        text: String,
+    },
+    JudgeDone {
+        #[serde(rename = "messageId")]
+        message_id: Option<i64>,
+        scores: Vec<JudgeScore>,
+        best: Option<usize>,
+    },
+    JudgeError {
+        message: String,
+    },
+}
+
+/// 一个模型的完整回答
+struct TargetAnswer {
+    target: usize,
+    provider_id: String,
+    model: String,
+    content: String,
+    reasoning: String,
+}
+
+/// 同一问题并发发给多个模型，所有回答通过同一个 Channel 按 target 标记下发
+#[tauri::command]
+pub async fn ask_ai_compare(
+    app: AppHandle,
+    state: State<'_, crate::GoleState>,
+    msg: Vec<Message>,
+    request: CompareRequest,
+    on_event: Channel<CompareEvent>,
+    clients: State<'_, HttpClients>,
+) -> Result<(), String> {
+    let CompareRequest {
+        targets,
+        session_id,
+        user_message_id,
+        judge,
+    } = request;
+    if targets.is_empty() {
+        return Err("请至少选择一个对比模型".into());
+    }
+    if targets.len() > MAX_TARGETS {
+        return Err(format!("最多同时对比 {} 个模型", MAX_TARGETS));
+    }
+
+    let config = config_cmd::load_config(app.clone()).await?;
+    let session = match session_id.as_deref().and_then(|id| id.parse::<i64>().ok()) {
+        Some(id) => {
+            let db = app.state::<DbState>();
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            crate::db::get_session(&conn, id).map_err(|e| e.to_string())?
+        }
+        None => None,
+    };
+    let parent = session.as_ref().map(|s| s.id).zip(user_message_id);
+    // 之前对比产生的兄弟回答不进入上下文
+    let msg: Vec<Message> = msg.into_iter().filter(|m| m.parent_id.is_none()).collect();
+
+    println!("⚖️ [对比] 同时请求 {} 个模型", targets.len());
+    let ctx = RunContext {
+        app: &app,
+        config: &config,
+        session: session.as_ref(),
+        clients: &clients,
+        stop_flag: &state.stop_flag,
+        on_event: &on_event,
+    };
+    let runs = targets.iter().enumerate().map(|(index, target)| {
+        let messages = msg.clone();
+        async move {
+            let started = Instant::now();
+            let mut first_token = None;
+            let result = ctx
+                .run_target(index, target, messages, &mut first_token)
+                .await
+                .and_then(|answer| {
+                    let message_id = ctx.save(
+                        parent,
+                        &answer.provider_id,
+                        &answer.model,
+                        &answer.content,
+                        &answer.reasoning,
+                    )?;
+                    Ok((answer, message_id))
+                });
+            match result {
+                Ok((answer, message_id)) => {
+                    let _ = ctx.on_event.send(CompareEvent::Done {
+                        target: index,
+                        message_id,
+                        first_token_ms: first_token
+                            .map(|t: Instant| t.duration_since(started).as_millis() as u64),
+                        elapsed_ms: started.elapsed().as_millis() as u64,
+                    });
+                    Ok(answer)
+                }
+                Err(e) => {
+                    println!(
+                        "❌ [对比] {} / {} 失败: {}",
+                        target.provider_id, target.model_id, e
+                    );
+                    let _ = ctx.on_event.send(CompareEvent::Error {
+                        target: index,
+                        message: e.clone(),
+                    });
+                    Err(e)
+                }
+            }
+        }
+    });
+    let results = futures_util::future::join_all(runs).await;
+    let answers: Vec<TargetAnswer> = results.into_iter().filter_map(Result::ok).collect();
+    if answers.is_empty() {
+        return Err("所有对比模型均调用失败".into());
+    }
+
+    // 评审：至少两个回答才有意义
+    if let Some(judge) = judge.filter(|_| answers.len() >= 2) {
+        if state.stop_flag.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if let Err(e) = ctx.run_judge(&judge, &msg, &answers, parent).await {
+            println!("❌ [对比] 评审失败: {}", e);
+            let _ = on_event.send(CompareEvent::JudgeError { message: e });
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+struct RunContext<'a> {
+    app: &'a AppHandle,
+    config: &'a AppConfig,
+    session: Option<&'a ChatSession>,
+    clients: &'a HttpClients,
+    stop_flag: &'a std::sync::atomic::AtomicBool,
+    on_event: &'a Channel<CompareEvent>,
+}
+
+impl RunContext<'_> {
+    /// 按目标模型准备消息 (系统提示词、模板、能力校验、上下文裁剪) 并流式请求
+    async fn run_target(
+        &self,
+        index: usize,
+        target: &CompareTarget,
+        mut messages: Vec<Message>,
+        first_token: &mut Option<Instant>,
+    ) -> Result<TargetAnswer, String> {
+        let resolved = resolve_generation(
+            self.config,
+            self.session.map(SessionOverrides::from_session).as_ref(),
+            Some(target.provider_id.clone()),
+            Some(target.model_id.clone()),
+            GenerationParams::default(),
+            GenerationParams::default(),
+        );
+        let provider = resolve_provider(self.config, &resolved.provider_id)?;
+        let client = self.clients.for_provider(&provider)?;
+        let model = resolved.model.clone();
+
+        resolved.apply_system_prompt(&mut messages);
+        render_system_prompt(self.app, self.config, self.session, &mut messages, None);
+        strip_control_tags(&mut messages);
+        {
+            let db = self.app.state::<DbState>();
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            let catalog_entry = crate::model_catalog::cached_model(&conn, &provider.id, &model)
+                .ok()
+                .flatten();
+            crate::model_catalog::check_features(
+                &model,
+                catalog_entry.as_ref(),
+                provider.model(&model),
+                &messages,
+                &resolved.params,
+            )?;
+        }
+        // 各模型上下文上限不同，摘要不写入会话缓存
+        let messages = crate::context_window::fit_to_context(
+            self.app,
+            None,
+            messages,
+            crate::context_window::context_limit_for(&provider, &model),
+            resolved.params.max_tokens,
+        )
+        .await;
+
+        // 与单模型对话一致：20ms 合批下发
+        let mut content = String::new();
+        let mut reasoning_content = String::new();
+        let mut pending_content = String::new();
+        let mut pending_reasoning = String::new();
+        let mut last_emit = Instant::now();
+        let flush = |pending_content: &mut String, pending_reasoning: &mut String| {
+            if !pending_content.is_empty() {
+                let _ = self.on_event.send(CompareEvent::Content {
+                    target: index,
+                    text: std::mem::take(pending_content),
+                });
+            }
+            if !pending_reasoning.is_empty() {
+                let _ = self.on_event.send(CompareEvent::Reasoning {
+                    target: index,
+                    text: std::mem::take(pending_reasoning),
+                });
+            }
+        };
+
+        crate::ai_utils::stream_chat(
+            &client,
+            &provider,
+            &model,
+            messages,
+            &resolved.params,
+            |text, reasoning| {
+                first_token.get_or_insert_with(Instant::now);
+                content.push_str(text);
+                reasoning_content.push_str(reasoning);
+                pending_content.push_str(text);
+                pending_reasoning.push_str(reasoning);
+                if last_emit.elapsed().as_millis() >= 20 || pending_content.len() > 100 {
+                    flush(&mut pending_content, &mut pending_reasoning);
+                    last_emit = Instant::now();
+                }
+                !self.stop_flag.load(Ordering::Relaxed)
+            },
+        )
+        .await?;
+        flush(&mut pending_content, &mut pending_reasoning);
+
+        Ok(TargetAnswer {
+            target: index,
+            provider_id: provider.id,
+            model,
+            content,
+            reasoning: reasoning_content,
+        })
+    }
+
+    /// 保存为挂在用户消息下的兄弟回答
+    fn save(
+        &self,
+        parent: Option<(i64, i64)>,
+        provider_id: &str,
+        model: &str,
+        content: &str,
+        reasoning: &str,
+    ) -> Result<Option<i64>, String> {
+        let Some((session_id, parent_id)) = parent else {
+            return Ok(None);
+        };
+        if content.trim().is_empty() {
+            return Ok(None);
+        }
+        let db = self.app.state::<DbState>();
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        crate::db::save_linked_reply(
+            &conn,
+            session_id,
+            parent_id,
+            model,
+            provider_id,
+            content,
+            Some(reasoning).filter(|r| !r.is_empty()),
+        )
+        .map(Some)
+        .map_err(|e| e.to_string())
+    }
+
+    async fn run_judge(
+        &self,
+        judge: &CompareJudge,
+        history: &[Message],
+        answers: &[TargetAnswer],
+        parent: Option<(i64, i64)>,
+    ) -> Result<(), String> {
+        let resolved = resolve_generation(
+            self.config,
+            None,
+            Some(judge.provider_id.clone()),
+            Some(judge.model_id.clone()),
+            GenerationParams::default(),
+            GenerationParams::default(),
+        );
+        let provider = resolve_provider(self.config, &resolved.provider_id)?;
+        let client = self.clients.for_provider(&provider)?;
+        let mut question_msgs = history.to_vec();
+        strip_control_tags(&mut question_msgs);
+        let question = question_msgs
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+        let labeled: Vec<(usize, &str)> = answers
+            .iter()
+            .map(|a| (a.target, a.content.as_str()))
+            .collect();
+
+        println!("⚖️ [对比] 评审模型 {} ({:?})", resolved.model, judge.mode);
+        let mut output = String::new();
+        crate::ai_utils::stream_chat(
+            &client,
+            &provider,
+            &resolved.model,
+            judge_messages(&question, &labeled, judge.mode),
+            &resolved.params,
+            |text, _| {
+                output.push_str(text);
+                if judge.mode == JudgeMode::Merge && !text.is_empty() {
+                    let _ = self.on_event.send(CompareEvent::JudgeContent {
+                        text: text.to_string(),
+                    });
+                }
+                !self.stop_flag.load(Ordering::Relaxed)
+            },
+        )
+        .await?;
+
+        let (scores, best, message_id) = match judge.mode {
+            JudgeMode::Score => {
+                let valid: Vec<usize> = answers.iter().map(|a| a.target).collect();
+                let (scores, best) = parse_judge_scores(&output, &valid)?;
+                (scores, best, None)
+            }
+            JudgeMode::Merge => {
+                let id = self.save(parent, &provider.id, &resolved.model, &output, "")?;
+                (Vec::new(), None, id)
+            }
+        };
+        let _ = self.on_event.send(CompareEvent::JudgeDone {
+            message_id,
+            scores,
+            best,
+        });
+        Ok(())
+    }
+}
+
+/// 构造评审请求；回答匿名编号，避免评审模型偏向某个品牌
+fn judge_messages(question: &str, answers: &[(usize, &str)], mode: JudgeMode) -> Vec<Message> {
+    let instruction = match mode {
+        JudgeMode::Score => {
+            "你是严谨的评审。请从准确性、完整性和清晰度评价下面针对同一问题的多个回答，每个回答打 1-10 分。\
+             只输出 JSON，不要输出其他内容，格式：\
+             {\"scores\":[{\"target\":编号,\"score\":分数,\"comment\":\"一句话点评\"}],\"best\":最佳回答编号}"
+        }
+        JudgeMode::Merge => {
+            "你是严谨的编辑。请综合下面针对同一问题的多个回答，取长补短、纠正错误，直接写出一份最佳回答，不要提及各回答的编号。"
+        }
+    };
+    let mut body = format!("【问题】\n{}\n", question);
+    for (target, content) in answers {
+        body.push_str(&format!("\n【回答 #{}】\n{}\n", target, content));
+    }
+
+    [("system", instruction.to_string()), ("user", body)]
+        .into_iter()
+        .map(|(role, content)| Message {
+            id: None,
+            model: None,
+            role: role.to_string(),
+            content,
+            reasoning_content: None,
+            file_metadata: None,
+            search_metadata: None,
+            provider: None,
+            mode: None,
+            role_id: None,
+            parent_id: None,
+        })
+        .collect()
+}
+
+/// 解析评审输出 (容忍 Markdown 代码块等包裹)，丢弃不存在的编号
+fn parse_judge_scores(
+    output: &str,
+    valid_targets: &[usize],
+) -> Result<(Vec<JudgeScore>, Option<usize>), String> {
+    #[derive(Deserialize)]
+    struct Verdict {
+        #[serde(default)]
+        scores: Vec<JudgeScore>,
+        best: Option<usize>,
+    }
+
+    let json = match (output.find('{'), output.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &output[start..=end],
+        _ => return Err("评审模型未返回评分 JSON".into()),
+    };
+    let verdict: Verdict =
+        serde_json::from_str(json).map_err(|e| format!("评审结果无法解析: {}", e))?;
+    let scores: Vec<JudgeScore> = verdict
+        .scores
+        .into_iter()
+        .filter(|s| valid_targets.contains(&s.target))
+        .collect();
+    let best = verdict
+        .best
+        .filter(|b| valid_targets.contains(b))
+        .or_else(|| {
+            scores
+                .iter()
+                .max_by(|a, b| a.score.total_cmp(&b.score))
+                .map(|s| s.target)
+        });
+    Ok((scores, best))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_judge_prompt_is_anonymous() {
+        let msgs = judge_messages("1+1=?", &[(0, "2"), (2, "二")], JudgeMode::Score);
+        assert_eq!(msgs[0].role, "system");
+        assert!(msgs[0].content.contains("\"best\""));
+        assert!(msgs[1].content.contains("【回答 #2】\n二"));
+        assert!(msgs[1].content.starts_with("【问题】\n1+1=?"));
+    }
+
+    #[test]
+    fn test_parse_judge_scores() {
+        let output = "```json\n{\"scores\":[{\"target\":0,\"score\":6},{\"target\":2,\"score\":9,\"comment\":\"更完整\"},{\"target\":5,\"score\":10}]}\n```";
+        let (scores, best) = parse_judge_scores(output, &[0, 2]).unwrap();
+        assert_eq!(scores.len(), 2);
+        assert_eq!(scores[1].comment, "更完整");
+        // 未给出 best 时取最高分
+        assert_eq!(best, Some(2));
+
+        let (_, best) = parse_judge_scores("{\"scores\":[],\"best\":0}", &[0, 1]).unwrap();
+        assert_eq!(best, Some(0));
+        assert!(parse_judge_scores("两个都不错", &[0, 1]).is_err());
+    }
+}
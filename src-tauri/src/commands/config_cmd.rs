@@ -1,7 +1,8 @@
+use crate::config_store::{self, ConfigBackup, ConfigFileError, RawConfig};
 use crate::immersive_settings::ImmersiveSettings;
-use base64::prelude::*;
+use crate::secret_store::{self, SecretStore, SecretStoreState, SecretStoreStatus};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -14,6 +15,19 @@ use tokio::sync::RwLock;
 
 pub struct ConfigState(pub Arc<RwLock<AppConfig>>);
 
+/// 最近一次从磁盘加载配置的结果
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ConfigLoadReport {
+    pub issues: Vec<ConfigFileError>,
+    /// 不阻止加载的问题 (例如提供商配置中的未知字段、非法 baseUrl)
+    pub warnings: Vec<ConfigFileError>,
+    /// 配置版本高于程序支持的版本时禁止写入
+    #[serde(rename = "readOnly")]
+    pub read_only: bool,
+}
+
+pub struct ConfigIssuesState(pub std::sync::Mutex<ConfigLoadReport>);
+
 impl ConfigState {
     pub async fn get_config(&self) -> AppConfig {
         self.0.read().await.clone()
@@ -49,7 +63,7 @@ pub struct AppConfig {
     #[serde(default = "default_search_provider", rename = "defaultSearchProvider")]
     pub default_search_provider: String,
 
-    // Providers (from providers.json + secrets.json)
+    // Providers (from providers.json + secret store)
     #[serde(default = "default_providers", rename = "providers")]
     pub providers: serde_json::Value,
     #[serde(default = "default_provider_id", rename = "defaultProviderId")]
@@ -103,6 +117,14 @@ pub struct AppConfig {
     #[serde(default = "default_false", rename = "enableRag")]
     pub enable_rag: bool,
 
+    // 全局网络设置 (代理 / 自定义根证书 / 超时)，提供商可单独覆盖
+    #[serde(default)]
+    pub network: crate::net::NetworkSettings,
+
+    // 本地 OpenAI 兼容接口 (默认关闭)
+    #[serde(default, rename = "apiServer")]
+    pub api_server: crate::api_server::ApiServerSettings,
+
     // NEW: Immersive Mode (沉浸式模式)
     #[serde(default = "default_immersive_mode", rename = "immersiveMode")]
     pub immersive_mode: ImmersiveSettings,
@@ -183,9 +205,15 @@ struct SettingsPart {
     #[serde(default = "default_immersive_mode", rename = "immersiveMode")]
     immersive_mode: ImmersiveSettings,
 
-    // Legacy support for promptLibrary in settings.json (optional)
-    #[serde(default, rename = "promptLibrary")]
-    prompt_library: Option<serde_json::Value>,
+    #[serde(default)]
+    network: crate::net::NetworkSettings,
+
+    #[serde(default, rename = "apiServer")]
+    api_server: crate::api_server::ApiServerSettings,
+
+    // 配置结构版本，旧版内嵌的 promptLibrary 由迁移移到 prompts.json
+    #[serde(default, rename = "configVersion")]
+    config_version: u32,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -206,13 +234,6 @@ struct PromptsPart {
     prompt_library: serde_json::Value,
 }
 
-#[derive(Serialize, Deserialize)]
-struct SecretsPart {
-    // Map: ProviderID -> EncodedApiKey
-    #[serde(default, rename = "secrets")]
-    secrets: HashMap<String, String>,
-}
-
 // defaults
 // defaults
 fn default_font_size() -> u32 {
@@ -252,6 +273,7 @@ fn default_providers() -> serde_json::Value {
             "apiKey": "",
             "baseUrl": "https://api.deepseek.com",
             "models": ["deepseek-chat", "deepseek-reasoner"],
+            "contextLimits": { "deepseek-chat": 64000, "deepseek-reasoner": 64000 },
             "defaultModel": "deepseek-chat",
             "temperature": 1.0,
             "maxTokens": 8192
@@ -264,6 +286,7 @@ fn default_providers() -> serde_json::Value {
             "apiKey": "",
             "baseUrl": "https://api.openai.com/v1",
             "models": ["gpt-4", "gpt-4-turbo", "gpt-3.5-turbo"],
+            "contextLimits": { "gpt-4": 8192, "gpt-4-turbo": 128000, "gpt-3.5-turbo": 16385 },
             "defaultModel": "gpt-4-turbo",
             "temperature": 0.7,
             "maxTokens": 4096
@@ -292,6 +315,7 @@ fn default_providers() -> serde_json::Value {
                 "gemini-3-flash-preview",
                 "gemini-3-pro-image-preview"
             ],
+            "contextWindow": 1000000,
             "defaultModel": "gemini-3-pro-preview",
             "temperature": 0.7,
             "maxTokens": 4096
@@ -308,6 +332,18 @@ fn default_providers() -> serde_json::Value {
             "temperature": 0.7,
             "maxTokens": 2048
         },
+        {
+            "id": "llamacpp",
+            "name": "llama.cpp",
+            "icon": "llamacpp",
+            "kind": "llamacpp",
+            "enabled": false,
+            "apiKey": "",
+            "baseUrl": "http://localhost:8080",
+            "models": [],
+            "temperature": 0.7,
+            "maxTokens": 2048
+        },
         {
             "id": "qwen",
             "name": "Qwen",
@@ -423,6 +459,8 @@ impl Default for AppConfig {
             font_family_english: "".into(),
             font_family_chinese: "".into(),
             enable_rag: false,
+            network: Default::default(),
+            api_server: Default::default(),
         }
     }
 }
@@ -431,96 +469,194 @@ impl Default for AppConfig {
 // logic
 // ==================================================================================
 
-/// Resolves the config directory. Priority: Portable EXE/data/config > Stable AppConfig
+/// Resolves the config directory (portable EXE/data/config, see crate::paths)
 pub fn resolve_config_dir(_app: &AppHandle) -> PathBuf {
-    // --- 1. 定位“便携式”数据目录 ---
-    let exe_path = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("."));
-    let exe_dir = exe_path.parent().unwrap_or_else(|| Path::new("."));
-    let portable_config_dir = exe_dir.join("data").join("config");
+    crate::paths::config_dir()
+}
 
-    if !portable_config_dir.exists() {
-        let _ = fs::create_dir_all(&portable_config_dir);
-    }
+/// 读取含真实 API Key 的完整配置，仅供后端内部使用，不注册为命令
+pub async fn load_config(app: AppHandle) -> Result<AppConfig, String> {
+    // 🚀 优先从内存缓存读取，实现 0 延迟响应
+    let state = app.state::<ConfigState>();
+    Ok(state.get_config().await)
+}
+
+/// 与 load_config 相同，但 API Key 以占位符代替，是 Webview 读取配置的唯一入口
+/// 原样保存回来的占位符会被 save_config 识别为"保持不变"
+#[tauri::command]
+pub async fn load_config_redacted(app: AppHandle) -> Result<AppConfig, String> {
+    let mut config = load_config(app).await?;
+    redact_config(&mut config);
+    Ok(config)
+}
 
-    // --- 2. 迁移逻辑 (仅用于兼容旧版 AppConfig 路径) ---
-    // 如果稳定目录中存在且便携式目录刚创建/为空，可以考虑搬迁
-    // 但根据用户要求，现在强制使用 debug/data 逻辑
+fn redact_config(config: &mut AppConfig) {
+    secret_store::redact_providers(&mut config.providers);
+    if !config.api_key.is_empty() {
+        config.api_key = secret_store::REDACTED_KEY.to_string();
+    }
+}
 
-    portable_config_dir
+/// 把来自 Webview 的占位符还原成当前密钥
+fn restore_secrets(config: &mut AppConfig, current: &AppConfig) {
+    secret_store::restore_redacted(&mut config.providers, &current.providers);
+    if config.api_key == secret_store::REDACTED_KEY {
+        config.api_key = current.api_key.clone();
+    }
 }
 
-fn encode_key(key: &str) -> String {
-    base64::engine::general_purpose::STANDARD.encode(key)
+/// 还原占位符后将提供商密钥写入存储
+/// 返回去掉明文的提供商列表，用于写入 providers.json
+fn store_secrets(
+    store: &mut SecretStore,
+    config: &mut AppConfig,
+    current: &AppConfig,
+) -> Result<serde_json::Value, String> {
+    restore_secrets(config, current);
+    let mut sanitized = config.providers.clone();
+    store.store_providers(&mut sanitized, &current.providers)?;
+    Ok(sanitized)
 }
 
-fn decode_key(encoded: &str) -> String {
-    String::from_utf8(
-        base64::engine::general_purpose::STANDARD
-            .decode(encoded)
-            .unwrap_or_default(),
-    )
-    .unwrap_or_default()
+#[tauri::command]
+pub fn get_secret_store_status(state: tauri::State<'_, SecretStoreState>) -> SecretStoreStatus {
+    state.0.lock().unwrap().status()
 }
 
+/// 解锁加密文件密钥存储，完成旧格式迁移并刷新内存中的 API Key
 #[tauri::command]
-pub async fn load_config(app: AppHandle) -> Result<AppConfig, String> {
-    // 🚀 优先从内存缓存读取，实现 0 延迟响应
-    let state = app.state::<ConfigState>();
-    Ok(state.get_config().await)
+pub async fn unlock_secret_store(app: AppHandle, passphrase: String) -> Result<(), String> {
+    let config_state = app.state::<ConfigState>();
+    let mut config = config_state.get_config().await;
+    {
+        let store_state = app.state::<SecretStoreState>();
+        let mut store = store_state.0.lock().map_err(|e| e.to_string())?;
+        store.unlock(&passphrase)?;
+        store.migrate_legacy()?;
+        store.fill_providers(&mut config.providers);
+    }
+    config_state.update_config(config).await;
+    Ok(())
+}
+
+/// 读取并严格校验单个配置文件
+/// 文件不存在时返回 None；解析失败时记录问题并返回 None (该部分使用默认值，且保存时不会覆盖原文件)
+fn read_checked<T: DeserializeOwned>(
+    config_dir: &Path,
+    file: &str,
+    issues: &mut Vec<ConfigFileError>,
+) -> Option<serde_json::Value> {
+    let text = fs::read_to_string(config_dir.join(file)).ok()?;
+    match config_store::parse_part::<T>(file, &text) {
+        Ok(_) => serde_json::from_str(&text).ok(),
+        Err(e) => {
+            println!("❌ [Config] {}", e);
+            issues.push(e);
+            None
+        }
+    }
 }
 
 /// 内部加载函数，用于 setup 阶段从磁盘加载初始化
+/// 加载中发现的问题写入 ConfigIssuesState，供前端展示并阻止覆盖出错的文件
 pub async fn load_config_internal(app: &AppHandle) -> Result<AppConfig, String> {
     let config_dir = resolve_config_dir(app);
-    let settings_path = config_dir.join("settings.json");
-    let providers_path = config_dir.join("providers.json");
-    let presets_path = config_dir.join("presets.json");
-    let prompts_path = config_dir.join("prompts.json");
-    let secrets_path = config_dir.join("secrets.json");
+    let (config, report) = {
+        let store_state = app.state::<SecretStoreState>();
+        let mut store = store_state.0.lock().map_err(|e| e.to_string())?;
+        load_config_from_dir(&config_dir, &mut store, true)
+    };
+    *app.state::<ConfigIssuesState>().0.lock().unwrap() = report;
+    Ok(config)
+}
 
-    // Strategy:
-    // 1. Try to load from new split files (portable/local mode).
-    // 2. If not found, try to load from legacy standard location (AppData).
-    // 3. If legacy exists, MIGRATE it to local mode immediately.
-    // 4. Fallback to default.
+/// 从配置目录读取、迁移并校验配置 (不依赖 AppHandle，命令行工具共用)
+/// persist_migration 为 false 时只在内存中迁移，不写回磁盘
+pub fn load_config_from_dir(
+    config_dir: &Path,
+    store: &mut secret_store::SecretStore,
+    persist_migration: bool,
+) -> (AppConfig, ConfigLoadReport) {
+    let settings_path = config_dir.join("settings.json");
+    let mut report = ConfigLoadReport::default();
 
     // 1. Check if new format exists (checking settings.json is enough as an indicator)
-    if settings_path.exists() {
-        let settings_content = fs::read_to_string(&settings_path).unwrap_or_else(|_| "{}".into());
-        let providers_content =
-            fs::read_to_string(&providers_path).unwrap_or_else(|_| "{\"providers\":[]}".into());
-        let presets_content =
-            fs::read_to_string(&presets_path).unwrap_or_else(|_| "{\"presets\":[]}".into());
-        let prompts_content =
-            fs::read_to_string(&prompts_path).unwrap_or_else(|_| "{\"promptLibrary\":[]}".into());
-        let secrets_content = fs::read_to_string(&secrets_path).unwrap_or_else(|_| "{}".into());
-
-        let settings: SettingsPart = serde_json::from_str(&settings_content)
-            .unwrap_or_else(|_| serde_json::from_str("{}").unwrap());
-        let mut providers_part: ProvidersPart = serde_json::from_str(&providers_content)
-            .unwrap_or_else(|_| serde_json::from_str("{\"providers\":[]}").unwrap());
-        let presets_part: PresetsPart = serde_json::from_str(&presets_content)
-            .unwrap_or_else(|_| serde_json::from_str("{\"presets\":[]}").unwrap());
-        let prompts_part: PromptsPart = serde_json::from_str(&prompts_content)
-            .unwrap_or_else(|_| serde_json::from_str("{\"promptLibrary\":[]}").unwrap());
-        let secrets_part: SecretsPart = serde_json::from_str(&secrets_content)
-            .unwrap_or_else(|_| serde_json::from_str("{}").unwrap());
-
-        // MERGE SECRETS INTO PROVIDERS
-        if let serde_json::Value::Array(ref mut providers) = providers_part.providers {
-            for provider in providers.iter_mut() {
-                if let Some(id_val) = provider.get("id") {
-                    if let Some(id) = id_val.as_str() {
-                        if let Some(encoded_key) = secrets_part.secrets.get(id) {
-                            let decoded = decode_key(encoded_key);
-                            if !decoded.is_empty() {
-                                provider["apiKey"] = serde_json::Value::String(decoded);
-                            }
-                        }
-                    }
+    let config = if settings_path.exists() {
+        let issues = &mut report.issues;
+        let settings_raw = read_checked::<SettingsPart>(config_dir, "settings.json", issues);
+        let mut raw = RawConfig {
+            settings: settings_raw.unwrap_or_else(|| serde_json::json!({})),
+            providers: read_checked::<ProvidersPart>(config_dir, "providers.json", issues),
+            presets: read_checked::<PresetsPart>(config_dir, "presets.json", issues),
+            prompts: read_checked::<PromptsPart>(config_dir, "prompts.json", issues),
+        };
+
+        // 2. 版本迁移 (仅在所有文件都能正确解析时写回磁盘)
+        match config_store::migrate(&mut raw) {
+            Ok(true) if issues.is_empty() && persist_migration => {
+                println!(
+                    "[Config] Migrating config files to version {}",
+                    config_store::CONFIG_VERSION
+                );
+                if let Err(e) = write_raw_config(config_dir, &raw) {
+                    println!("❌ [Config] 迁移写入失败: {}", e);
                 }
             }
+            Ok(_) => {}
+            Err(e) => {
+                println!("❌ [Config] {}", e);
+                issues.push(e);
+                report.read_only = true;
+            }
+        }
+        let issues = &mut report.issues;
 
+        // 3. 结构校验：提供商与预设必须是带唯一 id 的对象数组
+        if let Some(p) = &raw.providers {
+            if let Err(e) =
+                config_store::validate_id_list("providers.json", "providers", &p["providers"])
+            {
+                println!("❌ [Config] {}", e);
+                issues.push(e);
+                raw.providers = None;
+            }
+        }
+        if let Some(p) = &raw.presets {
+            if let Err(e) = config_store::validate_id_list("presets.json", "presets", &p["presets"])
+            {
+                println!("❌ [Config] {}", e);
+                issues.push(e);
+                raw.presets = None;
+            }
+        }
+
+        let settings: SettingsPart = config_store::from_value_part("settings.json", raw.settings)
+            .unwrap_or_else(|e| {
+                issues.push(e);
+                serde_json::from_str("{}").unwrap()
+            });
+        let mut providers_part: ProvidersPart = raw
+            .providers
+            .and_then(|v| config_store::from_value_part("providers.json", v).ok())
+            .unwrap_or_else(|| serde_json::from_str("{\"providers\":[]}").unwrap());
+        let presets_part: PresetsPart = raw
+            .presets
+            .and_then(|v| config_store::from_value_part("presets.json", v).ok())
+            .unwrap_or_else(|| serde_json::from_str("{\"presets\":[]}").unwrap());
+        let prompts_part: PromptsPart = raw
+            .prompts
+            .and_then(|v| config_store::from_value_part("prompts.json", v).ok())
+            .unwrap_or_else(|| serde_json::from_str("{\"promptLibrary\":[]}").unwrap());
+
+        // MERGE SECRETS INTO PROVIDERS (旧版 secrets.json 在此迁移到密钥存储)
+        if persist_migration {
+            if let Err(e) = store.migrate_legacy() {
+                println!("❌ [密钥] 迁移 secrets.json 失败: {}", e);
+            }
+        }
+        store.fill_providers(&mut providers_part.providers);
+
+        if let serde_json::Value::Array(ref mut providers) = providers_part.providers {
             // 🟢 [New Logic] Merge missing default providers into loaded providers
             let existing_ids: Vec<String> = providers
                 .iter()
@@ -543,6 +679,12 @@ pub async fn load_config_internal(app: &AppHandle) -> Result<AppConfig, String>
             }
         }
 
+        // 4. 提供商语义校验：拼写错误的字段、非法 URL 等只警告，使用时由 resolve_provider 报错
+        for w in crate::provider_config::validate_providers(&providers_part.providers) {
+            println!("⚠️ [Config] {}", w);
+            report.warnings.push(w);
+        }
+
         // Merge into complete AppConfig
         let mut config = AppConfig::default();
         config.font_size = settings.font_size;
@@ -572,42 +714,89 @@ pub async fn load_config_internal(app: &AppHandle) -> Result<AppConfig, String>
         // Map font settings
         config.font_family_english = settings.font_family_english;
         config.font_family_chinese = settings.font_family_chinese;
+        config.network = settings.network;
+        config.api_server = settings.api_server;
 
         config.providers = providers_part.providers;
         config.presets = presets_part.presets;
+        config.prompt_library = prompts_part.prompt_library;
+        config
+    } else {
+        // 5. Default
+        AppConfig::default()
+    };
 
-        // Prefer separate prompts.json, fallback to settings.json embedded
-        config.prompt_library = if let Some(p) = settings.prompt_library {
-            // If local prompts.json is empty but settings has it (migration scenario)
-            if prompts_part
-                .prompt_library
-                .as_array()
-                .map_or(0, |a| a.len())
-                == 0
-                && p.as_array().map_or(0, |a| a.len()) > 0
-            {
-                p
-            } else {
-                prompts_part.prompt_library
-            }
-        } else {
-            prompts_part.prompt_library
-        };
-
-        return Ok(config);
+    (config, report)
+}
+
+/// 将迁移后的原始 JSON 写回磁盘 (写入前先备份)
+fn write_raw_config(config_dir: &Path, raw: &RawConfig) -> Result<(), String> {
+    config_store::backup_current(config_dir)?;
+    let parts = [
+        ("settings.json", Some(&raw.settings)),
+        ("providers.json", raw.providers.as_ref()),
+        ("presets.json", raw.presets.as_ref()),
+        ("prompts.json", raw.prompts.as_ref()),
+    ];
+    for (file, value) in parts {
+        if let Some(value) = value {
+            let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+            config_store::write_atomic(&config_dir.join(file), &json)?;
+        }
     }
+    Ok(())
+}
+
+/// 获取最近一次加载配置时发现的问题
+#[tauri::command]
+pub fn get_config_issues(state: tauri::State<'_, ConfigIssuesState>) -> ConfigLoadReport {
+    state.0.lock().unwrap().clone()
+}
 
-    // --- 2. 移除 C 盘 AppData 回退逻辑，强制使用便携式目录 ---
+/// 从磁盘重新加载配置 (例如手动修复配置文件后)，出错的文件保留当前内存中的值
+#[tauri::command]
+pub async fn reload_config(app: AppHandle) -> Result<ConfigLoadReport, String> {
+    crate::config_watcher::reload_from_disk(&app).await?;
+    Ok(app.state::<ConfigIssuesState>().0.lock().unwrap().clone())
+}
 
-    // 4. Default
-    Ok(AppConfig::default())
+#[tauri::command]
+pub fn list_config_backups(app: AppHandle) -> Vec<ConfigBackup> {
+    config_store::list_backups(&resolve_config_dir(&app))
+}
+
+/// 从备份恢复配置并立即生效
+#[tauri::command]
+pub async fn restore_config_backup(app: AppHandle, id: String) -> Result<ConfigLoadReport, String> {
+    config_store::restore_backup(&resolve_config_dir(&app), &id)?;
+    println!("[Config] Restored backup {}", id);
+    reload_config(app).await
 }
 
 #[tauri::command]
 pub async fn save_config(app: AppHandle, mut config: AppConfig) -> Result<(), String> {
     // 1. 立即更新内存缓存，确保后续 AI 请求能立即看到最新配置
+    // 来自 load_config_redacted 的占位符先还原成真实密钥，密钥写入存储失败时不应用任何改动
     let state = app.state::<ConfigState>();
+    let current = state.get_config().await;
+    config.network.validate()?;
+    // providers.json 加载失败时内存中的提供商是默认值，不能据此改动已存储的密钥
+    let report = app.state::<ConfigIssuesState>().0.lock().unwrap().clone();
+    let providers_blocked =
+        report.read_only || report.issues.iter().any(|i| i.file == "providers.json");
+    let sanitized_providers = if providers_blocked {
+        println!("⚠️ [密钥] providers.json 未能正常加载，本次保存不更新 API Key");
+        restore_secrets(&mut config, &current);
+        serde_json::Value::Array(Vec::new())
+    } else {
+        let store_state = app.state::<SecretStoreState>();
+        let mut store = store_state.0.lock().map_err(|e| e.to_string())?;
+        store_secrets(&mut store, &mut config, &current)?
+    };
     state.update_config(config.clone()).await;
+    app.state::<crate::net::HttpClients>()
+        .set_global(config.network.clone());
+    crate::api_server::apply(&app, &config.api_server);
 
     // 2. 持久化到磁盘
     let config_dir = resolve_config_dir(&app);
@@ -619,60 +808,46 @@ pub async fn save_config(app: AppHandle, mut config: AppConfig) -> Result<(), St
 
     println!("[Config] Saving to {:?}", config_dir);
 
-    // Split and save
-
-    // 1. EXTRACT & SANITIZE SECRETS
-    let mut secrets_map = HashMap::new();
-
-    if let serde_json::Value::Array(ref mut providers) = config.providers {
-        for provider in providers {
-            if let Some(id_val) = provider.get("id") {
-                if let Some(id) = id_val.as_str() {
-                    // Extract API Key
-                    if let Some(key_val) = provider.get("apiKey") {
-                        if let Some(key) = key_val.as_str() {
-                            if !key.trim().is_empty() {
-                                secrets_map.insert(id.to_string(), encode_key(key));
-                            }
-                        }
-                    }
-                    // WIPE API Key from the struct being saved to providers.json
-                    provider["apiKey"] = serde_json::Value::String("".to_string());
-                }
-            }
-        }
+    // 加载时出错的文件不覆盖，避免一处笔误导致整份配置被默认值替换
+    if report.read_only {
+        return Err("配置文件由更新版本的程序创建，已禁止写入".into());
     }
+    let blocked: Vec<String> = report.issues.iter().map(|i| i.file.clone()).collect();
+    let mut skipped = Vec::new();
+    let mut write_part = |file: &str, json: String| -> Result<(), String> {
+        if blocked.iter().any(|b| b == file) {
+            skipped.push(file.to_string());
+            return Ok(());
+        }
+        config_store::write_atomic(&config_dir.join(file), &json)
+    };
+    config_store::backup_current(&config_dir)?;
 
-    // 2. Save Providers (Sanitized)
+    // Split and save
+
+    // 1. Save Providers (Sanitized, 密钥已写入存储，providers.json 中不保留明文)
     let providers_part = ProvidersPart {
-        providers: config.providers.clone(),
+        providers: sanitized_providers,
     };
     let providers_json =
         serde_json::to_string_pretty(&providers_part).map_err(|e| e.to_string())?;
-    fs::write(config_dir.join("providers.json"), providers_json).map_err(|e| e.to_string())?;
-
-    // 3. Save Secrets
-    let secrets_part = SecretsPart {
-        secrets: secrets_map,
-    };
-    let secrets_json = serde_json::to_string_pretty(&secrets_part).map_err(|e| e.to_string())?;
-    fs::write(config_dir.join("secrets.json"), secrets_json).map_err(|e| e.to_string())?;
+    write_part("providers.json", providers_json)?;
 
-    // 4. Presets
+    // 2. Presets
     let presets_part = PresetsPart {
         presets: config.presets.clone(),
     };
     let presets_json = serde_json::to_string_pretty(&presets_part).map_err(|e| e.to_string())?;
-    fs::write(config_dir.join("presets.json"), presets_json).map_err(|e| e.to_string())?;
+    write_part("presets.json", presets_json)?;
 
-    // 5. Prompts
+    // 3. Prompts
     let prompts_part = PromptsPart {
         prompt_library: config.prompt_library.clone(),
     };
     let prompts_json = serde_json::to_string_pretty(&prompts_part).map_err(|e| e.to_string())?;
-    fs::write(config_dir.join("prompts.json"), prompts_json).map_err(|e| e.to_string())?;
+    write_part("prompts.json", prompts_json)?;
 
-    // 6. Settings (Remainder)
+    // 4. Settings (Remainder)
     let settings_part = SettingsPart {
         font_size: config.font_size,
         line_ratio: config.line_ratio,
@@ -700,10 +875,58 @@ pub async fn save_config(app: AppHandle, mut config: AppConfig) -> Result<(), St
         immersive_mode: config.immersive_mode,
         font_family_english: config.font_family_english,
         font_family_chinese: config.font_family_chinese,
-        prompt_library: None, // No longer saving here to avoid duplication
+        network: config.network,
+        api_server: config.api_server,
+        config_version: config_store::CONFIG_VERSION,
     };
     let settings_json = serde_json::to_string_pretty(&settings_part).map_err(|e| e.to_string())?;
-    fs::write(config_dir.join("settings.json"), settings_json).map_err(|e| e.to_string())?;
+    write_part("settings.json", settings_json)?;
 
+    if !skipped.is_empty() {
+        return Err(format!(
+            "以下配置文件存在错误，为避免覆盖未保存: {}",
+            skipped.join(", ")
+        ));
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret_store::EncryptedFileBackend;
+    use serde_json::json;
+
+    #[test]
+    fn test_redacted_load_then_save_keeps_stored_key() {
+        let dir = std::env::temp_dir().join(format!("goge_config_redact_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut backend = EncryptedFileBackend::load(dir.join("secrets.enc.json")).unwrap();
+        backend.unlock("pw").unwrap();
+        let mut store = SecretStore::new(Box::new(backend), &dir);
+
+        let current = AppConfig {
+            providers: json!([{ "id": "openai", "apiKey": "sk-real" }]),
+            ..AppConfig::default()
+        };
+        store_secrets(&mut store, &mut current.clone(), &AppConfig::default()).unwrap();
+
+        // Webview 只拿到占位符，原样保存回来
+        let mut from_webview = current.clone();
+        redact_config(&mut from_webview);
+        assert_eq!(
+            from_webview.providers[0]["apiKey"],
+            secret_store::REDACTED_KEY
+        );
+        let sanitized = store_secrets(&mut store, &mut from_webview, &current).unwrap();
+        assert_eq!(sanitized[0]["apiKey"], "");
+        assert_eq!(from_webview.providers[0]["apiKey"], "sk-real");
+
+        let mut reloaded = json!([{ "id": "openai", "apiKey": "" }]);
+        store.fill_providers(&mut reloaded);
+        assert_eq!(reloaded[0]["apiKey"], "sk-real");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -37,6 +37,7 @@ pub fn get_sessions(state: State<DbState>) -> Result<Vec<Session>, String> {
             preset_id: cs.preset_id,
             model_id: cs.model_id,
             system_prompt: cs.system_prompt,
+            generation_params: cs.generation_params,
         })
         .collect();
     Ok(sessions)
@@ -65,6 +66,25 @@ pub fn update_session_config(
     Ok(())
 }
 
+/// 更新会话级生成参数覆盖 (JSON 字符串，传 None 清除)
+#[tauri::command]
+pub fn update_session_params(
+    id: String,
+    generation_params: Option<String>,
+    state: State<DbState>,
+) -> Result<(), String> {
+    // 保存前校验，避免把无法解析的内容写进数据库
+    if let Some(raw) = generation_params.as_deref() {
+        serde_json::from_str::<serde_json::Value>(raw)
+            .map_err(|e| format!("生成参数不是合法的 JSON: {}", e))?;
+    }
+    let conn = state.0.lock().unwrap();
+    let numeric_id = parse_id(&id)?;
+    crate::db::update_session_params(&conn, numeric_id, generation_params.as_deref())
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 #[tauri::command]
 pub fn create_session(
     title: String,
@@ -166,6 +186,7 @@ pub fn get_messages(session_id: String, state: State<DbState>) -> Result<Vec<Mes
                 provider: cm.provider,
                 mode: Some("Standard".into()),
                 role_id: Some("Global".into()),
+                parent_id: cm.parent_id,
             }
         })
         .collect();
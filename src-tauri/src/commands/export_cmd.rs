@@ -0,0 +1,702 @@
+use crate::chat_export::{
+    self, load_attachments, markdown_attachment_link, parse_file_metadata, safe_attachment_name,
+    ExportBundle, ExportContact, ExportFolder, ExportFormat, ExportMessage, ExportSession,
+    ExportSocialSession,
+};
+use crate::db::{self, ChatMessage, DbState};
+use crate::social_db::SocialDbState;
+use base64::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+/// 导出范围
+#[derive(Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ExportScope {
+    Session { id: String },
+    Folder { id: String },
+    SocialSession { id: i64 },
+    All,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExportSummary {
+    pub path: String,
+    pub sessions: usize,
+    pub messages: usize,
+    pub attachments: usize,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImportSummary {
+    pub folders: usize,
+    pub sessions: usize,
+    #[serde(rename = "socialSessions")]
+    pub social_sessions: usize,
+    pub messages: usize,
+}
+
+fn parse_id(id: &str) -> Result<i64, String> {
+    id.parse::<i64>()
+        .map_err(|_| format!("无效的 ID 格式: {}", id))
+}
+
+fn to_export_message(m: ChatMessage, include_attachments: bool) -> ExportMessage {
+    let attachments = if include_attachments {
+        load_attachments(m.file_metadata.as_deref())
+    } else {
+        Vec::new()
+    };
+    ExportMessage {
+        id: m.id,
+        parent_id: m.parent_id,
+        role: m.role,
+        content: m.content,
+        reasoning_content: m.reasoning_content,
+        file_metadata: m.file_metadata,
+        search_metadata: m.search_metadata,
+        model: m.model,
+        provider: m.provider,
+        created_at: m.created_at,
+        attachments,
+    }
+}
+
+pub(crate) fn collect_chat_sessions(
+    conn: &Connection,
+    scope: &ExportScope,
+    include_attachments: bool,
+    bundle: &mut ExportBundle,
+) -> Result<(), String> {
+    let all_sessions = db::get_sessions(conn).map_err(|e| e.to_string())?;
+    let all_folders = db::get_folders(conn).map_err(|e| e.to_string())?;
+
+    let sessions: Vec<db::ChatSession> = match scope {
+        ExportScope::Session { id } => {
+            let id = parse_id(id)?;
+            let session = db::get_session(conn, id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("会话不存在: {}", id))?;
+            vec![session]
+        }
+        ExportScope::Folder { id } => {
+            let id = parse_id(id)?;
+            if !all_folders.iter().any(|f| f.id == id) {
+                return Err(format!("文件夹不存在: {}", id));
+            }
+            all_sessions
+                .into_iter()
+                .filter(|s| s.folder_id == Some(id))
+                .collect()
+        }
+        ExportScope::All => all_sessions,
+        ExportScope::SocialSession { .. } => Vec::new(),
+    };
+
+    // 只导出被引用到的文件夹，保证导入时能重建归属
+    for folder in all_folders {
+        if sessions.iter().any(|s| s.folder_id == Some(folder.id)) {
+            bundle.folders.push(ExportFolder {
+                id: folder.id,
+                name: folder.name,
+                sort_order: folder.sort_order,
+                is_collapsed: folder.is_collapsed,
+            });
+        }
+    }
+
+    for s in sessions {
+        let messages = db::get_messages(conn, s.id)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|m| to_export_message(m, include_attachments))
+            .collect();
+        bundle.sessions.push(ExportSession {
+            id: s.id,
+            title: s.title,
+            folder_id: s.folder_id,
+            sort_order: s.sort_order,
+            updated_at: Some(s.updated_at),
+            preset_id: s.preset_id,
+            model_id: s.model_id,
+            system_prompt: s.system_prompt,
+            generation_params: s.generation_params,
+            messages,
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn collect_social_sessions(
+    conn: &Connection,
+    scope: &ExportScope,
+    include_attachments: bool,
+    bundle: &mut ExportBundle,
+) -> Result<(), String> {
+    let session_ids: Vec<i64> = match scope {
+        ExportScope::SocialSession { id } => vec![*id],
+        ExportScope::All => {
+            let mut stmt = conn
+                .prepare("SELECT id FROM social_sessions ORDER BY updated_at DESC")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, i64>(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        }
+        _ => Vec::new(),
+    };
+
+    for session_id in session_ids {
+        let (contact_id, title, created_at, updated_at): (i64, String, Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT contact_id, title, created_at, updated_at FROM social_sessions WHERE id = ?1",
+                params![session_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("社交会话不存在: {}", session_id))?;
+
+        let contact = conn
+            .query_row(
+                "SELECT name, avatar, prompt, model, provider, remark FROM contacts WHERE id = ?1",
+                params![contact_id],
+                |row| {
+                    Ok(ExportContact {
+                        name: row.get(0)?,
+                        avatar: row.get(1)?,
+                        prompt: row.get(2)?,
+                        model: row.get(3)?,
+                        provider: row.get(4)?,
+                        remark: row.get(5)?,
+                    })
+                },
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut stmt = conn
+            .prepare("SELECT role, content, file_metadata, created_at FROM social_messages WHERE session_id = ?1 ORDER BY id ASC")
+            .map_err(|e| e.to_string())?;
+        let messages = stmt
+            .query_map(params![session_id], |row| {
+                let file_metadata: Option<String> = row.get(2)?;
+                let attachments = if include_attachments {
+                    load_attachments(file_metadata.as_deref())
+                } else {
+                    Vec::new()
+                };
+                Ok(ExportMessage {
+                    id: None,
+                    parent_id: None,
+                    role: row.get(0)?,
+                    content: row.get(1)?,
+                    reasoning_content: None,
+                    file_metadata,
+                    search_metadata: None,
+                    model: None,
+                    provider: None,
+                    created_at: row.get(3)?,
+                    attachments,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        bundle.social_sessions.push(ExportSocialSession {
+            id: session_id,
+            title,
+            contact,
+            created_at,
+            updated_at,
+            messages,
+        });
+    }
+    Ok(())
+}
+
+/// Markdown 导出时把附件复制到目标文件旁的 `<文件名>_attachments` 目录
+/// 复制顺序必须与 render_markdown 中的链接编号一致
+fn copy_markdown_attachments(bundle: &ExportBundle, dir: &Path) -> Result<usize, String> {
+    let mut index = 0usize;
+    let mut copied = 0usize;
+    let all_messages = bundle
+        .sessions
+        .iter()
+        .flat_map(|s| s.messages.iter())
+        .chain(
+            bundle
+                .social_sessions
+                .iter()
+                .flat_map(|s| s.messages.iter()),
+        );
+
+    for m in all_messages {
+        for f in parse_file_metadata(m.file_metadata.as_deref()) {
+            index += 1;
+            if !Path::new(&f.path).exists() {
+                println!("⚠️ [导出] 附件缺失，跳过: {}", f.path);
+                continue;
+            }
+            if copied == 0 {
+                fs::create_dir_all(dir).map_err(|e| format!("无法创建附件目录: {}", e))?;
+            }
+            let file_name = markdown_attachment_link("", index, &f.name);
+            let target = dir.join(file_name.trim_start_matches('/'));
+            fs::copy(&f.path, &target).map_err(|e| format!("复制附件失败 {}: {}", f.path, e))?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+#[tauri::command]
+pub fn export_chats(
+    scope: ExportScope,
+    format: String,
+    target_path: String,
+    include_attachments: bool,
+    state: State<DbState>,
+    social_state: State<SocialDbState>,
+) -> Result<ExportSummary, String> {
+    let format = ExportFormat::parse(&format)?;
+    // Markdown 的附件以文件形式复制，不需要把内容读进内存
+    let embed_attachments = include_attachments && format != ExportFormat::Markdown;
+
+    let mut bundle = ExportBundle::new();
+    {
+        let conn = state.0.lock().unwrap();
+        collect_chat_sessions(&conn, &scope, embed_attachments, &mut bundle)?;
+    }
+    {
+        let conn = social_state.0.lock().map_err(|e| e.to_string())?;
+        collect_social_sessions(&conn, &scope, embed_attachments, &mut bundle)?;
+    }
+
+    write_export(
+        &bundle,
+        format,
+        &PathBuf::from(&target_path),
+        include_attachments,
+    )
+}
+
+/// 按格式渲染并写入导出文件 (Markdown 附件复制到同级目录)
+pub(crate) fn write_export(
+    bundle: &ExportBundle,
+    format: ExportFormat,
+    target: &Path,
+    include_attachments: bool,
+) -> Result<ExportSummary, String> {
+    let mut attachments = bundle
+        .sessions
+        .iter()
+        .flat_map(|s| s.messages.iter())
+        .chain(
+            bundle
+                .social_sessions
+                .iter()
+                .flat_map(|s| s.messages.iter()),
+        )
+        .map(|m| {
+            m.attachments
+                .iter()
+                .filter(|a| a.data_base64.is_some())
+                .count()
+        })
+        .sum::<usize>();
+
+    let output = match format {
+        ExportFormat::Json => chat_export::render_json(bundle)?,
+        ExportFormat::Html => chat_export::render_html(bundle),
+        ExportFormat::Markdown => {
+            if include_attachments {
+                let stem = target
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "export".to_string());
+                let dir_name = format!("{}_attachments", stem);
+                let dir = target
+                    .parent()
+                    .unwrap_or_else(|| Path::new("."))
+                    .join(&dir_name);
+                attachments = copy_markdown_attachments(bundle, &dir)?;
+                chat_export::render_markdown(bundle, Some(&dir_name))
+            } else {
+                chat_export::render_markdown(bundle, None)
+            }
+        }
+    };
+
+    if let Some(parent) = target.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+    }
+    fs::write(target, output).map_err(|e| format!("写入导出文件失败: {}", e))?;
+
+    let summary = ExportSummary {
+        path: target.to_string_lossy().to_string(),
+        sessions: bundle.sessions.len() + bundle.social_sessions.len(),
+        messages: bundle.message_count(),
+        attachments,
+    };
+    println!(
+        "📦 [导出] {} 个会话 / {} 条消息 -> {}",
+        summary.sessions, summary.messages, summary.path
+    );
+    Ok(summary)
+}
+
+/// 把导出文件中内嵌的附件写回本地，并返回重写路径后的 file_metadata
+fn restore_attachments(msg: &ExportMessage, upload_dir: &Path) -> Option<String> {
+    if msg.attachments.is_empty() {
+        return msg.file_metadata.clone();
+    }
+
+    let mut restored: HashMap<String, String> = HashMap::new();
+    for a in &msg.attachments {
+        let Some(data) = a.data_base64.as_deref() else {
+            continue;
+        };
+        let Ok(bytes) = BASE64_STANDARD.decode(data) else {
+            continue;
+        };
+        if fs::create_dir_all(upload_dir).is_err() {
+            return msg.file_metadata.clone();
+        }
+        let target = upload_dir.join(format!(
+            "{}_{}",
+            restored.len() + 1,
+            safe_attachment_name(&a.name)
+        ));
+        if fs::write(&target, bytes).is_ok() {
+            restored.insert(a.path.clone(), target.to_string_lossy().to_string());
+        }
+    }
+
+    let mut entries: Vec<serde_json::Value> = msg
+        .file_metadata
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or_default();
+    for entry in entries.iter_mut() {
+        if let Some(new_path) = entry["path"].as_str().and_then(|p| restored.get(p)) {
+            entry["path"] = json!(new_path);
+        }
+    }
+    serde_json::to_string(&entries).ok()
+}
+
+fn import_chat_sessions(
+    conn: &mut Connection,
+    bundle: &ExportBundle,
+    upload_dir: &Path,
+    summary: &mut ImportSummary,
+) -> Result<(), String> {
+    let (session_base, folder_base) = db::max_sort_orders(conn).map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    // 导入的内容排在现有内容之后，内部保持原有相对顺序
+    let mut folder_map: HashMap<i64, i64> = HashMap::new();
+    let mut folders: Vec<&ExportFolder> = bundle.folders.iter().collect();
+    folders.sort_by_key(|f| f.sort_order);
+    for (i, f) in folders.into_iter().enumerate() {
+        let new_id = db::import_folder(&tx, &f.name, folder_base + 1 + i as i32, f.is_collapsed)
+            .map_err(|e| e.to_string())?;
+        folder_map.insert(f.id, new_id);
+        summary.folders += 1;
+    }
+
+    let mut sessions: Vec<&ExportSession> = bundle.sessions.iter().collect();
+    sessions.sort_by_key(|s| s.sort_order);
+    for (i, s) in sessions.into_iter().enumerate() {
+        let folder_id = s.folder_id.and_then(|id| folder_map.get(&id).copied());
+        let session_id = db::import_session(
+            &tx,
+            folder_id,
+            &s.title,
+            session_base + 1 + i as i32,
+            s.updated_at.as_deref(),
+            s.preset_id.as_deref(),
+            s.model_id.as_deref(),
+            s.system_prompt.as_deref(),
+            s.generation_params.as_deref(),
+        )
+        .map_err(|e| e.to_string())?;
+
+        // 对比模式的 parentId 指向导出时的消息 ID，需映射到新 ID
+        let mut message_map: HashMap<i64, i64> = HashMap::new();
+        for m in &s.messages {
+            let file_metadata = restore_attachments(m, upload_dir);
+            let new_id = db::import_message(
+                &tx,
+                &ChatMessage {
+                    id: None,
+                    session_id,
+                    model: m.model.clone(),
+                    provider: m.provider.clone(),
+                    role: m.role.clone(),
+                    content: m.content.clone(),
+                    reasoning_content: m.reasoning_content.clone(),
+                    file_metadata,
+                    search_metadata: m.search_metadata.clone(),
+                    created_at: m.created_at.clone(),
+                    parent_id: m.parent_id.and_then(|id| message_map.get(&id).copied()),
+                },
+            )
+            .map_err(|e| e.to_string())?;
+            if let Some(old_id) = m.id {
+                message_map.insert(old_id, new_id);
+            }
+            summary.messages += 1;
+        }
+        summary.sessions += 1;
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn import_social_sessions(
+    conn: &mut Connection,
+    bundle: &ExportBundle,
+    upload_dir: &Path,
+    summary: &mut ImportSummary,
+) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    for s in &bundle.social_sessions {
+        // 同名联系人直接复用，避免重复创建角色
+        let existing: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM contacts WHERE name = ?1 ORDER BY id ASC LIMIT 1",
+                params![s.contact.name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let contact_id = match existing {
+            Some(id) => id,
+            None => {
+                let c = &s.contact;
+                tx.execute(
+                    "INSERT INTO contacts (name, avatar, prompt, model, provider, remark) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![c.name, c.avatar, c.prompt, c.model, c.provider, c.remark],
+                )
+                .map_err(|e| e.to_string())?;
+                tx.last_insert_rowid()
+            }
+        };
+
+        tx.execute(
+            "INSERT INTO social_sessions (contact_id, title, created_at, updated_at)
+             VALUES (?1, ?2, COALESCE(?3, CURRENT_TIMESTAMP), COALESCE(?4, CURRENT_TIMESTAMP))",
+            params![contact_id, s.title, s.created_at, s.updated_at],
+        )
+        .map_err(|e| e.to_string())?;
+        let session_id = tx.last_insert_rowid();
+
+        for m in &s.messages {
+            let file_metadata = restore_attachments(m, upload_dir);
+            tx.execute(
+                "INSERT INTO social_messages (contact_id, session_id, role, content, file_metadata, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, COALESCE(?6, CURRENT_TIMESTAMP))",
+                params![contact_id, session_id, m.role, m.content, file_metadata, m.created_at],
+            )
+            .map_err(|e| e.to_string())?;
+            summary.messages += 1;
+        }
+        summary.social_sessions += 1;
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn import_chats(
+    app: tauri::AppHandle,
+    source_path: String,
+    state: State<DbState>,
+    social_state: State<SocialDbState>,
+) -> Result<ImportSummary, String> {
+    let raw = fs::read_to_string(&source_path).map_err(|e| format!("读取导入文件失败: {}", e))?;
+    let bundle = chat_export::parse_import(&raw)?;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let upload_dir = crate::commands::config_cmd::resolve_config_dir(&app)
+        .join("upload")
+        .join(format!("import_{}", timestamp));
+
+    let mut summary = ImportSummary::default();
+    {
+        let mut conn = state.0.lock().unwrap();
+        import_chat_sessions(&mut conn, &bundle, &upload_dir, &mut summary)?;
+    }
+    if !bundle.social_sessions.is_empty() {
+        let mut conn = social_state.0.lock().map_err(|e| e.to_string())?;
+        import_social_sessions(&mut conn, &bundle, &upload_dir, &mut summary)?;
+    }
+
+    println!(
+        "📥 [导入] {} 个文件夹 / {} 个会话 / {} 个社交会话 / {} 条消息",
+        summary.folders, summary.sessions, summary.social_sessions, summary.messages
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_export::ExportAttachment;
+
+    #[test]
+    fn test_import_rejects_hostile_attachment_names() {
+        let root = std::env::temp_dir().join(format!("goge_import_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let upload_dir = root.join("uploads");
+
+        let hostile = [r"x\..\..\..\evil.exe", "../../evil.sh", "..", "C:evil.bat"];
+        let attachments: Vec<ExportAttachment> = hostile
+            .iter()
+            .enumerate()
+            .map(|(i, name)| ExportAttachment {
+                name: name.to_string(),
+                path: format!("/old/{}", i),
+                mime: String::new(),
+                data_base64: Some(BASE64_STANDARD.encode("payload")),
+            })
+            .collect();
+        let metadata: Vec<serde_json::Value> = attachments
+            .iter()
+            .map(|a| json!({ "name": a.name, "path": a.path }))
+            .collect();
+        let msg = ExportMessage {
+            id: None,
+            parent_id: None,
+            role: "user".into(),
+            content: String::new(),
+            reasoning_content: None,
+            file_metadata: Some(serde_json::to_string(&metadata).unwrap()),
+            search_metadata: None,
+            model: None,
+            provider: None,
+            created_at: None,
+            attachments,
+        };
+
+        let rewritten = restore_attachments(&msg, &upload_dir).unwrap();
+        let entries: Vec<serde_json::Value> = serde_json::from_str(&rewritten).unwrap();
+        let mut names: Vec<String> = fs::read_dir(&upload_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["1_evil.exe", "2_evil.sh", "3_attachment", "4_Cevil.bat"]
+        );
+        for entry in &entries {
+            let path = Path::new(entry["path"].as_str().unwrap());
+            assert_eq!(path.parent(), Some(upload_dir.as_path()));
+        }
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_import_remaps_compare_parent_ids() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        db::init_db(&conn).unwrap();
+        // 先占用几个 ID，确保导入后的新 ID 与导出时不同
+        let existing = db::create_session(&conn, "旧会话", None, None, None).unwrap();
+        for _ in 0..5 {
+            conn.execute(
+                "INSERT INTO messages (session_id, role, content) VALUES (?1, 'user', 'x')",
+                params![existing],
+            )
+            .unwrap();
+        }
+
+        let mut bundle = ExportBundle::new();
+        let messages = vec![
+            ExportMessage {
+                id: Some(1),
+                parent_id: None,
+                role: "user".into(),
+                content: "问题".into(),
+                reasoning_content: None,
+                file_metadata: None,
+                search_metadata: None,
+                model: None,
+                provider: None,
+                created_at: None,
+                attachments: Vec::new(),
+            },
+            ExportMessage {
+                id: Some(2),
+                parent_id: Some(1),
+                role: "assistant".into(),
+                content: "回答 A".into(),
+                reasoning_content: None,
+                file_metadata: None,
+                search_metadata: None,
+                model: None,
+                provider: None,
+                created_at: None,
+                attachments: Vec::new(),
+            },
+            ExportMessage {
+                id: Some(3),
+                parent_id: Some(1),
+                role: "assistant".into(),
+                content: "回答 B".into(),
+                reasoning_content: None,
+                file_metadata: None,
+                search_metadata: None,
+                model: None,
+                provider: None,
+                created_at: None,
+                attachments: Vec::new(),
+            },
+        ];
+        bundle.sessions.push(ExportSession {
+            id: 1,
+            title: "对比".into(),
+            folder_id: None,
+            sort_order: 0,
+            updated_at: None,
+            preset_id: None,
+            model_id: None,
+            system_prompt: None,
+            generation_params: None,
+            messages,
+        });
+
+        let mut summary = ImportSummary::default();
+        let upload_dir = std::env::temp_dir().join("goge_import_unused");
+        import_chat_sessions(&mut conn, &bundle, &upload_dir, &mut summary).unwrap();
+
+        let session = db::get_sessions(&conn)
+            .unwrap()
+            .into_iter()
+            .find(|s| s.title == "对比")
+            .unwrap();
+        let imported = db::get_messages(&conn, session.id).unwrap();
+        let user_id = imported[0].id;
+        assert_ne!(user_id, Some(1));
+        assert_eq!(imported[0].parent_id, None);
+        assert_eq!(imported[1].parent_id, user_id);
+        assert_eq!(imported[2].parent_id, user_id);
+    }
+}
@@ -1,15 +1,170 @@
-use crate::behavior_engine::{BehaviorEngine, SessionContext};
+use crate::behavior_engine::{BehaviorEngine, ReplyStream, SessionContext, StreamOpening};
 use crate::behavior_scheduler::MessageScheduler;
 use crate::commands::config_cmd;
-use crate::models::{ChatRequest, Message};
+use crate::immersive_settings::BehaviorAction;
+use crate::models::{ChatRequest, GenerationParams, Message};
 use crate::social_db::SocialDbState;
 use futures_util::StreamExt;
 use std::sync::Arc;
 use tauri::{command, AppHandle, Emitter, Manager, State};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// 社交模式使用全局预设 (globalPresetId)，联系人指定的提供商/模型视为显式参数
+pub(crate) fn resolve_contact_generation(
+    config: &config_cmd::AppConfig,
+    contact_provider: Option<String>,
+    contact_model: Option<String>,
+) -> crate::generation_config::ResolvedGeneration {
+    crate::generation_config::resolve_generation(
+        config,
+        Some(&crate::generation_config::SessionOverrides {
+            preset_id: Some(config.global_preset_id.clone()),
+            ..Default::default()
+        }),
+        contact_provider,
+        contact_model,
+        GenerationParams::default(),
+        GenerationParams {
+            temperature: Some(0.8),
+            max_tokens: Some(1024),
+            ..Default::default()
+        },
+    )
+}
+
+/// 联系人的系统提示词 (联系人未设置时回退到全局预设)
+///
+/// 渲染模板变量 ({{user.nickname}}、{{contact.name}}、{{mood}} 等)，
+/// 模板没有引用 {{activity}} 时把当前活动追加到末尾
+pub(crate) fn contact_system_prompt(
+    conn: &rusqlite::Connection,
+    config: &config_cmd::AppConfig,
+    contact_prompt: Option<String>,
+    contact_id: i64,
+    session_id: i64,
+    session_context: &SessionContext,
+) -> Option<String> {
+    let prompt = contact_prompt
+        .filter(|p| !p.trim().is_empty())
+        .or_else(|| {
+            // 读取全局预设
+            let global_preset_id = &config.global_preset_id;
+            config
+                .presets
+                .as_array()
+                .and_then(|arr| {
+                    arr.iter()
+                        .find(|p| p["id"].as_str() == Some(global_preset_id))
+                })
+                .and_then(|p| p["systemPrompt"].as_str())
+                .map(|s| s.to_string())
+        })
+        .filter(|p| !p.trim().is_empty())?;
+
+    let mut template_ctx = crate::prompt_template::TemplateContext::new();
+    crate::prompt_template::load_user(&mut template_ctx, conn);
+    let _ = crate::prompt_template::load_social(
+        &mut template_ctx,
+        conn,
+        contact_id,
+        Some(session_id),
+    );
+    template_ctx.with_state(
+        session_context.mood.as_deref(),
+        session_context.busy_level,
+        session_context.interest_level,
+    );
+    template_ctx.with_activity(session_context.activity.as_ref());
+    let resolver = crate::prompt_template::library_resolver(&config.prompt_library);
+    let rendered = crate::prompt_template::render_or_raw(&prompt, &template_ctx, &resolver);
+    let mut prompt = rendered.text;
+    if let Some(ref activity) = session_context.activity {
+        if !rendered.used.contains("activity") {
+            prompt = format!("{}\n\n{}", prompt, activity.prompt_line());
+        }
+    }
+    if let Some(hint) = crate::segmenter::delimiter_prompt(&config.immersive_mode) {
+        prompt = format!("{}\n\n{}", prompt, hint);
+    }
+    Some(prompt)
+}
+
+pub(crate) fn system_message(content: String) -> Message {
+    Message {
+        id: None,
+        model: None,
+        role: "system".to_string(),
+        content,
+        reasoning_content: None,
+        file_metadata: None,
+        search_metadata: None,
+        provider: None,
+        mode: None,
+        role_id: None,
+        parent_id: None,
+    }
+}
+
+/// 沉浸模式回复的处理方式
+enum ReplyMode {
+    /// 收集完整回复后再决策
+    Collect,
+    /// 流水线模式下决定不立即回复，完整回复生成后再生成行为链
+    Ignored(BehaviorAction),
+    /// 流水线模式：边生成边发送
+    Pipelined(Box<ReplyPipeline>),
+}
+
+impl ReplyMode {
+    fn push(&mut self, chunk: &str) {
+        if let ReplyMode::Pipelined(pipeline) = self {
+            pipeline.push(chunk);
+        }
+    }
+
+    fn cancel_token(&self) -> Option<CancellationToken> {
+        match self {
+            ReplyMode::Pipelined(pipeline) => Some(pipeline.token.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// 把生成中的回复逐段交给调度器执行
+struct ReplyPipeline {
+    engine: BehaviorEngine,
+    stream: Box<ReplyStream>,
+    batches: mpsc::UnboundedSender<Vec<BehaviorAction>>,
+    token: CancellationToken,
+    started: std::time::Instant,
+}
+
+impl ReplyPipeline {
+    fn elapsed_ms(&self) -> u32 {
+        self.started.elapsed().as_millis() as u32
+    }
+
+    fn push(&mut self, chunk: &str) {
+        let elapsed = self.elapsed_ms();
+        for batch in self.engine.stream_chunk(&mut self.stream, chunk, elapsed) {
+            let _ = self.batches.send(batch);
+        }
+    }
+
+    /// 回复生成完毕：送入剩余的段并关闭通道
+    fn finish(self) {
+        let elapsed = self.elapsed_ms();
+        for batch in self.engine.finish_stream(*self.stream, elapsed) {
+            let _ = self.batches.send(batch);
+        }
+    }
+}
 
 /// 发送沉浸式社交消息
 ///
 /// 如果沉浸式模式启用,将使用行为引擎生成行为链并异步执行
+/// 启用流水线回复时,回复边生成边分段发送,不必等完整回复
 /// 否则直接保存消息
 #[command]
 pub async fn send_social_message_immersive(
@@ -25,7 +180,7 @@ pub async fn send_social_message_immersive(
 
     // 2. 加载配置
     let config = config_cmd::load_config(app.clone()).await?;
-    let settings = config.immersive_mode;
+    let settings = config.immersive_mode.clone();
 
     // 3. 检查行为模拟是否启用 (注意: 这里只决定是否启用延迟/拆分等行为)
     // 即使关闭了行为模拟,只要在社交模式下,我们仍然要在这里处理 AI 调用
@@ -43,6 +198,7 @@ pub async fn send_social_message_immersive(
         mood: None,
         busy_level: None,
         interest_level: None,
+        activity: None,
     };
 
     // 增加消息计数
@@ -54,18 +210,21 @@ pub async fn send_social_message_immersive(
     // 检查是否启用状态追踪
     if let Some(ref state_config) = settings.behaviors.character_state_config {
         if state_config.enabled {
-            let analyzer = crate::character_state::StateAnalyzer::new();
-
             // 检查是否应该触发分析
-            let should_analyze = analyzer
-                .should_analyze(contact_id, session_id, state_config, &db_state)
-                .await?;
+            let should_analyze = crate::character_state::StateAnalyzer::should_analyze(
+                contact_id,
+                session_id,
+                state_config,
+                &db_state,
+            )
+            .await?;
 
+            let mut analysis = None;
             if should_analyze {
                 println!("[状态] 触发分析...");
 
                 // 获取最近的消息历史 (在单独的作用域中,确保锁被释放)
-                let messages = {
+                let (messages, contact_provider, contact_model) = {
                     let conn = db_state.0.lock().map_err(|e| e.to_string())?;
                     let mut stmt = conn
                         .prepare(
@@ -75,56 +234,77 @@ pub async fn send_social_message_immersive(
                         )
                         .map_err(|e| e.to_string())?;
 
-                    let messages: Vec<(String, String)> = stmt
+                    let mut messages: Vec<(String, String)> = stmt
                         .query_map(rusqlite::params![contact_id, session_id], |row| {
                             Ok((row.get(0)?, row.get(1)?))
                         })
                         .map_err(|e| e.to_string())?
                         .collect::<Result<Vec<_>, _>>()
                         .map_err(|e| e.to_string())?;
+                    // 查询按时间倒序取最近 N 条，分析需要正序
+                    messages.reverse();
+
+                    let (provider, model): (Option<String>, Option<String>) = conn
+                        .query_row(
+                            "SELECT provider, model FROM contacts WHERE id = ?1",
+                            rusqlite::params![contact_id],
+                            |row| Ok((row.get(0)?, row.get(1)?)),
+                        )
+                        .unwrap_or((None, None));
 
-                    messages
+                    (messages, provider, model)
                 };
 
-                // 执行状态分析
-                let analysis = analyzer
-                    .analyze_state(contact_id, session_id, messages)
-                    .await?;
-
-                // 保存分析结果
-                crate::social_db::upsert_character_state(
-                    db_state.clone(),
-                    contact_id,
-                    session_id,
-                    analysis.mood.clone(),
-                    analysis.busy_level,
-                    analysis.interest_level,
-                )?;
-
-                // 重置消息计数
+                // 执行状态分析 (使用与回复相同的联系人模型)
+                let resolved = resolve_contact_generation(&config, contact_provider, contact_model);
+                let result = async {
+                    let provider =
+                        crate::provider_config::resolve_provider(&config, &resolved.provider_id)?;
+                    let client = app
+                        .state::<crate::net::HttpClients>()
+                        .for_provider(&provider)?;
+                    crate::character_state::StateAnalyzer::new(client, provider, resolved.model)
+                        .analyze_state(contact_id, session_id, messages)
+                        .await
+                }
+                .await;
+
+                // 无论成功与否都重置消息计数，避免模型不可用时每条消息都重试
                 crate::social_db::reset_message_count(db_state.clone(), contact_id, session_id)?;
 
+                match result {
+                    Ok(result) => {
+                        // 保存分析结果
+                        crate::social_db::upsert_character_state(
+                            db_state.clone(),
+                            contact_id,
+                            session_id,
+                            result.mood.clone(),
+                            result.busy_level,
+                            result.interest_level,
+                        )?;
+                        println!("[状态] 分析已保存");
+                        analysis = Some(result);
+                    }
+                    Err(e) => println!("⚠️ [状态] {}，沿用缓存状态", e),
+                }
+            }
+
+            if let Some(analysis) = analysis {
                 // 更新上下文
                 session_context.mood = Some(analysis.mood);
                 session_context.busy_level = Some(analysis.busy_level);
                 session_context.interest_level = Some(analysis.interest_level);
-
-                println!("[状态] 分析已保存");
             } else {
                 // 从数据库加载现有状态
-                if let Some(state) =
-                    crate::social_db::get_character_state(db_state.clone(), contact_id, session_id)?
-                {
-                    session_context.mood =
-                        state.get("mood").and_then(|v| v.as_str()).map(String::from);
-                    session_context.busy_level = state
-                        .get("busyLevel")
-                        .and_then(|v| v.as_f64())
-                        .map(|f| f as f32);
-                    session_context.interest_level = state
-                        .get("interestLevel")
-                        .and_then(|v| v.as_f64())
-                        .map(|f| f as f32);
+                let cached = {
+                    let conn = db_state.0.lock().map_err(|e| e.to_string())?;
+                    crate::character_state::CharacterState::load(&conn, contact_id, session_id)?
+                };
+                if let Some(state) = cached {
+                    session_context.mood = Some(state.mood);
+                    session_context.busy_level = Some(state.busy_level);
+                    session_context.interest_level = Some(state.interest_level);
 
                     println!(
                         "[状态] 加载缓存: 心情={:?}, 忙碌={:?}, 兴趣={:?}",
@@ -140,30 +320,36 @@ pub async fn send_social_message_immersive(
     // 5. 🤖 调用 AI 获取回答 (内部流式收集)
     println!("[AI] [开始] 请求响应...");
 
-    // A. 获取对话历史 (20条)
+    // A. 获取最近的对话历史 (超出上下文预算的部分稍后在这些消息内由摘要压缩)
+    let history_limit = settings
+        .behaviors
+        .history_limit
+        .unwrap_or(crate::immersive_settings::DEFAULT_HISTORY_LIMIT)
+        .max(1);
     let mut history = {
         let conn = db_state.0.lock().map_err(|e| e.to_string())?;
         let mut stmt = conn
             .prepare(
-                "SELECT role, content FROM social_messages 
+                "SELECT id, role, content FROM social_messages 
                  WHERE session_id = ?1 
-                 ORDER BY id DESC LIMIT 21", // 包含刚刚保存的那条
+                 ORDER BY id DESC LIMIT ?2", // 包含刚刚保存的那条
             )
             .map_err(|e| e.to_string())?;
 
         let messages: Vec<Message> = stmt
-            .query_map(rusqlite::params![session_id], |row| {
+            .query_map(rusqlite::params![session_id, history_limit], |row| {
                 Ok(Message {
-                    id: None,
+                    id: Some(row.get(0)?),
                     model: None,
-                    role: row.get(0)?,
-                    content: row.get(1)?,
+                    role: row.get(1)?,
+                    content: row.get(2)?,
                     reasoning_content: None,
                     file_metadata: None,
                     search_metadata: None,
                     provider: None,
                     mode: None,
                     role_id: None,
+                    parent_id: None,
                 })
             })
             .map_err(|e| e.to_string())?
@@ -182,6 +368,16 @@ pub async fn send_social_message_immersive(
 
     {
         let conn = db_state.0.lock().map_err(|e| e.to_string())?;
+        // 按作息表推算角色当前在做什么 (影响回复节奏，并注入提示词)
+        session_context.activity =
+            crate::routine::contact_activity(&conn, contact_id, chrono::Utc::now());
+        if let Some(ref activity) = session_context.activity {
+            println!(
+                "[作息] {} 正在{} (可用度 {:.2})",
+                activity.local_time, activity.label, activity.availability
+            );
+        }
+
         let contact_info: Result<(Option<String>, Option<String>, Option<String>), _> = conn
             .query_row(
                 "SELECT prompt, provider, model FROM contacts WHERE id = ?1",
@@ -193,74 +389,45 @@ pub async fn send_social_message_immersive(
             contact_provider = provider;
             contact_model = model;
 
-            let final_prompt = if let Some(p) = prompt {
-                if !p.trim().is_empty() {
-                    Some(p)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            // 如果联系人没有提示词, 尝试从全局预设中获取
-            let prompt_to_inject = if let Some(p) = final_prompt {
-                Some(p)
-            } else {
-                // 读取全局预设
-                let global_preset_id = &config.global_preset_id;
-                let presets = config.presets.as_array();
-                presets
-                    .and_then(|arr| {
-                        arr.iter()
-                            .find(|p| p["id"].as_str() == Some(global_preset_id))
-                    })
-                    .and_then(|p| p["systemPrompt"].as_str())
-                    .map(|s| s.to_string())
-            };
-
-            if let Some(prompt) = prompt_to_inject {
-                if !prompt.trim().is_empty() {
-                    // 将系统提示词插入到历史记录的最前面
-                    history.insert(
-                        0,
-                        Message {
-                            id: None,
-                            model: None,
-                            role: "system".to_string(),
-                            content: prompt,
-                            reasoning_content: None,
-                            file_metadata: None,
-                            search_metadata: None,
-                            provider: None,
-                            mode: None,
-                            role_id: None,
-                        },
-                    );
-                    // println!("[Social] Injected system prompt");
-                }
+            if let Some(prompt) = contact_system_prompt(
+                &conn,
+                &config,
+                prompt,
+                contact_id,
+                session_id,
+                &session_context,
+            ) {
+                // 将系统提示词插入到历史记录的最前面
+                history.insert(0, system_message(prompt));
             }
         }
     }
 
     // B. 获取配置 (优先使用联系人配置)
-    let provider_id = contact_provider.unwrap_or_else(|| config.default_provider_id.clone());
-    let model = contact_model.unwrap_or_else(|| config.selected_model_id.clone());
+    let resolved = resolve_contact_generation(&config, contact_provider, contact_model);
+    let provider_id = resolved.provider_id;
+    let model = resolved.model;
+    let params = resolved.params;
 
     println!("[AI] 提供商: {}, 模型: {}", provider_id, model);
 
-    let providers = config.providers.as_array().ok_or("无法读取提供商列表")?;
-    let provider_config = providers
-        .iter()
-        .find(|p| p["id"].as_str() == Some(&provider_id))
-        .ok_or(format!("找不到提供商配置: {}", provider_id))?;
+    let provider = crate::provider_config::resolve_provider(&config, &provider_id)?;
 
-    let api_key = provider_config["apiKey"].as_str().unwrap_or_default();
-    let base_url = provider_config["baseUrl"].as_str().unwrap_or_default();
+    // B.2 上下文窗口管理：历史过长时把早期对话压缩成摘要
+    let history = crate::context_window::fit_to_context(
+        &app,
+        Some((crate::context_window::SessionKind::Social, session_id)),
+        history,
+        crate::context_window::context_limit_for(&provider, &model),
+        params.max_tokens,
+    )
+    .await;
 
     // C. 执行 AI 调用 (内部流式处理)
     // C. 执行 AI 调用 (内部流式处理 + ⚡️ 极致优化：20ms 合批同步)
-    let client = app.state::<reqwest::Client>();
+    let client = app
+        .state::<crate::net::HttpClients>()
+        .for_provider(&provider)?;
     let mut full_content = String::new();
     let mut pending_content = String::new();
     let mut last_emit = std::time::Instant::now();
@@ -293,93 +460,162 @@ pub async fn send_social_message_immersive(
         }
     };
 
-    if provider_id == "gemini" {
-        crate::ai_utils::call_gemini_streaming(
-            &client,
-            api_key,
-            base_url,
-            &model,
-            history,
-            |chunk| {
-                emit_chunk(
-                    &app,
-                    &chunk,
-                    &mut full_content,
-                    &mut pending_content,
-                    &mut last_emit,
-                    &mut emit_count,
-                );
-            },
-        )
-        .await?;
+    // C.2 流水线模式：回复边生成边把写完的段交给调度器，首段写完即可开始发送
+    // (表情规划需要完整回复，启用时按普通流程)
+    let mut reply = if settings.enabled
+        && settings.behaviors.pipelined_replies
+        && !settings.behaviors.expressions.enabled
+    {
+        let engine = BehaviorEngine::new(settings.clone());
+        let opening = engine.open_stream(&session_context);
+        match opening {
+            StreamOpening::Ignore(action) => ReplyMode::Ignored(action),
+            StreamOpening::Reply { stream, opening } => {
+                let (token, batches) = scheduler
+                    .begin_streamed_chain(
+                        session_id,
+                        contact_id,
+                        session_context.clone(),
+                        settings.clone(),
+                    )
+                    .await;
+                if !opening.is_empty() {
+                    let _ = batches.send(opening);
+                }
+                ReplyMode::Pipelined(Box::new(ReplyPipeline {
+                    engine,
+                    stream,
+                    batches,
+                    token,
+                    started: std::time::Instant::now(),
+                }))
+            }
+        }
     } else {
-        // OpenAI 兼容流式处理
-        let payload = ChatRequest {
-            model: model.clone(),
-            messages: history,
-            stream: true,
-            temperature: Some(0.8),
-            max_tokens: Some(1024),
-        };
+        ReplyMode::Collect
+    };
 
-        let base = base_url.trim_end_matches('/');
-        let url = if base.ends_with("/chat/completions") {
-            base.to_string()
-        } else if base.ends_with("/v1") {
-            format!("{}/chat/completions", base)
+    // 行为链被取消 (用户发了新消息或手动取消) 时丢弃请求 future，中止上游连接
+    let cancel_token = reply.cancel_token();
+    let mut stopped = false;
+    let generation = async {
+        if provider.is_gemini() {
+            crate::ai_utils::call_gemini_streaming(
+                &client,
+                &provider,
+                &model,
+                history,
+                &params,
+                |chunk| {
+                    emit_chunk(
+                        &app,
+                        &chunk,
+                        &mut full_content,
+                        &mut pending_content,
+                        &mut last_emit,
+                        &mut emit_count,
+                    );
+                    reply.push(&chunk);
+                },
+            )
+            .await?;
+        } else if provider.kind == crate::provider_config::ProviderKind::Ollama {
+            crate::local_llm::ollama_chat_streaming(
+                &client,
+                &provider,
+                &model,
+                &history,
+                &params,
+                |delta| {
+                    emit_chunk(
+                        &app,
+                        &delta.content,
+                        &mut full_content,
+                        &mut pending_content,
+                        &mut last_emit,
+                        &mut emit_count,
+                    );
+                    reply.push(&delta.content);
+                    !state.stop_flag.load(std::sync::atomic::Ordering::Relaxed)
+                },
+            )
+            .await?;
         } else {
-            format!("{}/v1/chat/completions", base)
-        };
-
-        let response = client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| format!("AI 网络请求失败: {}", e))?;
-
-        if !response.status().is_success() {
-            let err_body = response.text().await.unwrap_or_default();
-            return Err(format!("AI API 错误: {}", err_body));
-        }
+            // OpenAI 兼容流式处理
+            let payload = ChatRequest {
+                model: model.clone(),
+                messages: history,
+                stream: true,
+                params,
+            };
 
-        let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
+            let response = provider
+                .authorize(client.post(provider.chat_completions_url()))
+                .json(&payload)
+                .send()
+                .await
+                .map_err(|e| format!("AI 网络请求失败: {}", e))?;
 
-        while let Some(chunk) = stream.next().await {
-            // ✨ 沉浸模式也支持物理中断
-            if state.stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
-                return Ok(());
+            if !response.status().is_success() {
+                let err_body = response.text().await.unwrap_or_default();
+                return Err(format!("AI API 错误: {}", err_body));
             }
 
-            let chunk = chunk.map_err(|e| e.to_string())?;
-            buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-            while let Some(newline_idx) = buffer.find('\n') {
-                let line = buffer.drain(..=newline_idx).collect::<String>();
-                let line = line.trim();
+            let mut events = std::pin::pin!(crate::stream_decoder::decode(
+                response.bytes_stream(),
+                crate::stream_decoder::SseDecoder::default()
+            ));
 
-                if line.is_empty() || line == "data: [DONE]" {
-                    continue;
+            while let Some(event) = events.next().await {
+                // ✨ 沉浸模式也支持物理中断
+                if state.stop_flag.load(std::sync::atomic::Ordering::Relaxed) {
+                    stopped = true;
+                    return Ok(());
                 }
 
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                        if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                            emit_chunk(
-                                &app,
-                                content,
-                                &mut full_content,
-                                &mut pending_content,
-                                &mut last_emit,
-                                &mut emit_count,
-                            );
-                        }
-                    }
+                let delta = crate::ai_utils::parse_openai_event(&event?)?;
+                if delta.done {
+                    break;
                 }
+                if let Some(content) = delta.content {
+                    emit_chunk(
+                        &app,
+                        &content,
+                        &mut full_content,
+                        &mut pending_content,
+                        &mut last_emit,
+                        &mut emit_count,
+                    );
+                    reply.push(&content);
+                }
+            }
+        }
+        Ok::<(), String>(())
+    };
+
+    let cancelled = match cancel_token {
+        Some(token) => tokio::select! {
+            result = generation => {
+                result?;
+                false
             }
+            _ = token.cancelled() => true,
+        },
+        None => {
+            generation.await?;
+            false
         }
+    };
+    if cancelled {
+        println!("[AI] [取消] 行为链已取消，中止生成");
+        return Ok(());
+    }
+    if stopped {
+        // 流水线中已经送入调度器的段也一并停止
+        if matches!(reply, ReplyMode::Pipelined(_)) {
+            scheduler.cancel_session_behaviors(session_id).await;
+        }
+        return Ok(());
     }
 
     // 🚀 [收尾工作]：发送剩余内容和结束标记
@@ -407,8 +643,58 @@ pub async fn send_social_message_immersive(
     println!("[AI] [完成] 响应收集完成 ({} 字符)", ai_response.len());
 
     // 6. 使用行为引擎生成行为链 (针对 AI 的回答)
-    let engine = BehaviorEngine::new(settings.clone());
-    let chain = engine.decide(&ai_response, &session_context);
+    let chain = match reply {
+        ReplyMode::Pipelined(pipeline) => {
+            // 流水线模式：剩余的段送入调度器即完成
+            pipeline.finish();
+            return Ok(());
+        }
+        ReplyMode::Ignored(action) => {
+            BehaviorEngine::new(settings.clone()).ignored_chain(action, &ai_response)
+        }
+        ReplyMode::Collect => {
+            let engine = BehaviorEngine::new(settings.clone());
+            engine.decide(&ai_response, &session_context)
+        }
+    };
+
+    // 6.2 由模型选择表情回应、引用回复与表情包 (失败时按普通行为链发送)
+    let chain = if settings.enabled && settings.behaviors.expressions.enabled {
+        let context = {
+            let conn = db_state.0.lock().map_err(|e| e.to_string())?;
+            crate::expression::recent_messages(&conn, session_id).and_then(|recent| {
+                Ok((recent, crate::expression::list_stickers(&conn, contact_id)?))
+            })
+        };
+        let plan = match context {
+            Ok((recent, stickers)) => {
+                crate::expression::plan_expression(
+                    &client,
+                    &provider,
+                    &model,
+                    &recent,
+                    &ai_response,
+                    &stickers,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match plan {
+            Ok(plan) => {
+                if !plan.is_empty() {
+                    println!("[表达] {:?}", plan);
+                }
+                plan.apply(chain)
+            }
+            Err(e) => {
+                println!("⚠️ [表达] {}，按普通行为链发送", e);
+                chain
+            }
+        }
+    } else {
+        chain
+    };
 
     // 7. 异步执行行为链
     scheduler
@@ -418,7 +704,6 @@ pub async fn send_social_message_immersive(
             chain,
             session_context,
             settings,
-            app.clone(),
         )
         .await?;
 
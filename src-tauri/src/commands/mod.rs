@@ -1,9 +1,12 @@
 pub mod ai;
 pub mod asr_cmd;
+pub mod compare_cmd;
 pub mod config_cmd; // 【新增】
 pub mod db_cmd;
+pub mod export_cmd;
 pub mod file_cmd;
 pub mod immersive_cmd;
 pub mod memory_cmd;
+pub mod prompt_cmd;
 pub mod search;
 pub mod tts_cmd;
@@ -0,0 +1,81 @@
+use crate::commands::config_cmd;
+use crate::db::{self, DbState};
+use crate::prompt_template::{self, TemplateContext, TemplateError};
+use crate::social_db::SocialDbState;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+/// 模板预览结果 (渲染失败时 error 带行列号，供编辑器标注)
+#[derive(Serialize, Debug)]
+pub struct TemplatePreview {
+    pub output: Option<String>,
+    pub error: Option<TemplateError>,
+    /// 模板中实际引用到的变量
+    pub variables: Vec<String>,
+}
+
+/// 预览提示词模板
+/// session_kind: "chat" | "social"，未指定会话时仅填充用户与时间变量
+#[tauri::command]
+pub async fn preview_prompt_template(
+    app: AppHandle,
+    template: String,
+    session_kind: Option<String>,
+    session_id: Option<i64>,
+    state: State<'_, DbState>,
+    social_state: State<'_, SocialDbState>,
+) -> Result<TemplatePreview, String> {
+    let config = config_cmd::load_config(app).await?;
+
+    let mut ctx = TemplateContext::new();
+    {
+        let social = social_state.0.lock().map_err(|e| e.to_string())?;
+        prompt_template::load_user(&mut ctx, &social);
+
+        match (session_kind.as_deref(), session_id) {
+            (Some("social"), Some(id)) => {
+                let contact_id: Option<i64> = social
+                    .query_row(
+                        "SELECT contact_id FROM social_sessions WHERE id = ?1",
+                        params![id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(|e| e.to_string())?;
+                let contact_id = contact_id.ok_or(format!("找不到社交会话: {}", id))?;
+                prompt_template::load_social(&mut ctx, &social, contact_id, Some(id))
+                    .map_err(|e| e.to_string())?;
+            }
+            (Some("chat"), Some(id)) => {
+                let conn = state.0.lock().map_err(|e| e.to_string())?;
+                let session = db::get_session(&conn, id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or(format!("找不到会话: {}", id))?;
+                ctx.with_session(session.id, &session.title);
+            }
+            (Some(kind), Some(_)) if kind != "chat" && kind != "social" => {
+                return Err(format!("未知的会话类型: {}", kind));
+            }
+            _ => {}
+        }
+    }
+
+    let resolver = prompt_template::library_resolver(&config.prompt_library);
+    Ok(match prompt_template::render(&template, &ctx, &resolver) {
+        Ok(rendered) => {
+            let mut variables: Vec<String> = rendered.used.into_iter().collect();
+            variables.sort();
+            TemplatePreview {
+                output: Some(rendered.text),
+                error: None,
+                variables,
+            }
+        }
+        Err(e) => TemplatePreview {
+            output: None,
+            error: Some(e),
+            variables: Vec::new(),
+        },
+    })
+}
@@ -1,6 +1,5 @@
-use reqwest::Client;
+use crate::net::{HttpClients, NetworkSettings};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchResult {
@@ -45,7 +44,11 @@ struct TopicItem {
 use regex::Regex;
 use scraper::{Html, Selector};
 
+/// 搜索引擎需要浏览器 UA 才会返回正常页面 (全局网络设置中显式配置的 UA 优先)
+const BROWSER_UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/121.0.0.0 Safari/537.36";
+
 pub async fn perform_search(
+    clients: &HttpClients,
     _instance_url: &str,
     query: &str,
     provider: &str,
@@ -58,10 +61,10 @@ pub async fn perform_search(
             "🇨🇳 [SEARCH] 检测到中文，切换至 Bing 搜索: {}",
             augmented_query
         );
-        perform_bing_search(&augmented_query).await
+        perform_bing_search(clients, &augmented_query).await
     } else {
         println!("🌐 [SEARCH] 使用默认 DuckDuckGo 搜索: {}", augmented_query);
-        perform_duckduckgo_search(&augmented_query).await
+        perform_duckduckgo_search(clients, &augmented_query).await
     }
 }
 
@@ -89,11 +92,14 @@ fn is_chinese(query: &str) -> bool {
     re.is_match(query)
 }
 
-async fn perform_bing_search(query: &str) -> Result<Vec<SearchResult>, String> {
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/121.0.0.0 Safari/537.36 Edg/121.0.0.0")
-        .build()
-        .map_err(|e| e.to_string())?;
+async fn perform_bing_search(
+    clients: &HttpClients,
+    query: &str,
+) -> Result<Vec<SearchResult>, String> {
+    let client = clients.global_with_defaults(&NetworkSettings {
+        user_agent: Some(format!("{} Edg/121.0.0.0", BROWSER_UA)),
+        ..Default::default()
+    })?;
 
     // 增加 count=20 参数以获取更多候选结果用于过滤
     let url = format!(
@@ -200,12 +206,15 @@ async fn perform_bing_search(query: &str) -> Result<Vec<SearchResult>, String> {
     Ok(final_results)
 }
 
-async fn perform_duckduckgo_search(query: &str) -> Result<Vec<SearchResult>, String> {
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/121.0.0.0 Safari/537.36")
-        .timeout(Duration::from_secs(15))
-        .build()
-        .map_err(|e| e.to_string())?;
+async fn perform_duckduckgo_search(
+    clients: &HttpClients,
+    query: &str,
+) -> Result<Vec<SearchResult>, String> {
+    let client = clients.global_with_defaults(&NetworkSettings {
+        user_agent: Some(BROWSER_UA.to_string()),
+        timeout_secs: Some(15),
+        ..Default::default()
+    })?;
 
     // 第一步：尝试 DuckDuckGo Instant Answer API
     let api_url = format!(
@@ -1,6 +1,6 @@
+use crate::net::{HttpClients, NetworkSettings};
 use futures_util::StreamExt;
 use once_cell::sync::Lazy;
-use reqwest::Client;
 // use serde::Serialize;
 use std::fs;
 use std::sync::Mutex;
@@ -10,7 +10,6 @@ use tokio::io::AsyncWriteExt;
 // --- 配置 ---
 const GENIE_TTS_BASE_URL: &str = "http://127.0.0.1:9880";
 
-static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| Client::new());
 static REQUEST_COUNTER: Lazy<Mutex<u64>> = Lazy::new(|| Mutex::new(0));
 
 // --- 数据结构 ---
@@ -42,6 +41,7 @@ pub async fn generate_tts(
     request_id: u64,
     sequence_id: u32,
     on_event: tauri::ipc::Channel<TtsEvent>, // 🚀 [优化] 使用 Channel 进行二进制直连
+    clients: tauri::State<'_, HttpClients>,
 ) -> Result<String, String> {
     // 检查请求是否过期
     {
@@ -70,7 +70,12 @@ pub async fn generate_tts(
     ];
 
     // 发起 GET 请求
-    let response = HTTP_CLIENT
+    // 流式音频可能持续较久，未显式配置超时时不限制
+    let client = clients.global_with_defaults(&NetworkSettings {
+        timeout_secs: Some(0),
+        ..Default::default()
+    })?;
+    let response = client
         .get(GENIE_TTS_BASE_URL)
         .query(&params)
         .send()
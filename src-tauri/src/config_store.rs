@@ -0,0 +1,394 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 当前配置结构版本 (写入 settings.json 的 configVersion)
+pub const CONFIG_VERSION: u32 = 1;
+/// 保留的历史备份数量
+pub const MAX_BACKUPS: usize = 10;
+/// 参与版本管理与备份的配置文件
+pub const CONFIG_FILES: &[&str] = &[
+    "settings.json",
+    "providers.json",
+    "presets.json",
+    "prompts.json",
+];
+
+const BACKUP_DIR: &str = "backups";
+
+/// 某个配置文件的解析/校验错误 (行列号从 1 开始，0 表示无法定位)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConfigFileError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// 出错字段路径，例如 providers[2].id
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if self.line > 0 {
+            write!(f, ":{}:{}", self.line, self.column)?;
+        }
+        if !self.field.is_empty() && self.field != "." {
+            write!(f, " ({})", self.field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// 严格解析配置文件文本：语法错误与类型错误都带上行列号与字段路径
+pub fn parse_part<T: DeserializeOwned>(file: &str, text: &str) -> Result<T, ConfigFileError> {
+    let mut de = serde_json::Deserializer::from_str(text);
+    let result: Result<T, _> = serde_path_to_error::deserialize(&mut de);
+    let value = result.map_err(|e| {
+        let field = e.path().to_string();
+        let inner = e.into_inner();
+        ConfigFileError {
+            file: file.to_string(),
+            line: inner.line(),
+            column: inner.column(),
+            field,
+            message: inner.to_string(),
+        }
+    })?;
+    de.end().map_err(|e| ConfigFileError {
+        file: file.to_string(),
+        line: e.line(),
+        column: e.column(),
+        field: String::new(),
+        message: e.to_string(),
+    })?;
+    Ok(value)
+}
+
+/// 从 (已迁移的) Value 解析，无法提供行号
+pub fn from_value_part<T: DeserializeOwned>(
+    file: &str,
+    value: Value,
+) -> Result<T, ConfigFileError> {
+    serde_path_to_error::deserialize(value).map_err(|e| ConfigFileError {
+        file: file.to_string(),
+        line: 0,
+        column: 0,
+        field: e.path().to_string(),
+        message: e.into_inner().to_string(),
+    })
+}
+
+/// 校验列表型字段：必须是对象数组，且每项都有非空且唯一的字符串 id
+pub fn validate_id_list(file: &str, key: &str, list: &Value) -> Result<(), ConfigFileError> {
+    let error = |field: String, message: &str| ConfigFileError {
+        file: file.to_string(),
+        line: 0,
+        column: 0,
+        field,
+        message: message.to_string(),
+    };
+    let items = list
+        .as_array()
+        .ok_or_else(|| error(key.to_string(), "应为数组"))?;
+    let mut seen = std::collections::HashSet::new();
+    for (i, item) in items.iter().enumerate() {
+        let field = format!("{}[{}].id", key, i);
+        if !item.is_object() {
+            return Err(error(format!("{}[{}]", key, i), "应为对象"));
+        }
+        let id = item["id"]
+            .as_str()
+            .filter(|s| !s.trim().is_empty())
+            .ok_or_else(|| error(field.clone(), "缺少字符串类型的 id"))?;
+        if !seen.insert(id.to_string()) {
+            return Err(error(field, &format!("id 重复: {}", id)));
+        }
+    }
+    Ok(())
+}
+
+// ==================================================================================
+// 版本迁移
+// ==================================================================================
+
+/// 各配置文件的原始 JSON (迁移在此层面进行)
+pub struct RawConfig {
+    pub settings: Value,
+    pub providers: Option<Value>,
+    pub presets: Option<Value>,
+    pub prompts: Option<Value>,
+}
+
+/// MIGRATIONS[i] 将版本 i 升级到 i + 1
+const MIGRATIONS: &[fn(&mut RawConfig)] = &[migrate_v0_to_v1];
+
+/// v0 -> v1: 提示词库曾内嵌在 settings.json 中，迁移到独立的 prompts.json
+fn migrate_v0_to_v1(raw: &mut RawConfig) {
+    let embedded = raw
+        .settings
+        .as_object_mut()
+        .and_then(|s| s.remove("promptLibrary"));
+    let Some(embedded) = embedded.filter(|p| p.as_array().is_some_and(|a| !a.is_empty())) else {
+        return;
+    };
+    let prompts_empty = raw
+        .prompts
+        .as_ref()
+        .and_then(|p| p["promptLibrary"].as_array())
+        .is_none_or(|a| a.is_empty());
+    if prompts_empty {
+        raw.prompts = Some(serde_json::json!({ "promptLibrary": embedded }));
+    }
+}
+
+pub fn config_version(settings: &Value) -> u32 {
+    settings["configVersion"].as_u64().unwrap_or(0) as u32
+}
+
+/// 依次执行迁移，返回是否发生了变更
+/// 版本高于当前程序支持的版本时返回错误 (避免旧程序覆盖新格式)
+pub fn migrate(raw: &mut RawConfig) -> Result<bool, ConfigFileError> {
+    let from = config_version(&raw.settings);
+    if from > CONFIG_VERSION {
+        return Err(ConfigFileError {
+            file: "settings.json".into(),
+            line: 0,
+            column: 0,
+            field: "configVersion".into(),
+            message: format!(
+                "配置版本 {} 高于当前程序支持的版本 {}，请升级程序",
+                from, CONFIG_VERSION
+            ),
+        });
+    }
+    for step in &MIGRATIONS[from as usize..CONFIG_VERSION as usize] {
+        step(raw);
+    }
+    if let Some(obj) = raw.settings.as_object_mut() {
+        obj.insert("configVersion".into(), CONFIG_VERSION.into());
+    }
+    Ok(from != CONFIG_VERSION)
+}
+
+// ==================================================================================
+// 原子写入与备份
+// ==================================================================================
+
+/// 先写临时文件并落盘，再重命名覆盖，避免写到一半时崩溃留下残缺文件
+pub fn write_atomic(path: &Path, content: &str) -> Result<(), String> {
+    let tmp = path.with_extension(format!(
+        "{}.tmp",
+        path.extension().and_then(|e| e.to_str()).unwrap_or("")
+    ));
+    {
+        let mut file = fs::File::create(&tmp).map_err(|e| e.to_string())?;
+        file.write_all(content.as_bytes())
+            .map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+    }
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        e.to_string()
+    })
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigBackup {
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    pub files: Vec<String>,
+}
+
+fn backup_root(config_dir: &Path) -> PathBuf {
+    config_dir.join(BACKUP_DIR)
+}
+
+/// 列出备份 (新的在前)
+pub fn list_backups(config_dir: &Path) -> Vec<ConfigBackup> {
+    let Ok(entries) = fs::read_dir(backup_root(config_dir)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<ConfigBackup> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let id = e.file_name().to_str()?.to_string();
+            let created_at = chrono::NaiveDateTime::parse_from_str(&id, "%Y%m%d-%H%M%S%.3f")
+                .ok()?
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string();
+            let mut files: Vec<String> = CONFIG_FILES
+                .iter()
+                .filter(|f| e.path().join(f).exists())
+                .map(|f| f.to_string())
+                .collect();
+            files.sort();
+            Some(ConfigBackup {
+                id,
+                created_at,
+                files,
+            })
+        })
+        .collect();
+    backups.sort_by(|a, b| b.id.cmp(&a.id));
+    backups
+}
+
+fn same_as_backup(config_dir: &Path, backup: &ConfigBackup) -> bool {
+    let dir = backup_root(config_dir).join(&backup.id);
+    CONFIG_FILES.iter().all(|f| {
+        let current = fs::read(config_dir.join(f)).ok();
+        let saved = fs::read(dir.join(f)).ok();
+        current == saved
+    })
+}
+
+/// 备份当前磁盘上的配置文件 (与最近一次备份相同时跳过)，并清理超出数量的旧备份
+pub fn backup_current(config_dir: &Path) -> Result<Option<String>, String> {
+    let existing: Vec<&str> = CONFIG_FILES
+        .iter()
+        .copied()
+        .filter(|f| config_dir.join(f).exists())
+        .collect();
+    if existing.is_empty() {
+        return Ok(None);
+    }
+
+    let backups = list_backups(config_dir);
+    if backups
+        .first()
+        .is_some_and(|b| same_as_backup(config_dir, b))
+    {
+        return Ok(None);
+    }
+
+    let mut id = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+    while backups.iter().any(|b| b.id == id) || backup_root(config_dir).join(&id).exists() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        id = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+    }
+    let dir = backup_root(config_dir).join(&id);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    for f in existing {
+        fs::copy(config_dir.join(f), dir.join(f)).map_err(|e| e.to_string())?;
+    }
+
+    for old in list_backups(config_dir).iter().skip(MAX_BACKUPS) {
+        let _ = fs::remove_dir_all(backup_root(config_dir).join(&old.id));
+    }
+    Ok(Some(id))
+}
+
+/// 从备份恢复 (恢复前先备份当前文件)
+pub fn restore_backup(config_dir: &Path, id: &str) -> Result<(), String> {
+    if id.contains(['/', '\\']) || id.contains("..") {
+        return Err(format!("非法的备份 ID: {}", id));
+    }
+    let dir = backup_root(config_dir).join(id);
+    if !dir.is_dir() {
+        return Err(format!("找不到备份: {}", id));
+    }
+    backup_current(config_dir)?;
+    for f in CONFIG_FILES {
+        let src = dir.join(f);
+        if src.exists() {
+            let content = fs::read_to_string(&src).map_err(|e| e.to_string())?;
+            write_atomic(&config_dir.join(f), &content)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, Debug)]
+    struct Part {
+        #[serde(rename = "fontSize")]
+        font_size: u32,
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("goge_cfg_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_errors_report_position_and_field() {
+        let ok = parse_part::<Part>("settings.json", "{\"fontSize\": 14}").unwrap();
+        assert_eq!(ok.font_size, 14);
+
+        let err = parse_part::<Part>("settings.json", "{\n  \"fontSize\": \"big\"\n}").unwrap_err();
+        assert_eq!(err.file, "settings.json");
+        assert_eq!(err.line, 2);
+        assert_eq!(err.field, "fontSize");
+
+        let err = parse_part::<Part>("settings.json", "{\n  \"fontSize\": 14,\n}").unwrap_err();
+        assert_eq!(err.line, 3);
+
+        let err = validate_id_list(
+            "providers.json",
+            "providers",
+            &json!([{ "id": "a" }, { "id": "a" }]),
+        )
+        .unwrap_err();
+        assert_eq!(err.field, "providers[1].id");
+    }
+
+    #[test]
+    fn test_migrate_moves_embedded_prompt_library() {
+        let mut raw = RawConfig {
+            settings: json!({ "fontSize": 14, "promptLibrary": [{ "id": "p1" }] }),
+            providers: None,
+            presets: None,
+            prompts: None,
+        };
+        assert!(migrate(&mut raw).unwrap());
+        assert_eq!(raw.settings["configVersion"], CONFIG_VERSION);
+        assert!(raw.settings.get("promptLibrary").is_none());
+        assert_eq!(raw.prompts.unwrap()["promptLibrary"][0]["id"], "p1");
+
+        let mut newer = RawConfig {
+            settings: json!({ "configVersion": CONFIG_VERSION + 1 }),
+            providers: None,
+            presets: None,
+            prompts: None,
+        };
+        assert!(migrate(&mut newer).is_err());
+    }
+
+    #[test]
+    fn test_backup_rotation_and_restore() {
+        let dir = temp_dir("backup");
+        for i in 0..(MAX_BACKUPS + 3) {
+            write_atomic(
+                &dir.join("settings.json"),
+                &format!("{{\"fontSize\": {}}}", i),
+            )
+            .unwrap();
+            assert!(backup_current(&dir).unwrap().is_some());
+            // 内容未变化时不重复备份
+            assert!(backup_current(&dir).unwrap().is_none());
+        }
+        let backups = list_backups(&dir);
+        assert_eq!(backups.len(), MAX_BACKUPS);
+
+        let oldest = backups.last().unwrap().id.clone();
+        restore_backup(&dir, &oldest).unwrap();
+        let restored = fs::read_to_string(dir.join("settings.json")).unwrap();
+        assert_eq!(restored, "{\"fontSize\": 3}");
+        assert!(restore_backup(&dir, "../etc").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
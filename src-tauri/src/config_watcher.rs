@@ -0,0 +1,190 @@
+use crate::commands::config_cmd::{self, AppConfig, ConfigIssuesState, ConfigState};
+use crate::config_store::CONFIG_FILES;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// 持有 watcher，drop 即停止监听
+pub struct ConfigWatcherState {
+    _debouncer: Mutex<Debouncer<RecommendedWatcher>>,
+}
+
+/// config-changed 事件负载
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigChangedPayload {
+    /// 发生变化的部分: settings / providers / presets / prompts / immersiveMode
+    pub sections: Vec<String>,
+    pub issues: Vec<crate::config_store::ConfigFileError>,
+}
+
+/// 配置文件与 AppConfig 中字段的对应关系 (settings.json 对应其余所有字段)
+fn section_of(key: &str) -> &'static str {
+    match key {
+        "providers" => "providers",
+        "presets" => "presets",
+        "promptLibrary" => "prompts",
+        _ => "settings",
+    }
+}
+
+fn file_of(section: &str) -> &'static str {
+    match section {
+        "providers" => "providers.json",
+        "presets" => "presets.json",
+        "prompts" => "prompts.json",
+        _ => "settings.json",
+    }
+}
+
+/// 合并新旧配置：解析失败的文件保留旧值，返回合并结果与变化的部分
+fn merge_changes(
+    old: &AppConfig,
+    new: &AppConfig,
+    broken_files: &[String],
+) -> Result<(AppConfig, Vec<String>), String> {
+    let old_val = serde_json::to_value(old).map_err(|e| e.to_string())?;
+    let mut new_val = serde_json::to_value(new).map_err(|e| e.to_string())?;
+    let (Some(old_obj), Some(new_obj)) = (old_val.as_object(), new_val.as_object_mut()) else {
+        return Err("配置序列化结果不是对象".into());
+    };
+
+    let mut sections: Vec<String> = Vec::new();
+    let mut push = |s: &str| {
+        if !sections.iter().any(|x| x == s) {
+            sections.push(s.to_string());
+        }
+    };
+    for (key, old_field) in old_obj {
+        let section = section_of(key);
+        if broken_files.iter().any(|f| f == file_of(section)) {
+            new_obj.insert(key.clone(), old_field.clone());
+            continue;
+        }
+        if new_obj.get(key) != Some(old_field) {
+            push(section);
+            if key == "immersiveMode" {
+                push("immersiveMode");
+            }
+        }
+    }
+
+    let merged: AppConfig =
+        serde_json::from_value(Value::Object(new_obj.clone())).map_err(|e| e.to_string())?;
+    Ok((merged, sections))
+}
+
+/// 重新读取磁盘配置并替换内存缓存，有变化时通知前端
+pub async fn reload_from_disk(app: &AppHandle) -> Result<(), String> {
+    let config_state = app.state::<ConfigState>();
+    let old = config_state.get_config().await;
+    let new = config_cmd::load_config_internal(app).await?;
+    let report = app.state::<ConfigIssuesState>().0.lock().unwrap().clone();
+
+    let broken: Vec<String> = report.issues.iter().map(|i| i.file.clone()).collect();
+    let (merged, sections) = merge_changes(&old, &new, &broken)?;
+
+    if sections.is_empty() && report.issues.is_empty() {
+        return Ok(());
+    }
+    if !sections.is_empty() {
+        app.state::<crate::net::HttpClients>()
+            .set_global(merged.network.clone());
+        crate::api_server::apply(app, &merged.api_server);
+        config_state.update_config(merged).await;
+        println!("🔄 [Config] 外部修改已生效: {}", sections.join(", "));
+    }
+    if sections.iter().any(|s| s == "immersiveMode") {
+        app.state::<Arc<crate::behavior_scheduler::MessageScheduler>>()
+            .notify_config_changed();
+    }
+
+    let _ = app.emit(
+        "config-changed",
+        ConfigChangedPayload {
+            sections,
+            issues: report.issues,
+        },
+    );
+    Ok(())
+}
+
+/// 监听配置目录，文件变化 (去抖后) 触发重新加载
+pub fn start(app: &AppHandle) -> Result<(), String> {
+    let config_dir = config_cmd::resolve_config_dir(app);
+    let handle = app.clone();
+
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(500),
+        move |result: DebounceEventResult| {
+            let events = match result {
+                Ok(events) => events,
+                Err(e) => {
+                    println!("⚠️ [Config] 监听出错: {}", e);
+                    return;
+                }
+            };
+            let relevant = events.iter().any(|e| {
+                e.path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| CONFIG_FILES.contains(&n))
+            });
+            if !relevant {
+                return;
+            }
+            let app = handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = reload_from_disk(&app).await {
+                    println!("❌ [Config] 重新加载失败: {}", e);
+                }
+            });
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    debouncer
+        .watcher()
+        .watch(&config_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+    app.manage(ConfigWatcherState {
+        _debouncer: Mutex::new(debouncer),
+    });
+    println!("👀 [Config] 正在监听 {:?}", config_dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_reports_sections_and_keeps_broken_files() {
+        let old = AppConfig::default();
+
+        let mut new = AppConfig {
+            font_size: old.font_size + 2,
+            presets: serde_json::json!([]),
+            ..Default::default()
+        };
+        new.immersive_mode.enabled = !old.immersive_mode.enabled;
+
+        let (merged, sections) = merge_changes(&old, &new, &[]).unwrap();
+        assert_eq!(merged.font_size, old.font_size + 2);
+        assert!(sections.contains(&"settings".to_string()));
+        assert!(sections.contains(&"immersiveMode".to_string()));
+        assert!(sections.contains(&"presets".to_string()));
+        assert!(!sections.contains(&"providers".to_string()));
+
+        // presets.json 解析失败时保留旧的预设
+        let (merged, sections) = merge_changes(&old, &new, &["presets.json".to_string()]).unwrap();
+        assert_eq!(merged.presets, old.presets);
+        assert!(!sections.contains(&"presets".to_string()));
+
+        let (_, sections) = merge_changes(&old, &old, &[]).unwrap();
+        assert!(sections.is_empty());
+    }
+}
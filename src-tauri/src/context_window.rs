@@ -0,0 +1,591 @@
+use crate::db::DbState;
+use crate::models::Message;
+use crate::provider_config::ProviderConfig;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::{AppHandle, Manager};
+
+/// 未配置上下文上限且无法从模型名推断时的保守默认值
+const DEFAULT_CONTEXT_LIMIT: usize = 32_768;
+/// 未指定 max_tokens 时为回复预留的 token 数
+const DEFAULT_REPLY_RESERVE: usize = 4_096;
+/// 每条消息的格式开销 (role 标记、分隔符等)
+const MESSAGE_OVERHEAD: usize = 4;
+/// 摘要最多占用预算的比例 (1/N)
+const SUMMARY_BUDGET_DIVISOR: usize = 8;
+
+const DEFAULT_SUMMARY_PROMPT: &str = "你是对话摘要助手。请把下面的早期对话压缩成一段简洁的摘要，保留人物、事实、偏好、约定和未完成的事项，使用与对话相同的语言，不要添加评论。";
+
+/// 会话类型 (摘要缓存按类型区分，避免普通会话与社交会话 ID 冲突)
+#[derive(Debug, Clone, Copy)]
+pub enum SessionKind {
+    Chat,
+    Social,
+}
+
+impl SessionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SessionKind::Chat => "chat",
+            SessionKind::Social => "social",
+        }
+    }
+}
+
+// ==================================================================================
+// Token 估算
+// ==================================================================================
+
+fn is_cjk(ch: char) -> bool {
+    matches!(ch as u32,
+        0x3040..=0x30FF    // 日文假名
+        | 0x3400..=0x4DBF  // CJK 扩展 A
+        | 0x4E00..=0x9FFF  // CJK 统一汉字
+        | 0xAC00..=0xD7AF  // 韩文
+        | 0xF900..=0xFAFF  // CJK 兼容汉字
+        | 0xFF00..=0xFFEF  // 全角符号
+        | 0x3000..=0x303F // CJK 标点
+    )
+}
+
+/// 粗略估算文本 token 数
+/// CJK 字符约 1 token/字，其余字符约 4 字符/token；宁可高估也不低估
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut cjk = 0usize;
+    let mut other = 0usize;
+    for ch in text.chars() {
+        if is_cjk(ch) {
+            cjk += 1;
+        } else {
+            other += 1;
+        }
+    }
+    cjk + other.div_ceil(4)
+}
+
+pub fn estimate_message_tokens(msg: &Message) -> usize {
+    estimate_tokens(&msg.content) + MESSAGE_OVERHEAD
+}
+
+pub fn estimate_messages_tokens(messages: &[Message]) -> usize {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+// ==================================================================================
+// 上下文上限
+// ==================================================================================
+
+/// 根据模型名推断常见模型的上下文长度
+fn builtin_context_limit(model: &str) -> Option<usize> {
+    let m = model.to_ascii_lowercase();
+    let limit = if m.contains("gemini") {
+        1_000_000
+    } else if m.contains("claude") {
+        200_000
+    } else if m.contains("gpt-4o") || m.contains("gpt-4-turbo") || m.contains("gpt-4.1") {
+        128_000
+    } else if m.contains("gpt-3.5") {
+        16_385
+    } else if m.contains("gpt-4") {
+        8_192
+    } else if m.contains("deepseek") {
+        64_000
+    } else if m.contains("qwen") {
+        32_768
+    } else {
+        return None;
+    };
+    Some(limit)
+}
+
+/// 读取模型的上下文上限 (providers.json)
+/// 优先级：models[] 中的 contextWindow > provider.contextLimits[model] > provider.contextWindow > 内置推断 > 默认值
+pub fn context_limit_for(provider: &ProviderConfig, model: &str) -> usize {
+    provider
+        .model(model)
+        .and_then(|m| m.context_window)
+        .or_else(|| provider.context_limits.get(model).copied())
+        .or(provider.context_window)
+        .map(|n| n as usize)
+        .or_else(|| builtin_context_limit(model))
+        .unwrap_or(DEFAULT_CONTEXT_LIMIT)
+}
+
+/// 计算历史消息可用的输入预算 (扣除回复预留与 10% 估算误差余量)
+pub fn input_budget(context_limit: usize, max_tokens: Option<u32>) -> usize {
+    let reserve = max_tokens
+        .map(|t| t as usize)
+        .unwrap_or(DEFAULT_REPLY_RESERVE)
+        .min(context_limit / 2);
+    let usable = context_limit.saturating_sub(reserve);
+    usable - usable / 10
+}
+
+// ==================================================================================
+// 窗口规划
+// ==================================================================================
+
+/// 历史裁剪计划
+#[derive(Debug, PartialEq)]
+pub struct WindowPlan {
+    /// 需要被压缩进摘要的非 system 消息数量 (从最早开始)
+    pub summarize_count: usize,
+    /// 是否需要裁剪
+    pub overflow: bool,
+}
+
+/// 规划需要摘要的消息范围
+/// system 消息始终保留；最近一条消息无论多长都保留；其余从新到旧尽量放入预算
+pub fn plan_window(messages: &[Message], budget: usize) -> WindowPlan {
+    let total = estimate_messages_tokens(messages);
+    if total <= budget {
+        return WindowPlan {
+            summarize_count: 0,
+            overflow: false,
+        };
+    }
+
+    let system_tokens: usize = messages
+        .iter()
+        .filter(|m| m.role == "system")
+        .map(estimate_message_tokens)
+        .sum();
+    let conversation: Vec<&Message> = messages.iter().filter(|m| m.role != "system").collect();
+
+    // 为摘要本身预留空间
+    let available = budget
+        .saturating_sub(system_tokens)
+        .saturating_sub(budget / SUMMARY_BUDGET_DIVISOR);
+
+    let mut used = 0usize;
+    let mut keep = 0usize;
+    for m in conversation.iter().rev() {
+        let cost = estimate_message_tokens(m);
+        if keep > 0 && used + cost > available {
+            break;
+        }
+        used += cost;
+        keep += 1;
+    }
+
+    // 保证保留部分从 user 消息开始，避免以孤立的助手回复开头
+    let mut summarize_count = conversation.len() - keep;
+    while summarize_count < conversation.len().saturating_sub(1)
+        && conversation[summarize_count].role != "user"
+    {
+        summarize_count += 1;
+    }
+
+    WindowPlan {
+        summarize_count,
+        overflow: true,
+    }
+}
+
+/// 消息指纹 (FNV-1a)，用于判断摘要缓存覆盖的消息是否被编辑/删除过
+fn fingerprint(messages: &[&Message]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for m in messages {
+        for b in m
+            .role
+            .bytes()
+            .chain([0u8])
+            .chain(m.content.bytes())
+            .chain([0u8])
+        {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+fn system_message(content: String) -> Message {
+    Message {
+        id: None,
+        model: None,
+        role: "system".to_string(),
+        content,
+        reasoning_content: None,
+        file_metadata: None,
+        search_metadata: None,
+        provider: None,
+        mode: None,
+        role_id: None,
+        parent_id: None,
+    }
+}
+
+/// 把摘要插入到 system 消息之后、保留的对话之前
+fn assemble(
+    messages: Vec<Message>,
+    summarize_count: usize,
+    summary: Option<String>,
+) -> Vec<Message> {
+    let (system, conversation): (Vec<Message>, Vec<Message>) =
+        messages.into_iter().partition(|m| m.role == "system");
+
+    let mut result = system;
+    if let Some(summary) = summary.filter(|s| !s.trim().is_empty()) {
+        result.push(system_message(format!(
+            "以下是此前对话的摘要，请将其视为已知上下文：\n{}",
+            summary.trim()
+        )));
+    }
+    result.extend(conversation.into_iter().skip(summarize_count));
+    result
+}
+
+// ==================================================================================
+// 摘要缓存 (goge.db: session_summaries)
+// ==================================================================================
+
+pub struct CachedSummary {
+    pub covered_count: usize,
+    pub covered_hash: String,
+    /// 最后一条已覆盖消息的 ID；消息带 ID 时按它定位覆盖范围，而不是按窗口内的位置
+    pub covered_last_id: Option<i64>,
+    pub summary: String,
+}
+
+pub fn get_cached_summary(
+    conn: &Connection,
+    kind: SessionKind,
+    session_id: i64,
+) -> rusqlite::Result<Option<CachedSummary>> {
+    conn.query_row(
+        "SELECT covered_count, covered_hash, covered_last_id, summary FROM session_summaries WHERE session_kind = ?1 AND session_id = ?2",
+        params![kind.as_str(), session_id],
+        |row| {
+            Ok(CachedSummary {
+                covered_count: row.get::<_, i64>(0)? as usize,
+                covered_hash: row.get(1)?,
+                covered_last_id: row.get(2)?,
+                summary: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub fn save_cached_summary(
+    conn: &Connection,
+    kind: SessionKind,
+    session_id: i64,
+    summary: &CachedSummary,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO session_summaries (session_kind, session_id, covered_count, covered_hash, covered_last_id, summary, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)
+         ON CONFLICT(session_kind, session_id) DO UPDATE SET
+            covered_count = excluded.covered_count,
+            covered_hash = excluded.covered_hash,
+            covered_last_id = excluded.covered_last_id,
+            summary = excluded.summary,
+            updated_at = CURRENT_TIMESTAMP",
+        params![
+            kind.as_str(),
+            session_id,
+            summary.covered_count as i64,
+            summary.covered_hash,
+            summary.covered_last_id,
+            summary.summary
+        ],
+    )?;
+    Ok(())
+}
+
+/// 摘要覆盖范围的校验值：消息带 ID 时只校验最后一条已覆盖消息 (更早的消息可能已滑出历史窗口)，
+/// 否则校验整个已覆盖前缀
+fn coverage_hash(covered: &[&Message]) -> String {
+    match covered.last() {
+        Some(last) if last.id.is_some() => fingerprint(&covered[covered.len() - 1..]),
+        _ => fingerprint(covered),
+    }
+}
+
+/// 缓存的摘要能覆盖 to_cover 的前多少条；None 表示不可复用
+fn reusable_count(cached: &CachedSummary, to_cover: &[&Message]) -> Option<usize> {
+    let count = match cached.covered_last_id {
+        Some(last_id) => to_cover.iter().position(|m| m.id == Some(last_id))? + 1,
+        None => cached.covered_count,
+    };
+    (count > 0
+        && count <= to_cover.len()
+        && cached.covered_hash == coverage_hash(&to_cover[..count]))
+    .then_some(count)
+}
+
+/// 会话删除时一并清理其摘要缓存
+pub fn delete_cached_summary(
+    conn: &Connection,
+    kind: SessionKind,
+    session_id: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM session_summaries WHERE session_kind = ?1 AND session_id = ?2",
+        params![kind.as_str(), session_id],
+    )?;
+    Ok(())
+}
+
+fn transcript(messages: &[&Message]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let speaker = match m.role.as_str() {
+                "user" => "用户",
+                "assistant" => "助手",
+                _ => "系统",
+            };
+            format!("{}: {}", speaker, m.content.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 调用标题生成所用的模型生成 (增量) 摘要
+async fn summarize(
+    app: &AppHandle,
+    previous: Option<&str>,
+    messages: &[&Message],
+) -> Result<String, String> {
+    let prompt = crate::character_state::StateAnalyzer::load_prompt_template("context_summary.txt")
+        .unwrap_or_else(|_| DEFAULT_SUMMARY_PROMPT.to_string());
+
+    let mut input = String::new();
+    if let Some(prev) = previous {
+        input.push_str("【已有摘要】\n");
+        input.push_str(prev.trim());
+        input.push_str("\n\n【需要并入摘要的新对话】\n");
+    }
+    input.push_str(&transcript(messages));
+
+    let request = vec![
+        system_message(prompt),
+        Message {
+            role: "user".to_string(),
+            ..system_message(input)
+        },
+    ];
+    crate::title_commands::generate_title_internal(app.clone(), request).await
+}
+
+/// 在发送前把历史裁剪到模型上下文预算内
+///
+/// 超出预算时，较早的对话会被压缩成摘要 (按会话缓存并增量更新)；
+/// 没有会话 ID 或摘要失败时退化为直接丢弃最早的消息
+pub async fn fit_to_context(
+    app: &AppHandle,
+    session: Option<(SessionKind, i64)>,
+    messages: Vec<Message>,
+    context_limit: usize,
+    max_tokens: Option<u32>,
+) -> Vec<Message> {
+    let budget = input_budget(context_limit, max_tokens);
+    let plan = plan_window(&messages, budget);
+    if !plan.overflow {
+        return messages;
+    }
+
+    println!(
+        "✂️ [上下文] 历史约 {} tokens 超出预算 {}，压缩最早的 {} 条消息",
+        estimate_messages_tokens(&messages),
+        budget,
+        plan.summarize_count
+    );
+
+    let Some((kind, session_id)) = session else {
+        return assemble(messages, plan.summarize_count, None);
+    };
+    if plan.summarize_count == 0 {
+        return messages;
+    }
+
+    let conversation: Vec<&Message> = messages.iter().filter(|m| m.role != "system").collect();
+    let to_cover = &conversation[..plan.summarize_count];
+
+    let cached = {
+        let db = app.state::<DbState>();
+        let conn = db.0.lock().unwrap();
+        get_cached_summary(&conn, kind, session_id).unwrap_or(None)
+    };
+
+    // 缓存可复用的条件：覆盖范围是本次需要摘要范围的前缀，且内容未被修改
+    let reusable = cached.and_then(|c| reusable_count(&c, to_cover).map(|count| (c, count)));
+
+    let summary = match reusable {
+        Some((c, count)) if count == to_cover.len() => {
+            println!("♻️ [上下文] 复用已缓存的摘要 ({} 条)", count);
+            Ok(c.summary)
+        }
+        Some((c, count)) => summarize(app, Some(&c.summary), &to_cover[count..]).await,
+        None => summarize(app, None, to_cover).await,
+    };
+
+    match summary {
+        Ok(summary) => {
+            let db = app.state::<DbState>();
+            let conn = db.0.lock().unwrap();
+            let entry = CachedSummary {
+                covered_count: to_cover.len(),
+                covered_hash: coverage_hash(to_cover),
+                covered_last_id: to_cover.last().and_then(|m| m.id),
+                summary,
+            };
+            if let Err(e) = save_cached_summary(&conn, kind, session_id, &entry) {
+                println!("⚠️ [上下文] 摘要缓存写入失败: {}", e);
+            }
+            drop(conn);
+            assemble(messages, plan.summarize_count, Some(entry.summary))
+        }
+        Err(e) => {
+            println!("⚠️ [上下文] 摘要生成失败，退化为直接截断: {}", e);
+            assemble(messages, plan.summarize_count, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            ..system_message(content.to_string())
+        }
+    }
+
+    #[test]
+    fn test_estimate_tokens_cjk_aware() {
+        assert_eq!(estimate_tokens("你好世界"), 4);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("你好 abcd"), 4);
+        assert_eq!(estimate_tokens(""), 0);
+    }
+
+    #[test]
+    fn test_context_limit_priority() {
+        let provider = ProviderConfig::from_value(&serde_json::json!({
+            "id": "custom",
+            "baseUrl": "https://example.com",
+            "contextWindow": 1000,
+            "contextLimits": { "b": 2000 },
+            "models": [{ "id": "a", "contextWindow": 3000 }, "b", "c"]
+        }))
+        .unwrap();
+        let bare = ProviderConfig::from_value(&serde_json::json!({ "id": "deepseek" })).unwrap();
+        assert_eq!(context_limit_for(&provider, "a"), 3000);
+        assert_eq!(context_limit_for(&provider, "b"), 2000);
+        assert_eq!(context_limit_for(&provider, "c"), 1000);
+        assert_eq!(context_limit_for(&bare, "deepseek-chat"), 64_000);
+        assert_eq!(context_limit_for(&bare, "unknown"), DEFAULT_CONTEXT_LIMIT);
+    }
+
+    #[test]
+    fn test_plan_window_keeps_recent_and_system() {
+        let long = "字".repeat(100);
+        let messages = vec![
+            msg("system", "设定"),
+            msg("user", &long),
+            msg("assistant", &long),
+            msg("user", &long),
+            msg("assistant", &long),
+            msg("user", "最新问题"),
+        ];
+        assert!(!plan_window(&messages, 10_000).overflow);
+
+        let plan = plan_window(&messages, 300);
+        assert!(plan.overflow);
+        // 保留部分必须以 user 开头
+        let conversation: Vec<&Message> = messages.iter().filter(|m| m.role != "system").collect();
+        assert_eq!(conversation[plan.summarize_count].role, "user");
+        assert!(plan.summarize_count >= 2);
+
+        let assembled = assemble(messages, plan.summarize_count, Some("摘要".into()));
+        assert_eq!(assembled[0].content, "设定");
+        assert!(assembled[1].content.contains("摘要"));
+        assert_eq!(assembled.last().unwrap().content, "最新问题");
+    }
+
+    #[test]
+    fn test_fingerprint_detects_edits() {
+        let a = msg("user", "hello");
+        let b = msg("user", "hellp");
+        assert_ne!(fingerprint(&[&a]), fingerprint(&[&b]));
+        assert_eq!(fingerprint(&[&a]), fingerprint(&[&a.clone()]));
+    }
+
+    #[test]
+    fn test_cached_coverage_survives_window_slide() {
+        let with_id = |id: i64, content: &str| Message {
+            id: Some(id),
+            ..msg("user", content)
+        };
+        let before: Vec<Message> = (1..=6).map(|i| with_id(i, &format!("m{}", i))).collect();
+        let covered: Vec<&Message> = before[..4].iter().collect();
+        let cached = CachedSummary {
+            covered_count: covered.len(),
+            covered_hash: coverage_hash(&covered),
+            covered_last_id: Some(4),
+            summary: "s".into(),
+        };
+
+        // 窗口前移两条后，已覆盖范围在窗口内只剩 2 条
+        let after: Vec<Message> = (3..=8).map(|i| with_id(i, &format!("m{}", i))).collect();
+        let to_cover: Vec<&Message> = after.iter().collect();
+        assert_eq!(reusable_count(&cached, &to_cover), Some(2));
+
+        // 锚点消息被编辑后不再复用
+        let mut edited = after.clone();
+        edited[1].content = "changed".into();
+        let to_cover: Vec<&Message> = edited.iter().collect();
+        assert_eq!(reusable_count(&cached, &to_cover), None);
+
+        // 锚点消息已滑出窗口
+        let gone: Vec<Message> = (5..=8).map(|i| with_id(i, &format!("m{}", i))).collect();
+        let to_cover: Vec<&Message> = gone.iter().collect();
+        assert_eq!(reusable_count(&cached, &to_cover), None);
+    }
+
+    #[test]
+    fn test_summary_cache_roundtrip() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_db(&conn).unwrap();
+        assert!(get_cached_summary(&conn, SessionKind::Chat, 1)
+            .unwrap()
+            .is_none());
+
+        let entry = CachedSummary {
+            covered_count: 4,
+            covered_hash: "abc".into(),
+            covered_last_id: Some(42),
+            summary: "s1".into(),
+        };
+        save_cached_summary(&conn, SessionKind::Chat, 1, &entry).unwrap();
+        let updated = CachedSummary {
+            summary: "s2".into(),
+            ..entry
+        };
+        save_cached_summary(&conn, SessionKind::Chat, 1, &updated).unwrap();
+
+        let got = get_cached_summary(&conn, SessionKind::Chat, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(got.summary, "s2");
+        assert_eq!(got.covered_count, 4);
+        assert_eq!(got.covered_last_id, Some(42));
+        assert!(get_cached_summary(&conn, SessionKind::Social, 1)
+            .unwrap()
+            .is_none());
+
+        save_cached_summary(&conn, SessionKind::Social, 1, &updated).unwrap();
+        delete_cached_summary(&conn, SessionKind::Social, 1).unwrap();
+        assert!(get_cached_summary(&conn, SessionKind::Social, 1)
+            .unwrap()
+            .is_none());
+        assert!(get_cached_summary(&conn, SessionKind::Chat, 1)
+            .unwrap()
+            .is_some());
+    }
+}
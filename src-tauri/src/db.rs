@@ -1,4 +1,4 @@
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 
@@ -18,6 +18,7 @@ pub struct ChatSession {
     pub preset_id: Option<String>,
     pub model_id: Option<String>,
     pub system_prompt: Option<String>,
+    pub generation_params: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,6 +41,7 @@ pub struct ChatMessage {
     pub file_metadata: Option<String>,
     pub search_metadata: Option<String>,
     pub created_at: Option<String>,
+    pub parent_id: Option<i64>,
 }
 
 // --- 数据库初始化与迁移 ---
@@ -83,6 +85,23 @@ pub fn init_db(conn: &Connection) -> Result<()> {
             FOREIGN KEY (session_id) REFERENCES sessions (id) ON DELETE CASCADE
         );
         CREATE INDEX IF NOT EXISTS idx_messages_session_id ON messages (session_id);
+        CREATE TABLE IF NOT EXISTS session_summaries (
+            session_kind TEXT NOT NULL,
+            session_id INTEGER NOT NULL,
+            covered_count INTEGER NOT NULL DEFAULT 0,
+            covered_hash TEXT NOT NULL DEFAULT '',
+            covered_last_id INTEGER,
+            summary TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (session_kind, session_id)
+        );
+        CREATE TABLE IF NOT EXISTS model_catalog (
+            provider_id TEXT NOT NULL,
+            model_id TEXT NOT NULL,
+            data TEXT NOT NULL,
+            fetched_at INTEGER NOT NULL,
+            PRIMARY KEY (provider_id, model_id)
+        );
     ",
     )?;
 
@@ -136,6 +155,11 @@ pub fn init_db(conn: &Connection) -> Result<()> {
         let _ = conn.execute("ALTER TABLE sessions ADD COLUMN system_prompt TEXT", []);
     }
 
+    // 会话级生成参数覆盖 (JSON: temperature/maxTokens/topP/...)
+    if !columns_sessions.contains(&"generation_params".to_string()) {
+        let _ = conn.execute("ALTER TABLE sessions ADD COLUMN generation_params TEXT", []);
+    }
+
     let mut stmt = conn.prepare("PRAGMA table_info(folders)")?;
     let columns_folders: Vec<String> = stmt
         .query_map([], |row| row.get::<_, String>(1))?
@@ -183,6 +207,24 @@ pub fn init_db(conn: &Connection) -> Result<()> {
         let _ = conn.execute("ALTER TABLE messages ADD COLUMN provider TEXT", []);
     }
 
+    // 对比模式：同一条用户消息的多个回答互为兄弟，parent_id 指向该用户消息
+    if !columns_messages.contains(&"parent_id".to_string()) {
+        let _ = conn.execute("ALTER TABLE messages ADD COLUMN parent_id INTEGER", []);
+    }
+
+    // 摘要缓存按最后一条已覆盖消息的 ID 定位 (历史只取最近 N 条时窗口会滑动)
+    let mut stmt = conn.prepare("PRAGMA table_info(session_summaries)")?;
+    let columns_summaries: Vec<String> = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?;
+
+    if !columns_summaries.contains(&"covered_last_id".to_string()) {
+        let _ = conn.execute(
+            "ALTER TABLE session_summaries ADD COLUMN covered_last_id INTEGER",
+            [],
+        );
+    }
+
     Ok(())
 }
 
@@ -198,7 +240,7 @@ pub fn init_db(conn: &Connection) -> Result<()> {
 
 pub(crate) fn get_sessions(conn: &Connection) -> Result<Vec<ChatSession>> {
     let mut stmt = conn.prepare(
-        "SELECT id, folder_id, title, last_scroll_pos, sort_order, updated_at, preset_id, model_id, system_prompt FROM sessions ORDER BY sort_order ASC, updated_at DESC"
+        "SELECT id, folder_id, title, last_scroll_pos, sort_order, updated_at, preset_id, model_id, system_prompt, generation_params FROM sessions ORDER BY sort_order ASC, updated_at DESC"
     )?;
 
     let session_iter = stmt.query_map([], |row| {
@@ -212,6 +254,7 @@ pub(crate) fn get_sessions(conn: &Connection) -> Result<Vec<ChatSession>> {
             preset_id: row.get(6)?,
             model_id: row.get(7)?,
             system_prompt: row.get(8)?,
+            generation_params: row.get(9)?,
         })
     })?;
 
@@ -222,6 +265,28 @@ pub(crate) fn get_sessions(conn: &Connection) -> Result<Vec<ChatSession>> {
     Ok(sessions)
 }
 
+pub(crate) fn get_session(conn: &Connection, id: i64) -> Result<Option<ChatSession>> {
+    conn.query_row(
+        "SELECT id, folder_id, title, last_scroll_pos, sort_order, updated_at, preset_id, model_id, system_prompt, generation_params FROM sessions WHERE id = ?1",
+        params![id],
+        |row| {
+            Ok(ChatSession {
+                id: row.get(0)?,
+                folder_id: row.get(1)?,
+                title: row.get(2)?,
+                last_scroll_pos: row.get(3)?,
+                sort_order: row.get(4)?,
+                updated_at: row.get(5)?,
+                preset_id: row.get(6)?,
+                model_id: row.get(7)?,
+                system_prompt: row.get(8)?,
+                generation_params: row.get(9)?,
+            })
+        },
+    )
+    .optional()
+}
+
 pub(crate) fn create_session(
     conn: &Connection,
     title: &str,
@@ -298,6 +363,10 @@ pub(crate) fn move_session_to_folder(
 
 pub(crate) fn delete_session(conn: &Connection, id: i64) -> Result<()> {
     conn.execute("DELETE FROM messages WHERE session_id = ?1", params![id])?;
+    conn.execute(
+        "DELETE FROM session_summaries WHERE session_kind = 'chat' AND session_id = ?1",
+        params![id],
+    )?;
     conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
     Ok(())
 }
@@ -340,11 +409,23 @@ pub(crate) fn update_session_config(
     Ok(())
 }
 
+pub(crate) fn update_session_params(
+    conn: &Connection,
+    id: i64,
+    generation_params: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE sessions SET generation_params = ?1 WHERE id = ?2",
+        params![generation_params, id],
+    )?;
+    Ok(())
+}
+
 // --- 消息管理逻辑 ---
 
 pub(crate) fn get_messages(conn: &Connection, session_id: i64) -> Result<Vec<ChatMessage>> {
     let mut stmt = conn.prepare(
-        "SELECT id, session_id, role, content, reasoning_content, file_metadata, search_metadata, created_at, model, provider FROM messages WHERE session_id = ?1 ORDER BY id ASC"
+        "SELECT id, session_id, role, content, reasoning_content, file_metadata, search_metadata, created_at, model, provider, parent_id FROM messages WHERE session_id = ?1 ORDER BY id ASC"
     )?;
 
     let msg_iter = stmt.query_map(params![session_id], |row| {
@@ -365,6 +446,7 @@ pub(crate) fn get_messages(conn: &Connection, session_id: i64) -> Result<Vec<Cha
             file_metadata,
             search_metadata,
             created_at: Some(row.get(7)?),
+            parent_id: row.get(10).unwrap_or(None),
         })
     })?;
 
@@ -404,6 +486,28 @@ pub(crate) fn save_message(
     }
 }
 
+/// 保存挂在某条用户消息下的回答 (对比模式的兄弟回答)
+pub(crate) fn save_linked_reply(
+    conn: &Connection,
+    session_id: i64,
+    parent_id: i64,
+    model: &str,
+    provider: &str,
+    content: &str,
+    reasoning_content: Option<&str>,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO messages (session_id, model, provider, role, content, reasoning_content, parent_id) VALUES (?1, ?2, ?3, 'assistant', ?4, ?5, ?6)",
+        params![session_id, model, provider, content, reasoning_content, parent_id],
+    )?;
+    let id = conn.last_insert_rowid();
+    let _ = conn.execute(
+        "UPDATE sessions SET updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![session_id],
+    );
+    Ok(id)
+}
+
 pub(crate) fn delete_message(conn: &Connection, id: i64) -> Result<()> {
     conn.execute("DELETE FROM messages WHERE id = ?1", params![id])?;
     Ok(())
@@ -452,3 +556,72 @@ pub(crate) fn update_folders_order(conn: &mut Connection, orders: Vec<(i64, i32)
     tx.commit()?;
     Ok(())
 }
+
+// --- 导入/导出辅助 ---
+
+pub(crate) fn max_sort_orders(conn: &Connection) -> Result<(i32, i32)> {
+    let sessions: i32 =
+        conn.query_row("SELECT COALESCE(MAX(sort_order), 0) FROM sessions", [], |r| {
+            r.get(0)
+        })?;
+    let folders: i32 =
+        conn.query_row("SELECT COALESCE(MAX(sort_order), 0) FROM folders", [], |r| {
+            r.get(0)
+        })?;
+    Ok((sessions, folders))
+}
+
+/// 导入文件夹 (保留排序与折叠状态)
+pub(crate) fn import_folder(
+    conn: &Connection,
+    name: &str,
+    sort_order: i32,
+    is_collapsed: bool,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO folders (name, sort_order, is_collapsed) VALUES (?1, ?2, ?3)",
+        params![name, sort_order, is_collapsed],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// 导入会话 (保留原始更新时间；为空时使用当前时间)
+pub(crate) fn import_session(
+    conn: &Connection,
+    folder_id: Option<i64>,
+    title: &str,
+    sort_order: i32,
+    updated_at: Option<&str>,
+    preset_id: Option<&str>,
+    model_id: Option<&str>,
+    system_prompt: Option<&str>,
+    generation_params: Option<&str>,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO sessions (folder_id, title, last_scroll_pos, sort_order, updated_at, preset_id, model_id, system_prompt, generation_params)
+         VALUES (?1, ?2, 0, ?3, COALESCE(?4, CURRENT_TIMESTAMP), ?5, ?6, ?7, ?8)",
+        params![folder_id, title, sort_order, updated_at, preset_id, model_id, system_prompt, generation_params],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// 导入消息 (与 save_message 不同：保留原始创建时间，且不刷新会话的 updated_at)
+pub(crate) fn import_message(conn: &Connection, msg: &ChatMessage) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO messages (session_id, model, provider, role, content, reasoning_content, file_metadata, search_metadata, created_at, parent_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, COALESCE(?9, CURRENT_TIMESTAMP), ?10)",
+        params![
+            msg.session_id,
+            msg.model,
+            msg.provider,
+            msg.role,
+            msg.content,
+            msg.reasoning_content,
+            msg.file_metadata,
+            msg.search_metadata,
+            msg.created_at,
+            msg.parent_id
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
@@ -0,0 +1,452 @@
+use crate::immersive_settings::BehaviorAction;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+// ==================================================================================
+// 表达方式：表情回应、引用回复、联系人素材库中的图片/表情包。
+// 回复生成后可再请求一次结构化输出决定用哪些 (ExpressionPlan)，
+// 再把它们织入行为引擎生成的行为链，由调度器写入 social_messages 的对应列
+// ==================================================================================
+
+/// 提供给模型选择引用的最近消息条数
+const RECENT_MESSAGES: usize = 6;
+/// 表情回应最多几个字符 (避免模型把整句话当成表情)
+const MAX_REACTION_CHARS: usize = 8;
+/// 回复发完后隔多久发表情包
+const STICKER_DELAY_MS: u32 = 600;
+
+const DEFAULT_PLAN_PROMPT: &str = "你在替聊天中的角色决定回复的表达方式。根据最近的对话和角色即将发出的回复，决定：\n- reaction: 是否给用户最新的消息点一个表情回应 (单个 emoji，不需要时为 null)\n- quote_message_id: 是否引用某条用户消息来回复 (只在回应较早的消息时使用，填消息前方括号里的编号，否则为 null)\n- sticker: 是否在回复后再发一个表情包 (只能从可用表情包中选择名称，不需要时为 null)\n大多数时候三项都应为 null，只输出 JSON：{\"reaction\": ..., \"quote_message_id\": ..., \"sticker\": ...}\n\n## 最近对话\n{{conversation_history}}\n\n## 角色即将发出的回复\n{{reply}}\n\n## 可用表情包\n{{stickers}}";
+
+/// 素材类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StickerKind {
+    Sticker,
+    Image,
+}
+
+impl StickerKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            StickerKind::Sticker => "sticker",
+            StickerKind::Image => "image",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "image" => StickerKind::Image,
+            _ => StickerKind::Sticker,
+        }
+    }
+}
+
+/// 联系人素材库中的一张图片或表情包
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sticker {
+    pub id: i64,
+    pub contact_id: i64,
+    /// 素材名称 (模型按名称选择，同一联系人内唯一)
+    pub name: String,
+    /// 本地文件路径或 data URL
+    pub path: String,
+    pub kind: StickerKind,
+    /// 用途说明，帮助模型判断什么时候发
+    pub description: Option<String>,
+}
+
+/// 消息附带的图片或表情包 (保存在 social_messages.attachment)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub kind: StickerKind,
+    pub name: String,
+    pub path: String,
+}
+
+impl From<&Sticker> for Attachment {
+    fn from(sticker: &Sticker) -> Self {
+        Self {
+            kind: sticker.kind,
+            name: sticker.name.clone(),
+            path: sticker.path.clone(),
+        }
+    }
+}
+
+/// 一个表情回应 (保存在 social_messages.reactions 数组中)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reaction {
+    pub contact_id: i64,
+    pub emoji: String,
+    /// Unix 毫秒
+    pub reacted_at: i64,
+}
+
+// ========== 素材库 ==========
+
+fn sticker_from_row(row: &rusqlite::Row) -> rusqlite::Result<Sticker> {
+    Ok(Sticker {
+        id: row.get(0)?,
+        contact_id: row.get(1)?,
+        name: row.get(2)?,
+        path: row.get(3)?,
+        kind: StickerKind::parse(&row.get::<_, String>(4)?),
+        description: row.get(5)?,
+    })
+}
+
+pub(crate) fn list_stickers(conn: &Connection, contact_id: i64) -> Result<Vec<Sticker>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, contact_id, name, path, kind, description FROM contact_stickers
+             WHERE contact_id = ?1 ORDER BY id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let stickers = stmt
+        .query_map(params![contact_id], sticker_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(stickers)
+}
+
+pub(crate) fn find_sticker(
+    conn: &Connection,
+    contact_id: i64,
+    name: &str,
+) -> Result<Option<Sticker>, String> {
+    conn.query_row(
+        "SELECT id, contact_id, name, path, kind, description FROM contact_stickers
+         WHERE contact_id = ?1 AND name = ?2",
+        params![contact_id, name],
+        sticker_from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+// ========== 结构化输出 ==========
+
+/// 模型为一次回复选择的表达方式
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ExpressionPlan {
+    /// 给最近一条用户消息的表情回应
+    pub reaction: Option<String>,
+    /// 引用回复的消息 ID
+    #[serde(rename = "quote_message_id")]
+    pub quote: Option<i64>,
+    /// 回复后发送的表情包名称
+    pub sticker: Option<String>,
+}
+
+impl ExpressionPlan {
+    /// 请求结构化输出时使用的 JSON Schema
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "reaction": { "type": ["string", "null"], "description": "单个 emoji" },
+                "quote_message_id": { "type": ["integer", "null"] },
+                "sticker": { "type": ["string", "null"] }
+            },
+            "required": ["reaction", "quote_message_id", "sticker"],
+            "additionalProperties": false
+        })
+    }
+
+    /// 解析模型输出；引用不在候选消息中、表情包不在素材库中、表情过长时丢弃对应项
+    pub fn parse(text: &str, quotable: &[i64], stickers: &[Sticker]) -> Result<Self, String> {
+        let start = text.find('{').ok_or("输出中没有 JSON 对象")?;
+        let end = text
+            .rfind('}')
+            .filter(|&end| end > start)
+            .ok_or("JSON 对象不完整")?;
+        let mut plan: Self = serde_json::from_str(&text[start..=end])
+            .map_err(|e| format!("JSON 格式不符合要求: {}", e))?;
+
+        plan.reaction = plan
+            .reaction
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty() && r.chars().count() <= MAX_REACTION_CHARS);
+        plan.quote = plan.quote.filter(|id| quotable.contains(id));
+        plan.sticker = plan
+            .sticker
+            .map(|s| s.trim().to_string())
+            .filter(|name| stickers.iter().any(|s| &s.name == name));
+        Ok(plan)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reaction.is_none() && self.quote.is_none() && self.sticker.is_none()
+    }
+
+    /// 把表达方式织入行为链：
+    /// 表情回应紧跟在已读之后，引用替换第一段发言，表情包在最后一段发言之后。
+    /// 延迟决策的行为链原样返回 (内容要等重新决策时才确定)
+    pub fn apply(&self, mut chain: Vec<BehaviorAction>) -> Vec<BehaviorAction> {
+        if chain
+            .iter()
+            .any(|a| matches!(a, BehaviorAction::DelayedDecision(..)))
+        {
+            return chain;
+        }
+
+        if let Some(id) = self.quote {
+            if let Some(action) = chain
+                .iter_mut()
+                .find(|a| matches!(a, BehaviorAction::Speak(_)))
+            {
+                if let BehaviorAction::Speak(content) = action {
+                    *action = BehaviorAction::Quote(id, std::mem::take(content));
+                }
+            }
+        }
+
+        if let Some(ref name) = self.sticker {
+            if let Some(last) = chain
+                .iter()
+                .rposition(|a| matches!(a, BehaviorAction::Speak(_) | BehaviorAction::Quote(..)))
+            {
+                chain.splice(
+                    last + 1..last + 1,
+                    [
+                        BehaviorAction::Wait(STICKER_DELAY_MS),
+                        BehaviorAction::Sticker(name.clone()),
+                    ],
+                );
+            }
+        }
+
+        if let Some(ref emoji) = self.reaction {
+            let at = match chain.first() {
+                Some(BehaviorAction::MarkRead) => 1,
+                _ => 0,
+            };
+            chain.insert(at, BehaviorAction::React(0, emoji.clone()));
+        }
+        chain
+    }
+}
+
+/// 最近的消息 (id, 角色, 内容)，用于让模型选择引用
+pub(crate) fn recent_messages(
+    conn: &Connection,
+    session_id: i64,
+) -> Result<Vec<(i64, String, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, role, content FROM social_messages
+             WHERE session_id = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut messages = stmt
+        .query_map(params![session_id, RECENT_MESSAGES as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    messages.reverse();
+    Ok(messages)
+}
+
+/// 请求模型选择表达方式 (服务端不支持结构化输出时逐级降级)
+pub async fn plan_expression(
+    client: &reqwest::Client,
+    provider: &crate::provider_config::ProviderConfig,
+    model: &str,
+    recent: &[(i64, String, String)],
+    reply: &str,
+    stickers: &[Sticker],
+) -> Result<ExpressionPlan, String> {
+    use crate::ai_utils::{call_json_backend, is_unsupported_request, JsonMode};
+    use crate::models::{ChatRequest, GenerationParams};
+
+    let template =
+        crate::character_state::StateAnalyzer::load_prompt_template("expression_plan.txt")
+            .unwrap_or_else(|_| DEFAULT_PLAN_PROMPT.to_string());
+    let history: Vec<String> = recent
+        .iter()
+        .map(|(id, role, content)| {
+            let speaker = if role == "user" { "用户" } else { "角色" };
+            format!("[{}] {}: {}", id, speaker, content.trim())
+        })
+        .collect();
+    let sticker_lines: Vec<String> = stickers
+        .iter()
+        .map(|s| match s.description {
+            Some(ref d) if !d.trim().is_empty() => format!("- {}：{}", s.name, d.trim()),
+            _ => format!("- {}", s.name),
+        })
+        .collect();
+    let mut ctx = crate::prompt_template::TemplateContext::new();
+    ctx.set(
+        "conversation_history",
+        serde_json::json!(history.join("\n")),
+    )
+    .set("reply", serde_json::json!(reply))
+    .set(
+        "stickers",
+        serde_json::json!(if sticker_lines.is_empty() {
+            "(无)".to_string()
+        } else {
+            sticker_lines.join("\n")
+        }),
+    );
+    let prompt = crate::prompt_template::render_or_raw(&template, &ctx, &|_| None).text;
+
+    let payload = ChatRequest {
+        model: model.to_string(),
+        messages: vec![crate::commands::immersive_cmd::system_message(prompt)],
+        stream: false,
+        params: GenerationParams {
+            temperature: Some(0.3),
+            max_tokens: Some(128),
+            ..Default::default()
+        },
+    };
+    let quotable: Vec<i64> = recent
+        .iter()
+        .filter(|(_, role, _)| role == "user")
+        .map(|(id, _, _)| *id)
+        .collect();
+    let schema = ExpressionPlan::json_schema();
+    let mut mode = JsonMode::Schema;
+    loop {
+        match call_json_backend(client, provider, &payload, "expression_plan", &schema, mode).await
+        {
+            Ok(output) => return ExpressionPlan::parse(&output, &quotable, stickers),
+            Err(e) => match mode.downgrade().filter(|_| is_unsupported_request(&e)) {
+                Some(next) => {
+                    println!("⚠️ [表达] {:?} 模式不受支持，降级为 {:?}", mode, next);
+                    mode = next;
+                }
+                None => return Err(e),
+            },
+        }
+    }
+}
+
+// ========== 命令 ==========
+
+#[tauri::command]
+pub fn list_contact_stickers(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    contact_id: i64,
+) -> Result<Vec<Sticker>, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    list_stickers(&conn, contact_id)
+}
+
+#[tauri::command]
+pub fn add_contact_sticker(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    contact_id: i64,
+    name: String,
+    path: String,
+    kind: StickerKind,
+    description: Option<String>,
+) -> Result<i64, String> {
+    let name = name.trim();
+    if name.is_empty() || path.trim().is_empty() {
+        return Err("素材名称和路径不能为空".to_string());
+    }
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO contact_stickers (contact_id, name, path, kind, description)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![contact_id, name, path, kind.as_str(), description],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub fn delete_contact_sticker(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    id: i64,
+) -> Result<(), String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM contact_stickers WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sticker(name: &str) -> Sticker {
+        Sticker {
+            id: 1,
+            contact_id: 1,
+            name: name.to_string(),
+            path: "/stickers/cat.png".into(),
+            kind: StickerKind::Sticker,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_filters_invalid_choices() {
+        let stickers = vec![sticker("猫猫点头")];
+        let plan = ExpressionPlan::parse(
+            "```json\n{\"reaction\": \" 👍 \", \"quote_message_id\": 12, \"sticker\": \"猫猫点头\"}\n```",
+            &[12, 14],
+            &stickers,
+        )
+        .unwrap();
+        assert_eq!(plan.reaction.as_deref(), Some("👍"));
+        assert_eq!(plan.quote, Some(12));
+        assert_eq!(plan.sticker.as_deref(), Some("猫猫点头"));
+
+        let plan = ExpressionPlan::parse(
+            "{\"reaction\": \"这条消息太好笑了哈哈哈\", \"quote_message_id\": 3, \"sticker\": \"狗狗\"}",
+            &[12, 14],
+            &stickers,
+        )
+        .unwrap();
+        assert!(plan.is_empty());
+        assert!(ExpressionPlan::parse("null", &[], &stickers).is_err());
+    }
+
+    #[test]
+    fn test_apply_weaves_actions_into_chain() {
+        let plan = ExpressionPlan {
+            reaction: Some("😂".into()),
+            quote: Some(7),
+            sticker: Some("猫猫点头".into()),
+        };
+        let chain = plan.apply(vec![
+            BehaviorAction::MarkRead,
+            BehaviorAction::Wait(1000),
+            BehaviorAction::Speak("哈哈".into()),
+            BehaviorAction::Wait(300),
+            BehaviorAction::Speak("太逗了".into()),
+        ]);
+        assert_eq!(
+            chain,
+            vec![
+                BehaviorAction::MarkRead,
+                BehaviorAction::React(0, "😂".into()),
+                BehaviorAction::Wait(1000),
+                BehaviorAction::Quote(7, "哈哈".into()),
+                BehaviorAction::Wait(300),
+                BehaviorAction::Speak("太逗了".into()),
+                BehaviorAction::Wait(STICKER_DELAY_MS),
+                BehaviorAction::Sticker("猫猫点头".into()),
+            ]
+        );
+
+        // 已读不回时仍可以点个表情；延迟决策不做改动
+        assert_eq!(
+            plan.apply(vec![BehaviorAction::Idle]),
+            vec![BehaviorAction::React(0, "😂".into()), BehaviorAction::Idle]
+        );
+        let delayed = vec![BehaviorAction::DelayedDecision(1000, "嗯".into())];
+        assert_eq!(plan.apply(delayed.clone()), delayed);
+    }
+}
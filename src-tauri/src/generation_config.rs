@@ -0,0 +1,394 @@
+use crate::commands::config_cmd::AppConfig;
+use crate::models::{GenerationParams, Message};
+use crate::provider_config::parse_providers;
+use serde_json::{json, Value};
+
+/// 会话层覆盖 (来自 sessions 表或调用方指定)
+#[derive(Debug, Clone, Default)]
+pub struct SessionOverrides {
+    pub preset_id: Option<String>,
+    pub model_id: Option<String>,
+    pub system_prompt: Option<String>,
+    pub params: GenerationParams,
+}
+
+impl SessionOverrides {
+    pub fn from_session(session: &crate::db::ChatSession) -> Self {
+        let params = session
+            .generation_params
+            .as_deref()
+            .and_then(|raw| serde_json::from_str::<Value>(raw).ok())
+            .map(|v| params_from_value(&v))
+            .unwrap_or_default();
+        Self {
+            preset_id: session.preset_id.clone(),
+            model_id: session.model_id.clone(),
+            system_prompt: session.system_prompt.clone(),
+            params,
+        }
+    }
+}
+
+/// 最终生效的生成配置
+#[derive(Debug, Clone)]
+pub struct ResolvedGeneration {
+    pub provider_id: String,
+    pub model: String,
+    /// 会话显式设置的系统提示词 (优先级高于前端传入的 system 消息)
+    pub session_system_prompt: Option<String>,
+    /// 预设中的系统提示词 (仅在消息中没有 system 时使用)
+    pub preset_system_prompt: Option<String>,
+    pub params: GenerationParams,
+}
+
+fn non_empty(s: Option<&str>) -> Option<String> {
+    s.map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+}
+
+fn f32_of(v: &Value, key: &str) -> Option<f32> {
+    v[key].as_f64().map(|f| f as f32)
+}
+
+/// 从配置 JSON (预设 / 提供商 / 会话覆盖，均为驼峰命名) 中读取生成参数
+pub fn params_from_value(v: &Value) -> GenerationParams {
+    let stop = match &v["stop"] {
+        Value::String(s) if !s.is_empty() => Some(vec![s.clone()]),
+        Value::Array(arr) => {
+            let list: Vec<String> = arr
+                .iter()
+                .filter_map(|s| s.as_str())
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect();
+            if list.is_empty() {
+                None
+            } else {
+                Some(list)
+            }
+        }
+        _ => None,
+    };
+
+    GenerationParams {
+        temperature: f32_of(v, "temperature"),
+        max_tokens: v["maxTokens"]
+            .as_u64()
+            .or_else(|| v["max_tokens"].as_u64())
+            .filter(|n| *n > 0)
+            .map(|n| n as u32),
+        top_p: f32_of(v, "topP").or_else(|| f32_of(v, "top_p")),
+        presence_penalty: f32_of(v, "presencePenalty").or_else(|| f32_of(v, "presence_penalty")),
+        frequency_penalty: f32_of(v, "frequencyPenalty").or_else(|| f32_of(v, "frequency_penalty")),
+        stop,
+        seed: v["seed"].as_i64(),
+        reasoning_effort: non_empty(
+            v["reasoningEffort"]
+                .as_str()
+                .or_else(|| v["reasoning_effort"].as_str()),
+        ),
+    }
+}
+
+/// 逐字段合并：upper 中为空的字段由 lower 补齐
+pub fn merge_params(upper: GenerationParams, lower: GenerationParams) -> GenerationParams {
+    GenerationParams {
+        temperature: upper.temperature.or(lower.temperature),
+        max_tokens: upper.max_tokens.or(lower.max_tokens),
+        top_p: upper.top_p.or(lower.top_p),
+        presence_penalty: upper.presence_penalty.or(lower.presence_penalty),
+        frequency_penalty: upper.frequency_penalty.or(lower.frequency_penalty),
+        stop: upper.stop.or(lower.stop),
+        seed: upper.seed.or(lower.seed),
+        reasoning_effort: upper.reasoning_effort.or(lower.reasoning_effort),
+    }
+}
+
+/// 拆分 "提供商ID/模型ID" 形式的模型引用；只写模型 ID 时提供商为 None，空串或 "default" 表示默认模型
+/// 模型 ID 本身可能含有 "/" (如 deepseek-ai/DeepSeek-V3.2)，前缀是已配置的提供商 ID 时才拆分
+pub fn split_model_ref(model: &str, providers: &Value) -> (Option<String>, Option<String>) {
+    let model = model.trim();
+    if model.is_empty() || model == "default" {
+        return (None, None);
+    }
+    let is_provider = |id: &str| {
+        providers
+            .as_array()
+            .is_some_and(|list| list.iter().any(|p| p["id"].as_str() == Some(id)))
+    };
+    match model.split_once('/') {
+        Some((provider, rest)) if is_provider(provider) => {
+            (Some(provider.to_string()), Some(rest.to_string()))
+        }
+        _ => (None, Some(model.to_string())),
+    }
+}
+
+/// 解析生效配置
+///
+/// 参数优先级：调用方显式参数 > 会话覆盖 > 预设 (会话预设，否则全局默认预设) > 提供商默认值 > 调用方兜底值
+pub fn resolve_generation(
+    config: &AppConfig,
+    session: Option<&SessionOverrides>,
+    explicit_provider_id: Option<String>,
+    explicit_model_id: Option<String>,
+    call_params: GenerationParams,
+    fallback_params: GenerationParams,
+) -> ResolvedGeneration {
+    let providers = parse_providers(&config.providers);
+    let session_model = session.and_then(|s| non_empty(s.model_id.as_deref()));
+
+    let model = explicit_model_id
+        .clone()
+        .or_else(|| session_model.clone())
+        .unwrap_or_else(|| config.selected_model_id.clone());
+
+    // 会话只保存了模型 ID：未显式指定提供商时，选择声明了该模型的提供商
+    let provider_id = explicit_provider_id
+        .or_else(|| {
+            if explicit_model_id.is_some() {
+                return None;
+            }
+            let session_model = session_model.as_deref()?;
+            providers
+                .iter()
+                .find(|p| p.has_model(session_model))
+                .map(|p| p.id.clone())
+        })
+        .unwrap_or_else(|| config.default_provider_id.clone());
+
+    let provider = providers.iter().find(|p| p.id == provider_id);
+
+    let preset_id = session
+        .and_then(|s| non_empty(s.preset_id.as_deref()))
+        .or_else(|| non_empty(Some(config.default_preset_id.as_str())));
+    let preset = preset_id.as_deref().and_then(|id| {
+        config
+            .presets
+            .as_array()
+            .and_then(|arr| arr.iter().find(|p| p["id"].as_str() == Some(id)))
+    });
+
+    let mut params = call_params;
+    if let Some(s) = session {
+        params = merge_params(params, s.params.clone());
+    }
+    if let Some(p) = preset {
+        params = merge_params(params, params_from_value(p));
+    }
+    if let Some(p) = provider {
+        params = merge_params(params, p.defaults.clone());
+    }
+    params = merge_params(params, fallback_params);
+
+    ResolvedGeneration {
+        provider_id,
+        model,
+        session_system_prompt: session.and_then(|s| non_empty(s.system_prompt.as_deref())),
+        preset_system_prompt: preset.and_then(|p| non_empty(p["systemPrompt"].as_str())),
+        params,
+    }
+}
+
+impl ResolvedGeneration {
+    /// 把系统提示词应用到消息列表
+    /// 会话显式设置的提示词替换首条 system 消息；否则仅在缺少 system 消息时注入预设提示词
+    pub fn apply_system_prompt(&self, messages: &mut Vec<Message>) {
+        let existing = messages.iter_mut().find(|m| m.role == "system");
+        match (
+            existing,
+            &self.session_system_prompt,
+            &self.preset_system_prompt,
+        ) {
+            (Some(sys), Some(prompt), _) => sys.content = prompt.clone(),
+            (None, Some(prompt), _) | (None, None, Some(prompt)) => messages.insert(
+                0,
+                Message {
+                    id: None,
+                    model: None,
+                    role: "system".to_string(),
+                    content: prompt.clone(),
+                    reasoning_content: None,
+                    file_metadata: None,
+                    search_metadata: None,
+                    provider: None,
+                    mode: None,
+                    role_id: None,
+                    parent_id: None,
+                },
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// 转换为 Gemini 原生 generationConfig
+pub fn gemini_generation_config(params: &GenerationParams) -> Option<Value> {
+    let mut cfg = serde_json::Map::new();
+    if let Some(v) = params.temperature {
+        cfg.insert("temperature".into(), json!(v));
+    }
+    if let Some(v) = params.max_tokens {
+        cfg.insert("maxOutputTokens".into(), json!(v));
+    }
+    if let Some(v) = params.top_p {
+        cfg.insert("topP".into(), json!(v));
+    }
+    if let Some(v) = params.presence_penalty {
+        cfg.insert("presencePenalty".into(), json!(v));
+    }
+    if let Some(v) = params.frequency_penalty {
+        cfg.insert("frequencyPenalty".into(), json!(v));
+    }
+    if let Some(v) = &params.stop {
+        cfg.insert("stopSequences".into(), json!(v));
+    }
+    if let Some(v) = params.seed {
+        cfg.insert("seed".into(), json!(v));
+    }
+    // reasoning_effort 没有 Gemini 对应字段，忽略
+    if cfg.is_empty() {
+        None
+    } else {
+        Some(Value::Object(cfg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AppConfig {
+        let mut config: AppConfig = serde_json::from_value(json!({})).unwrap();
+        config.providers = json!([
+            { "id": "deepseek", "models": ["deepseek-chat"], "temperature": 1.0, "maxTokens": 8192 },
+            { "id": "openai", "models": [{ "id": "gpt-4o" }], "temperature": 0.7, "maxTokens": 4096, "topP": 0.9 }
+        ]);
+        config.presets = json!([
+            { "id": "default_preset", "temperature": 0.5, "systemPrompt": "" },
+            { "id": "coder", "temperature": 0.2, "maxTokens": 2000, "stop": "END", "systemPrompt": "你是程序员" }
+        ]);
+        config.default_provider_id = "deepseek".into();
+        config.selected_model_id = "deepseek-chat".into();
+        config.default_preset_id = "default_preset".into();
+        config
+    }
+
+    #[test]
+    fn test_precedence_chain() {
+        let cfg = config();
+        let session = SessionOverrides {
+            preset_id: Some("coder".into()),
+            model_id: Some("gpt-4o".into()),
+            system_prompt: None,
+            params: GenerationParams {
+                seed: Some(42),
+                ..Default::default()
+            },
+        };
+        let r = resolve_generation(
+            &cfg,
+            Some(&session),
+            None,
+            None,
+            GenerationParams {
+                max_tokens: Some(100),
+                ..Default::default()
+            },
+            GenerationParams::default(),
+        );
+        // 会话模型推断出提供商
+        assert_eq!(r.provider_id, "openai");
+        assert_eq!(r.model, "gpt-4o");
+        assert_eq!(r.params.max_tokens, Some(100)); // 调用方
+        assert_eq!(r.params.seed, Some(42)); // 会话
+        assert_eq!(r.params.temperature, Some(0.2)); // 预设
+        assert_eq!(r.params.stop, Some(vec!["END".to_string()])); // 预设 (字符串形式)
+        assert_eq!(r.params.top_p, Some(0.9)); // 提供商
+        assert_eq!(r.preset_system_prompt.as_deref(), Some("你是程序员"));
+    }
+
+    #[test]
+    fn test_falls_back_to_global_defaults() {
+        let cfg = config();
+        let r = resolve_generation(
+            &cfg,
+            None,
+            None,
+            None,
+            GenerationParams::default(),
+            GenerationParams {
+                reasoning_effort: Some("low".into()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(r.provider_id, "deepseek");
+        assert_eq!(r.model, "deepseek-chat");
+        assert_eq!(r.params.temperature, Some(0.5));
+        assert_eq!(r.params.max_tokens, Some(8192));
+        assert_eq!(r.params.reasoning_effort.as_deref(), Some("low"));
+        // 空的预设提示词不生效
+        assert!(r.preset_system_prompt.is_none());
+    }
+
+    #[test]
+    fn test_apply_system_prompt() {
+        let cfg = config();
+        let session = SessionOverrides {
+            preset_id: Some("coder".into()),
+            ..Default::default()
+        };
+        let r = resolve_generation(
+            &cfg,
+            Some(&session),
+            None,
+            None,
+            GenerationParams::default(),
+            GenerationParams::default(),
+        );
+        let mut msgs: Vec<Message> = vec![];
+        r.apply_system_prompt(&mut msgs);
+        assert_eq!(msgs[0].content, "你是程序员");
+
+        let session = SessionOverrides {
+            system_prompt: Some("会话提示词".into()),
+            ..Default::default()
+        };
+        let r = resolve_generation(
+            &cfg,
+            Some(&session),
+            None,
+            None,
+            GenerationParams::default(),
+            GenerationParams::default(),
+        );
+        r.apply_system_prompt(&mut msgs);
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].content, "会话提示词");
+    }
+
+    #[test]
+    fn test_request_serialization_uses_openai_names() {
+        let params = GenerationParams {
+            temperature: Some(0.5),
+            top_p: Some(0.8),
+            reasoning_effort: Some("high".into()),
+            ..Default::default()
+        };
+        let body = serde_json::to_value(crate::models::ChatRequest {
+            model: "m".into(),
+            messages: vec![],
+            stream: true,
+            params: params.clone(),
+        })
+        .unwrap();
+        assert_eq!(body["top_p"], json!(0.8f32));
+        assert_eq!(body["reasoning_effort"], "high");
+        assert!(body.get("seed").is_none());
+
+        let gemini = gemini_generation_config(&params).unwrap();
+        assert_eq!(gemini["topP"], json!(0.8f32));
+        assert!(gemini_generation_config(&GenerationParams::default()).is_none());
+    }
+}
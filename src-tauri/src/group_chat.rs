@@ -0,0 +1,975 @@
+use crate::behavior_engine::{BehaviorEngine, SessionContext};
+use crate::behavior_scheduler::MessageScheduler;
+use crate::commands::config_cmd;
+use crate::immersive_settings::GroupChatConfig;
+use crate::models::Message;
+use crate::social_db::{SocialDbState, SocialMessage};
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tokio_util::sync::CancellationToken;
+
+// ==================================================================================
+// 群聊：多个 AI 联系人共享同一个会话。用户发言后由轮流发言编排器决定谁回复、
+// 按什么顺序、间隔多久；每位成员使用自己的提示词、模型、角色状态与作息，
+// 看到的历史带有发言人标注，行为链按 (会话, 联系人) 各自执行
+// ==================================================================================
+
+/// 生成回复时最多带上的群聊历史条数
+const HISTORY_LIMIT: i64 = 40;
+/// 上一位发言者再次接话的概率折扣 (避免同一个人连续霸占话题)
+const LAST_SPEAKER_PENALTY: f32 = 0.7;
+
+/// 群聊成员
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupMember {
+    pub contact_id: i64,
+    pub name: String,
+    pub avatar: Option<String>,
+}
+
+/// 群聊会话
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupSession {
+    pub id: i64,
+    pub title: String,
+    pub members: Vec<GroupMember>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// 参与本轮发言规划的成员
+#[derive(Debug, Clone)]
+pub struct Participant {
+    pub contact_id: i64,
+    pub name: String,
+    pub context: SessionContext,
+}
+
+/// 规划出的一次发言
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedTurn {
+    pub contact_id: i64,
+    /// 上一位发言者的行为链结束后再等待的毫秒数 (第一位为 0，回复延迟由行为引擎决定)
+    pub delay_ms: u32,
+    /// 被用户点名：一定回复，且不走已读不回
+    pub mentioned: bool,
+}
+
+/// 群聊历史中的一条消息 (contact_id 为发言的联系人，用户消息为群主)
+#[derive(Debug, Clone)]
+pub struct GroupLine {
+    pub contact_id: i64,
+    pub role: String,
+    pub content: String,
+}
+
+// ========== 数据库 ==========
+
+fn is_group(conn: &Connection, session_id: i64) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT is_group FROM social_sessions WHERE id = ?1",
+        params![session_id],
+        |row| row.get::<_, bool>(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("会话 {} 不存在", session_id))
+}
+
+fn distinct_members(contact_ids: &[i64]) -> Result<Vec<i64>, String> {
+    let mut members = Vec::new();
+    for id in contact_ids {
+        if !members.contains(id) {
+            members.push(*id);
+        }
+    }
+    if members.len() < 2 {
+        return Err("群聊至少需要两位联系人".to_string());
+    }
+    Ok(members)
+}
+
+fn insert_members(conn: &Connection, session_id: i64, members: &[i64]) -> Result<(), String> {
+    for (index, contact_id) in members.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO social_session_members (session_id, contact_id, sort_order)
+             VALUES (?1, ?2, ?3)",
+            params![session_id, contact_id, index as i64],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// 创建群聊；social_sessions.contact_id 记为第一位成员 (群主)，用户消息也记在群主名下
+pub(crate) fn create_group(
+    conn: &Connection,
+    title: &str,
+    contact_ids: &[i64],
+) -> Result<i64, String> {
+    let members = distinct_members(contact_ids)?;
+    let title = if title.trim().is_empty() {
+        "群聊"
+    } else {
+        title.trim()
+    };
+    conn.execute(
+        "INSERT INTO social_sessions (contact_id, title, is_group) VALUES (?1, ?2, 1)",
+        params![members[0], title],
+    )
+    .map_err(|e| e.to_string())?;
+    let session_id = conn.last_insert_rowid();
+    insert_members(conn, session_id, &members)?;
+    Ok(session_id)
+}
+
+/// 替换群聊成员 (顺序即成员列表的显示顺序)
+pub(crate) fn set_members(
+    conn: &Connection,
+    session_id: i64,
+    contact_ids: &[i64],
+) -> Result<(), String> {
+    if !is_group(conn, session_id)? {
+        return Err(format!("会话 {} 不是群聊", session_id));
+    }
+    let members = distinct_members(contact_ids)?;
+    conn.execute(
+        "DELETE FROM social_session_members WHERE session_id = ?1",
+        params![session_id],
+    )
+    .map_err(|e| e.to_string())?;
+    insert_members(conn, session_id, &members)?;
+    transfer_owner(conn, session_id, members[0])
+}
+
+/// 更换群主；用户消息记在群主名下，需要随群主一起迁移
+fn transfer_owner(conn: &Connection, session_id: i64, new_owner: i64) -> Result<(), String> {
+    let old_owner: i64 = conn
+        .query_row(
+            "SELECT contact_id FROM social_sessions WHERE id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if old_owner == new_owner {
+        return Ok(());
+    }
+    conn.execute(
+        "UPDATE social_messages SET contact_id = ?1
+         WHERE session_id = ?2 AND contact_id = ?3 AND role = 'user'",
+        params![new_owner, session_id, old_owner],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE social_sessions SET contact_id = ?1 WHERE id = ?2",
+        params![new_owner, session_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 删除联系人前调用：由其担任群主的群聊改由下一位成员接任，
+/// 避免外键级联把整个群聊 (包括用户的发言) 一起删掉；没有其他成员的群聊随之删除
+pub(crate) fn hand_over_groups(conn: &Connection, contact_id: i64) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM social_sessions WHERE is_group = 1 AND contact_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let sessions = stmt
+        .query_map(params![contact_id], |row| row.get::<_, i64>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    for session_id in sessions {
+        let next: Option<i64> = conn
+            .query_row(
+                "SELECT contact_id FROM social_session_members
+                 WHERE session_id = ?1 AND contact_id != ?2
+                 ORDER BY sort_order ASC LIMIT 1",
+                params![session_id, contact_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if let Some(next) = next {
+            transfer_owner(conn, session_id, next)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn load_members(conn: &Connection, session_id: i64) -> Result<Vec<GroupMember>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.id, c.name, c.avatar
+             FROM social_session_members m
+             JOIN contacts c ON c.id = m.contact_id
+             WHERE m.session_id = ?1
+             ORDER BY m.sort_order ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let members = stmt
+        .query_map(params![session_id], |row| {
+            Ok(GroupMember {
+                contact_id: row.get(0)?,
+                name: row.get(1)?,
+                avatar: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(members)
+}
+
+pub(crate) fn list_groups(conn: &Connection) -> Result<Vec<GroupSession>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT s.id, s.title, s.created_at,
+                    COALESCE(MAX(m.created_at), s.updated_at) as last_activity
+             FROM social_sessions s
+             LEFT JOIN social_messages m ON s.id = m.session_id
+             WHERE s.is_group = 1
+             GROUP BY s.id
+             ORDER BY last_activity DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    rows.into_iter()
+        .map(|(id, title, created_at, updated_at)| {
+            Ok(GroupSession {
+                id,
+                title,
+                members: load_members(conn, id)?,
+                created_at,
+                updated_at,
+            })
+        })
+        .collect()
+}
+
+/// 群聊最近的 limit 条消息 (正序)
+fn recent_lines(conn: &Connection, session_id: i64, limit: i64) -> Result<Vec<GroupLine>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT contact_id, role, content FROM social_messages
+             WHERE session_id = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut lines = stmt
+        .query_map(params![session_id, limit], |row| {
+            Ok(GroupLine {
+                contact_id: row.get(0)?,
+                role: row.get(1)?,
+                content: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    lines.reverse();
+    Ok(lines)
+}
+
+/// 成员在群聊中的上下文：该会话缓存的角色状态 + 当前作息
+fn participant_context(conn: &Connection, session_id: i64, contact_id: i64) -> SessionContext {
+    let state = crate::character_state::CharacterState::load(conn, contact_id, session_id)
+        .ok()
+        .flatten();
+    SessionContext {
+        session_id,
+        contact_id,
+        mood: state.as_ref().map(|s| s.mood.clone()),
+        busy_level: state.as_ref().map(|s| s.busy_level),
+        interest_level: state.as_ref().map(|s| s.interest_level),
+        activity: crate::routine::contact_activity(conn, contact_id, chrono::Utc::now()),
+    }
+}
+
+// ========== 轮流发言规划 ==========
+
+/// 决定谁回复、按什么顺序
+///
+/// - 消息里提到名字的成员一定回复，按被提到的先后排在最前
+/// - 其余成员按 基础概率 × 作息可用度 × 兴趣/忙碌 掷骰，越想说的越先说
+/// - 除点名外最多 max_responders 人；没人愿意说时由最想说的那位接话
+pub fn plan_turns<R: Rng>(
+    participants: &[Participant],
+    message: &str,
+    last_speaker: Option<i64>,
+    config: &GroupChatConfig,
+    rng: &mut R,
+) -> Vec<PlannedTurn> {
+    let mut mentioned: Vec<(usize, i64)> = participants
+        .iter()
+        .filter(|p| !p.name.trim().is_empty())
+        .filter_map(|p| message.find(p.name.as_str()).map(|pos| (pos, p.contact_id)))
+        .collect();
+    mentioned.sort_by_key(|(pos, _)| *pos);
+
+    // (意愿余量, 联系人)：余量 > 0 表示这一轮愿意开口
+    let mut eager: Vec<(f32, i64)> = participants
+        .iter()
+        .filter(|p| !mentioned.iter().any(|(_, id)| *id == p.contact_id))
+        .map(|p| {
+            let probability = reply_probability(p, last_speaker, config);
+            (probability - rng.gen::<f32>(), p.contact_id)
+        })
+        .collect();
+    eager.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let slots = (config.max_responders as usize).saturating_sub(mentioned.len());
+    let mut order: Vec<(i64, bool)> = mentioned.iter().map(|(_, id)| (*id, true)).collect();
+    order.extend(
+        eager
+            .iter()
+            .filter(|(margin, _)| *margin > 0.0)
+            .take(slots)
+            .map(|(_, id)| (*id, false)),
+    );
+    if order.is_empty() {
+        if let Some((_, id)) = eager.first() {
+            order.push((*id, false));
+        }
+    }
+
+    let (gap_min, gap_max) = config.turn_gap_ms;
+    order
+        .into_iter()
+        .enumerate()
+        .map(|(index, (contact_id, mentioned))| PlannedTurn {
+            contact_id,
+            delay_ms: if index == 0 {
+                0
+            } else {
+                rng.gen_range(gap_min.min(gap_max)..=gap_max.max(gap_min))
+            },
+            mentioned,
+        })
+        .collect()
+}
+
+fn reply_probability(
+    participant: &Participant,
+    last_speaker: Option<i64>,
+    config: &GroupChatConfig,
+) -> f32 {
+    let context = &participant.context;
+    let availability = context
+        .activity
+        .as_ref()
+        .map(|a| a.availability)
+        .unwrap_or(1.0);
+    let interest = 0.5 + context.interest_level.unwrap_or(0.5);
+    let busy = 1.0 - context.busy_level.unwrap_or(0.0) * 0.5;
+    let mut probability = config.reply_probability * availability * interest * busy;
+    if last_speaker == Some(participant.contact_id) {
+        probability *= LAST_SPEAKER_PENALTY;
+    }
+    probability.clamp(0.0, 1.0)
+}
+
+// ========== 发言人标注 ==========
+
+/// 以 speaker 的视角组织群聊历史：自己的发言是 assistant，
+/// 用户与其他成员的发言是 user，并以「【名字】: 」标注发言人
+pub fn attributed_history(
+    lines: &[GroupLine],
+    speaker: i64,
+    names: &HashMap<i64, String>,
+    user_name: &str,
+) -> Vec<Message> {
+    lines
+        .iter()
+        .map(|line| {
+            let (role, content) = if line.role == "user" {
+                ("user", format!("【{}】: {}", user_name, line.content))
+            } else if line.contact_id == speaker {
+                ("assistant", line.content.clone())
+            } else {
+                let name = names
+                    .get(&line.contact_id)
+                    .map(String::as_str)
+                    .unwrap_or("群成员");
+                ("user", format!("【{}】: {}", name, line.content))
+            };
+            Message {
+                id: None,
+                model: None,
+                role: role.to_string(),
+                content,
+                reasoning_content: None,
+                file_metadata: None,
+                search_metadata: None,
+                provider: None,
+                mode: None,
+                role_id: None,
+                parent_id: None,
+            }
+        })
+        .collect()
+}
+
+/// 追加在成员系统提示词后的群聊说明
+pub fn group_prompt(
+    title: &str,
+    members: &[GroupMember],
+    speaker: &str,
+    user_name: &str,
+) -> String {
+    let others: Vec<&str> = members
+        .iter()
+        .map(|m| m.name.as_str())
+        .filter(|name| *name != speaker)
+        .collect();
+    format!(
+        "【群聊】你正在群聊「{}」中，成员有：{} (用户)、{}。\
+         其他人的消息以「【名字】: 」开头。你只以{}的身份发言，不要替别人说话，\
+         回复时不要加名字前缀；可以回应任何人，也可以接着别人的话题聊。",
+        title,
+        user_name,
+        others.join("、"),
+        speaker
+    )
+}
+
+/// 模型偶尔会模仿历史格式给自己加上「【名字】: 」，去掉它
+pub fn strip_speaker_prefix(reply: &str, name: &str) -> String {
+    let trimmed = reply.trim();
+    let prefix = format!("【{}】", name);
+    match trimmed.strip_prefix(prefix.as_str()) {
+        Some(rest) => rest
+            .trim_start_matches([':', '：'])
+            .trim_start()
+            .to_string(),
+        None => trimmed.to_string(),
+    }
+}
+
+// ========== 编排 ==========
+
+/// 为一位成员生成回复 (非流式：群聊回复通过行为链逐段发出)
+async fn generate_reply(
+    app: &AppHandle,
+    config: &config_cmd::AppConfig,
+    session_id: i64,
+    participant: &Participant,
+) -> Result<String, String> {
+    let (messages, contact_provider, contact_model) = {
+        let db_state = app.state::<SocialDbState>();
+        let conn = db_state.0.lock().map_err(|e| e.to_string())?;
+        let members = load_members(&conn, session_id)?;
+        let names: HashMap<i64, String> = members
+            .iter()
+            .map(|m| (m.contact_id, m.name.clone()))
+            .collect();
+        let title: String = conn
+            .query_row(
+                "SELECT title FROM social_sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let user_name: String = conn
+            .query_row(
+                "SELECT nickname FROM profiles ORDER BY id ASC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or_else(|_| "我".to_string());
+        let (prompt, provider, model): (Option<String>, Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT prompt, provider, model FROM contacts WHERE id = ?1",
+                params![participant.contact_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let note = group_prompt(&title, &members, &participant.name, &user_name);
+        let system = match crate::commands::immersive_cmd::contact_system_prompt(
+            &conn,
+            config,
+            prompt,
+            participant.contact_id,
+            session_id,
+            &participant.context,
+        ) {
+            Some(prompt) => format!("{}\n\n{}", prompt, note),
+            None => format!("你是{}。\n\n{}", participant.name, note),
+        };
+
+        let lines = recent_lines(&conn, session_id, HISTORY_LIMIT)?;
+        let mut messages = vec![crate::commands::immersive_cmd::system_message(system)];
+        messages.extend(attributed_history(
+            &lines,
+            participant.contact_id,
+            &names,
+            &user_name,
+        ));
+        (messages, provider, model)
+    };
+
+    let resolved = crate::commands::immersive_cmd::resolve_contact_generation(
+        config,
+        contact_provider,
+        contact_model,
+    );
+    let provider = crate::provider_config::resolve_provider(config, &resolved.provider_id)?;
+    let client = app
+        .state::<crate::net::HttpClients>()
+        .for_provider(&provider)?;
+    let reply = crate::ai_utils::complete_chat(
+        &client,
+        &provider,
+        &resolved.model,
+        messages,
+        resolved.params,
+    )
+    .await?;
+    Ok(strip_speaker_prefix(&reply, &participant.name))
+}
+
+/// 依次执行规划好的发言：每位成员生成时都能看到前面成员刚发出的消息
+async fn run_group_turns(
+    app: AppHandle,
+    scheduler: Arc<MessageScheduler>,
+    config: config_cmd::AppConfig,
+    session_id: i64,
+    participants: Vec<Participant>,
+    turns: Vec<PlannedTurn>,
+    token: CancellationToken,
+) {
+    let settings = config.immersive_mode.clone();
+    for turn in turns {
+        if turn.delay_ms > 0 {
+            tokio::select! {
+                _ = token.cancelled() => return,
+                _ = tokio::time::sleep(tokio::time::Duration::from_millis(turn.delay_ms as u64)) => {}
+            }
+        }
+        if token.is_cancelled() {
+            return;
+        }
+        let Some(participant) = participants
+            .iter()
+            .find(|p| p.contact_id == turn.contact_id)
+        else {
+            continue;
+        };
+
+        println!("[群聊] {} 准备发言", participant.name);
+        let reply = match generate_reply(&app, &config, session_id, participant).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                eprintln!("❌ [群聊] {} 生成回复失败: {}", participant.name, e);
+                continue;
+            }
+        };
+        if reply.is_empty() || token.is_cancelled() {
+            continue;
+        }
+
+        let chain = {
+            let engine = BehaviorEngine::new(settings.clone());
+            if turn.mentioned {
+                engine.decide_delivery(&reply, &participant.context)
+            } else {
+                engine.decide(&reply, &participant.context)
+            }
+        };
+        scheduler
+            .run_participant_chain(
+                session_id,
+                participant.contact_id,
+                chain,
+                participant.context.clone(),
+                settings.clone(),
+                &token,
+            )
+            .await;
+    }
+    scheduler.end_group_turns(session_id, &token).await;
+}
+
+// ========== 命令 ==========
+
+#[tauri::command]
+pub fn create_group_session(
+    state: State<'_, SocialDbState>,
+    title: String,
+    contact_ids: Vec<i64>,
+) -> Result<i64, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    create_group(&conn, &title, &contact_ids)
+}
+
+#[tauri::command]
+pub fn get_group_sessions(state: State<'_, SocialDbState>) -> Result<Vec<GroupSession>, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    list_groups(&conn)
+}
+
+#[tauri::command]
+pub fn get_group_members(
+    state: State<'_, SocialDbState>,
+    session_id: i64,
+) -> Result<Vec<GroupMember>, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    load_members(&conn, session_id)
+}
+
+#[tauri::command]
+pub fn set_group_members(
+    state: State<'_, SocialDbState>,
+    session_id: i64,
+    contact_ids: Vec<i64>,
+) -> Result<(), String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    set_members(&conn, session_id, &contact_ids)
+}
+
+/// 群聊消息分页 (contact_id 为发言的联系人)
+#[tauri::command]
+pub fn get_group_messages(
+    state: State<'_, SocialDbState>,
+    session_id: i64,
+    limit: i64,
+    before_id: i64,
+) -> Result<Vec<SocialMessage>, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, contact_id, session_id, role, content, file_metadata, created_at,
+                    quote_id, attachment, reactions, read_at, edited_at
+             FROM social_messages
+             WHERE session_id = ?1 AND id < ?2
+             ORDER BY id DESC LIMIT ?3",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut messages = stmt
+        .query_map(
+            params![session_id, before_id, limit],
+            SocialMessage::from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    messages.reverse();
+    Ok(messages)
+}
+
+/// 用户在群聊中发言后调用 (用户消息已经由前端保存在群主名下)
+///
+/// 取消上一轮尚未完成的发言，规划本轮谁回复，并在后台依次执行
+#[tauri::command]
+pub async fn send_group_message_immersive(
+    app: AppHandle,
+    scheduler: State<'_, Arc<MessageScheduler>>,
+    session_id: i64,
+    content: String,
+) -> Result<(), String> {
+    let config = config_cmd::load_config(app.clone()).await?;
+
+    let (participants, last_speaker) = {
+        let db_state = app.state::<SocialDbState>();
+        let conn = db_state.0.lock().map_err(|e| e.to_string())?;
+        if !is_group(&conn, session_id)? {
+            return Err(format!("会话 {} 不是群聊", session_id));
+        }
+        let participants: Vec<Participant> = load_members(&conn, session_id)?
+            .into_iter()
+            .map(|m| Participant {
+                context: participant_context(&conn, session_id, m.contact_id),
+                contact_id: m.contact_id,
+                name: m.name,
+            })
+            .collect();
+        let last_speaker: Option<i64> = conn
+            .query_row(
+                "SELECT contact_id FROM social_messages
+                 WHERE session_id = ?1 AND role = 'assistant'
+                 ORDER BY id DESC LIMIT 1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        (participants, last_speaker)
+    };
+    if participants.is_empty() {
+        return Err("群聊没有成员".to_string());
+    }
+
+    let turns = plan_turns(
+        &participants,
+        &content,
+        last_speaker,
+        &config.immersive_mode.behaviors.group_chat,
+        &mut rand::thread_rng(),
+    );
+    println!(
+        "[群聊] 会话 {} 本轮发言: {:?}",
+        session_id,
+        turns.iter().map(|t| t.contact_id).collect::<Vec<_>>()
+    );
+
+    let token = scheduler.begin_group_turns(session_id).await;
+    let scheduler = scheduler.inner().clone();
+    tauri::async_runtime::spawn(run_group_turns(
+        app,
+        scheduler,
+        config,
+        session_id,
+        participants,
+        turns,
+        token,
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routine::{ActivityKind, ActivityState};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn participant(contact_id: i64, name: &str) -> Participant {
+        Participant {
+            contact_id,
+            name: name.to_string(),
+            context: SessionContext {
+                session_id: 1,
+                contact_id,
+                mood: None,
+                busy_level: None,
+                interest_level: None,
+                activity: None,
+            },
+        }
+    }
+
+    fn asleep(mut p: Participant) -> Participant {
+        p.context.activity = Some(ActivityState {
+            kind: ActivityKind::Sleeping,
+            label: "睡觉".into(),
+            availability: 0.0,
+            local_time: "星期三 03:12".into(),
+            until: None,
+        });
+        p
+    }
+
+    #[test]
+    fn test_mentioned_members_reply_first() {
+        let config = GroupChatConfig {
+            reply_probability: 0.0,
+            max_responders: 1,
+            turn_gap_ms: (1000, 1000),
+        };
+        let members = vec![
+            participant(1, "Alice"),
+            participant(2, "Bob"),
+            participant(3, "Carol"),
+        ];
+        let mut rng = StdRng::seed_from_u64(7);
+        let turns = plan_turns(
+            &members,
+            "@Carol 你觉得呢？Bob 也说说",
+            None,
+            &config,
+            &mut rng,
+        );
+        assert_eq!(
+            turns,
+            vec![
+                PlannedTurn {
+                    contact_id: 3,
+                    delay_ms: 0,
+                    mentioned: true
+                },
+                PlannedTurn {
+                    contact_id: 2,
+                    delay_ms: 1000,
+                    mentioned: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_respects_cap_and_availability() {
+        let config = GroupChatConfig {
+            reply_probability: 1.0,
+            max_responders: 2,
+            turn_gap_ms: (1500, 4000),
+        };
+        let members = vec![
+            participant(1, "Alice"),
+            asleep(participant(2, "Bob")),
+            participant(3, "Carol"),
+            participant(4, "Dave"),
+        ];
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let turns = plan_turns(&members, "大家好", Some(1), &config, &mut rng);
+            assert!(!turns.is_empty() && turns.len() <= 2);
+            assert!(turns.iter().all(|t| t.contact_id != 2 && !t.mentioned));
+            assert!(turns[1..]
+                .iter()
+                .all(|t| (1500..=4000).contains(&t.delay_ms)));
+        }
+
+        // 没人愿意说时仍有一位接话
+        let quiet = GroupChatConfig {
+            reply_probability: 0.0,
+            ..config
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(
+            plan_turns(&members, "在吗", None, &quiet, &mut rng).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_attributed_history() {
+        let names = HashMap::from([(1, "Alice".to_string()), (2, "Bob".to_string())]);
+        let line = |contact_id: i64, role: &str, content: &str| GroupLine {
+            contact_id,
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+        let lines = vec![
+            line(1, "user", "周末去哪玩？"),
+            line(1, "assistant", "去爬山吧"),
+            line(2, "assistant", "我想去看展"),
+        ];
+
+        let history = attributed_history(&lines, 2, &names, "小明");
+        let view: Vec<(&str, &str)> = history
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(
+            view,
+            vec![
+                ("user", "【小明】: 周末去哪玩？"),
+                ("user", "【Alice】: 去爬山吧"),
+                ("assistant", "我想去看展"),
+            ]
+        );
+
+        assert_eq!(strip_speaker_prefix("【Bob】：我也去", "Bob"), "我也去");
+        assert_eq!(strip_speaker_prefix(" 好呀 ", "Bob"), "好呀");
+    }
+
+    #[test]
+    fn test_group_members() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::social_db::init_social_db(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO contacts (id, name) VALUES (1, 'Alice'), (2, 'Bob'), (3, 'Carol');",
+        )
+        .unwrap();
+
+        assert!(create_group(&conn, "周末", &[1, 1]).is_err());
+        let session_id = create_group(&conn, "周末", &[2, 1, 2]).unwrap();
+        let names: Vec<String> = load_members(&conn, session_id)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(names, vec!["Bob", "Alice"]);
+
+        set_members(&conn, session_id, &[3, 1]).unwrap();
+        let groups = list_groups(&conn).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].members[0].name, "Carol");
+        let owner: i64 = conn
+            .query_row(
+                "SELECT contact_id FROM social_sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(owner, 3);
+    }
+
+    #[test]
+    fn test_deleting_owner_keeps_group() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::social_db::init_social_db(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO contacts (id, name) VALUES (1, 'Alice'), (2, 'Bob'), (3, 'Carol');
+             INSERT INTO social_sessions (id, contact_id, title) VALUES (10, 1, '私聊');
+             INSERT INTO social_messages (contact_id, session_id, role, content)
+                VALUES (1, 10, 'user', '私聊消息');
+             INSERT INTO contact_stickers (contact_id, name, path) VALUES (1, '笑', '/tmp/x.png');",
+        )
+        .unwrap();
+        let group = create_group(&conn, "周末", &[1, 2, 3]).unwrap();
+        conn.execute_batch(&format!(
+            "INSERT INTO social_messages (contact_id, session_id, role, content) VALUES
+                (1, {group}, 'user', '大家好'),
+                (2, {group}, 'assistant', '你好呀');"
+        ))
+        .unwrap();
+
+        crate::social_db::delete_contact(&mut conn, 1).unwrap();
+
+        let owner: i64 = conn
+            .query_row(
+                "SELECT contact_id FROM social_sessions WHERE id = ?1",
+                params![group],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(owner, 2);
+        let lines: Vec<(i64, String)> = conn
+            .prepare("SELECT contact_id, content FROM social_messages ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            lines,
+            vec![(2, "大家好".to_string()), (2, "你好呀".to_string())]
+        );
+        let names: Vec<String> = load_members(&conn, group)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(names, vec!["Bob", "Carol"]);
+        let remaining: i64 = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM social_sessions WHERE id = 10)
+                      + (SELECT COUNT(*) FROM contact_stickers)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+/// 未配置 historyLimit 时每次回复读取的最近消息条数
+pub const DEFAULT_HISTORY_LIMIT: u32 = 200;
+
 /// 沉浸式模式核心配置
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImmersiveSettings {
@@ -27,6 +30,18 @@ pub struct BehaviorToggles {
     #[serde(rename = "segmentationThresholdRange")]
     pub segmentation_threshold_range: Option<(u32, u32)>,
 
+    /// 分段标记：设置后提示模型在想分开发送的地方插入该标记，并优先按标记拆分
+    #[serde(rename = "segmentDelimiter", default)]
+    pub segment_delimiter: Option<String>,
+
+    /// 流水线回复：边生成边分段发送，首段写完即可开始 (需要完整回复的表情规划启用时不生效)
+    #[serde(rename = "pipelinedReplies", default)]
+    pub pipelined_replies: bool,
+
+    /// 每次回复最多读取的最近消息条数，超出上下文预算时只在这些消息内做摘要
+    #[serde(rename = "historyLimit", default)]
+    pub history_limit: Option<u32>,
+
     /// 已读不回概率 [0.0-1.0]
     #[serde(rename = "ignoreRate")]
     pub ignore_rate: f32,
@@ -50,6 +65,22 @@ pub struct BehaviorToggles {
     /// 打字状态抖动
     #[serde(rename = "typingJitter")]
     pub typing_jitter: bool,
+
+    /// 应用重启后未完成行为链的恢复方式
+    #[serde(rename = "resumeConfig", default)]
+    pub resume_config: ResumeConfig,
+
+    /// 事件触发的主动发言 (纪念日、跟进、提醒)
+    #[serde(rename = "eventTriggers", default)]
+    pub event_triggers: EventTriggerConfig,
+
+    /// 群聊轮流发言配置
+    #[serde(rename = "groupChat", default)]
+    pub group_chat: GroupChatConfig,
+
+    /// 表情回应、引用回复、表情包与已读回执
+    #[serde(default)]
+    pub expressions: ExpressionConfig,
 }
 
 /// 撤回修正行为配置
@@ -62,6 +93,10 @@ pub struct TypoConfig {
     /// 撤回后重发延迟 (毫秒)
     #[serde(rename = "fixDelayMs")]
     pub fix_delay_ms: u32,
+
+    /// 直接编辑发错的消息，而不是撤回后重发
+    #[serde(rename = "useEdit", default)]
+    pub use_edit: bool,
 }
 
 /// 主动发言配置
@@ -128,8 +163,103 @@ pub struct CharacterStateConfig {
     pub busy_weight: f32,
 }
 
+/// 未完成行为链的恢复配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResumeConfig {
+    /// 重启时已过期的行为 (等待已结束但尚未执行) 如何处理
+    #[serde(rename = "overduePolicy")]
+    pub overdue_policy: OverduePolicy,
+    /// 过期超过该分钟数的行为链总是丢弃
+    #[serde(rename = "maxOverdueMin")]
+    pub max_overdue_min: u32,
+}
+
+impl Default for ResumeConfig {
+    fn default() -> Self {
+        Self {
+            overdue_policy: OverduePolicy::Execute,
+            max_overdue_min: 30,
+        }
+    }
+}
+
+/// 事件触发器配置 (触发器本身保存在 proactive_triggers 表中)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventTriggerConfig {
+    pub enabled: bool,
+    /// 提取记忆时顺带识别用户提到的未来事件与提醒请求
+    #[serde(rename = "extractFromMemory")]
+    pub extract_from_memory: bool,
+    /// 到期超过该小时数仍未发出 (例如应用未运行) 的触发器直接跳过
+    #[serde(rename = "maxLateHours")]
+    pub max_late_hours: u32,
+}
+
+impl Default for EventTriggerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            extract_from_memory: true,
+            max_late_hours: 24,
+        }
+    }
+}
+
+/// 群聊轮流发言配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupChatConfig {
+    /// 未被点名的成员参与回复的基础概率 [0.0-1.0]
+    #[serde(rename = "replyProbability")]
+    pub reply_probability: f32,
+    /// 每条用户消息最多几位成员回复 (被 @ 的成员不受限制)
+    #[serde(rename = "maxResponders")]
+    pub max_responders: u8,
+    /// 相邻两位成员开始回复的间隔范围 (min_ms, max_ms)
+    #[serde(rename = "turnGapMs")]
+    pub turn_gap_ms: (u32, u32),
+}
+
+impl Default for GroupChatConfig {
+    fn default() -> Self {
+        Self {
+            reply_probability: 0.6,
+            max_responders: 2,
+            turn_gap_ms: (1500, 4000),
+        }
+    }
+}
+
+/// 表达方式配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExpressionConfig {
+    /// 回复生成后额外请求一次结构化输出，由模型决定表情回应、引用回复和表情包
+    pub enabled: bool,
+    /// 回复 (或已读不回) 前把用户消息标记为已读
+    #[serde(rename = "readReceipts")]
+    pub read_receipts: bool,
+}
+
+impl Default for ExpressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            read_receipts: true,
+        }
+    }
+}
+
+/// 过期行为的处理策略
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverduePolicy {
+    /// 立即执行剩余行为
+    Execute,
+    /// 丢弃剩余行为
+    Discard,
+}
+
 /// 行为原子 - 可组合的基础行为单元
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BehaviorAction {
     /// 等待指定毫秒数 (模拟"正在输入")
     Wait(u32),
@@ -145,6 +275,21 @@ pub enum BehaviorAction {
 
     /// 延迟后重新决策 (延迟毫秒数, 原始消息内容)
     DelayedDecision(u32, String),
+
+    /// 对用户消息做表情回应 (消息 ID, 表情)；0 表示最近一条用户消息
+    React(i64, String),
+
+    /// 引用一条消息回复 (被引用的消息 ID, 内容)；0 表示最近一条用户消息
+    Quote(i64, String),
+
+    /// 发送联系人素材库中的图片或表情包 (素材名称)
+    Sticker(String),
+
+    /// 把用户消息标记为已读 (已读回执)
+    MarkRead,
+
+    /// 编辑已发送的消息 (消息 ID, 新内容)；0 表示最后一条
+    Edit(i64, String),
 }
 
 impl Default for ImmersiveSettings {
@@ -163,6 +308,9 @@ impl Default for BehaviorToggles {
             multi_segment: Some(3),
             segment_delay_factor: (0.2, 0.5),
             segmentation_threshold_range: Some((40, 100)),
+            segment_delimiter: None,
+            pipelined_replies: false,
+            history_limit: Some(DEFAULT_HISTORY_LIMIT),
             ignore_rate: 0.0,
             idle_delay_config: Some(IdleDelayConfig {
                 delay_range_ms: (60000, 300000), // 1-5分钟
@@ -171,6 +319,7 @@ impl Default for BehaviorToggles {
             typo_correction: Some(TypoConfig {
                 trigger_rate: 0.02,
                 fix_delay_ms: 1500,
+                use_edit: false,
             }),
             proactive_initiation: Some(ProactiveConfig {
                 idle_threshold_range: (120, 600),
@@ -187,6 +336,10 @@ impl Default for BehaviorToggles {
                 busy_weight: 0.5,
             }),
             typing_jitter: true,
+            resume_config: ResumeConfig::default(),
+            event_triggers: EventTriggerConfig::default(),
+            group_chat: GroupChatConfig::default(),
+            expressions: ExpressionConfig::default(),
         }
     }
 }
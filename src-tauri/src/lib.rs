@@ -1,16 +1,37 @@
 // Live2D logic moved to standalone project
 
 mod ai_utils;
+mod api_server;
 mod behavior_engine;
 mod behavior_scheduler;
+mod behavior_sim;
 mod character_state;
+mod chat_export;
+mod cli;
 mod commands;
+mod config_store;
+mod config_watcher;
+mod context_window;
 mod db;
+mod expression;
+mod generation_config;
+mod group_chat;
 mod immersive_settings;
+mod local_llm;
 mod memory;
 mod memory_commands;
+mod model_catalog;
 mod models;
+mod net;
+mod paths;
+mod proactive_trigger;
+mod prompt_template;
+mod provider_config;
+mod routine;
+mod secret_store;
+mod segmenter;
 mod social_db;
+mod stream_decoder;
 mod title_commands;
 
 use crate::db::DbState;
@@ -92,6 +113,11 @@ async fn start_passthrough_monitor(
     Ok(())
 }
 
+/// 命令行工具入口 (src/bin/goge-cli.rs)，返回进程退出码
+pub fn run_cli() -> i32 {
+    cli::run()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -102,20 +128,9 @@ pub fn run() {
         .setup(|app| {
             let app_handle = app.handle();
 
-            // --- 1. 定位“便携式”数据目录 (当前可执行文件同级目录下的 data) ---
-            let exe_path =
-                std::env::current_exe().unwrap_or_else(|_| std::path::PathBuf::from("."));
-            let exe_dir = exe_path
-                .parent()
-                .unwrap_or_else(|| std::path::Path::new("."));
-            let app_data_dir = exe_dir.join("data");
-
-            if !app_data_dir.exists() {
-                std::fs::create_dir_all(&app_data_dir).expect("无法创建数据目录");
-            }
-
-            let target_db_path = app_data_dir.join("goge.db");
-            let target_social_db_path = app_data_dir.join("gole_social.db");
+            // --- 1. 定位“便携式”数据目录 (当前可执行文件同级目录下的 data，可用 GOGE_DATA_DIR 覆盖) ---
+            let target_db_path = paths::chat_db_path();
+            let target_social_db_path = paths::social_db_path();
 
             // --- 2. 数据库搬迁逻辑已移除，强制使用 D 盘便携目录 ---
 
@@ -194,7 +209,16 @@ pub fn run() {
                 }
             });
 
+            // --- 🔐 密钥存储 (系统钥匙串，或加密文件回退) ---
+            let config_dir = commands::config_cmd::resolve_config_dir(app_handle);
+            app.manage(secret_store::SecretStoreState(Mutex::new(
+                secret_store::SecretStore::open(&config_dir),
+            )));
+
             // --- Config State Setup (Cache) ---
+            app.manage(commands::config_cmd::ConfigIssuesState(Mutex::new(
+                Default::default(),
+            )));
             let initial_config = tauri::async_runtime::block_on(async {
                 commands::config_cmd::load_config_internal(app_handle).await
             })?;
@@ -204,59 +228,30 @@ pub fn run() {
             app.manage(config_state);
 
             // --- ⚡ HTTP Client Setup (高性能配置) ---
-            // HTTP/3 客户端需要在 Tokio 运行时中构建
-            let http_client = tauri::async_runtime::block_on(async {
-                reqwest::Client::builder()
-                    // 🚀 连接池优化：增加每个 Host 的最大空闲连接数
-                    .pool_max_idle_per_host(20)
-                    .pool_idle_timeout(std::time::Duration::from_secs(120))
-                    .tcp_keepalive(std::time::Duration::from_secs(60))
-                    .tcp_nodelay(true)
-                    // 🚀 高性能 DNS 与现代协议安全
-                    .hickory_dns(true)
-                    // 🛡️ 兼容性调优：最低支持 TLS 1.2，确保 SiliconFlow 等平台可用
-                    .min_tls_version(reqwest::tls::Version::TLS_1_2)
-                    .connect_timeout(std::time::Duration::from_secs(10))
-                    .timeout(std::time::Duration::from_secs(60))
-                    .brotli(true)
-                    .gzip(true)
-                    .build()
-                    .expect("Failed to build HTTP client")
-            });
-
-            app.manage(http_client.clone());
+            // 按网络配置 (代理 / 证书 / 超时) 缓存客户端，提供商可覆盖全局设置
+            let http_clients = net::HttpClients::new(initial_config.network.clone());
 
             // --- 🚀 启动项优化：DNS 预解析 (从配置动态获取) ---
-            let prefetch_client = http_client.clone();
-            let mut domains = Vec::new();
-            if let Some(providers) = initial_config.providers.as_array() {
-                for provider in providers {
-                    // 只预热启用的 Provider
-                    if provider["enabled"].as_bool().unwrap_or(false) {
-                        if let Some(base_url) = provider["baseUrl"].as_str() {
-                            if !base_url.is_empty() {
-                                // 构造一个简单的探测 URL (与指令中的预热逻辑一致)
-                                let url = if base_url.contains("generativelanguage.googleapis.com")
-                                {
-                                    format!("{}/v1beta/models", base_url.trim_end_matches('/'))
-                                } else {
-                                    let base = base_url.trim_end_matches('/');
-                                    if base.ends_with("/v1") {
-                                        format!("{}/models", base)
-                                    } else {
-                                        format!("{}/v1/models", base)
-                                    }
-                                };
-                                domains.push(url);
-                            }
+            // HTTP/3 客户端需要在 Tokio 运行时中构建
+            // 只预热启用的 Provider
+            let targets: Vec<(reqwest::Client, String)> = tauri::async_runtime::block_on(async {
+                provider_config::parse_providers(&initial_config.providers)
+                    .iter()
+                    .filter(|p| p.enabled)
+                    .filter_map(|p| match http_clients.for_provider(p) {
+                        Ok(client) => Some((client, p.models_url())),
+                        Err(e) => {
+                            println!("⚠️ [网络] 提供商 {} 的网络设置无效: {}", p.id, e);
+                            None
                         }
-                    }
-                }
-            }
+                    })
+                    .collect()
+            });
+            app.manage(http_clients);
 
             tauri::async_runtime::spawn(async move {
-                for url in domains {
-                    let _ = prefetch_client
+                for (client, url) in targets {
+                    let _ = client
                         .get(&url)
                         .timeout(std::time::Duration::from_secs(2))
                         .send()
@@ -288,20 +283,45 @@ pub fn run() {
             app.manage(memory_state);
 
             // --- Immersive Mode Scheduler Setup ---
-            let scheduler = Arc::new(behavior_scheduler::MessageScheduler::new());
-            scheduler.start_idle_monitor(app_handle.clone());
+            let scheduler = Arc::new(behavior_scheduler::MessageScheduler::new(
+                behavior_scheduler::SchedulerEnv::for_app(app_handle.clone()),
+            ));
+            scheduler.restore();
+            scheduler.start_idle_monitor();
             app.manage(scheduler);
 
+            // --- 👀 配置文件热重载 (外部编辑 data/config 后立即生效) ---
+            if let Err(e) = config_watcher::start(app_handle) {
+                println!("⚠️ [Config] 无法监听配置目录: {}", e);
+            }
+
+            // --- 🌐 本地 OpenAI 兼容接口 (需在设置中启用并配置令牌) ---
+            app.manage(api_server::ApiServerState::default());
+            api_server::apply(app_handle, &initial_config.api_server);
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // 配置管理
-            commands::config_cmd::load_config,
             commands::config_cmd::save_config,
+            commands::config_cmd::load_config_redacted,
+            commands::config_cmd::get_secret_store_status,
+            // 本地接口
+            api_server::get_api_server_status,
+            api_server::generate_api_token,
+            commands::config_cmd::unlock_secret_store,
+            commands::config_cmd::get_config_issues,
+            commands::config_cmd::reload_config,
+            commands::config_cmd::list_config_backups,
+            commands::config_cmd::restore_config_backup,
             // AI 交互
             commands::ai::ask_ai,
             commands::ai::discover_models_raw,
+            commands::ai::probe_provider_raw,
+            commands::ai::get_model_catalog,
+            commands::ai::pull_local_model,
             commands::ai::prewarm_connection,
+            commands::compare_cmd::ask_ai_compare,
             stop_ai_generation,
             reset_ai_generation,
             set_window_ignore_cursor_events,
@@ -328,6 +348,12 @@ pub fn run() {
             commands::db_cmd::update_folder_collapsed,
             commands::db_cmd::update_folders_order,
             commands::db_cmd::update_session_config,
+            commands::db_cmd::update_session_params,
+            // 导入导出
+            commands::export_cmd::export_chats,
+            commands::export_cmd::import_chats,
+            // 提示词模板
+            commands::prompt_cmd::preview_prompt_template,
             // 文件指令
             commands::file_cmd::open_file,
             commands::file_cmd::read_file_text_content,
@@ -385,6 +411,25 @@ pub fn run() {
             social_db::is_state_cache_valid,
             social_db::reset_message_count,
             social_db::cleanup_duplicate_messages,
+            // 🕰️ Routine Commands
+            routine::get_contact_routine,
+            routine::set_contact_routine,
+            routine::get_contact_activity,
+            // ⏰ Proactive Trigger Commands
+            proactive_trigger::list_proactive_triggers,
+            proactive_trigger::add_proactive_trigger,
+            proactive_trigger::delete_proactive_trigger,
+            // 🎨 Sticker Library Commands
+            expression::list_contact_stickers,
+            expression::add_contact_sticker,
+            expression::delete_contact_sticker,
+            // 👥 Group Chat Commands
+            group_chat::create_group_session,
+            group_chat::get_group_sessions,
+            group_chat::get_group_members,
+            group_chat::set_group_members,
+            group_chat::get_group_messages,
+            group_chat::send_group_message_immersive,
         ])
         .run(tauri::generate_context!())
         .expect("Tauri 运行异常");
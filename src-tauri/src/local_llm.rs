@@ -0,0 +1,347 @@
+use crate::models::{GenerationParams, Message};
+use crate::provider_config::ProviderConfig;
+use crate::stream_decoder::{decode, NdjsonLines};
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+// ==================================================================================
+// Ollama /api/chat
+// ==================================================================================
+
+/// Ollama 原生接口根地址 (兼容填写了 OpenAI 兼容路径 /v1 的旧配置)
+pub fn ollama_base(provider: &ProviderConfig) -> String {
+    let base = provider.base_url.trim_end_matches('/');
+    base.strip_suffix("/v1").unwrap_or(base).to_string()
+}
+
+/// 生成参数映射为 Ollama options
+pub fn ollama_options(params: &GenerationParams) -> Option<Value> {
+    let mut opts = Map::new();
+    if let Some(v) = params.temperature {
+        opts.insert("temperature".into(), json!(v));
+    }
+    if let Some(v) = params.max_tokens {
+        opts.insert("num_predict".into(), json!(v));
+    }
+    if let Some(v) = params.top_p {
+        opts.insert("top_p".into(), json!(v));
+    }
+    if let Some(v) = params.presence_penalty {
+        opts.insert("presence_penalty".into(), json!(v));
+    }
+    if let Some(v) = params.frequency_penalty {
+        opts.insert("frequency_penalty".into(), json!(v));
+    }
+    if let Some(v) = &params.stop {
+        opts.insert("stop".into(), json!(v));
+    }
+    if let Some(v) = params.seed {
+        opts.insert("seed".into(), json!(v));
+    }
+    (!opts.is_empty()).then_some(Value::Object(opts))
+}
+
+pub fn ollama_chat_body(
+    model: &str,
+    messages: &[Message],
+    params: &GenerationParams,
+    stream: bool,
+) -> Value {
+    let messages: Vec<Value> = messages
+        .iter()
+        .map(|m| json!({ "role": m.role, "content": m.content }))
+        .collect();
+    let mut body = json!({ "model": model, "messages": messages, "stream": stream });
+    if let Some(options) = ollama_options(params) {
+        body["options"] = options;
+    }
+    body
+}
+
+/// 单行响应中的增量 (content 与思考过程)
+#[derive(Debug, Default, PartialEq)]
+pub struct OllamaDelta {
+    pub content: String,
+    pub thinking: String,
+    pub done: bool,
+}
+
+fn parse_chat_line(line: &Value) -> Result<OllamaDelta, String> {
+    if let Some(err) = line["error"].as_str() {
+        return Err(format!("Ollama 错误: {}", err));
+    }
+    Ok(OllamaDelta {
+        content: line["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        thinking: line["message"]["thinking"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        done: line["done"].as_bool().unwrap_or(false),
+    })
+}
+
+/// 流式调用 Ollama /api/chat，on_delta 返回 false 时中止读取
+pub async fn ollama_chat_streaming<F>(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    model: &str,
+    messages: &[Message],
+    params: &GenerationParams,
+    mut on_delta: F,
+) -> Result<(), String>
+where
+    F: FnMut(&OllamaDelta) -> bool,
+{
+    let url = format!("{}/api/chat", ollama_base(provider));
+    let response = provider
+        .authorize(client.post(&url))
+        .json(&ollama_chat_body(model, messages, params, true))
+        .send()
+        .await
+        .map_err(|e| format!("Ollama 网络请求失败: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let err_text = response.text().await.unwrap_or_default();
+        return Err(format!("Ollama API 错误 (状态码 {}): {}", status, err_text));
+    }
+
+    let mut lines = std::pin::pin!(decode(response.bytes_stream(), NdjsonLines::default()));
+    while let Some(line) = lines.next().await {
+        let delta = parse_chat_line(&line?)?;
+        if !on_delta(&delta) || delta.done {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// 非流式调用 (内部仍走流式并拼接，避免大模型长时间无响应导致超时)
+pub async fn ollama_chat(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    model: &str,
+    messages: &[Message],
+    params: &GenerationParams,
+) -> Result<String, String> {
+    let mut full_content = String::new();
+    ollama_chat_streaming(client, provider, model, messages, params, |delta| {
+        full_content.push_str(&delta.content);
+        true
+    })
+    .await?;
+    Ok(full_content)
+}
+
+// ==================================================================================
+// 模型拉取 (Ollama /api/pull)
+// ==================================================================================
+
+/// 拉取进度 (total / completed 为当前分层的字节数)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PullProgress {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+    /// 0-100，仅下载阶段有值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+}
+
+fn parse_pull_line(line: &Value) -> Result<PullProgress, String> {
+    if let Some(err) = line["error"].as_str() {
+        return Err(format!("模型拉取失败: {}", err));
+    }
+    let total = line["total"].as_u64();
+    let completed = line["completed"].as_u64();
+    Ok(PullProgress {
+        status: line["status"].as_str().unwrap_or_default().to_string(),
+        digest: line["digest"].as_str().map(String::from),
+        percent: match (total, completed) {
+            (Some(t), Some(c)) if t > 0 => Some((c as f64 / t as f64 * 100.0).min(100.0)),
+            _ => None,
+        },
+        total,
+        completed,
+    })
+}
+
+/// 拉取模型，逐条回调进度，以 status == "success" 结束
+pub async fn pull_model<F>(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    model: &str,
+    mut on_progress: F,
+) -> Result<(), String>
+where
+    F: FnMut(PullProgress),
+{
+    let url = format!("{}/api/pull", ollama_base(provider));
+    let response = provider
+        .authorize(client.post(&url))
+        .json(&json!({ "model": model, "stream": true }))
+        .send()
+        .await
+        .map_err(|e| format!("Ollama 网络请求失败: {}", e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let err_text = response.text().await.unwrap_or_default();
+        return Err(format!("Ollama API 错误 (状态码 {}): {}", status, err_text));
+    }
+
+    let mut lines = std::pin::pin!(decode(response.bytes_stream(), NdjsonLines::default()));
+    let mut succeeded = false;
+    while let Some(line) = lines.next().await {
+        let progress = parse_pull_line(&line?)?;
+        succeeded |= progress.status == "success";
+        on_progress(progress);
+    }
+
+    if succeeded {
+        Ok(())
+    } else {
+        Err("模型拉取中断：未收到完成状态".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 本地替身服务器：接受一次请求，返回给定的 NDJSON 响应体，并回传收到的请求
+    async fn stand_in_server(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(head_end) = text.find("\r\n\r\n") {
+                    let len = text[..head_end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= head_end + 4 + len || n == 0 {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (format!("http://{}", addr), handle)
+    }
+
+    fn local_provider(base_url: &str) -> ProviderConfig {
+        ProviderConfig::from_value(&json!({ "id": "ollama", "baseUrl": base_url })).unwrap()
+    }
+
+    #[test]
+    fn test_options_mapping() {
+        let params = GenerationParams {
+            temperature: Some(0.5),
+            max_tokens: Some(256),
+            ..Default::default()
+        };
+        let body = ollama_chat_body("llama3", &[], &params, true);
+        assert_eq!(body["options"]["num_predict"], 256);
+        assert_eq!(body["options"]["temperature"], 0.5);
+        assert!(
+            ollama_chat_body("llama3", &[], &GenerationParams::default(), false)
+                .get("options")
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_against_stand_in_server() {
+        let (base, server) = stand_in_server(concat!(
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"你\",\"thinking\":\"嗯\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"好\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n"
+        ))
+        .await;
+        let provider = local_provider(&format!("{}/v1", base));
+        // 本地服务无需 API Key
+        assert!(provider.require_credentials().is_ok());
+
+        let mut thinking = String::new();
+        let mut content = String::new();
+        ollama_chat_streaming(
+            &reqwest::Client::new(),
+            &provider,
+            "qwen3",
+            &[],
+            &GenerationParams::default(),
+            |d| {
+                thinking.push_str(&d.thinking);
+                content.push_str(&d.content);
+                true
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(content, "你好");
+        assert_eq!(thinking, "嗯");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /api/chat "));
+        assert!(!request.to_ascii_lowercase().contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn test_pull_reports_progress_and_errors() {
+        let (base, _server) = stand_in_server(concat!(
+            "{\"status\":\"pulling manifest\"}\n",
+            "{\"status\":\"downloading\",\"digest\":\"sha256:ab\",\"total\":200,\"completed\":50}\n",
+            "{\"status\":\"success\"}\n"
+        ))
+        .await;
+        let mut events = Vec::new();
+        pull_model(
+            &reqwest::Client::new(),
+            &local_provider(&base),
+            "qwen3",
+            |p| events.push(p),
+        )
+        .await
+        .unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].percent, Some(25.0));
+
+        let (base, _server) =
+            stand_in_server("{\"error\":\"pull model manifest: file does not exist\"}\n").await;
+        let err = pull_model(
+            &reqwest::Client::new(),
+            &local_provider(&base),
+            "nope",
+            |_| {},
+        )
+        .await
+        .unwrap_err();
+        assert!(err.contains("file does not exist"));
+    }
+}
@@ -11,7 +11,6 @@ use lancedb::table::OptimizeAction;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tauri::AppHandle;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FactRecord {
@@ -37,16 +36,10 @@ pub struct LanceDbManager {
 }
 
 impl LanceDbManager {
-    pub fn new(_app_handle: &AppHandle) -> Result<Self, String> {
-        // --- 1. 定位“便携式”数据目录 (当前可执行文件同级目录下的 data) ---
-        let exe_path = std::env::current_exe().unwrap_or_else(|_| std::path::PathBuf::from("."));
-        let exe_dir = exe_path
-            .parent()
-            .unwrap_or_else(|| std::path::Path::new("."));
-        let data_dir = exe_dir.join("data").join("alice_memory");
-
+    /// 打开指定目录下的向量库 (目录不存在时创建)
+    pub fn open(data_dir: &std::path::Path) -> Result<Self, String> {
         if !data_dir.exists() {
-            std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+            std::fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
         }
 
         let uri = data_dir.to_str().ok_or("路径转换失败")?.to_string();
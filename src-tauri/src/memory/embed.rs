@@ -1,10 +1,9 @@
 use candle_core::{Device, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::bert::{BertModel, Config};
-use tauri::path::BaseDirectory;
-use tauri::{AppHandle, Manager};
 use tokenizers::Tokenizer;
 
+use std::path::Path;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -15,16 +14,11 @@ pub struct EmbeddingEngine {
 }
 
 impl EmbeddingEngine {
-    pub fn new(app_handle: &AppHandle) -> Result<Self, String> {
+    /// 从模型目录加载 (目录由 MemoryState 解析：界面程序取 Tauri 资源目录，命令行工具见 crate::paths)
+    pub fn new(actual_dir: &Path) -> Result<Self, String> {
         // 🔥 强制在 CPU 运行，避免抢占 4070 显存
         let device = Device::Cpu;
 
-        // 获取动态资源路径 (Tauri 2.0 标准解析器)
-        let actual_dir = app_handle
-            .path()
-            .resolve("resources/bge-small-zh-v1.5", BaseDirectory::Resource)
-            .map_err(|e| format!("无法解析资源路径: {}", e))?;
-
         if !actual_dir.exists() {
             return Err(format!(
                 "找不到模型目录: {:?}。请运行下载脚本或手动放置模型文件。",
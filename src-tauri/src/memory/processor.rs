@@ -1,24 +1,37 @@
 use crate::memory::db::{FactRecord, LanceDbManager};
 use crate::memory::embed::EmbeddingEngine;
 use serde_json::json;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
+use tauri::path::BaseDirectory;
 use tauri::{AppHandle, Manager};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
 pub struct MemoryState {
-    pub app_handle: AppHandle,
+    /// Embedding 模型目录 (首次检索时才加载)
+    pub model_dir: PathBuf,
     pub engine: RwLock<Option<EmbeddingEngine>>, // Use RwLock for interior mutability of the Option
     pub db: LanceDbManager,
 }
 
 impl MemoryState {
     pub fn new(app_handle: &AppHandle) -> Result<Self, String> {
+        // 获取动态资源路径 (Tauri 2.0 标准解析器)
+        let model_dir = app_handle
+            .path()
+            .resolve(crate::paths::EMBEDDING_MODEL, BaseDirectory::Resource)
+            .map_err(|e| format!("无法解析资源路径: {}", e))?;
+        Self::open(&crate::paths::memory_dir(), model_dir)
+    }
+
+    /// 不依赖 AppHandle 打开记忆库 (命令行工具使用)
+    pub fn open(data_dir: &Path, model_dir: PathBuf) -> Result<Self, String> {
         // Lazy load: Don't init engine here
-        let db = LanceDbManager::new(app_handle)?;
+        let db = LanceDbManager::open(data_dir)?;
         Ok(Self {
-            app_handle: app_handle.clone(),
+            model_dir,
             engine: RwLock::new(None),
             db,
         })
@@ -42,7 +55,7 @@ impl MemoryState {
 
         println!("🧠 [Memory] Initializing Embedding Engine (Lazy Load)...");
         let start = Instant::now();
-        let engine = EmbeddingEngine::new(&self.app_handle)?;
+        let engine = EmbeddingEngine::new(&self.model_dir)?;
         println!("🧠 [Memory] Engine loaded in {:?}", start.elapsed());
 
         *guard = Some(engine.clone());
@@ -312,8 +325,21 @@ pub async fn extract_and_store_facts(
             messages_str.len()
         );
 
+        // 社交模式下顺带识别未来事件与提醒请求，生成主动发言触发器
+        let extract_events = mode == "Social"
+            && crate::commands::config_cmd::load_config(app_handle.clone())
+                .await
+                .map(|c| {
+                    let settings = c.immersive_mode;
+                    settings.enabled && {
+                        let events = &settings.behaviors.event_triggers;
+                        events.enabled && events.extract_from_memory
+                    }
+                })
+                .unwrap_or(false);
+
         // 2. 准备 Prompt - 极简版
-        let prompt = format!(
+        let mut prompt = format!(
             "请分析对话并提取关于【用户】的持久事实（如偏好、身份、经历）。\n\
              要求：\n\
              1. 仅限用户：严禁将AI的猜测、建议或提问当作用户事实。\n\
@@ -326,6 +352,16 @@ pub async fn extract_and_store_facts(
              事实：",
             messages_str
         );
+        if extract_events {
+            prompt = prompt.replacen(
+                "\n对话：",
+                &format!(
+                    "{}\n\n对话：",
+                    crate::proactive_trigger::extraction_instructions(chrono::Local::now())
+                ),
+                1,
+            );
+        }
 
         // 3. 调用 AI (复用 generate_title 的逻辑，但为内部调用)
         let messages = vec![crate::models::Message {
@@ -339,14 +375,12 @@ pub async fn extract_and_store_facts(
             provider: None,
             mode: None,
             role_id: None,
+            parent_id: None,
         }];
 
         let start_llm = Instant::now();
         // 调用 generate_title (内部调用，不需要 command 标记)
-        let client = app_handle.state::<reqwest::Client>();
-        match crate::title_commands::generate_title_internal(app_handle.clone(), messages, &client)
-            .await
-        {
+        match crate::title_commands::generate_title_internal(app_handle.clone(), messages).await {
             Ok(facts_str) => {
                 let duration_llm = start_llm.elapsed();
                 println!("⏱️ [性能] AI 事实提取耗时: {:?}", duration_llm);
@@ -361,7 +395,31 @@ pub async fn extract_and_store_facts(
                     facts_str
                 );
 
-                let facts: Vec<&str> = facts_str.split('\n').collect();
+                // 开启事件提取时，"#跟进" / "#提醒" 行是事件，不作为事实存储
+                let (events, facts): (Vec<&str>, Vec<&str>) = if extract_events {
+                    facts_str
+                        .split('\n')
+                        .partition(|line| line.trim_start_matches(['-', '*', ' ']).starts_with('#'))
+                } else {
+                    (Vec::new(), facts_str.split('\n').collect())
+                };
+                if !events.is_empty() {
+                    if let Ok(contact_id) = role_id.parse::<i64>() {
+                        let social_db = app_handle.state::<crate::social_db::SocialDbState>();
+                        match social_db.0.lock() {
+                            Ok(conn) => {
+                                crate::proactive_trigger::record_extracted(
+                                    &conn,
+                                    contact_id,
+                                    session_id,
+                                    &events,
+                                    chrono::Utc::now().timestamp_millis(),
+                                );
+                            }
+                            Err(e) => println!("❌ [记忆] 记录事件失败: {}", e),
+                        };
+                    }
+                }
                 // 🛡️ 核心限额：每次复盘绝不记录超过 2 条事实
                 for content in facts.into_iter().take(2) {
                     let content = content.trim();
@@ -0,0 +1,462 @@
+use crate::models::{GenerationParams, Message};
+use crate::provider_config::{ModelEntry, ProviderConfig, ProviderKind};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 模型目录缓存有效期 (秒)
+pub const CATALOG_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// 规范化后的模型条目 (能力字段为 None 表示未知)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CatalogModel {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "contextLength", skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vision: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<bool>,
+}
+
+/// 某个提供商的模型目录
+#[derive(Serialize, Debug, Clone)]
+pub struct ModelCatalog {
+    #[serde(rename = "providerId")]
+    pub provider_id: String,
+    pub models: Vec<CatalogModel>,
+    /// 拉取时间 (Unix 秒)
+    #[serde(rename = "fetchedAt")]
+    pub fetched_at: i64,
+    /// 刷新失败时返回过期缓存
+    pub stale: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// ==================================================================================
+// 响应解析
+// ==================================================================================
+
+/// 仅凭模型名推断的能力 (只填补未知字段)
+fn infer_from_name(model: &mut CatalogModel) {
+    let id = model.id.to_lowercase();
+    if model.reasoning.is_none()
+        && ["reasoner", "-r1", "o1", "o3", "o4-", "thinking", "qwq"]
+            .iter()
+            .any(|k| id.contains(k))
+    {
+        model.reasoning = Some(true);
+    }
+    if model.vision.is_none()
+        && [
+            "vision", "gpt-4o", "gpt-4.1", "-vl", "llava", "claude-3", "gemini",
+        ]
+        .iter()
+        .any(|k| id.contains(k))
+    {
+        model.vision = Some(true);
+    }
+}
+
+fn strings_of(v: &Value) -> Option<Vec<&str>> {
+    v.as_array()
+        .map(|arr| arr.iter().filter_map(|s| s.as_str()).collect())
+}
+
+/// OpenAI 兼容 /v1/models (兼容 OpenRouter、Groq、llama.cpp 等扩展字段)
+pub fn parse_openai(json: &Value) -> Vec<CatalogModel> {
+    let list = json["data"].as_array().or_else(|| json.as_array());
+    list.into_iter()
+        .flatten()
+        .filter_map(|m| {
+            let id = m["id"].as_str()?.to_string();
+            let modalities = strings_of(&m["architecture"]["input_modalities"]);
+            let supported = strings_of(&m["supported_parameters"]);
+            Some(CatalogModel {
+                name: m["name"].as_str().map(String::from),
+                context_length: m["context_length"]
+                    .as_u64()
+                    .or_else(|| m["context_window"].as_u64())
+                    .or_else(|| m["max_context_length"].as_u64())
+                    .or_else(|| m["meta"]["n_ctx_train"].as_u64()),
+                vision: modalities.map(|list| list.contains(&"image")),
+                tools: supported.as_ref().map(|list| list.contains(&"tools")),
+                reasoning: supported
+                    .as_ref()
+                    .and_then(|list| list.contains(&"reasoning").then_some(true)),
+                id,
+            })
+        })
+        .collect()
+}
+
+/// Gemini models.list (跳过不支持 generateContent 的嵌入类模型)
+pub fn parse_gemini(json: &Value) -> Vec<CatalogModel> {
+    json["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|m| {
+            strings_of(&m["supportedGenerationMethods"])
+                .is_none_or(|methods| methods.contains(&"generateContent"))
+        })
+        .filter_map(|m| {
+            let name = m["name"].as_str()?;
+            Some(CatalogModel {
+                id: name.trim_start_matches("models/").to_string(),
+                name: m["displayName"].as_str().map(String::from),
+                context_length: m["inputTokenLimit"].as_u64(),
+                vision: Some(true),
+                tools: Some(true),
+                reasoning: m["thinking"].as_bool(),
+            })
+        })
+        .collect()
+}
+
+/// Ollama /api/tags (families 含 clip / mllama 的为多模态模型)
+pub fn parse_ollama(json: &Value) -> Vec<CatalogModel> {
+    json["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            let id = m["name"].as_str().or_else(|| m["model"].as_str())?;
+            let families = strings_of(&m["details"]["families"]);
+            Some(CatalogModel {
+                id: id.to_string(),
+                vision: families.map(|f| f.iter().any(|x| *x == "clip" || *x == "mllama")),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// 按提供商协议解析模型列表响应
+pub fn normalize(kind: ProviderKind, json: &Value) -> Vec<CatalogModel> {
+    let mut models = match kind {
+        ProviderKind::OpenAi | ProviderKind::LlamaCpp => parse_openai(json),
+        ProviderKind::Gemini => parse_gemini(json),
+        ProviderKind::Ollama => parse_ollama(json),
+    };
+    models.iter_mut().for_each(infer_from_name);
+    models
+}
+
+// ==================================================================================
+// 拉取
+// ==================================================================================
+
+async fn get_json(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    url: &str,
+    query: &[(&str, &str)],
+) -> Result<Value, String> {
+    let response = provider
+        .authorize(client.get(url).query(query))
+        .timeout(std::time::Duration::from_secs(15))
+        .send()
+        .await
+        .map_err(|e| format!("Network error: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let err_text = response.text().await.unwrap_or_default();
+        return Err(format!("API Error ({}): {}", status, err_text));
+    }
+    response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("JSON parse error: {}", e))
+}
+
+/// 从提供商拉取模型列表并规范化
+pub async fn fetch_catalog(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+) -> Result<Vec<CatalogModel>, String> {
+    match provider.kind {
+        ProviderKind::Ollama => {
+            let url = format!("{}/api/tags", crate::local_llm::ollama_base(provider));
+            let json = get_json(client, provider, &url, &[]).await?;
+            Ok(normalize(provider.kind, &json))
+        }
+        ProviderKind::Gemini => {
+            // models.list 分页返回
+            let mut models = Vec::new();
+            let mut page_token: Option<String> = None;
+            loop {
+                let mut query = vec![("pageSize", "1000")];
+                if let Some(token) = &page_token {
+                    query.push(("pageToken", token));
+                }
+                let json = get_json(client, provider, &provider.models_url(), &query).await?;
+                models.extend(normalize(provider.kind, &json));
+                page_token = json["nextPageToken"]
+                    .as_str()
+                    .filter(|t| !t.is_empty())
+                    .map(String::from);
+                if page_token.is_none() {
+                    break;
+                }
+            }
+            Ok(models)
+        }
+        ProviderKind::OpenAi | ProviderKind::LlamaCpp => {
+            let json = get_json(client, provider, &provider.models_url(), &[]).await?;
+            Ok(normalize(provider.kind, &json))
+        }
+    }
+}
+
+// ==================================================================================
+// 缓存 (goge.db: model_catalog)
+// ==================================================================================
+
+pub fn is_fresh(fetched_at: i64, now: i64) -> bool {
+    now - fetched_at < CATALOG_TTL_SECS
+}
+
+/// 读取缓存的模型目录，返回 (模型列表, 拉取时间)
+pub(crate) fn load_cached(
+    conn: &Connection,
+    provider_id: &str,
+) -> rusqlite::Result<Option<(Vec<CatalogModel>, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT data, fetched_at FROM model_catalog WHERE provider_id = ?1 ORDER BY model_id",
+    )?;
+    let rows = stmt
+        .query_map(params![provider_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let Some(fetched_at) = rows.iter().map(|(_, t)| *t).min() else {
+        return Ok(None);
+    };
+    let models = rows
+        .iter()
+        .filter_map(|(data, _)| serde_json::from_str(data).ok())
+        .collect();
+    Ok(Some((models, fetched_at)))
+}
+
+/// 查询单个模型的缓存条目 (不触发网络请求)
+pub(crate) fn cached_model(
+    conn: &Connection,
+    provider_id: &str,
+    model_id: &str,
+) -> rusqlite::Result<Option<CatalogModel>> {
+    let data: Option<String> = conn
+        .query_row(
+            "SELECT data FROM model_catalog WHERE provider_id = ?1 AND model_id = ?2",
+            params![provider_id, model_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(data.and_then(|d| serde_json::from_str(&d).ok()))
+}
+
+/// 用新拉取的列表整体替换某个提供商的缓存
+pub(crate) fn save_cached(
+    conn: &mut Connection,
+    provider_id: &str,
+    models: &[CatalogModel],
+    fetched_at: i64,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM model_catalog WHERE provider_id = ?1",
+        params![provider_id],
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT OR REPLACE INTO model_catalog (provider_id, model_id, data, fetched_at) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for model in models {
+            let data = serde_json::to_string(model).unwrap_or_default();
+            stmt.execute(params![provider_id, model.id, data, fetched_at])?;
+        }
+    }
+    tx.commit()
+}
+
+/// 删除某个提供商的缓存 (例如本地拉取了新模型后)，下次读取时重新拉取
+pub(crate) fn clear_cached(conn: &Connection, provider_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM model_catalog WHERE provider_id = ?1",
+        params![provider_id],
+    )?;
+    Ok(())
+}
+
+// ==================================================================================
+// 发送前的能力校验
+// ==================================================================================
+
+/// 只看本次发出的 (最后一条) 用户消息；历史中的旧图片不应阻止切换到纯文本模型
+fn has_image_attachment(messages: &[Message]) -> bool {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .is_some_and(|m| {
+            crate::chat_export::parse_file_metadata(m.file_metadata.as_deref())
+                .iter()
+                .any(|f| {
+                    let name = if f.name.is_empty() { &f.path } else { &f.name };
+                    crate::chat_export::guess_mime(name).starts_with("image/")
+                })
+        })
+}
+
+/// 请求使用了模型明确不支持的能力时提前报错
+/// 仅在目录明确标记为不支持、且用户未在 providers.json 中手动声明该能力时拒绝
+pub fn check_features(
+    model_id: &str,
+    catalog: Option<&CatalogModel>,
+    configured: Option<&ModelEntry>,
+    messages: &[Message],
+    params: &GenerationParams,
+) -> Result<(), String> {
+    let Some(catalog) = catalog else {
+        return Ok(());
+    };
+    let declared = configured.map(|m| &m.capabilities);
+
+    if catalog.vision == Some(false)
+        && !declared.is_some_and(|c| c.vision)
+        && has_image_attachment(messages)
+    {
+        return Err(format!(
+            "模型 {} 不支持图片输入，请更换支持视觉的模型",
+            model_id
+        ));
+    }
+    if catalog.reasoning == Some(false)
+        && !declared.is_some_and(|c| c.reasoning)
+        && params.reasoning_effort.is_some()
+    {
+        return Err(format!(
+            "模型 {} 不支持推理强度 (reasoning_effort) 参数",
+            model_id
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user_with_file(name: &str) -> Message {
+        Message {
+            id: None,
+            model: None,
+            role: "user".into(),
+            content: "看看这个".into(),
+            reasoning_content: None,
+            file_metadata: Some(
+                json!([{ "name": name, "path": format!("/tmp/{}", name) }]).to_string(),
+            ),
+            search_metadata: None,
+            provider: None,
+            mode: None,
+            role_id: None,
+            parent_id: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize_responses() {
+        let openai = normalize(
+            ProviderKind::OpenAi,
+            &json!({ "data": [
+                { "id": "gpt-4o" },
+                { "id": "deepseek-reasoner" },
+                { "id": "meta/llama", "context_length": 131072,
+                  "architecture": { "input_modalities": ["text"] },
+                  "supported_parameters": ["tools", "temperature"] }
+            ]}),
+        );
+        assert_eq!(openai[0].vision, Some(true));
+        assert_eq!(openai[1].reasoning, Some(true));
+        assert_eq!(openai[2].context_length, Some(131072));
+        assert_eq!(openai[2].vision, Some(false));
+        assert_eq!(openai[2].tools, Some(true));
+
+        let gemini = normalize(
+            ProviderKind::Gemini,
+            &json!({ "models": [
+                { "name": "models/gemini-2.5-pro", "inputTokenLimit": 1048576,
+                  "supportedGenerationMethods": ["generateContent"], "thinking": true },
+                { "name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"] }
+            ]}),
+        );
+        assert_eq!(gemini.len(), 1);
+        assert_eq!(gemini[0].id, "gemini-2.5-pro");
+        assert_eq!(gemini[0].context_length, Some(1048576));
+        assert_eq!(gemini[0].reasoning, Some(true));
+
+        let ollama = normalize(
+            ProviderKind::Ollama,
+            &json!({ "models": [
+                { "name": "llava:7b", "details": { "families": ["llama", "clip"] } },
+                { "name": "mistral:latest", "details": { "families": ["llama"] } }
+            ]}),
+        );
+        assert_eq!(ollama[0].vision, Some(true));
+        assert_eq!(ollama[1].vision, Some(false));
+    }
+
+    #[test]
+    fn test_cache_roundtrip_and_ttl() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::init_db(&conn).unwrap();
+        assert!(load_cached(&conn, "openai").unwrap().is_none());
+
+        let models = vec![CatalogModel {
+            id: "gpt-4o".into(),
+            vision: Some(true),
+            ..Default::default()
+        }];
+        save_cached(&mut conn, "openai", &models, 1_000).unwrap();
+        let (cached, fetched_at) = load_cached(&conn, "openai").unwrap().unwrap();
+        assert_eq!(cached, models);
+        assert_eq!(
+            cached_model(&conn, "openai", "gpt-4o").unwrap(),
+            Some(models[0].clone())
+        );
+        assert!(is_fresh(fetched_at, 1_000 + CATALOG_TTL_SECS - 1));
+        assert!(!is_fresh(fetched_at, 1_000 + CATALOG_TTL_SECS));
+
+        save_cached(&mut conn, "openai", &[], 2_000).unwrap();
+        assert!(cached_model(&conn, "openai", "gpt-4o").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_check_features_rejects_images_for_text_models() {
+        let text_only = CatalogModel {
+            id: "mistral".into(),
+            vision: Some(false),
+            ..Default::default()
+        };
+        let params = GenerationParams::default();
+        let image = [user_with_file("cat.png")];
+        let doc = [user_with_file("notes.md")];
+
+        assert!(check_features("mistral", Some(&text_only), None, &image, &params).is_err());
+        assert!(check_features("mistral", Some(&text_only), None, &doc, &params).is_ok());
+        // 只有历史消息带图片时不拦截
+        let earlier_image = [user_with_file("cat.png"), user_with_file("notes.md")];
+        assert!(check_features("mistral", Some(&text_only), None, &earlier_image, &params).is_ok());
+        // 目录中没有该模型时不拦截
+        assert!(check_features("mistral", None, None, &image, &params).is_ok());
+    }
+}
@@ -34,6 +34,33 @@ pub struct Message {
     #[serde(rename = "roleId")]
     #[serde(alias = "role_id")]
     pub role_id: Option<String>,
+    /// 对比模式下回答所属的用户消息 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "parentId")]
+    #[serde(alias = "parent_id")]
+    pub parent_id: Option<i64>,
+}
+
+/// 生成参数 (序列化为 OpenAI 兼容字段名)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "max_tokens")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
 }
 
 /// AI 请求封装
@@ -42,11 +69,8 @@ pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<Message>,
     pub stream: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub temperature: Option<f32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(rename = "max_tokens")]
-    pub max_tokens: Option<u32>,
+    #[serde(flatten)]
+    pub params: GenerationParams,
 }
 
 /// 会话元数据：完全兼容蛇形和驼峰
@@ -92,6 +116,12 @@ pub struct Session {
     #[serde(alias = "systemPrompt")]
     #[serde(alias = "system_prompt")]
     pub system_prompt: Option<String>,
+
+    #[serde(rename = "generation_params")]
+    #[serde(alias = "generationParams")]
+    #[serde(alias = "generation_params")]
+    #[serde(default)]
+    pub generation_params: Option<String>,
 }
 
 /// 文件夹结构：完全兼容蛇形和驼峰
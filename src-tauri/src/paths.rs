@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+// ==================================================================================
+// 数据目录定位：界面程序与命令行工具共用，不依赖 AppHandle
+// ==================================================================================
+
+/// 覆盖数据目录 (构建机、脚本可指向任意位置)
+pub const DATA_DIR_ENV: &str = "GOGE_DATA_DIR";
+/// 覆盖资源目录 (Embedding 模型等)，仅命令行工具使用；界面程序由 Tauri 解析资源路径
+pub const RESOURCE_DIR_ENV: &str = "GOGE_RESOURCE_DIR";
+/// 相对资源目录的 Embedding 模型路径
+pub const EMBEDDING_MODEL: &str = "resources/bge-small-zh-v1.5";
+
+fn exe_dir() -> PathBuf {
+    let exe_path = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("."));
+    exe_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf()
+}
+
+fn ensure(dir: PathBuf) -> PathBuf {
+    if !dir.exists() {
+        let _ = std::fs::create_dir_all(&dir);
+    }
+    dir
+}
+
+/// “便携式”数据目录：默认为可执行文件同级目录下的 data
+pub fn data_dir() -> PathBuf {
+    let dir = match std::env::var_os(DATA_DIR_ENV) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => exe_dir().join("data"),
+    };
+    ensure(dir)
+}
+
+pub fn config_dir() -> PathBuf {
+    ensure(data_dir().join("config"))
+}
+
+pub fn chat_db_path() -> PathBuf {
+    data_dir().join("goge.db")
+}
+
+pub fn social_db_path() -> PathBuf {
+    data_dir().join("gole_social.db")
+}
+
+/// 向量记忆库 (LanceDB) 目录
+pub fn memory_dir() -> PathBuf {
+    ensure(data_dir().join("alice_memory"))
+}
+
+/// 命令行工具使用的 Embedding 模型目录
+pub fn embedding_model_dir() -> PathBuf {
+    let base = match std::env::var_os(RESOURCE_DIR_ENV) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => exe_dir(),
+    };
+    base.join(EMBEDDING_MODEL)
+}
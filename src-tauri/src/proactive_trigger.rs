@@ -0,0 +1,535 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+// ==================================================================================
+// 事件触发的主动发言：纪念日 (每年重复)、对记忆中未来事件的事后跟进、用户要求的提醒。
+// 触发器保存在 gole_social.db，由空闲监控循环检查到期项 (见 behavior_scheduler)
+// ==================================================================================
+
+/// 跟进在事件发生后多久触发 (给用户留出经历这件事的时间)
+const FOLLOW_UP_DELAY_MS: i64 = 3 * 3600 * 1000;
+/// 提取结果只给了日期时，事件默认的时间
+const DEFAULT_EVENT_TIME: (u32, u32) = (18, 0);
+
+/// 触发器类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerKind {
+    /// 生日、纪念日等日期
+    Date,
+    /// 用户提到的未来事件，事后关心一下结果
+    FollowUp,
+    /// 用户要求的一次性提醒 (不受作息与空闲阈值限制)
+    Reminder,
+}
+
+impl TriggerKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            TriggerKind::Date => "date",
+            TriggerKind::FollowUp => "follow_up",
+            TriggerKind::Reminder => "reminder",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "date" => Some(TriggerKind::Date),
+            "follow_up" => Some(TriggerKind::FollowUp),
+            "reminder" => Some(TriggerKind::Reminder),
+            _ => None,
+        }
+    }
+}
+
+/// 新建触发器的参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerInput {
+    pub contact_id: i64,
+    /// 发到哪个会话；为空时发到联系人最近的会话
+    #[serde(default)]
+    pub session_id: Option<i64>,
+    pub kind: TriggerKind,
+    /// 简短说明，如 "用户的生日"、"周五期末考试"
+    pub title: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// 触发时间 (Unix 毫秒)
+    pub fire_at: i64,
+    /// 每年的同一天重复 (仅对日期类有意义)
+    #[serde(default)]
+    pub yearly: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProactiveTrigger {
+    pub id: i64,
+    pub contact_id: i64,
+    pub session_id: Option<i64>,
+    pub kind: TriggerKind,
+    pub title: String,
+    pub note: Option<String>,
+    pub fire_at: i64,
+    pub yearly: bool,
+    /// 来源："user" (手动添加) 或 "memory" (从对话中提取)
+    pub source: String,
+}
+
+impl ProactiveTrigger {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let kind: String = row.get(3)?;
+        Ok(Self {
+            id: row.get(0)?,
+            contact_id: row.get(1)?,
+            session_id: row.get(2)?,
+            kind: TriggerKind::parse(&kind).unwrap_or(TriggerKind::Date),
+            title: row.get(4)?,
+            note: row.get(5)?,
+            fire_at: row.get(6)?,
+            yearly: row.get(7)?,
+            source: row.get(8)?,
+        })
+    }
+
+    /// 向角色解释这次为什么主动联系 (注入主动发言提示词)
+    pub fn reason(&self) -> String {
+        let mut reason = match self.kind {
+            TriggerKind::Date => format!(
+                "今天是一个特别的日子：{}。请像朋友一样自然地提起，送上祝福或问候。",
+                self.title
+            ),
+            TriggerKind::FollowUp => format!(
+                "对方之前提到过：{}。这件事现在应该已经有结果了，请自然地关心一下进展或感受。",
+                self.title
+            ),
+            TriggerKind::Reminder => format!(
+                "对方之前请你在这个时间提醒：{}。请直接提醒对方，不要遗漏提醒内容。",
+                self.title
+            ),
+        };
+        if let Some(note) = self.note.as_deref().filter(|n| !n.trim().is_empty()) {
+            reason.push_str(&format!("\n补充说明：{}", note));
+        }
+        reason
+    }
+}
+
+const SELECT_COLUMNS: &str =
+    "id, contact_id, session_id, kind, title, note, fire_at, yearly, source";
+
+pub(crate) fn insert_trigger(
+    conn: &Connection,
+    input: &TriggerInput,
+    source: &str,
+) -> Result<i64, String> {
+    let title = input.title.trim();
+    if title.is_empty() {
+        return Err("触发器说明不能为空".to_string());
+    }
+
+    // 同一件事重复提取时不重复创建
+    let existing: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM proactive_triggers
+             WHERE contact_id = ?1 AND kind = ?2 AND title = ?3 AND fire_at = ?4",
+            params![input.contact_id, input.kind.as_str(), title, input.fire_at],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    conn.execute(
+        "INSERT INTO proactive_triggers (contact_id, session_id, kind, title, note, fire_at, yearly, source)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            input.contact_id,
+            input.session_id,
+            input.kind.as_str(),
+            title,
+            input.note,
+            input.fire_at,
+            input.yearly,
+            source
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub(crate) fn list_triggers(
+    conn: &Connection,
+    contact_id: Option<i64>,
+) -> Result<Vec<ProactiveTrigger>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM proactive_triggers
+             WHERE ?1 IS NULL OR contact_id = ?1
+             ORDER BY fire_at",
+            SELECT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![contact_id], ProactiveTrigger::from_row)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// 到期的触发器及它要发往的会话 (联系人没有任何会话的触发器不返回)
+pub(crate) fn due_triggers(
+    conn: &Connection,
+    now_ms: i64,
+) -> Result<Vec<(ProactiveTrigger, i64)>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}, COALESCE(
+                 (SELECT s.id FROM social_sessions s WHERE s.id = t.session_id),
+                 (SELECT s.id FROM social_sessions s WHERE s.contact_id = t.contact_id AND s.is_group = 0
+                  ORDER BY s.updated_at DESC, s.id DESC LIMIT 1)
+             )
+             FROM proactive_triggers t
+             WHERE fire_at <= ?1
+             ORDER BY fire_at",
+            SELECT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![now_ms], |row| {
+            Ok((
+                ProactiveTrigger::from_row(row)?,
+                row.get::<_, Option<i64>>(9)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut due = Vec::new();
+    for row in rows {
+        if let (trigger, Some(session_id)) = row.map_err(|e| e.to_string())? {
+            due.push((trigger, session_id));
+        }
+    }
+    Ok(due)
+}
+
+/// 触发器已处理：每年重复的推到下一次，其余删除
+pub(crate) fn complete_trigger(
+    conn: &Connection,
+    trigger: &ProactiveTrigger,
+    now_ms: i64,
+) -> Result<(), String> {
+    if trigger.yearly {
+        conn.execute(
+            "UPDATE proactive_triggers SET fire_at = ?1 WHERE id = ?2",
+            params![next_yearly(trigger.fire_at, now_ms), trigger.id],
+        )
+    } else {
+        conn.execute(
+            "DELETE FROM proactive_triggers WHERE id = ?1",
+            params![trigger.id],
+        )
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub(crate) fn delete_trigger(conn: &Connection, id: i64) -> Result<(), String> {
+    conn.execute("DELETE FROM proactive_triggers WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 按本地日历把时间推到晚于 now 的下一年同一天 (2 月 29 日在平年落到 2 月 28 日)
+fn next_yearly(fire_at_ms: i64, now_ms: i64) -> i64 {
+    let Some(original) = Local.timestamp_millis_opt(fire_at_ms).earliest() else {
+        return fire_at_ms;
+    };
+    let original = original.naive_local();
+    let mut year = original.year();
+    loop {
+        year += 1;
+        let date = original
+            .date()
+            .with_year(year)
+            .or_else(|| NaiveDate::from_ymd_opt(year, original.month(), 28));
+        let Some(next) = date
+            .map(|d| d.and_time(original.time()))
+            .and_then(|dt| local_ms(&dt))
+        else {
+            return fire_at_ms;
+        };
+        if next > now_ms {
+            return next;
+        }
+    }
+}
+
+fn local_ms(naive: &NaiveDateTime) -> Option<i64> {
+    Local
+        .from_local_datetime(naive)
+        .earliest()
+        .map(|dt| dt.timestamp_millis())
+}
+
+// ==================================================================================
+// 从记忆提取结果中识别事件
+// ==================================================================================
+
+/// 附加到记忆提取提示词中的说明 (today 用于把 "周五"、"明天" 换算成具体日期)
+pub(crate) fn extraction_instructions(today: DateTime<Local>) -> String {
+    format!(
+        "另外，今天是 {}。如果用户提到未来某个时间会发生的事 (考试、面试、出行等)，\
+         另起一行输出：#跟进 YYYY-MM-DD HH:MM 事件简述；\
+         如果用户明确要求在某个时间提醒，另起一行输出：#提醒 YYYY-MM-DD HH:MM 提醒内容。\
+         时间不确定时可以省略 HH:MM。这些行不计入事实条数。",
+        today.format("%Y-%m-%d %A")
+    )
+}
+
+/// 解析一行 "#跟进 2026-10-23 09:00 期末考试" / "#提醒 2026-10-19 出门带伞"
+/// 返回 (类型, 触发时间, 说明)；跟进会推迟到事件结束之后
+pub(crate) fn parse_extracted_line(line: &str) -> Option<(TriggerKind, i64, String)> {
+    let line = line.trim().trim_start_matches(['-', '*', ' ']);
+    let (kind, rest) = if let Some(rest) = line.strip_prefix("#跟进") {
+        (TriggerKind::FollowUp, rest)
+    } else if let Some(rest) = line.strip_prefix("#提醒") {
+        (TriggerKind::Reminder, rest)
+    } else {
+        return None;
+    };
+
+    let mut parts = rest.split_whitespace();
+    let date = NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?;
+    let mut words: Vec<&str> = parts.collect();
+    let time = match words
+        .first()
+        .and_then(|w| NaiveTime::parse_from_str(w, "%H:%M").ok())
+    {
+        Some(time) => {
+            words.remove(0);
+            time
+        }
+        None => NaiveTime::from_hms_opt(DEFAULT_EVENT_TIME.0, DEFAULT_EVENT_TIME.1, 0)?,
+    };
+    let title = words.join(" ");
+    if title.is_empty() {
+        return None;
+    }
+
+    let at = local_ms(&date.and_time(time))?;
+    let fire_at = match kind {
+        TriggerKind::FollowUp => at + FOLLOW_UP_DELAY_MS,
+        _ => at,
+    };
+    Some((kind, fire_at, title))
+}
+
+/// 保存提取出的事件 (已经过去的忽略)，返回新建的触发器数
+pub(crate) fn record_extracted(
+    conn: &Connection,
+    contact_id: i64,
+    session_id: i64,
+    lines: &[&str],
+    now_ms: i64,
+) -> usize {
+    let mut created = 0;
+    for line in lines {
+        let Some((kind, fire_at, title)) = parse_extracted_line(line) else {
+            println!("⚠️ [触发器] 无法解析: {}", line);
+            continue;
+        };
+        if fire_at <= now_ms {
+            continue;
+        }
+        let input = TriggerInput {
+            contact_id,
+            session_id: Some(session_id),
+            kind,
+            title,
+            note: None,
+            fire_at,
+            yearly: false,
+        };
+        match insert_trigger(conn, &input, "memory") {
+            Ok(id) => {
+                println!("⏰ [触发器] 已记录 #{} {:?}: {}", id, kind, input.title);
+                created += 1;
+            }
+            Err(e) => println!("❌ [触发器] 保存失败: {}", e),
+        }
+    }
+    created
+}
+
+// ==================================================================================
+// 命令
+// ==================================================================================
+
+#[tauri::command]
+pub fn list_proactive_triggers(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    contact_id: Option<i64>,
+) -> Result<Vec<ProactiveTrigger>, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    list_triggers(&conn, contact_id)
+}
+
+#[tauri::command]
+pub fn add_proactive_trigger(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    trigger: TriggerInput,
+) -> Result<i64, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    insert_trigger(&conn, &trigger, "user")
+}
+
+#[tauri::command]
+pub fn delete_proactive_trigger(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    id: i64,
+) -> Result<(), String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    delete_trigger(&conn, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::social_db::init_social_db(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO contacts (id, name) VALUES (1, 'Alice'), (2, 'Bob');
+             INSERT INTO social_sessions (id, contact_id, updated_at) VALUES
+                 (10, 1, '2026-01-01 00:00:00'),
+                 (11, 1, '2026-02-01 00:00:00');
+             INSERT INTO social_sessions (id, contact_id, updated_at, is_group) VALUES
+                 (12, 1, '2026-03-01 00:00:00', 1);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn input(contact_id: i64, kind: TriggerKind, fire_at: i64) -> TriggerInput {
+        TriggerInput {
+            contact_id,
+            session_id: None,
+            kind,
+            title: "生日".into(),
+            note: None,
+            fire_at,
+            yearly: false,
+        }
+    }
+
+    fn local(y: i32, m: u32, d: u32, h: u32, mi: u32) -> i64 {
+        local_ms(
+            &NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, mi, 0)
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_due_triggers_resolve_session() {
+        let conn = setup();
+        let first = insert_trigger(&conn, &input(1, TriggerKind::Date, 1_000), "user").unwrap();
+        // 重复添加返回已有的触发器
+        assert_eq!(
+            insert_trigger(&conn, &input(1, TriggerKind::Date, 1_000), "user").unwrap(),
+            first
+        );
+        insert_trigger(&conn, &input(1, TriggerKind::Reminder, 5_000), "user").unwrap();
+        // Bob 没有会话，到期也不返回
+        insert_trigger(&conn, &input(2, TriggerKind::Date, 1_000), "user").unwrap();
+        assert!(insert_trigger(
+            &conn,
+            &TriggerInput {
+                title: "  ".into(),
+                ..input(1, TriggerKind::Date, 1_000)
+            },
+            "user"
+        )
+        .is_err());
+
+        let due = due_triggers(&conn, 2_000).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.id, first);
+        // 未指定会话时发往最近更新的私聊会话 (不会落到 Alice 担任群主的群聊)
+        assert_eq!(due[0].1, 11);
+        assert_eq!(list_triggers(&conn, Some(1)).unwrap().len(), 2);
+        assert_eq!(list_triggers(&conn, None).unwrap().len(), 3);
+
+        complete_trigger(&conn, &due[0].0, 2_000).unwrap();
+        assert!(due_triggers(&conn, 2_000).unwrap().is_empty());
+        assert_eq!(list_triggers(&conn, Some(1)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_yearly_trigger_advances() {
+        let conn = setup();
+        let birthday = local(2024, 2, 29, 9, 0);
+        insert_trigger(
+            &conn,
+            &TriggerInput {
+                yearly: true,
+                ..input(1, TriggerKind::Date, birthday)
+            },
+            "user",
+        )
+        .unwrap();
+
+        // 应用很久没运行：直接推到 now 之后的那一年
+        let now = local(2026, 3, 1, 0, 0);
+        let (trigger, _) = due_triggers(&conn, now).unwrap().remove(0);
+        complete_trigger(&conn, &trigger, now).unwrap();
+        let next = list_triggers(&conn, Some(1)).unwrap()[0].fire_at;
+        assert_eq!(next, local(2027, 2, 28, 9, 0));
+    }
+
+    #[test]
+    fn test_parse_extracted_line() {
+        let (kind, fire_at, title) =
+            parse_extracted_line("#跟进 2026-10-23 09:00 期末考试").unwrap();
+        assert_eq!(kind, TriggerKind::FollowUp);
+        assert_eq!(fire_at, local(2026, 10, 23, 12, 0));
+        assert_eq!(title, "期末考试");
+
+        let (kind, fire_at, title) = parse_extracted_line("- #提醒 2026-10-19 出门 带伞").unwrap();
+        assert_eq!(kind, TriggerKind::Reminder);
+        assert_eq!(fire_at, local(2026, 10, 19, 18, 0));
+        assert_eq!(title, "出门 带伞");
+
+        assert!(parse_extracted_line("用户喜欢猫").is_none());
+        assert!(parse_extracted_line("#提醒 明天 交房租").is_none());
+        assert!(parse_extracted_line("#提醒 2026-10-19 08:00").is_none());
+    }
+
+    #[test]
+    fn test_record_extracted_skips_past_events() {
+        let conn = setup();
+        let now = local(2026, 10, 20, 0, 0);
+        let created = record_extracted(
+            &conn,
+            1,
+            10,
+            &[
+                "#跟进 2026-10-23 09:00 期末考试",
+                "#提醒 2026-10-19 08:00 交房租",
+                "#提醒 乱写",
+            ],
+            now,
+        );
+        assert_eq!(created, 1);
+        let triggers = list_triggers(&conn, Some(1)).unwrap();
+        assert_eq!(triggers[0].session_id, Some(10));
+        assert_eq!(triggers[0].source, "memory");
+        assert!(triggers[0].reason().contains("期末考试"));
+    }
+}
@@ -0,0 +1,756 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+
+/// include 的最大嵌套深度 (防止 A 引用 B、B 又引用 A)
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// 模板中允许出现的顶层变量
+/// 未出现在此列表中的变量视为拼写错误；已声明但当前没有值的变量渲染为空
+pub const KNOWN_VARIABLES: &[&str] = &[
+    "user",
+    "contact",
+    "session",
+    "now",
+    "date",
+    "time",
+    "weekday",
+    "mood",
+    "busy_level",
+    "interest_level",
+    "memory",
+    "recent_messages",
+    "conversation_history",
+    "idle_minutes",
+    "activity",
+    "local_time",
+    "trigger",
+    // 兼容旧模板 (proactive_message.txt) 的扁平写法
+    "contact_name",
+];
+
+/// 模板错误 (行列号从 1 开始)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "第 {} 行第 {} 列: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+impl Pos {
+    fn error(self, message: impl Into<String>) -> TemplateError {
+        TemplateError {
+            message: message.into(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Var {
+        path: Vec<String>,
+        pos: Pos,
+    },
+    If {
+        path: Vec<String>,
+        negate: bool,
+        pos: Pos,
+        then_branch: Vec<Node>,
+        else_branch: Vec<Node>,
+    },
+    Include {
+        name: String,
+        pos: Pos,
+    },
+}
+
+// ==================================================================================
+// 解析
+// ==================================================================================
+
+struct Tag {
+    body: String,
+    pos: Pos,
+}
+
+enum Token {
+    Text(String),
+    Tag(Tag),
+}
+
+fn advance(pos: &mut Pos, text: &str) {
+    for ch in text.chars() {
+        if ch == '\n' {
+            pos.line += 1;
+            pos.column = 1;
+        } else {
+            pos.column += 1;
+        }
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut pos = Pos { line: 1, column: 1 };
+    let mut rest = src;
+    let mut text = String::new();
+
+    while !rest.is_empty() {
+        // `\{{` 转义为字面量
+        if let Some(after) = rest.strip_prefix("\\{{") {
+            text.push_str("{{");
+            advance(&mut pos, "\\{{");
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("{{") {
+            let tag_pos = pos;
+            let end = after
+                .find("}}")
+                .ok_or_else(|| tag_pos.error("标签未闭合，缺少 }}"))?;
+            if !text.is_empty() {
+                tokens.push(Token::Text(std::mem::take(&mut text)));
+            }
+            let body = &after[..end];
+            tokens.push(Token::Tag(Tag {
+                body: body.trim().to_string(),
+                pos: tag_pos,
+            }));
+            let consumed = &rest[..end + 4];
+            advance(&mut pos, consumed);
+            rest = &rest[end + 4..];
+            continue;
+        }
+        let ch = rest.chars().next().unwrap();
+        text.push(ch);
+        advance(&mut pos, &rest[..ch.len_utf8()]);
+        rest = &rest[ch.len_utf8()..];
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok(tokens)
+}
+
+fn parse_path(expr: &str, pos: Pos) -> Result<Vec<String>, TemplateError> {
+    if expr.is_empty() {
+        return Err(pos.error("缺少变量名"));
+    }
+    let path: Vec<String> = expr.split('.').map(|s| s.trim().to_string()).collect();
+    for seg in &path {
+        if seg.is_empty() || !seg.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(pos.error(format!("非法的变量名: {}", expr)));
+        }
+    }
+    Ok(path)
+}
+
+/// 解析到遇到 `{{else}}` / `{{/if}}` 或结束为止
+fn parse_nodes(
+    tokens: &mut std::iter::Peekable<std::vec::IntoIter<Token>>,
+    in_block: bool,
+) -> Result<(Vec<Node>, Option<Tag>), TemplateError> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        let tag = match token {
+            Token::Text(t) => {
+                nodes.push(Node::Text(t));
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+        let body = tag.body.as_str();
+
+        if body.starts_with('!') {
+            continue; // 注释
+        }
+        if body == "else" || body == "/if" || body == "/unless" {
+            if !in_block {
+                return Err(tag
+                    .pos
+                    .error(format!("多余的 {{{{{}}}}}，没有对应的条件块", body)));
+            }
+            return Ok((nodes, Some(tag)));
+        }
+        if let Some(expr) = body
+            .strip_prefix("#if ")
+            .map(|e| (e, false))
+            .or_else(|| body.strip_prefix("#unless ").map(|e| (e, true)))
+        {
+            let (expr, negate) = expr;
+            let path = parse_path(expr.trim(), tag.pos)?;
+            let closing = if negate { "/unless" } else { "/if" };
+
+            let (then_branch, end) = parse_nodes(tokens, true)?;
+            let end = end.ok_or_else(|| {
+                tag.pos
+                    .error(format!("条件块未闭合，缺少 {{{{{}}}}}", closing))
+            })?;
+            let else_branch = if end.body == "else" {
+                let (else_nodes, end) = parse_nodes(tokens, true)?;
+                let end = end.ok_or_else(|| {
+                    tag.pos
+                        .error(format!("条件块未闭合，缺少 {{{{{}}}}}", closing))
+                })?;
+                if end.body != closing {
+                    return Err(end.pos.error(format!("期望 {{{{{}}}}}", closing)));
+                }
+                else_nodes
+            } else if end.body == closing {
+                Vec::new()
+            } else {
+                return Err(end.pos.error(format!("期望 {{{{{}}}}}", closing)));
+            };
+
+            nodes.push(Node::If {
+                path,
+                negate,
+                pos: tag.pos,
+                then_branch,
+                else_branch,
+            });
+            continue;
+        }
+        if body.starts_with('#') || body.starts_with('/') {
+            return Err(tag.pos.error(format!("不支持的块标签: {}", body)));
+        }
+        if let Some(name) = body.strip_prefix('>') {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(tag.pos.error("include 缺少模板名"));
+            }
+            nodes.push(Node::Include {
+                name: name.to_string(),
+                pos: tag.pos,
+            });
+            continue;
+        }
+        nodes.push(Node::Var {
+            path: parse_path(body, tag.pos)?,
+            pos: tag.pos,
+        });
+    }
+    Ok((nodes, None))
+}
+
+fn parse(src: &str) -> Result<Vec<Node>, TemplateError> {
+    let tokens = tokenize(src)?;
+    let mut iter = tokens.into_iter().peekable();
+    let (nodes, _) = parse_nodes(&mut iter, false)?;
+    Ok(nodes)
+}
+
+// ==================================================================================
+// 上下文
+// ==================================================================================
+
+/// 模板变量上下文
+#[derive(Debug, Clone)]
+pub struct TemplateContext {
+    vars: Map<String, Value>,
+}
+
+impl Default for TemplateContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TemplateContext {
+    /// 创建上下文并填充时间相关变量
+    pub fn new() -> Self {
+        let now = chrono::Local::now();
+        let weekday = match now.format("%u").to_string().as_str() {
+            "1" => "星期一",
+            "2" => "星期二",
+            "3" => "星期三",
+            "4" => "星期四",
+            "5" => "星期五",
+            "6" => "星期六",
+            _ => "星期日",
+        };
+        let mut vars = Map::new();
+        vars.insert(
+            "now".into(),
+            json!(now.format("%Y-%m-%d %H:%M").to_string()),
+        );
+        vars.insert("date".into(), json!(now.format("%Y-%m-%d").to_string()));
+        vars.insert("time".into(), json!(now.format("%H:%M").to_string()));
+        vars.insert("weekday".into(), json!(weekday));
+        Self { vars }
+    }
+
+    pub fn set(&mut self, key: &str, value: Value) -> &mut Self {
+        self.vars.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn with_user(&mut self, nickname: &str, bio: Option<&str>) -> &mut Self {
+        self.set(
+            "user",
+            json!({ "nickname": nickname, "bio": bio.unwrap_or_default() }),
+        )
+    }
+
+    pub fn with_contact(&mut self, contact: Value) -> &mut Self {
+        if let Some(name) = contact["name"].as_str() {
+            self.vars.insert("contact_name".into(), json!(name));
+        }
+        self.set("contact", contact)
+    }
+
+    pub fn with_session(&mut self, id: i64, title: &str) -> &mut Self {
+        self.set("session", json!({ "id": id, "title": title }))
+    }
+
+    pub fn with_state(
+        &mut self,
+        mood: Option<&str>,
+        busy_level: Option<f32>,
+        interest_level: Option<f32>,
+    ) -> &mut Self {
+        self.set("mood", json!(mood.unwrap_or("neutral")));
+        self.set(
+            "busy_level",
+            json!(format!("{:.1}", busy_level.unwrap_or(0.5))),
+        );
+        self.set(
+            "interest_level",
+            json!(format!("{:.1}", interest_level.unwrap_or(0.5))),
+        )
+    }
+
+    /// 作息变量：{{activity}} 为当前活动描述，{{local_time}} 为角色所在时区的时间
+    /// (没有作息表时 activity 为空，local_time 取本机时间)
+    pub fn with_activity(&mut self, activity: Option<&crate::routine::ActivityState>) -> &mut Self {
+        let local_time = match activity {
+            Some(a) => json!(a.local_time),
+            None => self.vars.get("time").cloned().unwrap_or_default(),
+        };
+        self.set("local_time", local_time);
+        self.set(
+            "activity",
+            json!(activity.map(|a| a.label.as_str()).unwrap_or_default()),
+        )
+    }
+}
+
+// ==================================================================================
+// 渲染
+// ==================================================================================
+
+/// 渲染结果 (附带实际用到的顶层变量，便于调用方判断是否还需要额外注入)
+#[derive(Debug)]
+pub struct Rendered {
+    pub text: String,
+    pub used: HashSet<String>,
+}
+
+struct Renderer<'a> {
+    ctx: &'a TemplateContext,
+    resolve_include: &'a dyn Fn(&str) -> Option<String>,
+    stack: Vec<String>,
+    used: HashSet<String>,
+}
+
+fn is_truthy(v: &Value) -> bool {
+    match v {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|f| f != 0.0),
+        Value::String(s) => !s.trim().is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+impl Renderer<'_> {
+    fn lookup(&mut self, path: &[String], pos: Pos) -> Result<Value, TemplateError> {
+        let root = &path[0];
+        if !KNOWN_VARIABLES.contains(&root.as_str()) {
+            return Err(pos.error(format!(
+                "未知变量: {} (可用: {})",
+                root,
+                KNOWN_VARIABLES.join(", ")
+            )));
+        }
+        self.used.insert(root.clone());
+
+        let mut current = match self.ctx.vars.get(root) {
+            Some(v) => v.clone(),
+            None => return Ok(Value::Null),
+        };
+        for (i, seg) in path.iter().enumerate().skip(1) {
+            current = match current {
+                Value::Object(mut map) => match map.remove(seg) {
+                    Some(v) => v,
+                    None => {
+                        return Err(pos.error(format!("{} 没有字段 {}", path[..i].join("."), seg)))
+                    }
+                },
+                Value::Null => return Ok(Value::Null),
+                _ => {
+                    return Err(pos.error(format!("{} 不是对象", path[..i].join("."))));
+                }
+            };
+        }
+        Ok(current)
+    }
+
+    fn render_nodes(&mut self, nodes: &[Node], out: &mut String) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(t) => out.push_str(t),
+                Node::Var { path, pos } => match self.lookup(path, *pos)? {
+                    Value::Null => {}
+                    Value::String(s) => out.push_str(&s),
+                    Value::Array(items) => {
+                        let lines: Vec<String> = items
+                            .iter()
+                            .map(|v| v.as_str().map(String::from).unwrap_or(v.to_string()))
+                            .collect();
+                        out.push_str(&lines.join("\n"));
+                    }
+                    Value::Object(_) => {
+                        return Err(pos.error(format!(
+                            "{} 是对象，请指定字段，例如 {{{{{}.name}}}}",
+                            path.join("."),
+                            path.join(".")
+                        )))
+                    }
+                    other => out.push_str(&other.to_string()),
+                },
+                Node::If {
+                    path,
+                    negate,
+                    pos,
+                    then_branch,
+                    else_branch,
+                } => {
+                    let truthy = is_truthy(&self.lookup(path, *pos)?);
+                    let branch = if truthy != *negate {
+                        then_branch
+                    } else {
+                        else_branch
+                    };
+                    self.render_nodes(branch, out)?;
+                }
+                Node::Include { name, pos } => {
+                    if self.stack.len() >= MAX_INCLUDE_DEPTH || self.stack.contains(name) {
+                        return Err(pos.error(format!(
+                            "循环引用模板: {} -> {}",
+                            self.stack.join(" -> "),
+                            name
+                        )));
+                    }
+                    let src = (self.resolve_include)(name)
+                        .ok_or_else(|| pos.error(format!("找不到被引用的模板: {}", name)))?;
+                    let nodes = parse(&src).map_err(|e| {
+                        pos.error(format!(
+                            "模板 {} 第 {} 行第 {} 列: {}",
+                            name, e.line, e.column, e.message
+                        ))
+                    })?;
+                    self.stack.push(name.clone());
+                    let result = self.render_nodes(&nodes, out);
+                    self.stack.pop();
+                    result.map_err(|e| {
+                        if self.stack.is_empty() {
+                            pos.error(format!(
+                                "模板 {} 第 {} 行第 {} 列: {}",
+                                name, e.line, e.column, e.message
+                            ))
+                        } else {
+                            e
+                        }
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 渲染模板
+pub fn render(
+    src: &str,
+    ctx: &TemplateContext,
+    resolve_include: &dyn Fn(&str) -> Option<String>,
+) -> Result<Rendered, TemplateError> {
+    let nodes = parse(src)?;
+    let mut renderer = Renderer {
+        ctx,
+        resolve_include,
+        stack: Vec::new(),
+        used: HashSet::new(),
+    };
+    let mut out = String::new();
+    renderer.render_nodes(&nodes, &mut out)?;
+    Ok(Rendered {
+        text: out,
+        used: renderer.used,
+    })
+}
+
+/// 渲染失败时记录日志并退回原文 (用于运行时注入，不能因为模板错误中断对话)
+pub fn render_or_raw(
+    src: &str,
+    ctx: &TemplateContext,
+    resolve_include: &dyn Fn(&str) -> Option<String>,
+) -> Rendered {
+    match render(src, ctx, resolve_include) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("⚠️ [模板] 渲染失败，使用原文: {}", e);
+            Rendered {
+                text: src.to_string(),
+                used: HashSet::new(),
+            }
+        }
+    }
+}
+
+/// 标准 include 解析：优先提示词库 (按 id 或名称)，其次 assets/prompts 下的模板文件
+pub fn library_resolver(prompt_library: &Value) -> impl Fn(&str) -> Option<String> + '_ {
+    move |name: &str| {
+        let from_library = prompt_library.as_array().and_then(|items| {
+            items
+                .iter()
+                .find(|p| p["id"].as_str() == Some(name) || p["name"].as_str() == Some(name))
+                .and_then(|p| p["content"].as_str())
+                .map(String::from)
+        });
+        from_library.or_else(|| {
+            let file = if name.ends_with(".txt") {
+                name.to_string()
+            } else {
+                format!("{}.txt", name)
+            };
+            crate::character_state::StateAnalyzer::load_prompt_template(&file).ok()
+        })
+    }
+}
+
+// ==================================================================================
+// 从数据库加载上下文
+// ==================================================================================
+
+/// 从社交库读取当前用户资料
+pub fn load_user(ctx: &mut TemplateContext, social_conn: &Connection) {
+    let profile: Option<(String, Option<String>)> = social_conn
+        .query_row(
+            "SELECT nickname, bio FROM profiles ORDER BY id ASC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .unwrap_or(None);
+    if let Some((nickname, bio)) = profile {
+        ctx.with_user(&nickname, bio.as_deref());
+    }
+}
+
+/// 读取联系人、社交会话与角色状态
+pub fn load_social(
+    ctx: &mut TemplateContext,
+    social_conn: &Connection,
+    contact_id: i64,
+    session_id: Option<i64>,
+) -> rusqlite::Result<()> {
+    let contact = social_conn
+        .query_row(
+            "SELECT name, remark, status, model FROM contacts WHERE id = ?1",
+            params![contact_id],
+            |row| {
+                Ok(json!({
+                    "id": contact_id,
+                    "name": row.get::<_, String>(0)?,
+                    "remark": row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    "status": row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    "model": row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                }))
+            },
+        )
+        .optional()?;
+    if let Some(contact) = contact {
+        ctx.with_contact(contact);
+    }
+
+    if let Some(session_id) = session_id {
+        let title: Option<String> = social_conn
+            .query_row(
+                "SELECT title FROM social_sessions WHERE id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(title) = title {
+            ctx.with_session(session_id, &title);
+        }
+
+        let state: Option<(String, f32, f32)> = social_conn
+            .query_row(
+                "SELECT mood, busy_level, interest_level FROM character_states WHERE contact_id = ?1 AND session_id = ?2",
+                params![contact_id, session_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        match state {
+            Some((mood, busy, interest)) => {
+                ctx.with_state(Some(&mood), Some(busy), Some(interest));
+            }
+            None => {
+                ctx.with_state(None, None, None);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_includes(_: &str) -> Option<String> {
+        None
+    }
+
+    fn ctx() -> TemplateContext {
+        let mut ctx = TemplateContext::new();
+        ctx.with_user("小明", None)
+            .with_contact(json!({ "name": "小红", "remark": "" }))
+            .with_session(3, "周末计划")
+            .with_state(Some("happy"), Some(0.2), None);
+        ctx
+    }
+
+    #[test]
+    fn test_variables_and_paths() {
+        let r = render(
+            "你好 {{user.nickname}}，我是{{contact.name}}。会话: {{session.title}} 心情={{mood}} 忙碌={{busy_level}}",
+            &ctx(),
+            &no_includes,
+        )
+        .unwrap();
+        assert_eq!(
+            r.text,
+            "你好 小明，我是小红。会话: 周末计划 心情=happy 忙碌=0.2"
+        );
+        assert!(r.used.contains("user"));
+        assert!(!r.used.contains("memory"));
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let tpl = "{{#if memory}}记忆: {{memory}}{{else}}无记忆{{/if}}|{{#unless contact.remark}}无备注{{/unless}}";
+        assert_eq!(
+            render(tpl, &ctx(), &no_includes).unwrap().text,
+            "无记忆|无备注"
+        );
+
+        let mut c = ctx();
+        c.set("memory", json!("喜欢猫"));
+        assert_eq!(
+            render(tpl, &c, &no_includes).unwrap().text,
+            "记忆: 喜欢猫|无备注"
+        );
+    }
+
+    #[test]
+    fn test_includes_and_cycles() {
+        let resolver = |name: &str| match name {
+            "greeting" => Some("你好，{{user.nickname}}".to_string()),
+            "loop_a" => Some("{{> loop_b}}".to_string()),
+            "loop_b" => Some("{{> loop_a}}".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            render("{{> greeting}}！", &ctx(), &resolver).unwrap().text,
+            "你好，小明！"
+        );
+        let err = render("\n  {{> loop_a}}", &ctx(), &resolver).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert!(err.message.contains("循环引用"));
+        assert!(render("{{> missing}}", &ctx(), &resolver).is_err());
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let err = render("第一行\n第二行 {{usr.nickname}}", &ctx(), &no_includes).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 5));
+        assert!(err.message.contains("未知变量"));
+
+        let err = parse("abc {{#if mood}} 没有闭合").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 5));
+
+        let err = parse("{{/if}}").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+
+        let err = parse("x {{mood").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 3));
+
+        let err = render("{{contact}}", &ctx(), &no_includes).unwrap_err();
+        assert!(err.message.contains("对象"));
+
+        let err = render("{{contact.age}}", &ctx(), &no_includes).unwrap_err();
+        assert!(err.message.contains("没有字段"));
+    }
+
+    #[test]
+    fn test_legacy_flat_variables_and_escape() {
+        let r = render(
+            "你是{{contact_name}}，{{! 注释 }}已经 {{idle_minutes}} 分钟 \\{{raw}}",
+            &ctx(),
+            &no_includes,
+        )
+        .unwrap();
+        assert_eq!(r.text, "你是小红，已经  分钟 {{raw}}");
+    }
+
+    #[test]
+    fn test_load_social_context() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::social_db::init_social_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO contacts (name, remark) VALUES ('阿青', '老同学')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO social_sessions (contact_id, title) VALUES (1, '叙旧')",
+            [],
+        )
+        .unwrap();
+
+        let mut ctx = TemplateContext::new();
+        load_user(&mut ctx, &conn);
+        load_social(&mut ctx, &conn, 1, Some(1)).unwrap();
+        let r = render(
+            "{{contact.name}}({{contact.remark}}) / {{session.title}} / {{mood}}",
+            &ctx,
+            &no_includes,
+        )
+        .unwrap();
+        assert_eq!(r.text, "阿青(老同学) / 叙旧 / neutral");
+    }
+}
@@ -0,0 +1,527 @@
+use crate::commands::config_cmd::AppConfig;
+use crate::config_store::ConfigFileError;
+use crate::models::GenerationParams;
+use crate::net::NetworkSettings;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// providers.json 中允许出现的字段 (其余字段视为拼写错误，加载时给出警告)
+const KNOWN_KEYS: &[&str] = &[
+    "id",
+    "name",
+    "icon",
+    "enabled",
+    "apiKey",
+    "baseUrl",
+    "kind",
+    "auth",
+    "headers",
+    "proxy",
+    "network",
+    "models",
+    "defaultModel",
+    "contextWindow",
+    "contextLimits",
+    "temperature",
+    "maxTokens",
+    "topP",
+    "presencePenalty",
+    "frequencyPenalty",
+    "stop",
+    "seed",
+    "reasoningEffort",
+    "customParams",
+    "disableUrlSuffix",
+    "isCustom",
+    "lastTestModelId",
+];
+
+/// 接口协议
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI 兼容的 /chat/completions
+    OpenAi,
+    /// Google Gemini 原生接口
+    Gemini,
+    /// Ollama 原生 /api/chat (NDJSON 流)
+    Ollama,
+    /// llama.cpp server (OpenAI 兼容接口，可不设 API Key)
+    #[serde(rename = "llamacpp")]
+    LlamaCpp,
+}
+
+impl ProviderKind {
+    /// 未显式指定 kind 时按 id 推断 (兼容旧配置)
+    fn infer(id: &str) -> Self {
+        match id {
+            "gemini" => ProviderKind::Gemini,
+            "ollama" => ProviderKind::Ollama,
+            "llamacpp" => ProviderKind::LlamaCpp,
+            _ => ProviderKind::OpenAi,
+        }
+    }
+}
+
+/// 鉴权方式
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AuthScheme {
+    /// Authorization: Bearer <key>
+    Bearer,
+    /// 自定义请求头，例如 x-api-key
+    Header {
+        name: String,
+    },
+    /// URL 查询参数，例如 Gemini 的 ?key=
+    Query {
+        param: String,
+    },
+    None,
+}
+
+impl AuthScheme {
+    fn default_for(kind: ProviderKind) -> Self {
+        match kind {
+            ProviderKind::OpenAi | ProviderKind::LlamaCpp => AuthScheme::Bearer,
+            ProviderKind::Gemini => AuthScheme::Query {
+                param: "key".into(),
+            },
+            ProviderKind::Ollama => AuthScheme::None,
+        }
+    }
+}
+
+/// 模型能力
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ModelCapabilities {
+    #[serde(default)]
+    pub vision: bool,
+    #[serde(default)]
+    pub web: bool,
+    #[serde(default)]
+    pub reasoning: bool,
+    #[serde(default)]
+    pub tools: bool,
+    #[serde(default)]
+    pub image: bool,
+}
+
+/// 前端的两种写法：{ vision: true } 或 ["vision", "search"]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawCapabilities {
+    Flags(ModelCapabilities),
+    List(Vec<String>),
+}
+
+impl From<RawCapabilities> for ModelCapabilities {
+    fn from(raw: RawCapabilities) -> Self {
+        match raw {
+            RawCapabilities::Flags(f) => f,
+            RawCapabilities::List(list) => {
+                let has = |name: &str| list.iter().any(|f| f == name);
+                ModelCapabilities {
+                    vision: has("vision"),
+                    web: has("web") || has("search"),
+                    reasoning: has("reasoning"),
+                    tools: has("tools"),
+                    image: has("image"),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ModelEntry {
+    pub id: String,
+    pub name: Option<String>,
+    pub group: Option<String>,
+    pub capabilities: ModelCapabilities,
+    #[serde(rename = "contextWindow")]
+    pub context_window: Option<u64>,
+}
+
+/// 模型列表兼容旧版字符串数组
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawModel {
+    Id(String),
+    Info {
+        id: String,
+        name: Option<String>,
+        group: Option<String>,
+        features: Option<RawCapabilities>,
+        #[serde(rename = "contextWindow")]
+        context_window: Option<u64>,
+    },
+}
+
+impl From<RawModel> for ModelEntry {
+    fn from(raw: RawModel) -> Self {
+        match raw {
+            RawModel::Id(id) => ModelEntry {
+                id,
+                name: None,
+                group: None,
+                capabilities: ModelCapabilities::default(),
+                context_window: None,
+            },
+            RawModel::Info {
+                id,
+                name,
+                group,
+                features,
+                context_window,
+            } => ModelEntry {
+                id,
+                name,
+                group,
+                capabilities: features.map(Into::into).unwrap_or_default(),
+                context_window,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawProvider {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default, rename = "apiKey")]
+    api_key: String,
+    #[serde(default, rename = "baseUrl")]
+    base_url: Option<String>,
+    #[serde(default)]
+    kind: Option<ProviderKind>,
+    #[serde(default)]
+    auth: Option<AuthScheme>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// 旧写法，等同于 network.proxy
+    #[serde(default)]
+    proxy: Option<String>,
+    #[serde(default)]
+    network: NetworkSettings,
+    #[serde(default)]
+    models: Vec<RawModel>,
+    #[serde(default, rename = "defaultModel")]
+    default_model: Option<String>,
+    #[serde(default, rename = "contextWindow")]
+    context_window: Option<u64>,
+    #[serde(default, rename = "contextLimits")]
+    context_limits: HashMap<String, u64>,
+    #[serde(default, rename = "disableUrlSuffix")]
+    disable_url_suffix: bool,
+}
+
+/// 强类型的提供商配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderConfig {
+    pub id: String,
+    pub name: String,
+    pub kind: ProviderKind,
+    pub enabled: bool,
+    pub api_key: String,
+    pub base_url: String,
+    pub auth: AuthScheme,
+    pub headers: BTreeMap<String, String>,
+    /// 提供商级网络设置 (覆盖全局设置)
+    pub network: NetworkSettings,
+    pub models: Vec<ModelEntry>,
+    pub default_model: Option<String>,
+    pub context_window: Option<u64>,
+    pub context_limits: HashMap<String, u64>,
+    /// 提供商级默认生成参数 (temperature / maxTokens 等)
+    pub defaults: GenerationParams,
+    pub disable_url_suffix: bool,
+}
+
+/// 内置提供商的默认 Base URL (配置中缺失时使用，其它提供商必须显式配置)
+fn builtin_base_url(id: &str) -> Option<&'static str> {
+    match id {
+        "deepseek" => Some("https://api.deepseek.com"),
+        "openai" => Some("https://api.openai.com/v1"),
+        "claude" => Some("https://api.anthropic.com"),
+        "gemini" => Some("https://generativelanguage.googleapis.com"),
+        "ollama" => Some("http://localhost:11434"),
+        "llamacpp" => Some("http://localhost:8080"),
+        "qwen" => Some("https://dashscope.aliyuncs.com/api"),
+        "siliconflow" => Some("https://api.siliconflow.cn"),
+        _ => None,
+    }
+}
+
+fn check_url(field: &str, url: &str) -> Result<(), String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("{} 不是合法的 URL: {}", field, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("{} 的协议不受支持: {}", field, parsed.scheme()));
+    }
+    Ok(())
+}
+
+impl ProviderConfig {
+    /// 解析并校验单个提供商
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let raw: RawProvider = serde_path_to_error::deserialize(value.clone())
+            .map_err(|e| format!("{}: {}", e.path(), e.inner()))?;
+        let kind = raw.kind.unwrap_or_else(|| ProviderKind::infer(&raw.id));
+
+        let base_url = match raw.base_url.as_deref().map(str::trim) {
+            Some(url) if !url.is_empty() => url.to_string(),
+            _ => builtin_base_url(&raw.id)
+                .ok_or_else(|| format!("提供商 {} 缺少 baseUrl", raw.id))?
+                .to_string(),
+        };
+        check_url("baseUrl", &base_url)?;
+        let mut network = raw.network;
+        if network.proxy.is_none() {
+            network.proxy = raw.proxy.filter(|p| !p.is_empty());
+        }
+        network.validate()?;
+        for name in raw.headers.keys() {
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("headers 中的请求头名称不合法: {}", name))?;
+        }
+
+        Ok(ProviderConfig {
+            name: if raw.name.is_empty() {
+                raw.id.clone()
+            } else {
+                raw.name
+            },
+            auth: raw.auth.unwrap_or_else(|| AuthScheme::default_for(kind)),
+            id: raw.id,
+            kind,
+            enabled: raw.enabled,
+            api_key: raw.api_key,
+            base_url,
+            headers: raw.headers,
+            network,
+            models: raw.models.into_iter().map(Into::into).collect(),
+            default_model: raw.default_model,
+            context_window: raw.context_window,
+            context_limits: raw.context_limits,
+            defaults: crate::generation_config::params_from_value(value),
+            disable_url_suffix: raw.disable_url_suffix,
+        })
+    }
+
+    pub fn is_gemini(&self) -> bool {
+        self.kind == ProviderKind::Gemini
+    }
+
+    /// 本地模型服务 (API Key 可选)
+    pub fn is_local(&self) -> bool {
+        matches!(self.kind, ProviderKind::Ollama | ProviderKind::LlamaCpp)
+    }
+
+    pub fn has_model(&self, model: &str) -> bool {
+        self.models.iter().any(|m| m.id == model)
+    }
+
+    pub fn model(&self, model: &str) -> Option<&ModelEntry> {
+        self.models.iter().find(|m| m.id == model)
+    }
+
+    /// 需要鉴权的远程提供商必须配置 API Key
+    pub fn require_credentials(&self) -> Result<(), String> {
+        if self.auth != AuthScheme::None && !self.is_local() && self.api_key.trim().is_empty() {
+            return Err(format!(
+                "{} 的 API Key 未配置，请前往设置页面填写",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// OpenAI 兼容的对话接口地址
+    pub fn chat_completions_url(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        if self.disable_url_suffix || base.ends_with("/chat/completions") {
+            base.to_string()
+        } else if base.ends_with("/v1") {
+            format!("{}/chat/completions", base)
+        } else {
+            // 如果不包含 v1，自动补全 /v1/chat/completions
+            format!("{}/v1/chat/completions", base)
+        }
+    }
+
+    /// Gemini 原生接口地址 (method 如 streamGenerateContent)
+    pub fn gemini_url(&self, model: &str, method: &str) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        if base.contains("/models/") {
+            // 用户提供了完整路径
+            base.to_string()
+        } else {
+            let version = if base.contains("/v1") { "" } else { "/v1beta" };
+            format!("{}{}/models/{}:{}", base, version, model, method)
+        }
+    }
+
+    /// 模型列表接口地址 (用于连通性探测与启动预热)
+    pub fn models_url(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        if self.is_gemini() {
+            format!("{}/v1beta/models", base)
+        } else if base.ends_with("/v1") {
+            format!("{}/models", base)
+        } else {
+            format!("{}/v1/models", base)
+        }
+    }
+
+    /// 为请求附加鉴权信息与自定义请求头
+    pub fn authorize(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let key = self.api_key.trim();
+        if !key.is_empty() {
+            request = match &self.auth {
+                AuthScheme::Bearer => request.header("Authorization", format!("Bearer {}", key)),
+                AuthScheme::Header { name } => request.header(name.as_str(), key),
+                AuthScheme::Query { param } => request.query(&[(param.as_str(), key)]),
+                AuthScheme::None => request,
+            };
+        }
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+}
+
+/// 解析全部提供商 (跳过无法解析的条目)
+pub fn parse_providers(providers: &Value) -> Vec<ProviderConfig> {
+    providers
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|p| ProviderConfig::from_value(p).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 唯一的提供商解析入口：按 id 查找、解析、校验并检查凭据
+pub fn resolve_provider(config: &AppConfig, provider_id: &str) -> Result<ProviderConfig, String> {
+    let raw = config
+        .providers
+        .as_array()
+        .ok_or("配置错误：无法读取提供商列表")?
+        .iter()
+        .find(|p| p["id"].as_str() == Some(provider_id))
+        .ok_or(format!("找不到提供商配置: {}", provider_id))?;
+    let provider = ProviderConfig::from_value(raw)
+        .map_err(|e| format!("提供商 {} 配置错误: {}", provider_id, e))?;
+    provider.require_credentials()?;
+    Ok(provider)
+}
+
+/// 加载时校验 providers.json：解析错误与未知字段 (多为拼写错误) 作为警告返回
+pub fn validate_providers(providers: &Value) -> Vec<ConfigFileError> {
+    let mut warnings = Vec::new();
+    let Some(list) = providers.as_array() else {
+        return warnings;
+    };
+    for (i, provider) in list.iter().enumerate() {
+        let warn = |field: String, message: String| ConfigFileError {
+            file: "providers.json".into(),
+            line: 0,
+            column: 0,
+            field,
+            message,
+        };
+        if let Err(e) = ProviderConfig::from_value(provider) {
+            warnings.push(warn(format!("providers[{}]", i), e));
+        }
+        if let Some(obj) = provider.as_object() {
+            for key in obj.keys().filter(|k| !KNOWN_KEYS.contains(&k.as_str())) {
+                warnings.push(warn(
+                    format!("providers[{}].{}", i, key),
+                    format!("未知字段 {}，是否拼写错误？", key),
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_legacy_provider() {
+        let p = ProviderConfig::from_value(&json!({
+            "id": "deepseek",
+            "name": "DeepSeek",
+            "enabled": true,
+            "apiKey": "sk-1",
+            "models": ["deepseek-chat", { "id": "deepseek-reasoner", "features": ["reasoning"], "contextWindow": 64000 }],
+            "temperature": 1.0,
+            "maxTokens": 8192
+        }))
+        .unwrap();
+        assert_eq!(p.kind, ProviderKind::OpenAi);
+        assert_eq!(p.auth, AuthScheme::Bearer);
+        assert_eq!(p.base_url, "https://api.deepseek.com");
+        assert_eq!(
+            p.chat_completions_url(),
+            "https://api.deepseek.com/v1/chat/completions"
+        );
+        assert!(p.model("deepseek-reasoner").unwrap().capabilities.reasoning);
+        assert_eq!(p.defaults.max_tokens, Some(8192));
+    }
+
+    #[test]
+    fn test_custom_provider_requires_base_url() {
+        let err =
+            ProviderConfig::from_value(&json!({ "id": "my-proxy", "apiKey": "k" })).unwrap_err();
+        assert!(err.contains("baseUrl"));
+
+        let err = ProviderConfig::from_value(&json!({ "id": "x", "baseUrl": "api.example.com" }))
+            .unwrap_err();
+        assert!(err.contains("URL"));
+
+        let err = ProviderConfig::from_value(
+            &json!({ "id": "x", "baseUrl": "https://a.b", "kind": "claude" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("kind"));
+    }
+
+    #[test]
+    fn test_gemini_and_ollama_defaults() {
+        let gemini = ProviderConfig::from_value(&json!({ "id": "gemini", "apiKey": "g" })).unwrap();
+        assert!(gemini.is_gemini());
+        assert_eq!(
+            gemini.gemini_url("gemini-3-pro-preview", "streamGenerateContent"),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-3-pro-preview:streamGenerateContent"
+        );
+
+        let ollama = ProviderConfig::from_value(&json!({ "id": "ollama" })).unwrap();
+        assert_eq!(ollama.auth, AuthScheme::None);
+        assert!(ollama.require_credentials().is_ok());
+
+        let openai =
+            ProviderConfig::from_value(&json!({ "id": "openai", "name": "OpenAI" })).unwrap();
+        assert!(openai.require_credentials().is_err());
+    }
+
+    #[test]
+    fn test_validate_reports_misspelled_keys() {
+        let warnings = validate_providers(&json!([
+            { "id": "deepseek", "baseURL": "https://example.com" },
+            { "id": "custom", "baseUrl": "https://example.com", "auth": { "type": "header", "name": "x-api-key" } }
+        ]));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, "providers[0].baseURL");
+    }
+}
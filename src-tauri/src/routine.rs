@@ -0,0 +1,476 @@
+use chrono::{DateTime, Datelike, FixedOffset, Local, Timelike, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+// ==================================================================================
+// 角色作息：按周时间表描述角色在什么时候睡觉、工作或空闲。
+// 当前活动会调整回复延迟、已读不回概率与主动发言，并注入到系统提示词中
+// ==================================================================================
+
+/// 活动类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Sleeping,
+    Working,
+    Commuting,
+    Eating,
+    Free,
+}
+
+impl ActivityKind {
+    /// 默认可用度 (方便回消息的概率)，时间段未指定 availability 时使用
+    pub fn default_availability(self) -> f32 {
+        match self {
+            ActivityKind::Sleeping => 0.05,
+            ActivityKind::Working => 0.4,
+            ActivityKind::Commuting => 0.6,
+            ActivityKind::Eating => 0.7,
+            ActivityKind::Free => 0.95,
+        }
+    }
+
+    /// 回复延迟系数
+    pub fn delay_factor(self) -> f32 {
+        match self {
+            ActivityKind::Sleeping => 3.0,
+            ActivityKind::Working => 1.8,
+            ActivityKind::Commuting => 1.5,
+            ActivityKind::Eating => 1.3,
+            ActivityKind::Free => 1.0,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ActivityKind::Sleeping => "睡觉",
+            ActivityKind::Working => "工作",
+            ActivityKind::Commuting => "通勤",
+            ActivityKind::Eating => "吃饭",
+            ActivityKind::Free => "空闲",
+        }
+    }
+}
+
+/// 时间表中的一个时间段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutineBlock {
+    /// 适用的星期 (1=周一 … 7=周日)，为空表示每天
+    #[serde(default)]
+    pub days: Vec<u8>,
+    /// 开始时间 "HH:MM"
+    pub start: String,
+    /// 结束时间 "HH:MM" (早于开始时间表示跨过午夜，属于开始那天)
+    pub end: String,
+    pub activity: ActivityKind,
+    /// 自定义描述 (如 "在公司加班")，为空时使用活动类型的名称
+    #[serde(default)]
+    pub label: Option<String>,
+    /// 可用度 [0.0-1.0]，为空时使用活动类型的默认值
+    #[serde(default)]
+    pub availability: Option<f32>,
+}
+
+/// 联系人的作息表
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Routine {
+    /// 固定时区偏移，如 "+08:00"、"UTC-5"；为空时使用本机时区
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// 按顺序匹配，先匹配到的时间段生效；都不匹配时视为空闲
+    #[serde(default)]
+    pub blocks: Vec<RoutineBlock>,
+}
+
+/// 角色当前的活动
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityState {
+    pub kind: ActivityKind,
+    pub label: String,
+    pub availability: f32,
+    /// 角色所在时区的当前时间，如 "星期三 03:12"
+    pub local_time: String,
+    /// 当前时间段的结束时间 "HH:MM" (不在任何时间段内时为空)
+    pub until: Option<String>,
+}
+
+impl ActivityState {
+    /// 注入到系统提示词的一行说明
+    pub fn prompt_line(&self) -> String {
+        match self.until {
+            Some(ref until) => format!(
+                "【当前作息】你那里现在是{}，你正在{} (持续到 {})。请按这个状态自然地回复。",
+                self.local_time, self.label, until
+            ),
+            None => format!(
+                "【当前作息】你那里现在是{}，你正在{}。请按这个状态自然地回复。",
+                self.local_time, self.label
+            ),
+        }
+    }
+}
+
+/// 解析 "HH:MM" 为当天的分钟数 (允许 "24:00" 表示一天结束)
+fn parse_minutes(value: &str) -> Result<u32, String> {
+    let (h, m) = value
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| format!("时间格式应为 HH:MM: {}", value))?;
+    let h: u32 = h.parse().map_err(|_| format!("无效的小时: {}", value))?;
+    let m: u32 = m.parse().map_err(|_| format!("无效的分钟: {}", value))?;
+    if m >= 60 || h > 24 || (h == 24 && m > 0) {
+        return Err(format!("时间超出范围: {}", value));
+    }
+    Ok(h * 60 + m)
+}
+
+/// 解析固定时区偏移："UTC"、"+08:00"、"-0530"、"UTC+9"
+fn parse_offset(value: &str) -> Result<FixedOffset, String> {
+    let raw = value.trim();
+    let rest = raw
+        .strip_prefix("UTC")
+        .or_else(|| raw.strip_prefix("GMT"))
+        .unwrap_or(raw);
+    if rest.is_empty() {
+        return Ok(FixedOffset::east_opt(0).expect("零偏移总是有效"));
+    }
+
+    let (sign, digits) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return Err(format!("时区应为 UTC 偏移 (如 +08:00): {}", value)),
+    };
+    // 只接受 ASCII 数字，下面按字节位置切分才不会落在多字节字符中间
+    if !digits.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return Err(format!("无效的时区: {}", value));
+    }
+    let (h, m) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    let h: i32 = h.parse().map_err(|_| format!("无效的时区: {}", value))?;
+    let m: i32 = m.parse().map_err(|_| format!("无效的时区: {}", value))?;
+    if h > 14 || m >= 60 {
+        return Err(format!("时区超出范围: {}", value));
+    }
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60))
+        .ok_or_else(|| format!("无效的时区: {}", value))
+}
+
+fn weekday_name(day: u32) -> &'static str {
+    match day {
+        1 => "星期一",
+        2 => "星期二",
+        3 => "星期三",
+        4 => "星期四",
+        5 => "星期五",
+        6 => "星期六",
+        _ => "星期日",
+    }
+}
+
+impl Routine {
+    /// 保存前校验时间、星期、时区与可用度
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ref tz) = self.timezone {
+            parse_offset(tz)?;
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            let prefix = |e: String| format!("第 {} 个时间段: {}", i + 1, e);
+            parse_minutes(&block.start).map_err(prefix)?;
+            parse_minutes(&block.end).map_err(prefix)?;
+            if let Some(day) = block.days.iter().find(|d| !(1..=7).contains(*d)) {
+                return Err(prefix(format!("星期应在 1-7 之间: {}", day)));
+            }
+            if let Some(a) = block.availability {
+                if !(0.0..=1.0).contains(&a) {
+                    return Err(prefix(format!("可用度应在 0-1 之间: {}", a)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 计算给定时刻 (UTC) 角色所处的活动
+    pub fn activity_at(&self, now: DateTime<Utc>) -> ActivityState {
+        let offset = match self.timezone.as_deref().map(parse_offset) {
+            Some(Ok(offset)) => offset,
+            Some(Err(e)) => {
+                println!("⚠️ [作息] {}，改用本机时区", e);
+                *now.with_timezone(&Local).offset()
+            }
+            None => *now.with_timezone(&Local).offset(),
+        };
+        let local = now.with_timezone(&offset);
+        let day = local.weekday().number_from_monday();
+        let yesterday = if day == 1 { 7 } else { day - 1 };
+        let minute = local.hour() * 60 + local.minute();
+        let local_time = format!("{} {}", weekday_name(day), local.format("%H:%M"));
+
+        let on = |block: &RoutineBlock, day: u32| {
+            block.days.is_empty() || block.days.iter().any(|d| *d as u32 == day)
+        };
+        let matched = self.blocks.iter().find(|block| {
+            let (Ok(start), Ok(end)) = (parse_minutes(&block.start), parse_minutes(&block.end))
+            else {
+                return false;
+            };
+            if start < end {
+                on(block, day) && (start..end).contains(&minute)
+            } else if start > end {
+                // 跨午夜：开始那天的晚上，或者前一天开始、今天早上结束
+                (on(block, day) && minute >= start) || (on(block, yesterday) && minute < end)
+            } else {
+                // 开始等于结束表示全天
+                on(block, day)
+            }
+        });
+
+        match matched {
+            Some(block) => ActivityState {
+                kind: block.activity,
+                label: block
+                    .label
+                    .clone()
+                    .filter(|l| !l.trim().is_empty())
+                    .unwrap_or_else(|| block.activity.label().to_string()),
+                availability: block
+                    .availability
+                    .unwrap_or_else(|| block.activity.default_availability()),
+                local_time,
+                until: Some(block.end.clone()),
+            },
+            None => ActivityState {
+                kind: ActivityKind::Free,
+                label: ActivityKind::Free.label().to_string(),
+                availability: ActivityKind::Free.default_availability(),
+                local_time,
+                until: None,
+            },
+        }
+    }
+}
+
+/// 读取联系人的作息表 (未设置时为 None)
+pub(crate) fn load_routine(conn: &Connection, contact_id: i64) -> Result<Option<Routine>, String> {
+    let raw: Option<String> = conn
+        .query_row(
+            "SELECT routine FROM contacts WHERE id = ?1",
+            params![contact_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten();
+    match raw {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| format!("作息表解析失败: {}", e)),
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn save_routine(
+    conn: &Connection,
+    contact_id: i64,
+    routine: Option<&Routine>,
+) -> Result<(), String> {
+    let json = routine
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE contacts SET routine = ?1 WHERE id = ?2",
+        params![json, contact_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 联系人在给定时刻的活动；没有作息表或读取失败时返回 None (不影响行为决策)
+pub(crate) fn contact_activity(
+    conn: &Connection,
+    contact_id: i64,
+    now: DateTime<Utc>,
+) -> Option<ActivityState> {
+    match load_routine(conn, contact_id) {
+        Ok(routine) => routine.map(|r| r.activity_at(now)),
+        Err(e) => {
+            println!("⚠️ [作息] 联系人 {}: {}", contact_id, e);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_contact_routine(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    contact_id: i64,
+) -> Result<Option<Routine>, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    load_routine(&conn, contact_id)
+}
+
+/// 保存作息表 (传 null 清除)
+#[tauri::command]
+pub fn set_contact_routine(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    contact_id: i64,
+    routine: Option<Routine>,
+) -> Result<(), String> {
+    if let Some(ref routine) = routine {
+        routine.validate()?;
+    }
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    save_routine(&conn, contact_id, routine.as_ref())
+}
+
+/// 预览联系人当前的活动 (用于设置界面)
+#[tauri::command]
+pub fn get_contact_activity(
+    state: tauri::State<'_, crate::social_db::SocialDbState>,
+    contact_id: i64,
+) -> Result<Option<ActivityState>, String> {
+    let conn = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(load_routine(&conn, contact_id)?.map(|r| r.activity_at(Utc::now())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn block(days: &[u8], start: &str, end: &str, activity: ActivityKind) -> RoutineBlock {
+        RoutineBlock {
+            days: days.to_vec(),
+            start: start.into(),
+            end: end.into(),
+            activity,
+            label: None,
+            availability: None,
+        }
+    }
+
+    fn routine() -> Routine {
+        Routine {
+            timezone: Some("+08:00".into()),
+            blocks: vec![
+                block(&[], "23:30", "07:30", ActivityKind::Sleeping),
+                block(&[1, 2, 3, 4, 5], "09:00", "18:00", ActivityKind::Working),
+                RoutineBlock {
+                    label: Some("吃午饭".into()),
+                    ..block(&[], "12:00", "13:00", ActivityKind::Eating)
+                },
+            ],
+        }
+    }
+
+    /// 北京时间 (UTC+8) 的某一刻
+    fn beijing(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(y, mo, d, h, mi, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_activity_resolution() {
+        let routine = routine();
+        // 2026-10-14 是星期三
+        let at = |h, m| routine.activity_at(beijing(2026, 10, 14, h, m));
+
+        let night = at(3, 12);
+        assert_eq!(night.kind, ActivityKind::Sleeping);
+        assert_eq!(night.local_time, "星期三 03:12");
+        assert_eq!(night.until.as_deref(), Some("07:30"));
+        assert_eq!(at(23, 45).kind, ActivityKind::Sleeping);
+
+        // 工作时间段排在午饭之前，先匹配先生效
+        assert_eq!(at(12, 30).kind, ActivityKind::Working);
+        assert_eq!(at(18, 0).kind, ActivityKind::Free);
+        assert!(at(18, 0).until.is_none());
+
+        // 周末不上班，午饭时间段生效
+        let lunch = routine.activity_at(beijing(2026, 10, 17, 12, 30));
+        assert_eq!(lunch.kind, ActivityKind::Eating);
+        assert_eq!(lunch.label, "吃午饭");
+        assert_eq!(lunch.availability, 0.7);
+    }
+
+    #[test]
+    fn test_overnight_block_follows_start_day() {
+        let routine = Routine {
+            timezone: Some("UTC".into()),
+            // 只有周五晚上通宵加班
+            blocks: vec![block(&[5], "22:00", "04:00", ActivityKind::Working)],
+        };
+        let utc = |d, h| Utc.with_ymd_and_hms(2026, 10, d, h, 0, 0).unwrap();
+        assert_eq!(routine.activity_at(utc(16, 23)).kind, ActivityKind::Working); // 周五
+        assert_eq!(routine.activity_at(utc(17, 2)).kind, ActivityKind::Working); // 周六凌晨
+        assert_eq!(routine.activity_at(utc(17, 23)).kind, ActivityKind::Free);
+        assert_eq!(routine.activity_at(utc(16, 2)).kind, ActivityKind::Free); // 周四晚没有加班
+    }
+
+    #[test]
+    fn test_timezone_shifts_activity() {
+        let mut routine = routine();
+        routine.timezone = Some("UTC-5".into());
+        // 北京时间周三 03:12 = UTC-5 的周二 14:12
+        let state = routine.activity_at(beijing(2026, 10, 14, 3, 12));
+        assert_eq!(state.kind, ActivityKind::Working);
+        assert_eq!(state.local_time, "星期二 14:12");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(routine().validate().is_ok());
+        for tz in ["UTC", "+0530", "GMT+9", "-03:30"] {
+            assert!(parse_offset(tz).is_ok(), "{}", tz);
+        }
+
+        let mut bad = routine();
+        bad.timezone = Some("Asia/Shanghai".into());
+        assert!(bad.validate().is_err());
+        for tz in ["+中1", "UTC-１２００", "+08:3０"] {
+            bad.timezone = Some(tz.into());
+            assert!(bad.validate().is_err(), "{}", tz);
+        }
+
+        let mut bad = routine();
+        bad.blocks[1].end = "25:00".into();
+        assert!(bad.validate().unwrap_err().starts_with("第 2 个时间段"));
+
+        let mut bad = routine();
+        bad.blocks[0].days = vec![0];
+        assert!(bad.validate().is_err());
+
+        let mut bad = routine();
+        bad.blocks[2].availability = Some(1.5);
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_routine_storage() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::social_db::init_social_db(&conn).unwrap();
+        conn.execute("INSERT INTO contacts (name) VALUES ('小王')", [])
+            .unwrap();
+        let id = conn.last_insert_rowid();
+
+        assert_eq!(load_routine(&conn, id).unwrap(), None);
+        assert!(contact_activity(&conn, id, Utc::now()).is_none());
+
+        save_routine(&conn, id, Some(&routine())).unwrap();
+        assert_eq!(load_routine(&conn, id).unwrap(), Some(routine()));
+        let state = contact_activity(&conn, id, beijing(2026, 10, 14, 3, 0)).unwrap();
+        assert_eq!(state.kind, ActivityKind::Sleeping);
+
+        save_routine(&conn, id, None).unwrap();
+        assert_eq!(load_routine(&conn, id).unwrap(), None);
+    }
+}
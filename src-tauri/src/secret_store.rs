@@ -0,0 +1,703 @@
+use argon2::Argon2;
+use base64::prelude::*;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 钥匙串中的服务名
+const KEYRING_SERVICE: &str = "goge-chat";
+/// 加密文件回退方案的口令环境变量 (未设置时需要前端调用 unlock_secret_store)
+pub const PASSPHRASE_ENV: &str = "GOGE_SECRETS_PASSPHRASE";
+/// 发送给前端时代替真实 API Key 的占位符；保存时收到占位符表示"保持不变"
+pub const REDACTED_KEY: &str = "__REDACTED__";
+
+const ENCRYPTED_FILE: &str = "secrets.enc.json";
+const LEGACY_FILE: &str = "secrets.json";
+/// 用于校验口令是否正确的固定明文
+const VERIFIER_PLAINTEXT: &[u8] = b"goge-chat-secrets";
+
+pub struct SecretStoreState(pub Mutex<SecretStore>);
+
+/// 密钥存储后端
+pub trait SecretBackend: Send {
+    fn name(&self) -> &'static str;
+    /// 加密文件在未提供口令前处于锁定状态
+    fn is_locked(&self) -> bool {
+        false
+    }
+    fn get(&self, id: &str) -> Result<Option<String>, String>;
+    fn set(&mut self, id: &str, secret: &str) -> Result<(), String>;
+    fn delete(&mut self, id: &str) -> Result<(), String>;
+}
+
+// ==================================================================================
+// 系统钥匙串
+// ==================================================================================
+
+pub struct KeyringBackend;
+
+impl KeyringBackend {
+    fn entry(id: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(KEYRING_SERVICE, &format!("provider:{}", id)).map_err(|e| e.to_string())
+    }
+
+    /// 探测系统钥匙串是否可用 (Linux 无 Secret Service 时会失败)
+    pub fn probe() -> bool {
+        match Self::entry("__probe__").and_then(|e| match e.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("⚠️ [密钥] 系统钥匙串不可用: {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl SecretBackend for KeyringBackend {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, String> {
+        match Self::entry(id)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn set(&mut self, id: &str, secret: &str) -> Result<(), String> {
+        Self::entry(id)?
+            .set_password(secret)
+            .map_err(|e| e.to_string())
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), String> {
+        match Self::entry(id)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+// ==================================================================================
+// 加密文件 (Argon2id 派生密钥 + XChaCha20-Poly1305)
+// ==================================================================================
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Sealed {
+    nonce: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    version: u32,
+    kdf: String,
+    salt: String,
+    verifier: Sealed,
+    #[serde(default)]
+    entries: HashMap<String, Sealed>,
+}
+
+pub struct EncryptedFileBackend {
+    path: PathBuf,
+    salt: Vec<u8>,
+    key: Option<[u8; 32]>,
+    entries: HashMap<String, Sealed>,
+    verifier: Option<Sealed>,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("密钥派生失败: {}", e))?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], aad: &str, plaintext: &[u8]) -> Result<Sealed, String> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let mut nonce = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let data = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| "加密失败".to_string())?;
+    Ok(Sealed {
+        nonce: BASE64_STANDARD.encode(nonce),
+        data: BASE64_STANDARD.encode(data),
+    })
+}
+
+fn open(key: &[u8; 32], aad: &str, sealed: &Sealed) -> Result<Vec<u8>, String> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = BASE64_STANDARD
+        .decode(&sealed.nonce)
+        .map_err(|e| e.to_string())?;
+    if nonce.len() != 24 {
+        return Err("密钥文件已损坏".into());
+    }
+    let data = BASE64_STANDARD
+        .decode(&sealed.data)
+        .map_err(|e| e.to_string())?;
+    cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &data,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| "解密失败：口令错误或文件已损坏".to_string())
+}
+
+impl EncryptedFileBackend {
+    /// 读取 (或准备新建) 加密文件，此时处于锁定状态
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        if path.exists() {
+            let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            let file: EncryptedFile =
+                serde_json::from_str(&content).map_err(|e| format!("密钥文件格式错误: {}", e))?;
+            if file.version != 1 || file.kdf != "argon2id" {
+                return Err(format!(
+                    "不支持的密钥文件版本: {} ({})",
+                    file.version, file.kdf
+                ));
+            }
+            Ok(Self {
+                path,
+                salt: BASE64_STANDARD
+                    .decode(&file.salt)
+                    .map_err(|e| e.to_string())?,
+                key: None,
+                entries: file.entries,
+                verifier: Some(file.verifier),
+            })
+        } else {
+            let mut salt = vec![0u8; 16];
+            rand::thread_rng().fill_bytes(&mut salt);
+            Ok(Self {
+                path,
+                salt,
+                key: None,
+                entries: HashMap::new(),
+                verifier: None,
+            })
+        }
+    }
+
+    /// 文件无法读取时的占位后端：始终锁定，不会写入文件
+    fn unavailable(path: PathBuf) -> Self {
+        Self {
+            path,
+            salt: Vec::new(),
+            key: None,
+            entries: HashMap::new(),
+            verifier: None,
+        }
+    }
+
+    /// 用口令解锁；新文件会以该口令初始化
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        if passphrase.is_empty() {
+            return Err("口令不能为空".into());
+        }
+        let key = derive_key(passphrase, &self.salt)?;
+        match &self.verifier {
+            Some(verifier) => {
+                if open(&key, "verifier", verifier)? != VERIFIER_PLAINTEXT {
+                    return Err("口令错误".into());
+                }
+            }
+            None => {
+                self.verifier = Some(seal(&key, "verifier", VERIFIER_PLAINTEXT)?);
+            }
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn key(&self) -> Result<&[u8; 32], String> {
+        self.key
+            .as_ref()
+            .ok_or_else(|| "密钥存储已锁定".to_string())
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let file = EncryptedFile {
+            version: 1,
+            kdf: "argon2id".into(),
+            salt: BASE64_STANDARD.encode(&self.salt),
+            verifier: self.verifier.clone().ok_or("密钥存储尚未初始化")?,
+            entries: self.entries.clone(),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        crate::config_store::write_atomic(&self.path, &json)
+    }
+}
+
+impl SecretBackend for EncryptedFileBackend {
+    fn name(&self) -> &'static str {
+        "encryptedFile"
+    }
+
+    fn is_locked(&self) -> bool {
+        self.key.is_none()
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, String> {
+        let key = self.key()?;
+        match self.entries.get(id) {
+            Some(sealed) => {
+                let plain = open(key, id, sealed)?;
+                String::from_utf8(plain)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn set(&mut self, id: &str, secret: &str) -> Result<(), String> {
+        let sealed = seal(self.key()?, id, secret.as_bytes())?;
+        self.entries.insert(id.to_string(), sealed);
+        self.persist()
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), String> {
+        if self.entries.remove(id).is_some() {
+            self.persist()?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取加密文件；文件损坏时不覆盖，改名保留后另建新文件
+fn load_or_recover(path: &Path) -> Result<EncryptedFileBackend, String> {
+    EncryptedFileBackend::load(path.to_path_buf()).or_else(|e| {
+        println!("❌ [密钥] {}，将另建加密文件", e);
+        fs::rename(path, path.with_extension("json.corrupt")).map_err(|e| e.to_string())?;
+        EncryptedFileBackend::load(path.to_path_buf())
+    })
+}
+
+// ==================================================================================
+// 存储门面
+// ==================================================================================
+
+#[derive(Serialize, Debug)]
+pub struct SecretStoreStatus {
+    pub backend: String,
+    pub locked: bool,
+    /// 仍存在未迁移的旧版 secrets.json
+    #[serde(rename = "legacyPending")]
+    pub legacy_pending: bool,
+}
+
+pub struct SecretStore {
+    backend: Box<dyn SecretBackend>,
+    config_dir: PathBuf,
+}
+
+impl SecretStore {
+    pub fn new(backend: Box<dyn SecretBackend>, config_dir: &Path) -> Self {
+        Self {
+            backend,
+            config_dir: config_dir.to_path_buf(),
+        }
+    }
+
+    /// 优先使用系统钥匙串，不可用时回退到加密文件 (口令取自环境变量)
+    pub fn open(config_dir: &Path) -> Self {
+        if KeyringBackend::probe() {
+            println!("🔐 [密钥] 使用系统钥匙串");
+            return Self::new(Box::new(KeyringBackend), config_dir);
+        }
+
+        let path = config_dir.join(ENCRYPTED_FILE);
+        let mut backend = match load_or_recover(&path) {
+            Ok(b) => b,
+            Err(e) => {
+                // 不覆盖无法读取的文件：保持锁定，之后解锁时会重新读取
+                println!("❌ [密钥] 无法打开加密文件: {}，密钥存储保持锁定", e);
+                return Self::new(
+                    Box::new(EncryptedFileBackend::unavailable(path)),
+                    config_dir,
+                );
+            }
+        };
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            if let Err(e) = backend.unlock(&passphrase) {
+                println!("❌ [密钥] 使用 {} 解锁失败: {}", PASSPHRASE_ENV, e);
+            }
+        }
+        println!(
+            "🔐 [密钥] 使用加密文件 ({})",
+            if backend.is_locked() {
+                "已锁定"
+            } else {
+                "已解锁"
+            }
+        );
+        Self::new(Box::new(backend), config_dir)
+    }
+
+    pub fn status(&self) -> SecretStoreStatus {
+        SecretStoreStatus {
+            backend: self.backend.name().to_string(),
+            locked: self.backend.is_locked(),
+            legacy_pending: self.config_dir.join(LEGACY_FILE).exists(),
+        }
+    }
+
+    /// 解锁加密文件后端 (钥匙串后端无需解锁)
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        if !self.backend.is_locked() {
+            return Ok(());
+        }
+        let path = self.config_dir.join(ENCRYPTED_FILE);
+        let mut backend = EncryptedFileBackend::load(path)?;
+        backend.unlock(passphrase)?;
+        self.backend = Box::new(backend);
+        Ok(())
+    }
+
+    /// 将旧版 base64 格式的 secrets.json 迁移到当前后端，成功后删除旧文件
+    pub fn migrate_legacy(&mut self) -> Result<usize, String> {
+        let legacy_path = self.config_dir.join(LEGACY_FILE);
+        if !legacy_path.exists() || self.backend.is_locked() {
+            return Ok(0);
+        }
+
+        let legacy = read_legacy(&legacy_path)?;
+        let mut migrated = 0;
+        for (id, decoded) in legacy {
+            // 已存在的新密钥优先，避免旧文件覆盖之后的修改
+            if self.backend.get(&id)?.is_none() {
+                self.backend.set(&id, &decoded)?;
+                migrated += 1;
+            }
+        }
+
+        fs::remove_file(&legacy_path).map_err(|e| e.to_string())?;
+        println!(
+            "✅ [密钥] 已从 secrets.json 迁移 {} 个 API Key 到 {}",
+            migrated,
+            self.backend.name()
+        );
+        Ok(migrated)
+    }
+
+    /// 将密钥填回提供商列表的 apiKey 字段
+    /// 存储锁定期间暂时沿用旧版 secrets.json，保证解锁前仍可正常对话
+    pub fn fill_providers(&self, providers: &mut Value) {
+        let legacy = if self.backend.is_locked() {
+            read_legacy(&self.config_dir.join(LEGACY_FILE)).unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        let Some(list) = providers.as_array_mut() else {
+            return;
+        };
+        for provider in list {
+            let Some(id) = provider["id"].as_str().map(String::from) else {
+                continue;
+            };
+            if self.backend.is_locked() {
+                if let Some(secret) = legacy.get(&id) {
+                    provider["apiKey"] = Value::String(secret.clone());
+                }
+                continue;
+            }
+            match self.backend.get(&id) {
+                Ok(Some(secret)) if !secret.is_empty() => {
+                    provider["apiKey"] = Value::String(secret);
+                }
+                Ok(_) => {}
+                Err(e) => println!("⚠️ [密钥] 读取 {} 失败: {}", id, e),
+            }
+        }
+    }
+
+    /// 从提供商列表中取出 apiKey 写入存储，并清空列表中的明文
+    /// 收到占位符 REDACTED_KEY 的提供商保持原密钥不变
+    /// 存储锁定时无法写入，与 current 中的密钥不同即返回错误，而不是静默丢弃
+    /// 只有 current 中原本有密钥的提供商收到空值才视为删除，避免配置加载失败时误删
+    pub fn store_providers(
+        &mut self,
+        providers: &mut Value,
+        current: &Value,
+    ) -> Result<(), String> {
+        let Some(list) = providers.as_array_mut() else {
+            return Ok(());
+        };
+        let locked = self.backend.is_locked();
+        if locked {
+            let changed = list.iter().find(|provider| {
+                let key = provider["apiKey"].as_str().unwrap_or_default().trim();
+                let id = provider["id"].as_str();
+                id.is_some() && key != REDACTED_KEY && key != previous_key(current, id)
+            });
+            if let Some(provider) = changed {
+                return Err(format!(
+                    "密钥存储已锁定，无法保存 {} 的 API Key，请先在设置中解锁",
+                    provider["id"].as_str().unwrap_or_default()
+                ));
+            }
+        }
+        for provider in list {
+            let Some(id) = provider["id"].as_str().map(String::from) else {
+                continue;
+            };
+            let key = provider["apiKey"]
+                .as_str()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            if !locked && key != REDACTED_KEY {
+                if key.is_empty() {
+                    if !previous_key(current, Some(&id)).is_empty() {
+                        self.backend.delete(&id)?;
+                    }
+                } else if self.backend.get(&id)?.as_deref() != Some(key.as_str()) {
+                    self.backend.set(&id, &key)?;
+                }
+            }
+            provider["apiKey"] = Value::String(String::new());
+        }
+        if locked {
+            println!("⚠️ [密钥] 存储已锁定，本次保存未更新 API Key");
+        }
+        Ok(())
+    }
+}
+
+fn previous_key<'a>(current: &'a Value, id: Option<&str>) -> &'a str {
+    current
+        .as_array()
+        .and_then(|arr| arr.iter().find(|p| p["id"].as_str() == id))
+        .and_then(|p| p["apiKey"].as_str())
+        .unwrap_or_default()
+        .trim()
+}
+
+/// 读取旧版 secrets.json (ProviderID -> base64 编码的 API Key)
+fn read_legacy(path: &Path) -> Result<HashMap<String, String>, String> {
+    #[derive(Deserialize)]
+    struct LegacySecrets {
+        #[serde(default)]
+        secrets: HashMap<String, String>,
+    }
+
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let legacy: LegacySecrets = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    Ok(legacy
+        .secrets
+        .into_iter()
+        .filter_map(|(id, encoded)| {
+            let decoded = BASE64_STANDARD
+                .decode(&encoded)
+                .ok()
+                .and_then(|b| String::from_utf8(b).ok())?;
+            (!decoded.trim().is_empty()).then_some((id, decoded))
+        })
+        .collect())
+}
+
+/// 将提供商列表中的 apiKey 替换为占位符 (发送给 Webview 前使用)
+pub fn redact_providers(providers: &mut Value) {
+    if let Some(list) = providers.as_array_mut() {
+        for provider in list {
+            let has_key = provider["apiKey"]
+                .as_str()
+                .is_some_and(|k| !k.trim().is_empty());
+            if has_key {
+                provider["apiKey"] = Value::String(REDACTED_KEY.to_string());
+            }
+        }
+    }
+}
+
+/// 把占位符还原为当前内存中的真实密钥
+pub fn restore_redacted(providers: &mut Value, current: &Value) {
+    let Some(list) = providers.as_array_mut() else {
+        return;
+    };
+    for provider in list {
+        if provider["apiKey"].as_str() != Some(REDACTED_KEY) {
+            continue;
+        }
+        let id = provider["id"].as_str();
+        let real = current
+            .as_array()
+            .and_then(|arr| arr.iter().find(|p| p["id"].as_str() == id))
+            .and_then(|p| p["apiKey"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        provider["apiKey"] = Value::String(real);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("goge_secret_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_encrypted_file_roundtrip_and_wrong_passphrase() {
+        let dir = temp_dir("roundtrip");
+        let path = dir.join(ENCRYPTED_FILE);
+
+        let mut backend = EncryptedFileBackend::load(path.clone()).unwrap();
+        assert!(backend.get("openai").is_err());
+        backend.unlock("correct horse").unwrap();
+        backend.set("openai", "sk-123").unwrap();
+
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("sk-123"));
+
+        let mut reopened = EncryptedFileBackend::load(path.clone()).unwrap();
+        assert!(reopened.unlock("wrong").is_err());
+        assert!(reopened.is_locked());
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get("openai").unwrap().as_deref(), Some("sk-123"));
+        assert_eq!(reopened.get("gemini").unwrap(), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_corrupt_file_is_kept_aside() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join(ENCRYPTED_FILE);
+        fs::write(&path, "not json").unwrap();
+        let backend = load_or_recover(&path).unwrap();
+        assert!(backend.is_locked());
+        assert_eq!(
+            fs::read_to_string(path.with_extension("json.corrupt")).unwrap(),
+            "not json"
+        );
+
+        // 无法读取也无法改名时返回错误而不是 panic
+        fs::remove_file(path.with_extension("json.corrupt")).unwrap();
+        fs::create_dir_all(path.join("x")).unwrap();
+        fs::create_dir_all(path.with_extension("json.corrupt").join("y")).unwrap();
+        assert!(load_or_recover(&path).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_legacy_migration_and_redaction() {
+        let dir = temp_dir("migrate");
+        fs::write(
+            dir.join(LEGACY_FILE),
+            json!({ "secrets": { "deepseek": BASE64_STANDARD.encode("sk-legacy") } }).to_string(),
+        )
+        .unwrap();
+
+        let mut file = EncryptedFileBackend::load(dir.join(ENCRYPTED_FILE)).unwrap();
+        file.unlock("pw").unwrap();
+        let mut store = SecretStore::new(Box::new(file), &dir);
+        assert!(store.status().legacy_pending);
+        assert_eq!(store.migrate_legacy().unwrap(), 1);
+        assert!(!dir.join(LEGACY_FILE).exists());
+
+        let mut providers =
+            json!([{ "id": "deepseek", "apiKey": "" }, { "id": "openai", "apiKey": "" }]);
+        store.fill_providers(&mut providers);
+        assert_eq!(providers[0]["apiKey"], "sk-legacy");
+
+        // 前端拿到的是占位符，原样保存回来时密钥不变
+        let current = providers.clone();
+        let mut from_webview = providers.clone();
+        redact_providers(&mut from_webview);
+        assert_eq!(from_webview[0]["apiKey"], REDACTED_KEY);
+        assert_eq!(from_webview[1]["apiKey"], "");
+        store.store_providers(&mut from_webview, &current).unwrap();
+        assert_eq!(from_webview[0]["apiKey"], "");
+        assert_eq!(
+            store.backend.get("deepseek").unwrap().as_deref(),
+            Some("sk-legacy")
+        );
+
+        let mut restored = json!([{ "id": "deepseek", "apiKey": REDACTED_KEY }]);
+        restore_redacted(&mut restored, &current);
+        assert_eq!(restored[0]["apiKey"], "sk-legacy");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_locked_store_rejects_new_key() {
+        let dir = temp_dir("locked");
+        let file = EncryptedFileBackend::load(dir.join(ENCRYPTED_FILE)).unwrap();
+        let mut store = SecretStore::new(Box::new(file), &dir);
+        assert!(store.status().locked);
+
+        let current = json!([{ "id": "openai", "apiKey": "sk-old" }]);
+        let mut unchanged = json!([{ "id": "openai", "apiKey": "sk-old" }]);
+        store.store_providers(&mut unchanged, &current).unwrap();
+        assert_eq!(unchanged[0]["apiKey"], "");
+
+        let mut redacted = json!([{ "id": "openai", "apiKey": REDACTED_KEY }]);
+        store.store_providers(&mut redacted, &current).unwrap();
+
+        let mut changed = json!([{ "id": "openai", "apiKey": "sk-new" }]);
+        let err = store.store_providers(&mut changed, &current).unwrap_err();
+        assert!(err.contains("openai"));
+        assert_eq!(changed[0]["apiKey"], "sk-new");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_empty_key_deletes_only_known_secret() {
+        let dir = temp_dir("delete");
+        let mut file = EncryptedFileBackend::load(dir.join(ENCRYPTED_FILE)).unwrap();
+        file.unlock("pw").unwrap();
+        file.set("openai", "sk-real").unwrap();
+        let mut store = SecretStore::new(Box::new(file), &dir);
+
+        // providers.json 读取失败时内存中只有默认提供商，apiKey 为空
+        let defaults = json!([{ "id": "openai", "apiKey": "" }]);
+        let mut saved = defaults.clone();
+        store.store_providers(&mut saved, &defaults).unwrap();
+        assert_eq!(
+            store.backend.get("openai").unwrap().as_deref(),
+            Some("sk-real")
+        );
+
+        let current = json!([{ "id": "openai", "apiKey": "sk-real" }]);
+        let mut cleared = json!([{ "id": "openai", "apiKey": "" }]);
+        store.store_providers(&mut cleared, &current).unwrap();
+        assert_eq!(store.backend.get("openai").unwrap(), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,648 @@
+use crate::immersive_settings::ImmersiveSettings;
+use rand::Rng;
+use std::ops::Range;
+
+/// 中文句末标点，其后总是可以断句
+const CJK_TERMINATORS: [char; 4] = ['。', '！', '？', '…'];
+/// 紧跟在句末标点后、仍属于本句的收尾符号
+const CLOSERS: [char; 12] = [
+    '"', '\'', '”', '’', '」', '』', ')', '）', '】', '》', '~', '～',
+];
+/// 句点后不断句的常见英文缩写 (小写，不含末尾句点)
+const ABBREVIATIONS: [&str; 9] = ["e.g", "i.e", "etc", "mr", "mrs", "ms", "dr", "vs", "st"];
+/// 链接在这些字符处结束
+const URL_END: [char; 14] = [
+    ')', ']', '>', '"', '\'', '`', '。', '，', '！', '？', '、', '）', '」', '】',
+];
+/// 英文单词两端可以去掉的标点/强调符号
+const WORD_PUNCT: [char; 16] = [
+    ',', '.', '!', '?', ';', ':', '"', '\'', '(', ')', '[', ']', '*', '_', '~', '-',
+];
+
+/// 不可再拆的文本单元类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnitKind {
+    Sentence,
+    Code,
+    List,
+    Table,
+    Quote,
+}
+
+/// 文本单元：一句话，或整段代码块/列表/表格/引用
+#[derive(Debug, Clone, PartialEq)]
+struct Unit {
+    kind: UnitKind,
+    range: Range<usize>,
+}
+
+/// 拆分回复
+/// 模型按提示输出了分段标记时直接按标记拆分；否则在句子边界处按长度阈值随机拆分，
+/// 代码块、列表、表格和引用始终保持完整
+pub fn segment_reply<R: Rng + ?Sized>(
+    message: &str,
+    delimiter: Option<&str>,
+    max_segments: usize,
+    threshold_range: (u32, u32),
+    rng: &mut R,
+) -> Vec<String> {
+    let mut segmenter = StreamSegmenter::new(delimiter, max_segments, threshold_range, rng);
+    let mut segments = segmenter.push(message, rng);
+    segments.extend(segmenter.finish(rng));
+    segments
+}
+
+/// 增量分段器：回复边生成边拆分，某一段确定不会再变化时立即交出
+///
+/// 只有后面已经出现了新单元的句子/块才算完整；配置了分段标记时只按标记拆分，
+/// 直到回复结束都没有出现标记才退回按句子拆分
+pub struct StreamSegmenter {
+    text: String,
+    /// 尚未交出的文本起点 (字节)
+    start: usize,
+    /// 已经判断过 (决定不拆) 的单元边界
+    checked: usize,
+    /// 已交出的段数
+    emitted: usize,
+    delimiter: Option<String>,
+    /// 是否已经按分段标记拆分过
+    marked: bool,
+    max_segments: usize,
+    /// 按句子拆分时本次回复的最大段数 (1 ~ max_segments 之间随机)
+    sentence_max: usize,
+    threshold_range: (u32, u32),
+}
+
+impl StreamSegmenter {
+    pub fn new<R: Rng + ?Sized>(
+        delimiter: Option<&str>,
+        max_segments: usize,
+        threshold_range: (u32, u32),
+        rng: &mut R,
+    ) -> Self {
+        let max_segments = max_segments.max(1);
+        // 随机化本次回复的最大段数 (1 ~ max_segments)
+        let sentence_max = if max_segments > 1 {
+            rng.gen_range(1..=max_segments)
+        } else {
+            1
+        };
+        Self {
+            text: String::new(),
+            start: 0,
+            checked: 0,
+            emitted: 0,
+            delimiter: delimiter
+                .filter(|d| !d.trim().is_empty())
+                .map(str::to_string),
+            marked: false,
+            max_segments,
+            sentence_max,
+            threshold_range,
+        }
+    }
+
+    /// 追加生成的文本，返回新近完整的段
+    pub fn push<R: Rng + ?Sized>(&mut self, chunk: &str, rng: &mut R) -> Vec<String> {
+        self.text.push_str(chunk);
+        if self.delimiter.is_some() {
+            self.take_marked()
+        } else {
+            self.take_sentences(rng, false)
+        }
+    }
+
+    /// 回复生成完毕，返回剩余的段
+    pub fn finish<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Vec<String> {
+        let mut segments = self.take_marked();
+        if !self.marked {
+            // 没有配置或模型没有使用分段标记，按句子拆分
+            self.delimiter = None;
+            segments = self.take_sentences(rng, true);
+        }
+
+        let rest = &self.text[self.start..];
+        if self.delimiter.is_none() && self.emitted == 0 {
+            // 没有拆分，原样发送整条回复
+            segments.push(rest.to_string());
+        } else {
+            let rest = join_marked(rest, self.delimiter.as_deref());
+            if !rest.trim().is_empty() {
+                segments.push(rest.trim().to_string());
+            }
+        }
+        self.start = self.text.len();
+        segments
+    }
+
+    /// 按分段标记交出完整的段 (最后一个标记之后的内容可能还没写完)
+    fn take_marked(&mut self) -> Vec<String> {
+        let Some(delimiter) = self.delimiter.clone() else {
+            return Vec::new();
+        };
+        let protected = protected_ranges(&self.text);
+        let cuts: Vec<usize> = self.text[self.start..]
+            .match_indices(delimiter.as_str())
+            .map(|(i, _)| self.start + i)
+            .filter(|i| !protected.iter().any(|r| r.contains(i)))
+            .collect();
+
+        let mut segments = Vec::new();
+        for cut in cuts {
+            // 超出段数上限后，剩下的内容在结束时合并为最后一段
+            if self.emitted + 1 >= self.max_segments {
+                self.marked = true;
+                break;
+            }
+            let piece = self.text[self.start..cut].trim();
+            if !piece.is_empty() {
+                segments.push(piece.to_string());
+                self.emitted += 1;
+            }
+            self.start = cut + delimiter.len();
+            self.marked = true;
+        }
+        segments
+    }
+
+    /// 在句子边界处交出完整的段 (最后一个单元可能还没写完，不参与判断)
+    /// 生成中 (`complete` 为 false) 列表等块要等下一行写完才算结束，例如 "2" 之后可能是 "2. "
+    fn take_sentences<R: Rng + ?Sized>(&mut self, rng: &mut R, complete: bool) -> Vec<String> {
+        let units = units(&self.text);
+        let (min_t, max_t) = self.threshold_range;
+        let mut segments = Vec::new();
+
+        for unit in units.iter().take(units.len().saturating_sub(1)) {
+            if unit.range.end <= self.checked.max(self.start) {
+                continue;
+            }
+            if self.emitted + 1 >= self.sentence_max {
+                break;
+            }
+            if !complete
+                && unit.kind != UnitKind::Sentence
+                && !self.text[unit.range.end..].contains('\n')
+            {
+                break;
+            }
+            self.checked = unit.range.end;
+
+            // 只有累积的片段足够长时才考虑拆分，阈值每次随机 (默认 40 ~ 100)
+            let current = self.text[self.start..unit.range.end].trim();
+            let split_threshold = rng.gen_range(min_t..=max_t.max(min_t)) as usize;
+            // 即使够长也只有 30% 概率拆分
+            if current.chars().count() >= split_threshold && rng.gen::<f32>() < 0.3 {
+                segments.push(current.to_string());
+                self.emitted += 1;
+                self.start = unit.range.end;
+            }
+        }
+        segments
+    }
+}
+
+/// 按分段标记拆分 (代码和链接中的标记不算)；消息中没有有效标记时返回 None
+fn split_marked(message: &str, delimiter: &str) -> Option<Vec<String>> {
+    if delimiter.trim().is_empty() {
+        return None;
+    }
+
+    let protected = protected_ranges(message);
+    let cuts: Vec<usize> = message
+        .match_indices(delimiter)
+        .map(|(i, _)| i)
+        .filter(|i| !protected.iter().any(|r| r.contains(i)))
+        .collect();
+    if cuts.is_empty() {
+        return None;
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for cut in cuts {
+        pieces.push(message[start..cut].trim().to_string());
+        start = cut + delimiter.len();
+    }
+    pieces.push(message[start..].trim().to_string());
+    pieces.retain(|p| !p.is_empty());
+    Some(pieces)
+}
+
+/// 去掉分段标记，按换行合并为一条消息 (撤回修正等整条发送的场景)
+pub fn join_marked(message: &str, delimiter: Option<&str>) -> String {
+    match delimiter.and_then(|d| split_marked(message, d)) {
+        Some(pieces) => pieces.join("\n"),
+        None => message.to_string(),
+    }
+}
+
+/// 提示模型用分段标记把回复拆成多条 (未启用沉浸模式或未配置标记时为 None)
+pub fn delimiter_prompt(settings: &ImmersiveSettings) -> Option<String> {
+    let delimiter = settings
+        .behaviors
+        .segment_delimiter
+        .as_deref()
+        .filter(|d| !d.trim().is_empty())?;
+    let max_segments = settings.behaviors.multi_segment.unwrap_or(1);
+    if !settings.enabled || max_segments <= 1 {
+        return None;
+    }
+    Some(format!(
+        "像发即时消息一样，可以把回复拆成几条发送：在需要分开发送的地方插入 {} (最多 {} 条，不要放在代码块里)。不需要拆分时不要使用。",
+        delimiter, max_segments
+    ))
+}
+
+/// 可以安全制造错字的字符位置 (char 下标)
+/// 只挑普通文字：跳过代码、链接、数字、邮箱、路径和术语，也不动首尾字符
+pub fn typo_candidates(message: &str) -> Vec<usize> {
+    let protected = protected_ranges(message);
+    let is_protected = |pos: usize| protected.iter().any(|r| r.contains(&pos));
+    let chars: Vec<(usize, char)> = message.char_indices().collect();
+    let mut candidates = Vec::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, ch) = chars[i];
+        if !ch.is_ascii() {
+            if is_cjk(ch) && !is_protected(pos) {
+                candidates.push(i);
+            }
+            i += 1;
+            continue;
+        }
+        if ch.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // 英文单词：连续的非空白 ASCII 字符；去掉两端标点后含数字或其他符号的整体跳过
+        let start = i;
+        while i < chars.len() && chars[i].1.is_ascii() && !chars[i].1.is_whitespace() {
+            i += 1;
+        }
+        let (mut a, mut b) = (start, i);
+        while a < b && WORD_PUNCT.contains(&chars[a].1) {
+            a += 1;
+        }
+        while b > a && WORD_PUNCT.contains(&chars[b - 1].1) {
+            b -= 1;
+        }
+        let plain = b - a >= 3
+            && chars[a..b]
+                .iter()
+                .all(|(_, c)| c.is_ascii_alphabetic() || *c == '\'' || *c == '-');
+        if plain {
+            candidates.extend(
+                (a..b).filter(|&k| chars[k].1.is_ascii_alphabetic() && !is_protected(chars[k].0)),
+            );
+        }
+    }
+
+    candidates.retain(|&k| k > 0 && k + 1 < chars.len());
+    candidates
+}
+
+fn is_cjk(ch: char) -> bool {
+    matches!(ch, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}')
+}
+
+/// 代码块、行内代码和链接所在的字节区间
+fn protected_ranges(message: &str) -> Vec<Range<usize>> {
+    let mut ranges = inline_spans(message);
+    ranges.extend(
+        units(message)
+            .into_iter()
+            .filter(|u| u.kind == UnitKind::Code)
+            .map(|u| u.range),
+    );
+    ranges
+}
+
+/// 行内代码、链接和 markdown 链接地址的字节区间
+fn inline_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+
+    let mut search = 0;
+    while let Some(open) = text[search..].find('`') {
+        let open = search + open;
+        match text[open + 1..].find('`') {
+            Some(close) => {
+                let end = open + 1 + close + 1;
+                spans.push(open..end);
+                search = end;
+            }
+            None => break,
+        }
+    }
+
+    for prefix in ["http://", "https://", "www.", "]("] {
+        for (start, _) in text.match_indices(prefix) {
+            let from = start + prefix.len();
+            let mut end = text[from..]
+                .find(|c: char| c.is_whitespace() || URL_END.contains(&c))
+                .map_or(text.len(), |e| from + e);
+            // 句末的标点不算链接的一部分
+            while end > from && text[..end].ends_with(['.', ',', '!', '?', ';', ':']) {
+                end -= 1;
+            }
+            spans.push(start..end);
+        }
+    }
+
+    spans
+}
+
+/// 判断整行属于哪种块 (列表/表格/引用)，普通文字为 None
+fn block_kind(line: &str) -> Option<UnitKind> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('|') {
+        return Some(UnitKind::Table);
+    }
+    if trimmed.starts_with('>') {
+        return Some(UnitKind::Quote);
+    }
+    if ["- ", "* ", "+ "].iter().any(|m| trimmed.starts_with(m)) {
+        return Some(UnitKind::List);
+    }
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && (trimmed[digits..].starts_with(". ") || trimmed[digits..].starts_with(") ")) {
+        return Some(UnitKind::List);
+    }
+    None
+}
+
+fn fence_marker(line: &str) -> Option<&'static str> {
+    let trimmed = line.trim_start();
+    ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m))
+}
+
+/// 把消息切成不可再拆的单元，按出现顺序返回
+fn units(message: &str) -> Vec<Unit> {
+    let mut units = Vec::new();
+    let mut block: Option<Unit> = None;
+    let mut fence: Option<(&str, usize)> = None;
+    let mut offset = 0;
+
+    for line in message.split_inclusive('\n') {
+        let range = offset..offset + line.len();
+        offset = range.end;
+
+        if let Some((marker, start)) = fence {
+            if line.trim_start().starts_with(marker) {
+                units.push(Unit {
+                    kind: UnitKind::Code,
+                    range: start..range.end,
+                });
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = fence_marker(line) {
+            units.extend(block.take());
+            fence = Some((marker, range.start));
+            continue;
+        }
+
+        let kind = block_kind(line);
+        // 列表项下缩进的续行仍属于该列表
+        let continuation = kind.is_none()
+            && block.as_ref().is_some_and(|b| b.kind == UnitKind::List)
+            && line.starts_with([' ', '\t'])
+            && !line.trim().is_empty();
+
+        match (kind, block.as_mut()) {
+            (Some(kind), Some(current)) if current.kind == kind => current.range.end = range.end,
+            (None, Some(current)) if continuation => current.range.end = range.end,
+            (Some(kind), _) => {
+                units.extend(block.take());
+                block = Some(Unit { kind, range });
+            }
+            (None, _) => {
+                units.extend(block.take());
+                units.extend(sentences(message, range));
+            }
+        }
+    }
+
+    // 未闭合的代码块一直延伸到结尾
+    if let Some((_, start)) = fence {
+        units.push(Unit {
+            kind: UnitKind::Code,
+            range: start..message.len(),
+        });
+    }
+    units.extend(block);
+    units
+}
+
+/// 把一行普通文字按中英文句末标点拆成句子
+fn sentences(message: &str, line: Range<usize>) -> Vec<Unit> {
+    let text = &message[line.clone()];
+    let protected = inline_spans(text);
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut result = Vec::new();
+    let mut push = |range: Range<usize>| {
+        if !text[range.clone()].trim().is_empty() {
+            result.push(Unit {
+                kind: UnitKind::Sentence,
+                range: line.start + range.start..line.start + range.end,
+            });
+        }
+    };
+
+    let mut start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_sentence_end(text, &chars, i, &protected) {
+            i += 1;
+            continue;
+        }
+        // 连续的句末标点和收尾符号归入本句
+        let mut j = i + 1;
+        while j < chars.len() && is_trailing_mark(chars[j].1) {
+            j += 1;
+        }
+        let end = chars.get(j).map_or(text.len(), |c| c.0);
+        push(start..end);
+        start = end;
+        i = j;
+    }
+    push(start..text.len());
+    result
+}
+
+fn is_trailing_mark(ch: char) -> bool {
+    CJK_TERMINATORS.contains(&ch) || matches!(ch, '.' | '!' | '?') || CLOSERS.contains(&ch)
+}
+
+fn is_sentence_end(
+    text: &str,
+    chars: &[(usize, char)],
+    i: usize,
+    protected: &[Range<usize>],
+) -> bool {
+    let (pos, ch) = chars[i];
+    if protected.iter().any(|r| r.contains(&pos)) {
+        return false;
+    }
+    if CJK_TERMINATORS.contains(&ch) {
+        return true;
+    }
+    if !matches!(ch, '.' | '!' | '?') {
+        return false;
+    }
+
+    // 英文标点后须是空白、行尾、收尾符号或中文，避免拆开 3.14、a.b 之类
+    let prev = i.checked_sub(1).map(|p| chars[p].1);
+    let next = chars.get(i + 1).map(|c| c.1);
+    let open = match next {
+        None => true,
+        Some(n) => {
+            n.is_whitespace()
+                || CLOSERS.contains(&n)
+                || (!n.is_ascii() && (ch != '.' || prev.is_some_and(|p| !p.is_ascii())))
+        }
+    };
+    if !open {
+        return false;
+    }
+
+    if ch == '.' {
+        let word_start = chars[..i]
+            .iter()
+            .rposition(|(_, c)| !(c.is_ascii_alphanumeric() || *c == '.'))
+            .map_or(0, |p| chars[p + 1].0);
+        let word = text[word_start..pos].to_ascii_lowercase();
+        if ABBREVIATIONS.contains(&word.as_str()) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn unit_texts(message: &str) -> Vec<&str> {
+        units(message)
+            .into_iter()
+            .map(|u| message[u.range].trim())
+            .collect()
+    }
+
+    #[test]
+    fn test_sentence_boundaries() {
+        assert_eq!(
+            unit_texts("好的。真的吗？！「对啊」我不信…… Pi is 3.14, e.g. roughly! Visit https://a.com/x?y=1. Done"),
+            vec![
+                "好的。",
+                "真的吗？！",
+                "「对啊」我不信……",
+                "Pi is 3.14, e.g. roughly!",
+                "Visit https://a.com/x?y=1.",
+                "Done",
+            ]
+        );
+        // 英文标点紧跟中文也能断句，但不拆开域名
+        assert_eq!(
+            unit_texts("好吧!我去看看example.com上面写了啥"),
+            vec!["好吧!", "我去看看example.com上面写了啥"]
+        );
+    }
+
+    #[test]
+    fn test_blocks_stay_atomic() {
+        let message = "看这段。\n```rust\nfn main() {\n    println!(\"hi. there!\");\n}\n```\n要点：\n1. 第一点。还有。\n   续行。\n2. 第二点\n\n| a | b |\n|---|---|\n> 引用。第二句。\n收尾。";
+        assert_eq!(
+            unit_texts(message),
+            vec![
+                "看这段。",
+                "```rust\nfn main() {\n    println!(\"hi. there!\");\n}\n```",
+                "要点：",
+                "1. 第一点。还有。\n   续行。\n2. 第二点",
+                "| a | b |\n|---|---|",
+                "> 引用。第二句。",
+                "收尾。",
+            ]
+        );
+
+        // 任意随机序列下，代码块和列表都不会被拆开
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let segments = segment_reply(message, None, 5, (0, 0), &mut rng);
+            for segment in &segments {
+                assert_eq!(segment.matches("```").count() % 2, 0, "{:?}", segments);
+                assert!(!segment.starts_with("2. "), "{:?}", segments);
+            }
+            assert!(segments.iter().any(|s| s.contains("   续行。\n2. 第二点")));
+        }
+    }
+
+    #[test]
+    fn test_explicit_delimiters() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(
+            segment_reply(
+                "哈哈哈||你说得对|| 那就这么定了",
+                Some("||"),
+                3,
+                (40, 100),
+                &mut rng
+            ),
+            vec!["哈哈哈", "你说得对", "那就这么定了"]
+        );
+        // 超过段数上限时合并进最后一段
+        assert_eq!(
+            segment_reply("一||二||三", Some("||"), 2, (40, 100), &mut rng),
+            vec!["一", "二\n三"]
+        );
+        // 代码里的标记不算
+        let code = "用 `a || b` 就行";
+        assert_eq!(split_marked(code, "||"), None);
+        assert_eq!(join_marked("好||走吧", Some("||")), "好\n走吧");
+        assert_eq!(join_marked(code, Some("||")), code);
+    }
+
+    #[test]
+    fn test_streaming_matches_whole_reply() {
+        let message = "先说结论。然后是步骤：\n1. 打开设置。\n2. 找到选项\n```\nrun --fast. now\n```\n最后 e.g. 重启一下! 就好了";
+        for seed in 0..30 {
+            let whole = segment_reply(message, None, 5, (0, 10), &mut StdRng::seed_from_u64(seed));
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut segmenter = StreamSegmenter::new(None, 5, (0, 10), &mut rng);
+            let mut streamed = Vec::new();
+            for ch in message.chars() {
+                streamed.extend(segmenter.push(&ch.to_string(), &mut rng));
+            }
+            streamed.extend(segmenter.finish(&mut rng));
+            assert_eq!(streamed, whole, "seed {}", seed);
+        }
+
+        // 分段标记：一段写完 (后面出现标记) 就交出
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut segmenter = StreamSegmenter::new(Some("||"), 3, (40, 100), &mut rng);
+        assert!(segmenter.push("哈哈|", &mut rng).is_empty());
+        assert_eq!(segmenter.push("|你说", &mut rng), vec!["哈哈"]);
+        assert_eq!(
+            segmenter.push("得对|| 那就||这么定了", &mut rng),
+            vec!["你说得对"]
+        );
+        assert_eq!(segmenter.finish(&mut rng), vec!["那就\n这么定了"]);
+    }
+
+    #[test]
+    fn test_typo_candidates_skip_unsafe_tokens() {
+        let message =
+            "看https://x.cn/a啊 call me at bob@mail.com on 2024-05-01 `code` v2 hello世界";
+        let chars: Vec<char> = message.chars().collect();
+        let picked: String = typo_candidates(message).iter().map(|&i| chars[i]).collect();
+        assert_eq!(picked, "callhello世");
+
+        assert!(typo_candidates("```\n变量名\n```").is_empty());
+        assert!(typo_candidates("12345").is_empty());
+    }
+}
@@ -42,8 +42,10 @@ pub struct Profile {
 }
 
 pub fn init_social_db(conn: &Connection) -> Result<()> {
+    // 表结构依赖 ON DELETE CASCADE，SQLite 默认不启用外键约束
     conn.execute_batch(
         "
+        PRAGMA foreign_keys = ON;
         CREATE TABLE IF NOT EXISTS groups (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             name TEXT NOT NULL,
@@ -102,6 +104,54 @@ pub fn init_social_db(conn: &Connection) -> Result<()> {
             FOREIGN KEY (session_id) REFERENCES social_sessions (id) ON DELETE CASCADE,
             UNIQUE(contact_id, session_id)
         );
+        CREATE TABLE IF NOT EXISTS scheduler_activity (
+            session_id INTEGER PRIMARY KEY,
+            last_interaction INTEGER NOT NULL,  -- Unix 毫秒
+            last_proactive INTEGER,
+            FOREIGN KEY (session_id) REFERENCES social_sessions (id) ON DELETE CASCADE
+        );
+        CREATE TABLE IF NOT EXISTS scheduler_pending (
+            session_id INTEGER NOT NULL,
+            contact_id INTEGER NOT NULL,
+            actions TEXT NOT NULL,              -- 剩余行为 (JSON)
+            started_at INTEGER NOT NULL,        -- 第一个剩余行为开始的 Unix 毫秒
+            last_message_id INTEGER,
+            PRIMARY KEY (session_id, contact_id),
+            FOREIGN KEY (session_id) REFERENCES social_sessions (id) ON DELETE CASCADE
+        );
+        CREATE TABLE IF NOT EXISTS contact_stickers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            contact_id INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            path TEXT NOT NULL,                 -- 本地文件路径或 data URL
+            kind TEXT NOT NULL DEFAULT 'sticker', -- sticker / image
+            description TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (contact_id, name),
+            FOREIGN KEY (contact_id) REFERENCES contacts (id) ON DELETE CASCADE
+        );
+        CREATE TABLE IF NOT EXISTS social_session_members (
+            session_id INTEGER NOT NULL,
+            contact_id INTEGER NOT NULL,
+            sort_order INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (session_id, contact_id),
+            FOREIGN KEY (session_id) REFERENCES social_sessions (id) ON DELETE CASCADE,
+            FOREIGN KEY (contact_id) REFERENCES contacts (id) ON DELETE CASCADE
+        );
+        CREATE TABLE IF NOT EXISTS proactive_triggers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            contact_id INTEGER NOT NULL,
+            session_id INTEGER,                 -- 为空时发往联系人最近的会话
+            kind TEXT NOT NULL,                 -- date / follow_up / reminder
+            title TEXT NOT NULL,
+            note TEXT,
+            fire_at INTEGER NOT NULL,           -- Unix 毫秒
+            yearly INTEGER NOT NULL DEFAULT 0,
+            source TEXT NOT NULL DEFAULT 'user',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (contact_id) REFERENCES contacts (id) ON DELETE CASCADE,
+            FOREIGN KEY (session_id) REFERENCES social_sessions (id) ON DELETE SET NULL
+        );
         ",
     )?;
 
@@ -133,6 +183,10 @@ pub fn init_social_db(conn: &Connection) -> Result<()> {
     if !columns.contains(&"remark".to_string()) {
         let _ = conn.execute("ALTER TABLE contacts ADD COLUMN remark TEXT", []);
     }
+    if !columns.contains(&"routine".to_string()) {
+        // 作息表 (JSON，见 routine.rs)
+        let _ = conn.execute("ALTER TABLE contacts ADD COLUMN routine TEXT", []);
+    }
 
     // Schema Migrations - social_messages (Adding session_id)
     let mut stmt_msg = conn.prepare("PRAGMA table_info(social_messages)")?;
@@ -179,6 +233,66 @@ pub fn init_social_db(conn: &Connection) -> Result<()> {
         );
     }
 
+    // 表情回应、引用回复、图片/表情包、已读回执与编辑
+    for (column, ty) in [
+        ("quote_id", "INTEGER"),
+        ("attachment", "TEXT"),
+        ("reactions", "TEXT"),
+        ("read_at", "INTEGER"),
+        ("edited_at", "INTEGER"),
+    ] {
+        if !msg_columns.contains(&column.to_string()) {
+            println!("🔧 Migrating social_messages: adding {}", column);
+            let _ = conn.execute(
+                &format!("ALTER TABLE social_messages ADD COLUMN {} {}", column, ty),
+                [],
+            );
+        }
+    }
+
+    // Schema Migrations - social_sessions (群聊)
+    let mut stmt_sess = conn.prepare("PRAGMA table_info(social_sessions)")?;
+    let sess_columns: Vec<String> = stmt_sess
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?;
+
+    if !sess_columns.contains(&"is_group".to_string()) {
+        println!("🔧 Migrating social_sessions: adding is_group");
+        let _ = conn.execute(
+            "ALTER TABLE social_sessions ADD COLUMN is_group INTEGER NOT NULL DEFAULT 0",
+            [],
+        );
+    }
+
+    // Schema Migrations - scheduler_pending (主键改为 (session_id, contact_id))
+    let mut stmt_pending = conn.prepare("PRAGMA table_info(scheduler_pending)")?;
+    let contact_in_pk = stmt_pending
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(1)?, row.get::<_, i64>(5)?))
+        })?
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .any(|(name, pk)| name == "contact_id" && pk > 0);
+
+    if !contact_in_pk {
+        // 未完成的行为链只是临时状态，直接重建表
+        println!("🔧 Migrating scheduler_pending: keying by (session_id, contact_id)");
+        conn.execute_batch(
+            "
+            DROP TABLE scheduler_pending;
+            CREATE TABLE scheduler_pending (
+                session_id INTEGER NOT NULL,
+                contact_id INTEGER NOT NULL,
+                actions TEXT NOT NULL,
+                started_at INTEGER NOT NULL,
+                last_message_id INTEGER,
+                PRIMARY KEY (session_id, contact_id),
+                FOREIGN KEY (session_id) REFERENCES social_sessions (id) ON DELETE CASCADE
+            );
+        ",
+        )?;
+    }
+
     // Schema Migrations - profiles
     let mut stmt_prof = conn.prepare("PRAGMA table_info(profiles)")?;
     let prof_columns: Vec<String> = stmt_prof
@@ -328,6 +442,47 @@ pub struct SocialMessage {
     pub file_metadata: Option<String>,
 
     pub created_at: Option<String>,
+
+    /// 引用回复的消息 ID
+    #[serde(rename = "quoteId", default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<i64>,
+
+    /// 图片或表情包 (JSON，见 expression::Attachment)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<String>,
+
+    /// 表情回应 (JSON 数组，见 expression::Reaction)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reactions: Option<String>,
+
+    /// 已读时间 (Unix 毫秒)
+    #[serde(rename = "readAt", default, skip_serializing_if = "Option::is_none")]
+    pub read_at: Option<i64>,
+
+    /// 最后编辑时间 (Unix 毫秒)
+    #[serde(rename = "editedAt", default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<i64>,
+}
+
+impl SocialMessage {
+    /// 按 id, contact_id, session_id, role, content, file_metadata, created_at,
+    /// quote_id, attachment, reactions, read_at, edited_at 的列顺序读取
+    pub(crate) fn from_row(row: &rusqlite::Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            contact_id: row.get(1)?,
+            session_id: row.get(2)?,
+            role: row.get(3)?,
+            content: row.get(4)?,
+            file_metadata: row.get(5)?,
+            created_at: row.get(6)?,
+            quote_id: row.get(7)?,
+            attachment: row.get(8)?,
+            reactions: row.get(9)?,
+            read_at: row.get(10)?,
+            edited_at: row.get(11)?,
+        })
+    }
 }
 
 #[tauri::command]
@@ -375,15 +530,24 @@ pub async fn delete_social_contact(
     state: tauri::State<'_, SocialDbState>,
     id: i64,
 ) -> Result<(), String> {
-    let conn = state.0.lock().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM contacts WHERE id = ?1", params![id])
-        .map_err(|e| e.to_string())?;
-    conn.execute(
-        "DELETE FROM social_messages WHERE contact_id = ?1",
+    let mut conn = state.0.lock().map_err(|e| e.to_string())?;
+    delete_contact(&mut conn, id)
+}
+
+/// 删除联系人及其私聊；群聊中的用户消息记在群主名下，先把群主交给其他成员
+pub(crate) fn delete_contact(conn: &mut Connection, id: i64) -> Result<(), String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    crate::group_chat::hand_over_groups(&tx, id)?;
+    tx.execute(
+        "DELETE FROM social_messages WHERE contact_id = ?1
+         AND session_id IN (SELECT id FROM social_sessions WHERE contact_id = ?1 AND is_group = 0)",
         params![id],
     )
     .map_err(|e| e.to_string())?;
-    Ok(())
+    // 私聊会话、群成员、表情包等由外键级联删除
+    tx.execute("DELETE FROM contacts WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -393,20 +557,10 @@ pub async fn get_social_messages(
 ) -> Result<Vec<SocialMessage>, String> {
     let conn = state.0.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare("SELECT id, contact_id, session_id, role, content, file_metadata, created_at FROM social_messages WHERE contact_id = ?1 ORDER BY id ASC")
+        .prepare("SELECT id, contact_id, session_id, role, content, file_metadata, created_at, quote_id, attachment, reactions, read_at, edited_at FROM social_messages WHERE contact_id = ?1 ORDER BY id ASC")
         .map_err(|e| e.to_string())?;
     let msg_iter = stmt
-        .query_map(params![contact_id], |row| {
-            Ok(SocialMessage {
-                id: row.get(0)?,
-                contact_id: row.get(1)?,
-                session_id: row.get(2)?,
-                role: row.get(3)?,
-                content: row.get(4)?,
-                file_metadata: row.get(5)?,
-                created_at: row.get(6)?,
-            })
-        })
+        .query_map(params![contact_id], SocialMessage::from_row)
         .map_err(|e| e.to_string())?;
 
     let mut messages = Vec::new();
@@ -426,10 +580,10 @@ pub async fn get_recent_social_messages(
     let conn = state.0.lock().map_err(|e| e.to_string())?;
     // Load the LAST N messages of a session
     let sql = if session_id.is_some() {
-        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at FROM social_messages WHERE contact_id = ?1 AND session_id = ?2 ORDER BY id DESC LIMIT ?3"
+        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at, quote_id, attachment, reactions, read_at, edited_at FROM social_messages WHERE contact_id = ?1 AND session_id = ?2 ORDER BY id DESC LIMIT ?3"
     } else {
         // Fallback: get all messages if no session specified (or for testing)
-        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at FROM social_messages WHERE contact_id = ?1 ORDER BY id DESC LIMIT ?3"
+        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at, quote_id, attachment, reactions, read_at, edited_at FROM social_messages WHERE contact_id = ?1 ORDER BY id DESC LIMIT ?3"
     };
 
     let params_list: Vec<&dyn rusqlite::ToSql> = if let Some(sid) = session_id.as_ref() {
@@ -442,17 +596,10 @@ pub async fn get_recent_social_messages(
 
     // Rust rusqlite params! macro is for fixed args. For dynamic, we use query_map with slice
     let msg_iter = stmt
-        .query_map(rusqlite::params_from_iter(params_list), |row| {
-            Ok(SocialMessage {
-                id: row.get(0)?,
-                contact_id: row.get(1)?,
-                session_id: row.get(2)?,
-                role: row.get(3)?,
-                content: row.get(4)?,
-                file_metadata: row.get(5)?,
-                created_at: row.get(6)?,
-            })
-        })
+        .query_map(
+            rusqlite::params_from_iter(params_list),
+            SocialMessage::from_row,
+        )
         .map_err(|e| e.to_string())?;
 
     let mut messages = Vec::new();
@@ -474,9 +621,9 @@ pub async fn get_social_messages_paginated(
     let conn = state.0.lock().map_err(|e| e.to_string())?;
 
     let sql = if session_id.is_some() {
-        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at FROM social_messages WHERE contact_id = ?1 AND session_id = ?2 AND id < ?3 ORDER BY id DESC LIMIT ?4"
+        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at, quote_id, attachment, reactions, read_at, edited_at FROM social_messages WHERE contact_id = ?1 AND session_id = ?2 AND id < ?3 ORDER BY id DESC LIMIT ?4"
     } else {
-        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at FROM social_messages WHERE contact_id = ?1 AND id < ?3 ORDER BY id DESC LIMIT ?4"
+        "SELECT id, contact_id, session_id, role, content, file_metadata, created_at, quote_id, attachment, reactions, read_at, edited_at FROM social_messages WHERE contact_id = ?1 AND id < ?3 ORDER BY id DESC LIMIT ?4"
     };
 
     let params_vec: Vec<&dyn rusqlite::ToSql> = if let Some(sid) = session_id.as_ref() {
@@ -488,17 +635,10 @@ pub async fn get_social_messages_paginated(
     let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
 
     let msg_iter = stmt
-        .query_map(rusqlite::params_from_iter(params_vec), |row| {
-            Ok(SocialMessage {
-                id: row.get(0)?,
-                contact_id: row.get(1)?,
-                session_id: row.get(2)?,
-                role: row.get(3)?,
-                content: row.get(4)?,
-                file_metadata: row.get(5)?,
-                created_at: row.get(6)?,
-            })
-        })
+        .query_map(
+            rusqlite::params_from_iter(params_vec),
+            SocialMessage::from_row,
+        )
         .map_err(|e| e.to_string())?;
 
     let mut messages = Vec::new();
@@ -666,7 +806,7 @@ pub fn get_social_sessions(
                     COALESCE(MAX(m.created_at), s.updated_at) as last_activity
              FROM social_sessions s
              LEFT JOIN social_messages m ON s.id = m.session_id
-             WHERE s.contact_id = ?1
+             WHERE s.contact_id = ?1 AND s.is_group = 0
              GROUP BY s.id
              ORDER BY last_activity DESC",
         )
@@ -734,12 +874,23 @@ pub fn touch_social_session(state: tauri::State<'_, SocialDbState>, id: i64) ->
 #[tauri::command]
 pub fn delete_social_session(
     state: tauri::State<'_, SocialDbState>,
+    chat_state: tauri::State<'_, crate::db::DbState>,
     id: i64,
 ) -> Result<(), String> {
     let conn = state.0.lock().map_err(|e| e.to_string())?;
     // Cascading delete handles messages
     conn.execute("DELETE FROM social_sessions WHERE id = ?1", params![id])
         .map_err(|e| e.to_string())?;
+    drop(conn);
+
+    // 摘要缓存存放在 goge.db 中
+    let chat_conn = chat_state.0.lock().map_err(|e| e.to_string())?;
+    crate::context_window::delete_cached_summary(
+        &chat_conn,
+        crate::context_window::SessionKind::Social,
+        id,
+    )
+    .map_err(|e| e.to_string())?;
     Ok(())
 }
 
@@ -0,0 +1,507 @@
+use futures_util::{Stream, StreamExt};
+use serde_json::Value;
+use std::collections::VecDeque;
+
+// ==================================================================================
+// 流式响应解码：所有提供商共用
+// 网络分块可能在任意字节处切开 (包括多字节 UTF-8 字符中间)，解码器一律按字节缓冲，
+// 只在完整的行 / 完整的 JSON 对象上做 UTF-8 解码
+// ==================================================================================
+
+/// 增量解码器：逐块喂入字节，返回已完整的条目
+pub trait StreamDecoder {
+    type Item;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<Self::Item>;
+
+    /// 流结束时处理缓冲区中的剩余数据
+    fn finish(&mut self) -> Vec<Self::Item>;
+}
+
+/// 将字节流包装为条目流 (例如 `decode(response.bytes_stream(), SseDecoder::default())`)
+pub fn decode<S, B, E, D>(stream: S, decoder: D) -> impl Stream<Item = Result<D::Item, String>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+    D: StreamDecoder,
+{
+    futures_util::stream::unfold(
+        (Box::pin(stream), decoder, VecDeque::new(), false),
+        |(mut stream, mut decoder, mut queue, mut ended)| async move {
+            loop {
+                if let Some(item) = queue.pop_front() {
+                    return Some((Ok(item), (stream, decoder, queue, ended)));
+                }
+                if ended {
+                    return None;
+                }
+                match stream.next().await {
+                    Some(Ok(chunk)) => queue.extend(decoder.push(chunk.as_ref())),
+                    Some(Err(e)) => {
+                        ended = true;
+                        return Some((Err(e.to_string()), (stream, decoder, queue, ended)));
+                    }
+                    None => {
+                        ended = true;
+                        queue.extend(decoder.finish());
+                    }
+                }
+            }
+        },
+    )
+}
+
+// ==================================================================================
+// Server-Sent Events (OpenAI 兼容接口)
+// ==================================================================================
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 一个已派发的 SSE 事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// event 字段，缺省为 "message"
+    pub event: String,
+    /// 多行 data 以 \n 连接
+    pub data: String,
+    /// 最近一次出现的 id 字段
+    pub id: Option<String>,
+}
+
+/// 按 WHATWG 规范解析 SSE：支持 \n / \r\n / \r 换行、注释行、event / id 字段与多行 data
+#[derive(Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    bom_checked: bool,
+    /// 上一块以 \r 结尾时，下一块开头的 \n 属于同一个换行符
+    skip_lf: bool,
+    event: String,
+    data: String,
+    last_id: Option<String>,
+}
+
+impl SseDecoder {
+    fn take_lines(&mut self, at_eof: bool) -> Vec<SseEvent> {
+        if !self.bom_checked {
+            if !at_eof && self.buffer.len() < UTF8_BOM.len() && UTF8_BOM.starts_with(&self.buffer) {
+                return Vec::new();
+            }
+            if self.buffer.starts_with(UTF8_BOM) {
+                self.buffer.drain(..UTF8_BOM.len());
+            }
+            self.bom_checked = true;
+        }
+        if self.skip_lf && !self.buffer.is_empty() {
+            if self.buffer[0] == b'\n' {
+                self.buffer.remove(0);
+            }
+            self.skip_lf = false;
+        }
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+        while i < self.buffer.len() {
+            let b = self.buffer[i];
+            if b != b'\n' && b != b'\r' {
+                i += 1;
+                continue;
+            }
+            // \r\n 与 \r、\n 一样算一个换行
+            let mut next = i + 1;
+            if b == b'\r' {
+                if next < self.buffer.len() {
+                    if self.buffer[next] == b'\n' {
+                        next += 1;
+                    }
+                } else {
+                    self.skip_lf = true;
+                }
+            }
+            // 换行符都是 ASCII，不会落在多字节字符中间，整行解码是安全的
+            let line = String::from_utf8_lossy(&self.buffer[start..i]).into_owned();
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+            start = next;
+            i = next;
+        }
+        self.buffer.drain(..start);
+
+        if at_eof && !self.buffer.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            // retry 只用于断线重连，这里不会重连；其余字段按规范忽略
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.event);
+        let mut data = std::mem::take(&mut self.data);
+        if data.is_empty() {
+            return None;
+        }
+        data.pop();
+        Some(SseEvent {
+            event: if event.is_empty() {
+                "message".into()
+            } else {
+                event
+            },
+            data,
+            id: self.last_id.clone(),
+        })
+    }
+}
+
+impl StreamDecoder for SseDecoder {
+    type Item = SseEvent;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        self.take_lines(false)
+    }
+
+    /// 规范要求丢弃没有以空行结束的事件，但不少兼容服务的最后一个事件不带空行，这里仍然派发
+    fn finish(&mut self) -> Vec<SseEvent> {
+        let mut events = self.take_lines(true);
+        events.extend(self.dispatch());
+        events
+    }
+}
+
+// ==================================================================================
+// JSON 数组流 (Gemini streamGenerateContent：[ {...}, {...} ])
+// ==================================================================================
+
+/// 逐个取出顶层 JSON 对象，跳过数组括号与分隔符；字符串内的括号与转义不影响配对
+#[derive(Default)]
+pub struct JsonArrayDecoder {
+    buffer: Vec<u8>,
+    /// 下次从这里继续扫描，避免每来一块都从头扫描
+    pos: usize,
+    start: Option<usize>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl StreamDecoder for JsonArrayDecoder {
+    type Item = Value;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<Value> {
+        self.buffer.extend_from_slice(chunk);
+        let mut values = Vec::new();
+        for i in self.pos..self.buffer.len() {
+            let b = self.buffer[i];
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            match b {
+                b'"' => self.in_string = true,
+                b'{' => {
+                    if self.depth == 0 {
+                        self.start = Some(i);
+                    }
+                    self.depth += 1;
+                }
+                b'}' if self.depth > 0 => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        if let Some(start) = self.start.take() {
+                            // 无法解析的对象直接跳过
+                            if let Ok(v) = serde_json::from_slice(&self.buffer[start..=i]) {
+                                values.push(v);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // 丢弃已处理的字节，只保留尚未闭合的对象
+        let keep_from = self.start.unwrap_or(self.buffer.len());
+        self.buffer.drain(..keep_from);
+        self.pos = self.buffer.len();
+        if self.start.is_some() {
+            self.start = Some(0);
+        }
+        values
+    }
+
+    /// 未闭合的对象说明响应被截断，直接丢弃
+    fn finish(&mut self) -> Vec<Value> {
+        *self = JsonArrayDecoder::default();
+        Vec::new()
+    }
+}
+
+// ==================================================================================
+// NDJSON (Ollama 流式响应：每行一个 JSON 对象)
+// ==================================================================================
+
+/// 按行切分字节流，按字节缓冲以免多字节字符被网络分块截断
+#[derive(Default)]
+pub struct NdjsonLines {
+    buffer: Vec<u8>,
+}
+
+impl StreamDecoder for NdjsonLines {
+    type Item = Value;
+
+    /// 追加一个网络分块，返回其中完整的 JSON 行 (无法解析的行被跳过)
+    fn push(&mut self, chunk: &[u8]) -> Vec<Value> {
+        self.buffer.extend_from_slice(chunk);
+        let mut values = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            if let Ok(v) = serde_json::from_slice::<Value>(&line) {
+                values.push(v);
+            }
+        }
+        values
+    }
+
+    /// 流结束时处理没有换行结尾的最后一行
+    fn finish(&mut self) -> Vec<Value> {
+        let rest = std::mem::take(&mut self.buffer);
+        serde_json::from_slice(&rest).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    /// 按给定切分点把字节喂给解码器
+    fn feed<D: StreamDecoder>(mut decoder: D, bytes: &[u8], cuts: &[usize]) -> Vec<D::Item> {
+        let mut items = Vec::new();
+        let mut last = 0;
+        for &cut in cuts {
+            items.extend(decoder.push(&bytes[last..cut]));
+            last = cut;
+        }
+        items.extend(decoder.push(&bytes[last..]));
+        items.extend(decoder.finish());
+        items
+    }
+
+    fn cut_points(len: usize, indices: &[prop::sample::Index]) -> Vec<usize> {
+        let mut cuts: Vec<usize> = indices.iter().map(|i| i.index(len + 1)).collect();
+        cuts.sort_unstable();
+        cuts
+    }
+
+    fn message(data: &str) -> SseEvent {
+        SseEvent {
+            event: "message".into(),
+            data: data.into(),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_sse_fields_comments_and_line_endings() {
+        let text = concat!(
+            "\u{FEFF}: keep-alive\r\n",
+            "event: delta\r\n",
+            "id: 7\r\n",
+            "data: {\"a\":\r\n",
+            "data:1}\r\n",
+            "\r\n",
+            "retry: 3000\r",
+            "data\r",
+            "\r",
+            "event: ignored-without-data\n\n",
+            "data: [DONE]"
+        );
+        let events = feed(SseDecoder::default(), text.as_bytes(), &[]);
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: "delta".into(),
+                    data: "{\"a\":\n1}".into(),
+                    id: Some("7".into()),
+                },
+                SseEvent {
+                    event: "message".into(),
+                    data: "".into(),
+                    id: Some("7".into()),
+                },
+                SseEvent {
+                    event: "message".into(),
+                    data: "[DONE]".into(),
+                    id: Some("7".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sse_cr_and_cjk_split_across_chunks() {
+        let bytes = "data: 你好\r\n\r\ndata: 世界\r\n\r\n".as_bytes();
+        let mut decoder = SseDecoder::default();
+        // 切在 "你" 的第二个字节与 \r\n 之间
+        assert!(decoder.push(&bytes[..7]).is_empty());
+        assert!(decoder.push(&bytes[7..13]).is_empty());
+        assert_eq!(decoder.push(&bytes[13..15]), vec![message("你好")]);
+        assert_eq!(decoder.push(&bytes[15..]), vec![message("世界")]);
+        assert!(decoder.finish().is_empty());
+    }
+
+    #[test]
+    fn test_json_array_braces_inside_strings() {
+        let text = "[{\"text\":\"} {\\\"x\\\" \\\\\"},\r\n{\"candidates\":[{\"n\":\"你\"}]}\n]";
+        let values = feed(JsonArrayDecoder::default(), text.as_bytes(), &[3, 9, 30]);
+        assert_eq!(
+            values,
+            vec![
+                json!({"text": "} {\"x\" \\"}),
+                json!({"candidates": [{"n": "你"}]})
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ndjson_lines_split_across_chunks() {
+        let mut lines = NdjsonLines::default();
+        let text = "{\"message\":{\"content\":\"你好\"}}\n{\"done\":true}";
+        let bytes = text.as_bytes();
+        // 在多字节字符中间切开
+        assert!(lines.push(&bytes[..24]).is_empty());
+        let values = lines.push(&bytes[24..]);
+        assert_eq!(values[0]["message"]["content"], "你好");
+        assert_eq!(lines.finish()[0]["done"], true);
+    }
+
+    #[tokio::test]
+    async fn test_decode_stream_reports_errors() {
+        let chunks: Vec<Result<Vec<u8>, String>> = vec![
+            Ok(b"data: a\n".to_vec()),
+            Ok(b"\ndata: b".to_vec()),
+            Err("connection reset".into()),
+        ];
+        let mut items = std::pin::pin!(decode(
+            futures_util::stream::iter(chunks),
+            SseDecoder::default()
+        ));
+        assert_eq!(items.next().await, Some(Ok(message("a"))));
+        assert_eq!(items.next().await, Some(Err("connection reset".into())));
+        assert_eq!(items.next().await, None);
+    }
+
+    prop_compose! {
+        fn sse_events()(
+            events in prop::collection::vec(
+                (
+                    prop::option::of("[a-z]{1,8}"),
+                    prop::collection::vec("[^\r\n]{0,12}", 1..4),
+                    prop::option::of("[^\r\n]{0,12}"),
+                ),
+                1..6,
+            )
+        ) -> Vec<(Option<String>, Vec<String>, Option<String>)> {
+            events
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_sse_any_chunking(
+            events in sse_events(),
+            eol in prop::sample::select(vec!["\n", "\r\n", "\r"]),
+            indices in prop::collection::vec(any::<prop::sample::Index>(), 0..12),
+        ) {
+            let mut text = String::new();
+            let mut expected = Vec::new();
+            for (name, lines, comment) in &events {
+                if let Some(comment) = comment {
+                    text.push_str(&format!(":{}{}", comment, eol));
+                }
+                if let Some(name) = name {
+                    text.push_str(&format!("event: {}{}", name, eol));
+                }
+                for line in lines {
+                    text.push_str(&format!("data: {}{}", line, eol));
+                }
+                text.push_str(eol);
+                expected.push(SseEvent {
+                    event: name.clone().unwrap_or_else(|| "message".into()),
+                    data: lines.join("\n"),
+                    id: None,
+                });
+            }
+            let bytes = text.as_bytes();
+            let cuts = cut_points(bytes.len(), &indices);
+            prop_assert_eq!(feed(SseDecoder::default(), bytes, &cuts), expected);
+        }
+
+        #[test]
+        fn prop_json_array_any_chunking(
+            items in prop::collection::vec(("\\PC{0,12}", any::<i32>()), 1..6),
+            indices in prop::collection::vec(any::<prop::sample::Index>(), 0..12),
+        ) {
+            let expected: Vec<Value> = items
+                .iter()
+                .map(|(s, n)| json!({"text": s, "n": n, "nested": {"parts": [{"text": s}]}}))
+                .collect();
+            let text = format!(
+                "[{}]",
+                expected.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",\r\n")
+            );
+            let bytes = text.as_bytes();
+            let cuts = cut_points(bytes.len(), &indices);
+            prop_assert_eq!(feed(JsonArrayDecoder::default(), bytes, &cuts), expected);
+        }
+
+        #[test]
+        fn prop_ndjson_any_chunking(
+            items in prop::collection::vec("\\PC{0,12}", 1..6),
+            indices in prop::collection::vec(any::<prop::sample::Index>(), 0..12),
+        ) {
+            let expected: Vec<Value> = items.iter().map(|s| json!({"content": s})).collect();
+            let text = expected.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n");
+            let bytes = text.as_bytes();
+            let cuts = cut_points(bytes.len(), &indices);
+            prop_assert_eq!(feed(NdjsonLines::default(), bytes, &cuts), expected);
+        }
+    }
+}
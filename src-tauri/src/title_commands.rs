@@ -1,7 +1,10 @@
 use crate::commands::config_cmd;
-use crate::models::Message;
+use crate::generation_config::{gemini_generation_config, resolve_generation};
+use crate::models::{GenerationParams, Message};
+use crate::net::HttpClients;
+use crate::provider_config::{resolve_provider, ProviderConfig, ProviderKind};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Manager};
 
 // --- 辅助结构体：用于 generate_title 的 API 请求与响应 ---
 #[derive(Serialize)]
@@ -9,6 +12,8 @@ struct TitleChatRequest {
     model: String,
     messages: Vec<Message>,
     stream: bool,
+    #[serde(flatten)]
+    params: GenerationParams,
 }
 
 #[derive(Deserialize)]
@@ -32,18 +37,12 @@ pub async fn generate_title(
     msg: Vec<Message>,
     explicit_provider_id: Option<String>,
     explicit_model_id: Option<String>,
-    client: State<'_, reqwest::Client>,
 ) -> Result<String, String> {
-    generate_title_internal_with_params(app, msg, explicit_provider_id, explicit_model_id, &client)
-        .await
+    generate_title_internal_with_params(app, msg, explicit_provider_id, explicit_model_id).await
 }
 
-pub async fn generate_title_internal(
-    app: AppHandle,
-    msg: Vec<Message>,
-    client: &reqwest::Client,
-) -> Result<String, String> {
-    generate_title_internal_with_params(app, msg, None, None, client).await
+pub async fn generate_title_internal(app: AppHandle, msg: Vec<Message>) -> Result<String, String> {
+    generate_title_internal_with_params(app, msg, None, None).await
 }
 
 async fn generate_title_internal_with_params(
@@ -51,71 +50,54 @@ async fn generate_title_internal_with_params(
     msg: Vec<Message>,
     explicit_provider_id: Option<String>,
     explicit_model_id: Option<String>,
-    client: &reqwest::Client,
 ) -> Result<String, String> {
     let start_total = std::time::Instant::now();
 
     // 1. 【动态读取】加载配置
-    let config = config_cmd::load_config(app).await?;
-
-    // 2. 【安全校验】获取当前选中的提供商和模型
-    let selected_provider_id = explicit_provider_id.unwrap_or(config.default_provider_id.clone());
-    let selected_model_id = explicit_model_id.unwrap_or(config.selected_model_id.clone());
-
-    // 从 providers 数组中找到当前选中的提供商配置
-    let providers = config
-        .providers
-        .as_array()
-        .ok_or("配置错误: 无法读取提供商列表")?;
-    let provider_config = providers
-        .iter()
-        .find(|p| p["id"].as_str() == Some(&selected_provider_id))
-        .ok_or(format!("找不到提供商配置: {}", selected_provider_id))?;
-
-    let api_key = provider_config["apiKey"].as_str().unwrap_or("").to_string();
-    let base_url_raw = provider_config["baseUrl"]
-        .as_str()
-        .unwrap_or("https://api.deepseek.com")
-        .to_string();
+    let config = config_cmd::load_config(app.clone()).await?;
+
+    // 2. 【安全校验】解析提供商、模型与生成参数 (与对话使用同一套优先级)
+    let resolved = resolve_generation(
+        &config,
+        None,
+        explicit_provider_id,
+        explicit_model_id,
+        GenerationParams::default(),
+        GenerationParams::default(),
+    );
+    let selected_provider_id = resolved.provider_id;
+    let selected_model_id = resolved.model;
+    let params = resolved.params;
 
-    if api_key.trim().is_empty() {
-        return Err(format!(
-            "{} 的 API Key 未配置，请前往设置页面填写",
-            provider_config["name"].as_str().unwrap_or("该提供商")
-        ));
-    }
+    let provider = resolve_provider(&config, &selected_provider_id)?;
+    let client = &app.state::<HttpClients>().for_provider(&provider)?;
 
     // --- Gemini Native 支持 ---
-    if selected_provider_id == "gemini" {
+    if provider.is_gemini() {
         let res =
-            handle_gemini_title_native(api_key, base_url_raw, selected_model_id, msg, client).await;
+            handle_gemini_title_native(&provider, selected_model_id, msg, &params, client).await;
         let duration = start_total.elapsed();
         println!("⏱️ [性能] AI 任务处理总耗时 (Gemini): {:?}", duration);
         return res;
     }
 
-    // 格式化 URL，复用 ai.rs 的强健逻辑
-    let base_url = {
-        let base = base_url_raw.trim_end_matches('/');
-        if base.ends_with("/chat/completions") {
-            base.to_string()
-        } else if base.ends_with("/v1") {
-            format!("{}/chat/completions", base)
-        } else {
-            // 如果不包含 v1，自动补全 /v1/chat/completions
-            format!("{}/v1/chat/completions", base)
-        }
-    };
+    // --- Ollama 原生接口 ---
+    if provider.kind == ProviderKind::Ollama {
+        let title =
+            crate::local_llm::ollama_chat(client, &provider, &selected_model_id, &msg, &params)
+                .await?;
+        return Ok(title.trim().to_string());
+    }
 
     let request_body = TitleChatRequest {
         model: selected_model_id,
         messages: msg,
         stream: false,
+        params,
     };
 
-    let response = client
-        .post(&base_url)
-        .header("Authorization", format!("Bearer {}", api_key))
+    let response = provider
+        .authorize(client.post(provider.chat_completions_url()))
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
@@ -149,10 +131,10 @@ async fn generate_title_internal_with_params(
 }
 
 async fn handle_gemini_title_native(
-    api_key: String,
-    base_url: String,
+    provider: &ProviderConfig,
     model: String,
     messages: Vec<Message>,
+    params: &GenerationParams,
     client: &reqwest::Client,
 ) -> Result<String, String> {
     // 1. 转换消息格式 (非流式：generateContent)
@@ -167,17 +149,17 @@ async fn handle_gemini_title_native(
         })
         .collect();
 
-    let url = format!(
-        "{}/v1beta/models/{}:generateContent?key={}",
-        base_url.trim_end_matches('/'),
-        model,
-        api_key
-    );
+    let url = provider.gemini_url(&model, "generateContent");
+
+    let mut body = serde_json::json!({ "contents": contents });
+    if let Some(cfg) = gemini_generation_config(params) {
+        body["generationConfig"] = cfg;
+    }
 
-    let response = client
-        .post(&url)
+    let response = provider
+        .authorize(client.post(&url))
         .header("Content-Type", "application/json")
-        .json(&serde_json::json!({ "contents": contents }))
+        .json(&body)
         .send()
         .await
         .map_err(|e| format!("Gemini 网络请求失败: {}", e))?;
@@ -9,10 +9,10 @@ export const configApi = {
      */
     async load(): Promise<AppSettings | null> {
         try {
-            // 这里的 'load_config' 必须对应 Rust 端的 #[tauri::command]
-            return await invoke<AppSettings>('load_config');
+            // Webview 只读取脱敏后的配置，API Key 以占位符代替
+            return await invoke<AppSettings>('load_config_redacted');
         } catch (e) {
-            console.error("API Error [load_config]:", e);
+            console.error("API Error [load_config_redacted]:", e);
             return null;
         }
     },
@@ -488,10 +488,10 @@ export function useMessageActions(state: MessageState, deps: MessageActionsDepen
                     await invoke("ask_ai", {
                         msg: msgsToSend,
                         onEvent,
-                        temperature: activePreset?.temperature,
-                        max_tokens: activePreset?.maxTokens,
+                        // 温度等生成参数由后端按 会话 > 预设 > 提供商 的顺序解析
                         explicitProviderId: currentProviderId,
                         explicitModelId: currentModelId,
+                        sessionId,
                         stream: isStreamEnabled
                     });
 
@@ -5,6 +5,7 @@ import { configCommands, fileCommands } from '../tauri/commands';
 import { AppSettings, DEFAULT_SETTINGS, ModelProviderConfig, ModelPreset, PromptLibraryItem, ModelInfo } from '../types/config';
 import { PREBUILT_PROMPTS } from '../constants/prompts';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 export const useConfigStore = defineStore('config', () => {
     // ========== 状态 ==========
@@ -13,6 +14,7 @@ export const useConfigStore = defineStore('config', () => {
     const lastError = ref<string | null>(null);
     const userAvatarUrl = ref<string>(''); // Loaded base64 avatar for display
     let lastLoadedPath = ''; // Prevent redundant loads
+    let externalWatchStarted = false; // config-changed 监听只注册一次
 
     // Import lazily to avoid circular issues or just at top
     // But we can't import inside defineStore easily if it is a module import
@@ -158,6 +160,7 @@ export const useConfigStore = defineStore('config', () => {
 
                 applyToCss(settings.value);
             }
+            await watchExternalChanges();
         } catch (e) {
             console.error("加载配置失败:", e);
             lastError.value = e instanceof Error ? e.message : String(e);
@@ -166,6 +169,23 @@ export const useConfigStore = defineStore('config', () => {
         }
     };
 
+    /**
+     * 配置文件在应用外被修改时 (后端热重载后发出 config-changed)，重新拉取配置
+     */
+    const watchExternalChanges = async () => {
+        if (externalWatchStarted) return;
+        externalWatchStarted = true;
+        await listen<{ sections: string[]; issues: { file: string; message: string }[] }>('config-changed', async (event) => {
+            console.log('[ConfigStore] External config change:', event.payload.sections.join(','));
+            if (event.payload.sections.length > 0) {
+                await init();
+            }
+            if (event.payload.issues.length > 0) {
+                lastError.value = event.payload.issues.map(i => `${i.file}: ${i.message}`).join('\n');
+            }
+        });
+    };
+
     /**
      * 合并已保存的提供商配置和默认配置
      * 确保新增的提供商也能出现在列表中
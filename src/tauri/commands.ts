@@ -8,8 +8,19 @@ import type {
 import type {
     SaveMessageParams,
     AskAIParams,
-    GenerateTitleParams
+    CompareEvent,
+    CompareRequest,
+    GenerateTitleParams,
+    Routine,
+    ActivityState,
+    TriggerInput,
+    ProactiveTrigger,
+    GroupMember,
+    GroupSession,
+    Sticker,
+    StickerKind
 } from '../types/tauri';
+import type { ApiServerStatus, SecretStoreStatus } from '../types/config';
 
 /**
  * 会话相关命令
@@ -90,6 +101,10 @@ export const aiCommands = {
     askAI: (msg: AskAIParams['msg'], onEvent: Channel<string>, temperature?: number, max_tokens?: number, explicit_provider_id?: string, explicit_model_id?: string) =>
         invoke<void>('ask_ai', { msg, onEvent, temperature, max_tokens, explicitProviderId: explicit_provider_id, explicitModelId: explicit_model_id }),
 
+    /** 多模型对比：回答按 target 标记从同一个 Channel 下发 */
+    askAICompare: (msg: AskAIParams['msg'], request: CompareRequest, onEvent: Channel<CompareEvent>) =>
+        invoke<void>('ask_ai_compare', { msg, request, onEvent }),
+
 
     /** 停止 AI 生成 */
     stopAIGeneration: () => invoke<void>('stop_ai_generation'),
@@ -109,7 +124,19 @@ export const configCommands = {
     saveConfig: (config: any) => invoke<void>('save_config', { config }),
 
     /** 加载配置 */
-    loadConfig: () => invoke<any>('load_config'),
+    loadConfig: () => invoke<any>('load_config_redacted'),
+
+    /** 本地 OpenAI 兼容接口的运行状态 */
+    getApiServerStatus: () => invoke<ApiServerStatus>('get_api_server_status'),
+
+    /** 生成随机访问令牌 */
+    generateApiToken: () => invoke<string>('generate_api_token'),
+
+    /** API Key 存储的后端与锁定状态 */
+    getSecretStoreStatus: () => invoke<SecretStoreStatus>('get_secret_store_status'),
+
+    /** 用口令解锁加密文件存储 */
+    unlockSecretStore: (passphrase: string) => invoke<void>('unlock_secret_store', { passphrase }),
 };
 
 /**
@@ -140,6 +167,74 @@ export const fileCommands = {
     uploadUserAvatar: (filePath: string) => invoke<string>('upload_user_avatar', { filePath: filePath }),
 };
 
+/**
+ * 角色作息相关命令
+ */
+export const routineCommands = {
+    /** 获取联系人的作息表 (未设置时为 null) */
+    getContactRoutine: (contactId: number) => invoke<Routine | null>('get_contact_routine', { contactId }),
+
+    /** 保存作息表，传 null 清除 */
+    setContactRoutine: (contactId: number, routine: Routine | null) => invoke<void>('set_contact_routine', { contactId, routine }),
+
+    /** 联系人当前所处的活动 */
+    getContactActivity: (contactId: number) => invoke<ActivityState | null>('get_contact_activity', { contactId }),
+};
+
+/**
+ * 主动发言触发器相关命令
+ */
+export const triggerCommands = {
+    /** 列出触发器 (不传联系人时列出全部) */
+    listProactiveTriggers: (contactId?: number) => invoke<ProactiveTrigger[]>('list_proactive_triggers', { contactId: contactId ?? null }),
+
+    /** 添加触发器，返回 ID */
+    addProactiveTrigger: (trigger: TriggerInput) => invoke<number>('add_proactive_trigger', { trigger }),
+
+    /** 删除触发器 */
+    deleteProactiveTrigger: (id: number) => invoke<void>('delete_proactive_trigger', { id }),
+};
+
+/**
+ * 群聊相关命令
+ */
+export const groupCommands = {
+    /** 创建群聊 (至少两位联系人)，返回会话 ID */
+    createGroupSession: (title: string, contactIds: number[]) => invoke<number>('create_group_session', { title, contactIds }),
+
+    /** 获取所有群聊 */
+    getGroupSessions: () => invoke<GroupSession[]>('get_group_sessions'),
+
+    /** 获取群聊成员 */
+    getGroupMembers: (sessionId: number) => invoke<GroupMember[]>('get_group_members', { sessionId }),
+
+    /** 替换群聊成员 */
+    setGroupMembers: (sessionId: number, contactIds: number[]) => invoke<void>('set_group_members', { sessionId, contactIds }),
+
+    /** 群聊消息分页 (contact_id 为发言的联系人) */
+    getGroupMessages: (sessionId: number, limit: number, beforeId: number) =>
+        invoke<any[]>('get_group_messages', { sessionId, limit, beforeId }),
+
+    /** 用户发言后触发轮流回复 (用户消息需先保存) */
+    sendGroupMessageImmersive: (sessionId: number, content: string) =>
+        invoke<void>('send_group_message_immersive', { sessionId, content }),
+};
+
+/**
+ * 联系人素材库 (图片与表情包) 相关命令
+ */
+export const stickerCommands = {
+    /** 列出联系人的素材 */
+    listContactStickers: (contactId: number) => invoke<Sticker[]>('list_contact_stickers', { contactId }),
+
+    /** 添加素材，返回 ID */
+    addContactSticker: (contactId: number, name: string, path: string, kind: StickerKind, description?: string) =>
+        invoke<number>('add_contact_sticker', { contactId, name, path, kind, description: description ?? null }),
+
+    /** 删除素材 */
+    deleteContactSticker: (id: number) => invoke<void>('delete_contact_sticker', { id }),
+};
+
 /**
  * 所有命令的聚合对象
  */
@@ -151,6 +246,10 @@ export const tauriCommands = {
     ...configCommands,
     ...dbCommands,
     ...fileCommands,
+    ...routineCommands,
+    ...triggerCommands,
+    ...groupCommands,
+    ...stickerCommands,
 };
 
 /**
@@ -26,6 +26,7 @@ export interface ChatMessage {
     content: string;
     reasoningContent?: string | null;
     fileMetadata?: string | null;
+    parentId?: number | null; // 对比模式：所属用户消息 ID，同一 parentId 的回答互为兄弟
 }
 
 // AI 提供者类型
@@ -26,6 +26,36 @@ export interface ModelInfo {
 // 模型特性类型
 export type ModelFeature = 'vision' | 'search' | 'reasoning' | 'tools' | 'web';
 
+// 网络设置，未填写的字段使用全局设置或默认值
+export interface NetworkSettings {
+    proxy?: string; // 支持 http://、https://、socks5:// 或 socks5h:// 代理
+    noProxy?: string; // 逗号分隔，本地回环地址始终直连
+    caCerts?: string[]; // 额外信任的根证书文件路径 (PEM / DER)
+    connectTimeoutSecs?: number;
+    timeoutSecs?: number; // 0 表示不限制
+    userAgent?: string;
+}
+
+// 本地 OpenAI 兼容接口 (仅监听 127.0.0.1)
+export interface ApiServerSettings {
+    enabled: boolean;
+    port: number; // 默认 11435
+    token: string; // Authorization: Bearer <token>，为空时不会启动
+}
+
+export interface ApiServerStatus {
+    running: boolean;
+    baseUrl?: string;
+    error?: string;
+}
+
+// API Key 存储状态；加密文件后端锁定时无法保存新的密钥
+export interface SecretStoreStatus {
+    backend: string;
+    locked: boolean;
+    legacyPending: boolean;
+}
+
 // 模型提供商配置
 export interface ModelProviderConfig {
     id: string;
@@ -34,12 +64,16 @@ export interface ModelProviderConfig {
     enabled: boolean;
     apiKey: string;
     baseUrl?: string;
+    // 接口协议，缺省时按 id 推断；ollama / llamacpp 为本地服务，API Key 可留空
+    kind?: 'openai' | 'gemini' | 'ollama' | 'llamacpp';
     models: (string | ModelInfo)[]; // 兼容旧版本的字符串数组
     defaultModel?: string;
     temperature?: number;
     maxTokens?: number;
     customParams?: Record<string, any>;
     disableUrlSuffix?: boolean;
+    // 覆盖全局网络设置 (代理 / 证书 / 超时)
+    network?: NetworkSettings;
     isCustom?: boolean;
     lastTestModelId?: string;
 }
@@ -132,6 +166,12 @@ export interface AppSettings {
     // 🅰️ Global Typography
     fontFamilyEnglish: string;
     fontFamilyChinese: string;
+
+    // 🌐 Network (代理 / 自定义根证书)
+    network?: NetworkSettings;
+
+    // 🔌 本地 OpenAI 兼容接口
+    apiServer?: ApiServerSettings;
 }
 
 export interface ChatModeConfig {
@@ -153,6 +193,9 @@ export interface ImmersiveModeConfig {
         replyDelay?: [number, number];         // Legacy alias
 
         segmentationThresholdRange?: [number, number]; // [min, max] chars
+        segmentDelimiter?: string | null;              // model-emitted split marker, e.g. "||"
+        pipelinedReplies?: boolean;                    // send segments while the reply is still generating
+        historyLimit?: number | null;                  // most recent messages loaded per reply
         typingSpeedRange?: [number, number];           // [min, max] chars/sec
 
         multiSegment: number | null;           // max segments
@@ -162,6 +205,7 @@ export interface ImmersiveModeConfig {
         typoCorrection: {
             triggerRate: number;               // 0.0 - 1.0
             fixDelayMs: number;                // delay before fixing typo
+            useEdit?: boolean;                 // edit the message instead of retract + resend
         } | null;
 
         proactiveInitiation: {
@@ -177,6 +221,28 @@ export interface ImmersiveModeConfig {
 
         typingJitter: boolean;                 // add randomness to typing delays
 
+        resumeConfig?: {                       // restore unfinished replies after restart
+            overduePolicy: 'execute' | 'discard';
+            maxOverdueMin: number;
+        };
+
+        eventTriggers?: {                      // birthdays, follow-ups and reminders
+            enabled: boolean;
+            extractFromMemory: boolean;
+            maxLateHours: number;
+        };
+
+        groupChat?: {                          // turn-taking in group sessions
+            replyProbability: number;
+            maxResponders: number;
+            turnGapMs: [number, number];
+        };
+
+        expressions?: {                        // reactions, quotes, stickers and read receipts
+            enabled: boolean;
+            readReceipts: boolean;
+        };
+
         characterStateConfig?: {               // Added missing definition
             enabled: boolean;
             analysisFrequency: number;
@@ -252,6 +318,18 @@ export const DEFAULT_PROVIDERS: ModelProviderConfig[] = [
         temperature: 0.7,
         maxTokens: 2048
     },
+    {
+        id: 'llamacpp',
+        name: 'llama.cpp',
+        icon: 'llamacpp',
+        kind: 'llamacpp',
+        enabled: false,
+        apiKey: '',
+        baseUrl: 'http://localhost:8080',
+        models: [],
+        temperature: 0.7,
+        maxTokens: 2048
+    },
     {
         id: 'qwen',
         name: 'Qwen',
@@ -335,6 +413,9 @@ export const DEFAULT_SETTINGS: AppSettings = {
             responseDelayRange: [800, 3000],  // alias
 
             segmentationThresholdRange: [40, 100],
+            segmentDelimiter: null,
+            pipelinedReplies: false,
+            historyLimit: 200,
             typingSpeedRange: [2, 8],
 
             multiSegment: 3,
@@ -343,7 +424,8 @@ export const DEFAULT_SETTINGS: AppSettings = {
 
             typoCorrection: {
                 triggerRate: 0.02,
-                fixDelayMs: 1500
+                fixDelayMs: 1500,
+                useEdit: false
             },
             proactiveInitiation: {
                 idleThresholdRange: [120, 600],
@@ -351,7 +433,25 @@ export const DEFAULT_SETTINGS: AppSettings = {
                 cooldownRange: [600, 3600],
                 idleCheckIntervalRange: [30, 90]
             },
-            typingJitter: true
+            typingJitter: true,
+            resumeConfig: {
+                overduePolicy: 'execute',
+                maxOverdueMin: 30
+            },
+            eventTriggers: {
+                enabled: true,
+                extractFromMemory: true,
+                maxLateHours: 24
+            },
+            groupChat: {
+                replyProbability: 0.6,
+                maxResponders: 2,
+                turnGapMs: [1500, 4000]
+            },
+            expressions: {
+                enabled: false,
+                readReceipts: true
+            }
         }
     },
     globalScale: 1.0,
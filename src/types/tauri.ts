@@ -67,6 +67,35 @@ export interface AskAIParams {
     maxTokens?: number;
 }
 
+// 多模型对比：同一问题并发发给多个模型
+export interface CompareTarget {
+    providerId: string;
+    modelId: string;
+}
+
+export interface CompareRequest {
+    targets: CompareTarget[];
+    sessionId?: string;
+    userMessageId?: number; // 提供时每个回答保存为该用户消息下的兄弟回答
+    judge?: CompareTarget & { mode?: 'score' | 'merge' };
+}
+
+export interface JudgeScore {
+    target: number;
+    score: number;
+    comment: string;
+}
+
+// target 为 targets 数组下标
+export type CompareEvent =
+    | { type: 'content'; target: number; text: string }
+    | { type: 'reasoning'; target: number; text: string }
+    | { type: 'done'; target: number; messageId: number | null; firstTokenMs: number | null; elapsedMs: number }
+    | { type: 'error'; target: number; message: string }
+    | { type: 'judgeContent'; text: string }
+    | { type: 'judgeDone'; messageId: number | null; scores: JudgeScore[]; best: number | null }
+    | { type: 'judgeError'; message: string };
+
 
 
 export interface GenerateTitleParams {
@@ -84,4 +113,133 @@ export interface UpdateSessionsOrderParams {
 
 export interface UpdateFoldersOrderParams {
     orders: [string, number][];
-}
\ No newline at end of file
+}
+// 角色作息 (见 src-tauri/src/routine.rs)
+export type ActivityKind = 'sleeping' | 'working' | 'commuting' | 'eating' | 'free';
+
+export interface RoutineBlock {
+    /** 适用的星期 (1=周一 … 7=周日)，为空表示每天 */
+    days: number[];
+    /** "HH:MM"；结束早于开始表示跨过午夜 */
+    start: string;
+    end: string;
+    activity: ActivityKind;
+    label?: string | null;
+    /** 方便回消息的概率 [0-1]，为空时按活动类型取默认值 */
+    availability?: number | null;
+}
+
+export interface Routine {
+    /** 固定 UTC 偏移 (如 "+08:00")，为空时使用本机时区 */
+    timezone?: string | null;
+    blocks: RoutineBlock[];
+}
+
+export interface ActivityState {
+    kind: ActivityKind;
+    label: string;
+    availability: number;
+    localTime: string;
+    until: string | null;
+}
+
+// 事件触发的主动发言 (见 src-tauri/src/proactive_trigger.rs)
+export type TriggerKind = 'date' | 'follow_up' | 'reminder';
+
+export interface TriggerInput {
+    contactId: number;
+    /** 为空时发往联系人最近的会话 */
+    sessionId?: number | null;
+    kind: TriggerKind;
+    title: string;
+    note?: string | null;
+    /** Unix 毫秒 */
+    fireAt: number;
+    /** 每年同一天重复 (生日、纪念日) */
+    yearly?: boolean;
+}
+
+export interface ProactiveTrigger extends Required<TriggerInput> {
+    id: number;
+    /** 'user' 手动添加，'memory' 从对话中识别 */
+    source: string;
+}
+
+export interface GroupMember {
+    contactId: number;
+    name: string;
+    avatar?: string | null;
+}
+
+export interface GroupSession {
+    id: number;
+    title: string;
+    members: GroupMember[];
+    createdAt: string;
+    updatedAt: string;
+}
+
+export type StickerKind = 'sticker' | 'image';
+
+export interface Sticker {
+    id: number;
+    contactId: number;
+    /** 模型按名称选择，同一联系人内唯一 */
+    name: string;
+    /** 本地文件路径或 data URL */
+    path: string;
+    kind: StickerKind;
+    description?: string | null;
+}
+
+export interface MessageAttachment {
+    kind: StickerKind;
+    name: string;
+    path: string;
+}
+
+export interface MessageReaction {
+    contactId: number;
+    emoji: string;
+    /** Unix 毫秒 */
+    reactedAt: number;
+}
+
+/** 沉浸模式调度器发出的事件 */
+export interface NewSocialMessageEvent {
+    messageId: number;
+    contactId: number;
+    sessionId: number;
+    role: 'assistant';
+    content: string;
+    quoteId: number | null;
+    attachment: MessageAttachment | null;
+    createdAt: string;
+}
+
+export interface MessageRetractedEvent {
+    messageId: number;
+    contactId: number;
+}
+
+export interface MessageReactionEvent extends MessageReaction {
+    messageId: number;
+    sessionId: number;
+}
+
+export interface MessagesReadEvent {
+    messageIds: number[];
+    contactId: number;
+    sessionId: number;
+    /** Unix 毫秒 */
+    readAt: number;
+}
+
+export interface MessageEditedEvent {
+    messageId: number;
+    contactId: number;
+    sessionId: number;
+    content: string;
+    /** Unix 毫秒 */
+    editedAt: number;
+}